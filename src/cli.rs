@@ -1,6 +1,6 @@
 //! command line interface
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
@@ -8,7 +8,7 @@ use std::path::{Path, PathBuf};
     name = "zzz",
     version,
     about = "zzz: compression multitool",
-    long_about = "Create and extract archives in multiple formats (zst, tgz, txz, zip, 7z) with smart file filtering and magic number detection"
+    long_about = "Create and extract archives in multiple formats (zst, tgz, txz, zip, 7z, tar, tbz2, lz4, lzma, br, zzd) with smart file filtering and magic number detection"
 )]
 pub struct Cli {
     #[command(subcommand)]
@@ -18,9 +18,17 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub verbose: bool,
 
-    /// number of threads (0 = auto-detect available parallelism)
-    #[arg(short = 'j', long, global = true, default_value = "0")]
-    pub threads: u32,
+    /// number of threads (0 = auto-detect available parallelism) [default: 0]
+    #[arg(short = 'j', long, global = true)]
+    pub threads: Option<u32>,
+
+    /// apply a named profile from the config files
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// ignore config files (.zzz.toml and the user config)
+    #[arg(long, global = true, conflicts_with = "profile")]
+    pub no_config: bool,
 }
 
 #[derive(Subcommand)]
@@ -28,71 +36,250 @@ pub enum Commands {
     /// compress files/directories (supports .zst, .tgz, .txz, .zip, .7z)
     #[command(alias = "c")]
     Compress {
-        /// compression level (1-22)
-        #[arg(short, long, default_value = "19", value_parser = clap::value_parser!(i32).range(1..=22))]
-        level: i32,
+        /// compression level (1-22) [default: 19]
+        #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        level: Option<i32>,
 
         /// output file path
         #[arg(short, long)]
         output: Option<PathBuf>,
 
         /// show progress bar
-        #[arg(short = 'P', long)]
-        progress: bool,
+        #[arg(
+            short = 'P',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        progress: Option<bool>,
 
         /// exclude files matching pattern (repeatable)
         #[arg(short = 'e', long)]
         exclude: Vec<String>,
 
+        /// read exclude patterns from file, one per line (repeatable)
+        #[arg(short = 'X', long)]
+        exclude_from: Vec<PathBuf>,
+
         /// preserve extended attributes (xattrs) in tar-based archives
-        #[arg(long)]
-        keep_xattrs: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_xattrs: Option<bool>,
 
         /// preserve original file permissions in archive entries
-        #[arg(long)]
-        keep_permissions: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_permissions: Option<bool>,
 
         /// preserve ownership (uid/gid) in tar-based archives
-        #[arg(long)]
-        keep_ownership: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_ownership: Option<bool>,
 
         /// follow symlinks and archive target contents (may include files outside input)
-        #[arg(long)]
-        follow_symlinks: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        follow_symlinks: Option<bool>,
 
         /// allow symlink targets outside the input root (requires --follow-symlinks)
-        #[arg(long)]
-        allow_symlink_escape: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        allow_symlink_escape: Option<bool>,
 
         /// strip timestamps and xattrs, normalize ownership/permissions, and exclude common secrets (overrides keep flags)
-        #[arg(long)]
-        redact: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        redact: Option<bool>,
 
         /// strip filesystem timestamps in archive entries
-        #[arg(long)]
-        strip_timestamps: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        strip_timestamps: Option<bool>,
+
+        /// zip compression method (store, deflate, bzip2, zstd)
+        #[arg(long, value_parser = parse_zip_method)]
+        zip_method: Option<crate::formats::ZipMethod>,
+
+        /// recompress already-compressed files in zip archives instead of storing them
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        no_auto_store: Option<bool>,
+
+        /// clamp entry mtimes to this unix timestamp (defaults to $SOURCE_DATE_EPOCH)
+        #[arg(long, value_name = "EPOCH")]
+        mtime_clamp: Option<u64>,
+
+        /// zstd long-distance matching for large inputs, with an optional window log [default: 27]
+        #[arg(
+            long,
+            value_name = "WINDOWLOG",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "27",
+            value_parser = clap::value_parser!(u32).range(10..=31)
+        )]
+        long: Option<u32>,
+
+        /// zstd window log (10-31; above 27 requires --ultra)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(10..=31))]
+        window_log: Option<u32>,
+
+        /// zstd strategy (fast, dfast, greedy, lazy, lazy2, btlazy2, btopt, btultra, btultra2)
+        #[arg(long, value_parser = parse_zstd_strategy)]
+        strategy: Option<crate::formats::ZstdStrategy>,
+
+        /// zstd target compressed block size, e.g. 16K (1340 bytes to 128K)
+        #[arg(long, value_name = "SIZE", value_parser = parse_zstd_block_size)]
+        target_block_size: Option<u32>,
+
+        /// add a content checksum to zstd frames
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        checksum: Option<bool>,
+
+        /// allow zstd windows above 128 MiB (window log 28-31); decoders need a matching limit
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        ultra: Option<bool>,
+
+        /// zstd dictionary to compress with (see `zzz dict train`)
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+
+        /// write seekable zstd: independent frames of FRAME_SIZE plus a seek table and tar index [default: 1M]
+        #[arg(
+            long,
+            value_name = "FRAME_SIZE",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "1M",
+            value_parser = parse_zstd_frame_size
+        )]
+        seekable: Option<u32>,
+
+        /// split xz output into independent blocks of SIZE, e.g. 8M [default: 3× the preset's dictionary]
+        #[arg(long, value_name = "SIZE", value_parser = parse_xz_block_size)]
+        xz_block_size: Option<u64>,
+
+        /// 7z compression method (lzma2, lzma)
+        #[arg(long = "7z-method", value_name = "METHOD", value_parser = parse_sevenz_method)]
+        sevenz_method: Option<crate::formats::SevenZMethod>,
+
+        /// pack 7z files into shared solid blocks instead of compressing each on its own
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        solid: Option<bool>,
+
+        /// solid 7z blocks of at most SIZE of input, e.g. 64M; implies --solid [default: one block]
+        #[arg(long, value_name = "SIZE", value_parser = parse_solid_block_size)]
+        solid_block_size: Option<u64>,
+
+        /// split the archive into volumes of at most SIZE, e.g. 25M or 5G
+        /// (name.001, name.002, …; zip writes name.z01, …, name.zip)
+        #[arg(long, value_name = "SIZE", value_parser = parse_split_size)]
+        split_size: Option<u64>,
+
+        /// incremental backup: archive only what changed since the run recorded in FILE,
+        /// then update it (a missing FILE starts with a full backup)
+        #[arg(long, value_name = "FILE")]
+        snapshot: Option<PathBuf>,
 
         /// disable built-in garbage file filtering
-        #[arg(short = 'E', long)]
-        no_default_excludes: bool,
-
-        /// force specific format (zst, tgz, txz, zip, 7z)
+        #[arg(
+            short = 'E',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        no_default_excludes: Option<bool>,
+
+        /// force specific format (zst, tgz, txz, zip, 7z, tar, tbz2, lz4, lzma, br, zzd)
         #[arg(short = 'f', long, value_parser = parse_format)]
         format: Option<crate::formats::Format>,
 
         /// overwrite existing output file
-        #[arg(short = 'y', long)]
-        overwrite: bool,
+        #[arg(
+            short = 'y',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        overwrite: Option<bool>,
 
         /// input file or directory
         input: PathBuf,
 
-        /// password for encryption (supported by zst and 7z)
+        /// password for encryption (supported by zst, 7z and zzd)
         #[arg(short = 'p', long)]
         password: Option<String>,
+
+        /// fail unless the 7z header is encrypted too, hiding file names from anyone without the password
+        #[arg(long, requires = "password")]
+        encrypt_headers: bool,
     },
 
-    /// extract archives (auto-detects format: .zst, .tgz, .txz, .zip, .7z)
+    /// extract archives (auto-detects format: .zst, .tgz, .txz, .zip, .7z, .tar, .tbz2, .lz4, .lzma, .br, .zzd)
     #[command(alias = "x")]
     Extract {
         /// archive file to extract
@@ -106,34 +293,123 @@ pub enum Commands {
         directory: Option<PathBuf>,
 
         /// show progress bar
-        #[arg(short = 'P', long)]
-        progress: bool,
-
-        /// strip leading path components
-        #[arg(long, default_value = "0")]
-        strip_components: usize,
+        #[arg(
+            short = 'P',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        progress: Option<bool>,
+
+        /// strip leading path components [default: 0]
+        #[arg(long)]
+        strip_components: Option<usize>,
 
         /// preserve extended attributes (xattrs) when extracting tar-based archives
-        #[arg(long)]
-        keep_xattrs: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_xattrs: Option<bool>,
 
         /// strip filesystem timestamps when extracting tar-based archives
-        #[arg(long)]
-        strip_timestamps: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        strip_timestamps: Option<bool>,
 
         /// preserve file permissions when extracting
-        #[arg(long)]
-        keep_permissions: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_permissions: Option<bool>,
 
         /// preserve ownership (uid/gid) when extracting tar-based archives
-        #[arg(long)]
-        keep_ownership: bool,
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_ownership: Option<bool>,
 
         /// overwrite existing files
-        #[arg(short = 'y', long)]
-        overwrite: bool,
+        #[arg(
+            short = 'y',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        overwrite: Option<bool>,
+
+        /// password for decryption (for zst, 7z, zzd and rar)
+        #[arg(short = 'p', long)]
+        password: Option<String>,
+
+        /// treat the decompressed stream as a tarball instead of sniffing it
+        #[arg(long, conflicts_with = "raw")]
+        tar: bool,
+
+        /// treat the decompressed stream as a single raw file instead of sniffing it
+        #[arg(long)]
+        raw: bool,
+
+        /// zstd dictionary the archive was compressed with
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+
+        /// extract only this path, or everything below it if it is a directory (repeatable)
+        #[arg(long = "entry", value_name = "PATH")]
+        entries: Vec<String>,
+    },
+
+    /// restore a full backup and its incrementals (made with --snapshot), in order, into a directory
+    Restore {
+        /// full backup followed by the incremental backups made after it
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+
+        /// directory to restore into
+        #[arg(short = 'C', long)]
+        directory: PathBuf,
+
+        /// preserve file permissions when extracting
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_permissions: Option<bool>,
 
-        /// password for decryption (for zst and 7z)
+        /// preserve ownership (uid/gid) when extracting tar-based archives
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        keep_ownership: Option<bool>,
+
+        /// password for decryption (for zst, 7z and zzd)
         #[arg(short = 'p', long)]
         password: Option<String>,
     },
@@ -143,6 +419,26 @@ pub enum Commands {
     List {
         /// archive file to list
         archive: PathBuf,
+
+        /// treat the decompressed stream as a tarball instead of sniffing it
+        #[arg(long, conflicts_with = "raw")]
+        tar: bool,
+
+        /// treat the decompressed stream as a single raw file instead of sniffing it
+        #[arg(long)]
+        raw: bool,
+
+        /// zstd dictionary the archive was compressed with
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+
+        /// list only this path, or everything below it if it is a directory (repeatable)
+        #[arg(long = "entry", value_name = "PATH")]
+        entries: Vec<String>,
+
+        /// password for decryption (for zst, 7z, zzd and rar)
+        #[arg(short = 'p', long)]
+        password: Option<String>,
     },
 
     /// test archive integrity
@@ -150,19 +446,481 @@ pub enum Commands {
     Test {
         /// archive file to test
         archive: PathBuf,
+
+        /// treat the decompressed stream as a tarball instead of sniffing it
+        #[arg(long, conflicts_with = "raw")]
+        tar: bool,
+
+        /// treat the decompressed stream as a single raw file instead of sniffing it
+        #[arg(long)]
+        raw: bool,
+
+        /// zstd dictionary the archive was compressed with
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+
+        /// password for decryption (for zst, 7z, zzd and rar)
+        #[arg(short = 'p', long)]
+        password: Option<String>,
     },
+
+    /// convert an archive to another format without extracting it to disk
+    Convert {
+        /// archive to convert
+        input: PathBuf,
+
+        /// archive to write; its extension picks the format unless --format is given
+        output: PathBuf,
+
+        /// force specific output format (zst, tgz, txz, zip, 7z, tar, tbz2, lz4, lzma, br, zzd)
+        #[arg(short = 'f', long, value_parser = parse_format)]
+        format: Option<crate::formats::Format>,
+
+        /// compression level (1-22) [default: 19]
+        #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        level: Option<i32>,
+
+        /// exclude entries matching pattern (repeatable)
+        #[arg(short = 'e', long)]
+        exclude: Vec<String>,
+
+        /// read exclude patterns from file, one per line (repeatable)
+        #[arg(short = 'X', long)]
+        exclude_from: Vec<PathBuf>,
+
+        /// disable built-in garbage file filtering
+        #[arg(
+            short = 'E',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        no_default_excludes: Option<bool>,
+
+        /// strip timestamps, normalize ownership/permissions, and exclude common secrets
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        redact: Option<bool>,
+
+        /// strip timestamps from the converted entries
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        strip_timestamps: Option<bool>,
+
+        /// overwrite existing output file
+        #[arg(
+            short = 'y',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        overwrite: Option<bool>,
+
+        /// password for decrypting the input (for zst, 7z, zzd and rar)
+        #[arg(short = 'p', long)]
+        password: Option<String>,
+
+        /// password for encrypting the output (for zst, 7z and zzd)
+        #[arg(long)]
+        output_password: Option<String>,
+    },
+
+    /// add files to an existing archive, replacing entries of the same name
+    Add(AddArgs),
+
+    /// add files to an existing archive, replacing only entries older than the file on disk
+    Update(AddArgs),
+
+    /// delete entries from an archive, rewriting it without them
+    #[command(alias = "rm")]
+    Remove {
+        /// archive to modify
+        archive: PathBuf,
+
+        /// entries to delete; patterns match like --exclude and take directories whole
+        #[arg(required = true)]
+        patterns: Vec<String>,
+
+        /// compression level used when the archive has to be recompressed (1-22) [default: 19]
+        #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        level: Option<i32>,
+
+        /// password of an encrypted archive (for zst and 7z); it is kept encrypted with it
+        #[arg(short = 'p', long)]
+        password: Option<String>,
+
+        /// zstd dictionary the archive was compressed with; the rewrite keeps using it
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+    },
+
+    /// rename an entry, or a directory and everything below it
+    #[command(alias = "mv")]
+    Rename {
+        /// archive to modify
+        archive: PathBuf,
+
+        /// current entry name
+        from: String,
+
+        /// new entry name
+        to: String,
+
+        /// compression level used when the archive has to be recompressed (1-22) [default: 19]
+        #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        level: Option<i32>,
+
+        /// password of an encrypted archive (for zst and 7z); it is kept encrypted with it
+        #[arg(short = 'p', long)]
+        password: Option<String>,
+
+        /// zstd dictionary the archive was compressed with; the rewrite keeps using it
+        #[arg(long, value_name = "FILE")]
+        dict: Option<PathBuf>,
+    },
+
+    /// show which exclude rule (if any) matches each path
+    CheckIgnore {
+        /// paths to check, relative to the input root being archived
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// exclude files matching pattern (repeatable)
+        #[arg(short = 'e', long)]
+        exclude: Vec<String>,
+
+        /// read exclude patterns from file, one per line (repeatable)
+        #[arg(short = 'X', long)]
+        exclude_from: Vec<PathBuf>,
+
+        /// include the sensitive file patterns used by --redact
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        redact: Option<bool>,
+
+        /// disable built-in garbage file filtering
+        #[arg(
+            short = 'E',
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        no_default_excludes: Option<bool>,
+    },
+
+    /// train zstd dictionaries for archiving many small, similar files
+    Dict {
+        #[command(subcommand)]
+        action: DictAction,
+    },
+
+    /// inspect configuration files and profiles
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+/// arguments shared by `add` and `update`
+#[derive(Args)]
+pub struct AddArgs {
+    /// archive to modify
+    pub archive: PathBuf,
+
+    /// files or directories to add, named in the archive as given
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// read the paths from DIR, which is not part of their names
+    #[arg(short = 'C', long, value_name = "DIR")]
+    pub directory: Option<PathBuf>,
+
+    /// compression level (1-22) [default: 19]
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..=22))]
+    pub level: Option<i32>,
+
+    /// exclude files matching pattern (repeatable)
+    #[arg(short = 'e', long)]
+    pub exclude: Vec<String>,
+
+    /// read exclude patterns from file, one per line (repeatable)
+    #[arg(short = 'X', long)]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// disable built-in garbage file filtering
+    #[arg(
+        short = 'E',
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub no_default_excludes: Option<bool>,
+
+    /// preserve original file permissions in the new entries
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub keep_permissions: Option<bool>,
+
+    /// preserve ownership (uid/gid) in the new entries of tar-based archives
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub keep_ownership: Option<bool>,
+
+    /// follow symlinks and archive target contents (may include files outside input)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub follow_symlinks: Option<bool>,
+
+    /// allow symlink targets outside the input root (requires --follow-symlinks)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub allow_symlink_escape: Option<bool>,
+
+    /// strip timestamps, normalize ownership/permissions, and exclude common secrets (overrides keep flags)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub redact: Option<bool>,
+
+    /// strip filesystem timestamps in the new entries
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub strip_timestamps: Option<bool>,
+
+    /// clamp entry mtimes to this unix timestamp (defaults to $SOURCE_DATE_EPOCH)
+    #[arg(long, value_name = "EPOCH")]
+    pub mtime_clamp: Option<u64>,
+
+    /// zip compression method (store, deflate, bzip2, zstd)
+    #[arg(long, value_parser = parse_zip_method)]
+    pub zip_method: Option<crate::formats::ZipMethod>,
+
+    /// recompress already-compressed files in zip archives instead of storing them
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub no_auto_store: Option<bool>,
+
+    /// password of an encrypted archive (for zst and 7z); it is kept encrypted with it
+    #[arg(short = 'p', long)]
+    pub password: Option<String>,
+
+    /// zstd dictionary the archive was compressed with; the rewrite keeps using it
+    #[arg(long, value_name = "FILE")]
+    pub dict: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum DictAction {
+    /// train a dictionary from sample files (directories are walked recursively)
+    Train {
+        /// sample files or directories
+        #[arg(required = true)]
+        samples: Vec<PathBuf>,
+
+        /// output dictionary file
+        #[arg(short = 'o', long)]
+        output: PathBuf,
+
+        /// maximum dictionary size, e.g. 110K
+        #[arg(long, value_name = "SIZE", default_value = "110K", value_parser = parse_size)]
+        max_size: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// print the effective settings after merging config files and the selected profile
+    Show,
+}
+
+/// Parse zip method string into ZipMethod enum
+pub fn parse_zip_method(s: &str) -> Result<crate::formats::ZipMethod, String> {
+    match s.to_lowercase().as_str() {
+        "store" | "stored" | "none" => Ok(crate::formats::ZipMethod::Store),
+        "deflate" | "deflated" => Ok(crate::formats::ZipMethod::Deflate),
+        "bzip2" | "bz2" => Ok(crate::formats::ZipMethod::Bzip2),
+        "zstd" | "zst" => Ok(crate::formats::ZipMethod::Zstd),
+        _ => Err(format!(
+            "unsupported zip method '{s}'. Supported methods: store, deflate, bzip2, zstd"
+        )),
+    }
+}
+
+/// Parse 7z method string into SevenZMethod enum
+pub fn parse_sevenz_method(s: &str) -> Result<crate::formats::SevenZMethod, String> {
+    match s.to_lowercase().as_str() {
+        "lzma2" => Ok(crate::formats::SevenZMethod::Lzma2),
+        "lzma" => Ok(crate::formats::SevenZMethod::Lzma),
+        // the 7z backend can decode these but has no encoder for them
+        "copy" | "store" | "bcj" | "bcj2" | "x86" | "arm" | "armt" | "ppc" | "sparc" | "ia64" => {
+            Err(format!(
+                "7z method '{s}' cannot be written by the 7z backend. Supported methods: lzma2, lzma"
+            ))
+        }
+        _ => Err(format!(
+            "unsupported 7z method '{s}'. Supported methods: lzma2, lzma"
+        )),
+    }
+}
+
+/// Parse zstd strategy name into ZstdStrategy enum
+pub fn parse_zstd_strategy(s: &str) -> Result<crate::formats::ZstdStrategy, String> {
+    let name = s.to_lowercase();
+    crate::formats::ZstdStrategy::ALL
+        .into_iter()
+        .find(|strategy| strategy.name() == name)
+        .ok_or_else(|| {
+            format!(
+                "unsupported zstd strategy '{s}'. Supported strategies: fast, dfast, greedy, lazy, lazy2, btlazy2, btopt, btultra, btultra2"
+            )
+        })
+}
+
+/// Parse a byte size such as `4096`, `16K`, `64MiB` or `2g` (binary multiples)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let digits = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(digits);
+    let number: u64 = number.parse().map_err(|_| format!("invalid size '{s}'"))?;
+    let shift = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        _ => return Err(format!("invalid size '{s}'")),
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size '{s}' is too large"))
+}
+
+/// Parse a zstd target block size, limited to what libzstd accepts
+pub fn parse_zstd_block_size(s: &str) -> Result<u32, String> {
+    const MIN: u64 = 1340;
+    const MAX: u64 = 128 * 1024;
+
+    let size = parse_size(s)?;
+    if !(MIN..=MAX).contains(&size) {
+        return Err(format!(
+            "zstd target block size '{s}' out of range (expected {MIN} bytes to 128K)"
+        ));
+    }
+    Ok(size as u32)
+}
+
+/// Parse a seekable zstd frame size
+pub fn parse_zstd_frame_size(s: &str) -> Result<u32, String> {
+    let size = parse_size(s)?;
+    if !(1..=crate::formats::seekable::MAX_FRAME_SIZE).contains(&size) {
+        return Err(format!(
+            "zstd frame size '{s}' out of range (expected 1 byte to 1G)"
+        ));
+    }
+    Ok(size as u32)
+}
+
+/// Parse an xz block size
+pub fn parse_xz_block_size(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err(format!("xz block size '{s}' must be greater than zero")),
+        size => Ok(size),
+    }
+}
+
+/// Parse a 7z solid block size
+pub fn parse_solid_block_size(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err(format!("solid block size '{s}' must be greater than zero")),
+        size => Ok(size),
+    }
+}
+
+/// Parse a volume size for split archives
+pub fn parse_split_size(s: &str) -> Result<u64, String> {
+    const MIN: u64 = 64 * 1024;
+
+    match parse_size(s)? {
+        size if size < MIN => Err(format!("split size '{s}' is too small (minimum 64K)")),
+        size => Ok(size),
+    }
 }
 
 /// Parse format string into Format enum
-fn parse_format(s: &str) -> Result<crate::formats::Format, String> {
+pub fn parse_format(s: &str) -> Result<crate::formats::Format, String> {
     match s.to_lowercase().as_str() {
         "zst" | "zstd" => Ok(crate::formats::Format::Zstd),
         "tgz" | "gz" | "gzip" => Ok(crate::formats::Format::Gzip),
         "txz" | "xz" => Ok(crate::formats::Format::Xz),
         "zip" => Ok(crate::formats::Format::Zip),
         "7z" | "sevenz" => Ok(crate::formats::Format::SevenZ),
+        "tar" => Ok(crate::formats::Format::Tar),
+        "tbz2" | "tbz" | "bz2" | "bzip2" => Ok(crate::formats::Format::Bzip2),
+        "lz4" => Ok(crate::formats::Format::Lz4),
+        "tlz" | "lzma" => Ok(crate::formats::Format::Lzma),
+        "br" | "brotli" => Ok(crate::formats::Format::Brotli),
+        "zzd" | "dedup" => Ok(crate::formats::Format::Dedup),
         _ => Err(format!(
-            "unsupported format '{s}'. Supported formats: zst, tgz, txz, zip, 7z"
+            "unsupported format '{s}'. Supported formats: zst, tgz, txz, zip, 7z, tar, tbz2, lz4, lzma, br, zzd"
         )),
     }
 }
@@ -199,4 +957,13 @@ impl Cli {
             .or(destination)
             .unwrap_or_else(|| PathBuf::from("."))
     }
+
+    /// resolve the `--tar`/`--raw` override for single-stream formats
+    pub fn stream_payload(tar: bool, raw: bool) -> crate::formats::StreamPayload {
+        match (tar, raw) {
+            (true, _) => crate::formats::StreamPayload::Tar,
+            (_, true) => crate::formats::StreamPayload::Raw,
+            _ => crate::formats::StreamPayload::Auto,
+        }
+    }
 }
@@ -2,11 +2,14 @@
 
 use crate::filter::FileFilter;
 use crate::formats::{
-    gz::GzipFormat, rar::RarFormat, sevenz::SevenZFormat, xz::XzFormat, zip::ZipFormat,
-    zstd::ZstdFormat, CompressionFormat, CompressionOptions, CompressionStats, Format,
+    br::BrotliFormat, bz2::Bzip2Format, dedup::DedupFormat, gz::GzipFormat, lz4::Lz4Format,
+    lzma::LzmaFormat, rar::RarFormat, sevenz::SevenZFormat, tar::TarFormat, xz::XzFormat,
+    zip::ZipFormat, zstd::ZstdFormat, CompressionFormat, CompressionOptions, CompressionStats,
+    Format,
 };
+use crate::incremental;
 use crate::progress::Progress;
-use crate::Result;
+use crate::{volumes, Result};
 use anyhow::Context;
 use std::path::{Path, PathBuf};
 
@@ -37,6 +40,7 @@ pub fn compress(
 
     if verbose {
         println!("using {} format", format.name());
+        report_excluded(input_path, &filter, options.follow_symlinks);
     }
 
     // calculate total size for progress tracking
@@ -49,30 +53,79 @@ pub fn compress(
     let progress = Progress::new(show_progress, total_size, verbose);
 
     // dispatch to appropriate format implementation
-    let stats = match format {
-        Format::Zstd => {
-            ZstdFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
-        }
-        Format::Gzip => {
-            GzipFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
-        }
-        Format::Xz => {
-            XzFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
-        }
-        Format::Zip => {
-            ZipFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
-        }
-        Format::SevenZ => {
-            SevenZFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
-        }
-        Format::Rar => {
-            RarFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+    let result = (|| -> Result<CompressionStats> {
+        Ok(match format {
+            // incremental backups pick their entries from the snapshot
+            _ if options.snapshot.is_some() => incremental::compress(
+                input_path,
+                output_path,
+                format,
+                &options,
+                &filter,
+                Some(&progress),
+                verbose,
+            )?,
+            Format::Zstd => {
+                ZstdFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Gzip => {
+                GzipFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Xz => {
+                XzFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Zip => {
+                ZipFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::SevenZ => {
+                SevenZFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Rar => {
+                RarFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Tar => {
+                TarFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Bzip2 => {
+                Bzip2Format::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Lz4 => {
+                Lz4Format::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Lzma => {
+                LzmaFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Brotli => {
+                BrotliFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+            Format::Dedup => {
+                DedupFormat::compress(input_path, output_path, &options, &filter, Some(&progress))?
+            }
+        })
+    })();
+    let mut stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            // a failed split archive would otherwise leave the volumes written so far behind
+            if options.split_size.is_some() {
+                for volume in volumes::written(output_path, options.split_size) {
+                    let _ = std::fs::remove_file(volume);
+                }
+            }
+            return Err(e);
         }
     };
 
     progress.finish();
 
+    if options.split_size.is_some() {
+        stats.volumes = volumes::written(output_path, options.split_size);
+    }
+
     if verbose {
+        for volume in &stats.volumes {
+            println!("  volume: {}", volume.display());
+        }
         println!(
             "compressed {} ({}) -> {} ({}) ratio {:.2}",
             input_path.display(),
@@ -81,12 +134,31 @@ pub fn compress(
             crate::utils::format_bytes(stats.output_size),
             stats.compression_ratio
         );
+        if let Some(dedup) = &stats.dedup {
+            println!("{dedup}");
+        }
     }
 
     Ok(stats)
 }
 
-fn ensure_output_outside_input(input_path: &Path, output_path: &Path) -> Result<()> {
+/// print every input path pruned by the filter along with the matching rule
+fn report_excluded(input_path: &Path, filter: &FileFilter, follow_symlinks: bool) {
+    if input_path.is_file() {
+        if let Some(filename) = input_path.file_name() {
+            if let Some(rule) = filter.explain_relative(Path::new(filename)) {
+                println!("  excluding: {} ({})", input_path.display(), rule);
+            }
+        }
+        return;
+    }
+
+    for (path, rule) in filter.excluded_entries(input_path, follow_symlinks) {
+        println!("  excluding: {} ({})", path.display(), rule);
+    }
+}
+
+pub(crate) fn ensure_output_outside_input(input_path: &Path, output_path: &Path) -> Result<()> {
     let input_abs = std::fs::canonicalize(input_path)
         .with_context(|| format!("Failed to resolve input path '{}'", input_path.display()))?;
     let output_abs = resolve_absolute_path(output_path)?;
@@ -138,7 +210,7 @@ fn canonicalize_with_fallback(path: &Path) -> PathBuf {
 }
 
 /// Detect compression format from output file extension
-fn detect_output_format(output_path: &Path) -> Result<Format> {
+pub(crate) fn detect_output_format(output_path: &Path) -> Result<Format> {
     Format::from_extension(output_path).ok_or_else(|| {
         anyhow::anyhow!(
             "cannot determine compression format from extension: {}",
@@ -0,0 +1,682 @@
+//! configuration files and named profiles
+//!
+//! Settings are read from the user config (`$XDG_CONFIG_HOME/zzz/config.toml`)
+//! and the nearest project-local `.zzz.toml`, in that order. A selected profile
+//! is applied on top of both, and command line flags take precedence over all
+//! of them.
+//!
+//! A `.zzz.toml` comes with whatever directory it is found in, so it cannot turn
+//! on settings that read or overwrite files outside of what was asked for; those
+//! are only taken from the user config and the command line.
+
+use crate::filter::FileFilter;
+use crate::formats::{CompressionOptions, ExtractionOptions, Format};
+use crate::Result;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// project-local config file name, searched from the current directory upwards
+pub const LOCAL_CONFIG_FILE: &str = ".zzz.toml";
+
+/// defaults for the compress command
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressDefaults {
+    pub format: Option<String>,
+    pub level: Option<i32>,
+    pub threads: Option<u32>,
+    pub exclude: Vec<String>,
+    pub exclude_from: Vec<PathBuf>,
+    pub progress: Option<bool>,
+    pub keep_xattrs: Option<bool>,
+    pub keep_permissions: Option<bool>,
+    pub keep_ownership: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub allow_symlink_escape: Option<bool>,
+    pub redact: Option<bool>,
+    pub strip_timestamps: Option<bool>,
+    pub mtime_clamp: Option<u64>,
+    pub zip_method: Option<String>,
+    pub no_auto_store: Option<bool>,
+    pub long: Option<u32>,
+    pub window_log: Option<u32>,
+    pub strategy: Option<String>,
+    pub target_block_size: Option<String>,
+    pub checksum: Option<bool>,
+    pub ultra: Option<bool>,
+    pub dict: Option<PathBuf>,
+    pub seekable: Option<String>,
+    pub xz_block_size: Option<String>,
+    pub sevenz_method: Option<String>,
+    pub solid: Option<bool>,
+    pub solid_block_size: Option<String>,
+    pub split_size: Option<String>,
+    pub no_default_excludes: Option<bool>,
+    pub overwrite: Option<bool>,
+}
+
+/// defaults for the extract command
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtractDefaults {
+    pub progress: Option<bool>,
+    pub strip_components: Option<usize>,
+    pub keep_xattrs: Option<bool>,
+    pub strip_timestamps: Option<bool>,
+    pub keep_permissions: Option<bool>,
+    pub keep_ownership: Option<bool>,
+    pub overwrite: Option<bool>,
+}
+
+/// a named set of overrides selected with `--profile`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub compress: CompressDefaults,
+    pub extract: ExtractDefaults,
+}
+
+/// on-disk layout of a config file
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub compress: CompressDefaults,
+    pub extract: ExtractDefaults,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// effective configuration after merging all files and the selected profile
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub sources: Vec<PathBuf>,
+    pub profile: Option<String>,
+    pub compress: CompressDefaults,
+    pub extract: ExtractDefaults,
+}
+
+impl CompressDefaults {
+    /// overlay `other` on top of these settings; excludes accumulate
+    pub fn merge(&mut self, other: &CompressDefaults) {
+        self.format = other.format.clone().or(self.format.take());
+        self.level = other.level.or(self.level);
+        self.threads = other.threads.or(self.threads);
+        self.exclude.extend(other.exclude.iter().cloned());
+        self.exclude_from.extend(other.exclude_from.iter().cloned());
+        self.progress = other.progress.or(self.progress);
+        self.keep_xattrs = other.keep_xattrs.or(self.keep_xattrs);
+        self.keep_permissions = other.keep_permissions.or(self.keep_permissions);
+        self.keep_ownership = other.keep_ownership.or(self.keep_ownership);
+        self.follow_symlinks = other.follow_symlinks.or(self.follow_symlinks);
+        self.allow_symlink_escape = other.allow_symlink_escape.or(self.allow_symlink_escape);
+        self.redact = other.redact.or(self.redact);
+        self.strip_timestamps = other.strip_timestamps.or(self.strip_timestamps);
+        self.mtime_clamp = other.mtime_clamp.or(self.mtime_clamp);
+        self.zip_method = other.zip_method.clone().or(self.zip_method.take());
+        self.no_auto_store = other.no_auto_store.or(self.no_auto_store);
+        self.long = other.long.or(self.long);
+        self.window_log = other.window_log.or(self.window_log);
+        self.strategy = other.strategy.clone().or(self.strategy.take());
+        self.target_block_size = other
+            .target_block_size
+            .clone()
+            .or(self.target_block_size.take());
+        self.checksum = other.checksum.or(self.checksum);
+        self.ultra = other.ultra.or(self.ultra);
+        self.dict = other.dict.clone().or(self.dict.take());
+        self.seekable = other.seekable.clone().or(self.seekable.take());
+        self.xz_block_size = other.xz_block_size.clone().or(self.xz_block_size.take());
+        self.sevenz_method = other.sevenz_method.clone().or(self.sevenz_method.take());
+        self.solid = other.solid.or(self.solid);
+        self.solid_block_size = other
+            .solid_block_size
+            .clone()
+            .or(self.solid_block_size.take());
+        self.split_size = other.split_size.clone().or(self.split_size.take());
+        self.no_default_excludes = other.no_default_excludes.or(self.no_default_excludes);
+        self.overwrite = other.overwrite.or(self.overwrite);
+    }
+
+    /// built-in defaults, used to fill unset values when showing the configuration
+    pub fn builtin() -> Self {
+        let options = CompressionOptions::default();
+        Self {
+            format: Some(crate::formats::Format::Zstd.extension().to_string()),
+            level: Some(options.level),
+            threads: Some(options.threads),
+            exclude: Vec::new(),
+            exclude_from: Vec::new(),
+            progress: Some(false),
+            keep_xattrs: Some(!options.strip_xattrs),
+            keep_permissions: Some(!options.normalize_permissions),
+            keep_ownership: Some(!options.normalize_ownership),
+            follow_symlinks: Some(options.follow_symlinks),
+            allow_symlink_escape: Some(options.allow_symlink_escape),
+            redact: Some(false),
+            strip_timestamps: Some(options.strip_timestamps),
+            mtime_clamp: options.mtime_clamp,
+            zip_method: Some(options.zip_method.name().to_string()),
+            no_auto_store: Some(!options.auto_store),
+            long: options.zstd_long,
+            window_log: options.zstd_window_log,
+            strategy: options.zstd_strategy.map(|s| s.name().to_string()),
+            target_block_size: options.zstd_target_block_size.map(|size| size.to_string()),
+            checksum: Some(options.zstd_checksum),
+            ultra: Some(options.zstd_ultra),
+            dict: options.zstd_dict,
+            seekable: options.zstd_seekable.map(|size| size.to_string()),
+            xz_block_size: options.xz_block_size.map(|size| size.to_string()),
+            sevenz_method: Some(options.sevenz_method.name().to_string()),
+            solid: Some(options.sevenz_solid),
+            solid_block_size: options.sevenz_solid_block_size.map(|size| size.to_string()),
+            split_size: options.split_size.map(|size| size.to_string()),
+            no_default_excludes: Some(false),
+            overwrite: Some(false),
+        }
+    }
+
+    /// clear the settings a project-local file may not set, returning their names
+    fn take_user_only(&mut self) -> Vec<&'static str> {
+        let mut taken = Vec::new();
+        if self.follow_symlinks.take().is_some() {
+            taken.push("follow_symlinks");
+        }
+        if self.allow_symlink_escape.take().is_some() {
+            taken.push("allow_symlink_escape");
+        }
+        if self.no_default_excludes.take().is_some() {
+            taken.push("no_default_excludes");
+        }
+        if !std::mem::take(&mut self.exclude_from).is_empty() {
+            taken.push("exclude_from");
+        }
+        taken
+    }
+
+    /// these settings with `overrides` (usually the command line) applied on top
+    pub fn overlay(&self, overrides: &CompressDefaults) -> CompressDefaults {
+        let mut settings = self.clone();
+        settings.merge(overrides);
+        settings
+    }
+
+    /// output format to use when none was forced
+    ///
+    /// The configured format only applies when the output name does not
+    /// already pick one by its extension.
+    pub fn output_format(&self, output: Option<&Path>) -> Result<Option<Format>> {
+        if output.and_then(Format::from_extension).is_some() {
+            return Ok(None);
+        }
+        self.format
+            .as_deref()
+            .map(crate::cli::parse_format)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// compression options for these settings; anything unset keeps its built-in default
+    pub fn compression_options(&self) -> Result<CompressionOptions> {
+        let defaults = CompressionOptions::default();
+        let follow_symlinks = self.follow_symlinks.unwrap_or(defaults.follow_symlinks);
+        let allow_symlink_escape = self
+            .allow_symlink_escape
+            .unwrap_or(defaults.allow_symlink_escape);
+        if allow_symlink_escape && !follow_symlinks {
+            return Err(anyhow::anyhow!(
+                "--allow-symlink-escape requires --follow-symlinks"
+            ));
+        }
+        let parse_error = |e: String| anyhow::anyhow!(e);
+
+        let mut options = CompressionOptions {
+            level: self.level.unwrap_or(defaults.level),
+            threads: self.threads.unwrap_or(defaults.threads),
+            normalize_permissions: !self.keep_permissions.unwrap_or_default(),
+            normalize_ownership: !self.keep_ownership.unwrap_or_default(),
+            strip_xattrs: !self.keep_xattrs.unwrap_or_default(),
+            strip_timestamps: self.strip_timestamps.unwrap_or(defaults.strip_timestamps),
+            follow_symlinks,
+            allow_symlink_escape,
+            mtime_clamp: match self.mtime_clamp {
+                Some(epoch) => Some(epoch),
+                None => source_date_epoch()?,
+            },
+            zip_method: self
+                .zip_method
+                .as_deref()
+                .map(crate::cli::parse_zip_method)
+                .transpose()
+                .map_err(parse_error)?
+                .unwrap_or(defaults.zip_method),
+            auto_store: !self.no_auto_store.unwrap_or(!defaults.auto_store),
+            zstd_long: self.long,
+            zstd_window_log: self.window_log,
+            zstd_strategy: self
+                .strategy
+                .as_deref()
+                .map(crate::cli::parse_zstd_strategy)
+                .transpose()
+                .map_err(parse_error)?,
+            zstd_target_block_size: self
+                .target_block_size
+                .as_deref()
+                .map(crate::cli::parse_zstd_block_size)
+                .transpose()
+                .map_err(parse_error)?,
+            zstd_checksum: self.checksum.unwrap_or(defaults.zstd_checksum),
+            zstd_ultra: self.ultra.unwrap_or(defaults.zstd_ultra),
+            zstd_dict: self.dict.clone(),
+            zstd_seekable: self
+                .seekable
+                .as_deref()
+                .map(crate::cli::parse_zstd_frame_size)
+                .transpose()
+                .map_err(parse_error)?,
+            xz_block_size: self
+                .xz_block_size
+                .as_deref()
+                .map(crate::cli::parse_xz_block_size)
+                .transpose()
+                .map_err(parse_error)?,
+            sevenz_method: self
+                .sevenz_method
+                .as_deref()
+                .map(crate::cli::parse_sevenz_method)
+                .transpose()
+                .map_err(parse_error)?
+                .unwrap_or(defaults.sevenz_method),
+            // a block size asks for solid blocks unless they are turned off explicitly
+            sevenz_solid: self
+                .solid
+                .unwrap_or(defaults.sevenz_solid || self.solid_block_size.is_some()),
+            sevenz_solid_block_size: self
+                .solid_block_size
+                .as_deref()
+                .map(crate::cli::parse_solid_block_size)
+                .transpose()
+                .map_err(parse_error)?,
+            split_size: self
+                .split_size
+                .as_deref()
+                .map(crate::cli::parse_split_size)
+                .transpose()
+                .map_err(parse_error)?,
+            ..defaults
+        };
+        if self.redact.unwrap_or_default() {
+            options.normalize_permissions = true;
+            options.normalize_ownership = true;
+            options.strip_xattrs = true;
+            options.strip_timestamps = true;
+            options.deterministic = true;
+        }
+        Ok(options)
+    }
+
+    /// file filter from the exclude patterns, ignore files and redact mode
+    pub fn file_filter(&self) -> Result<FileFilter> {
+        let mut filter =
+            FileFilter::new(!self.no_default_excludes.unwrap_or_default(), &self.exclude)?;
+        if self.redact.unwrap_or_default() {
+            filter.add_redact_patterns()?;
+        }
+        for ignore_file in &self.exclude_from {
+            filter.add_ignore_file(ignore_file)?;
+        }
+        Ok(filter)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in self.exclude_from.iter_mut().chain(self.dict.as_mut()) {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    fn validate(&self, source: &Path) -> Result<()> {
+        if let Some(level) = self.level {
+            if !(1..=22).contains(&level) {
+                return Err(anyhow::anyhow!(
+                    "invalid compression level {level} in {} (expected 1-22)",
+                    source.display()
+                ));
+            }
+        }
+        if let Some(format) = &self.format {
+            crate::cli::parse_format(format)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(method) = &self.zip_method {
+            crate::cli::parse_zip_method(method)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        for (key, log) in [("long", self.long), ("window_log", self.window_log)] {
+            if let Some(log) = log {
+                if !(10..=31).contains(&log) {
+                    return Err(anyhow::anyhow!(
+                        "invalid {key} {log} in {} (expected 10-31)",
+                        source.display()
+                    ));
+                }
+            }
+        }
+        if let Some(strategy) = &self.strategy {
+            crate::cli::parse_zstd_strategy(strategy)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(size) = &self.target_block_size {
+            crate::cli::parse_zstd_block_size(size)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(size) = &self.seekable {
+            crate::cli::parse_zstd_frame_size(size)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(size) = &self.xz_block_size {
+            crate::cli::parse_xz_block_size(size)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(method) = &self.sevenz_method {
+            crate::cli::parse_sevenz_method(method)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(size) = &self.solid_block_size {
+            crate::cli::parse_solid_block_size(size)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        if let Some(size) = &self.split_size {
+            crate::cli::parse_split_size(size)
+                .map_err(|e| anyhow::anyhow!("{e} (in {})", source.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl ExtractDefaults {
+    /// overlay `other` on top of these settings
+    pub fn merge(&mut self, other: &ExtractDefaults) {
+        self.progress = other.progress.or(self.progress);
+        self.strip_components = other.strip_components.or(self.strip_components);
+        self.keep_xattrs = other.keep_xattrs.or(self.keep_xattrs);
+        self.strip_timestamps = other.strip_timestamps.or(self.strip_timestamps);
+        self.keep_permissions = other.keep_permissions.or(self.keep_permissions);
+        self.keep_ownership = other.keep_ownership.or(self.keep_ownership);
+        self.overwrite = other.overwrite.or(self.overwrite);
+    }
+
+    /// clear the settings a project-local file may not set, returning their names
+    fn take_user_only(&mut self) -> Vec<&'static str> {
+        let mut taken = Vec::new();
+        if self.overwrite.take().is_some() {
+            taken.push("overwrite");
+        }
+        if self.keep_ownership.take().is_some() {
+            taken.push("keep_ownership");
+        }
+        taken
+    }
+
+    /// these settings with `overrides` (usually the command line) applied on top
+    pub fn overlay(&self, overrides: &ExtractDefaults) -> ExtractDefaults {
+        let mut settings = self.clone();
+        settings.merge(overrides);
+        settings
+    }
+
+    /// extraction options for these settings; anything unset keeps its built-in default
+    pub fn extraction_options(&self) -> ExtractionOptions {
+        let defaults = ExtractionOptions::default();
+        ExtractionOptions {
+            overwrite: self.overwrite.unwrap_or(defaults.overwrite),
+            strip_components: self.strip_components.unwrap_or(defaults.strip_components),
+            strip_xattrs: !self.keep_xattrs.unwrap_or(!defaults.strip_xattrs),
+            strip_timestamps: self.strip_timestamps.unwrap_or(defaults.strip_timestamps),
+            preserve_permissions: self
+                .keep_permissions
+                .unwrap_or(defaults.preserve_permissions),
+            preserve_ownership: self.keep_ownership.unwrap_or(defaults.preserve_ownership),
+            ..defaults
+        }
+    }
+
+    /// built-in defaults, used to fill unset values when showing the configuration
+    pub fn builtin() -> Self {
+        let options = ExtractionOptions::default();
+        Self {
+            progress: Some(false),
+            strip_components: Some(options.strip_components),
+            keep_xattrs: Some(!options.strip_xattrs),
+            strip_timestamps: Some(options.strip_timestamps),
+            keep_permissions: Some(options.preserve_permissions),
+            keep_ownership: Some(options.preserve_ownership),
+            overwrite: Some(options.overwrite),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// read and validate a config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+        let mut config: ConfigFile = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file '{}'", path.display()))?;
+
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.compress.resolve_paths(base);
+        config.compress.validate(path)?;
+        for (name, profile) in &mut config.profiles {
+            profile.compress.resolve_paths(base);
+            profile
+                .compress
+                .validate(path)
+                .with_context(|| format!("invalid profile '{name}'"))?;
+        }
+
+        Ok(config)
+    }
+
+    /// drop the settings only the user config may change, warning about each one
+    fn ignore_user_only(&mut self, path: &Path) {
+        let mut ignored = Vec::new();
+        let mut sections = vec![("", &mut self.compress, &mut self.extract)];
+        for (name, profile) in &mut self.profiles {
+            sections.push((name, &mut profile.compress, &mut profile.extract));
+        }
+        for (profile, compress, extract) in sections {
+            let prefix = match profile {
+                "" => String::new(),
+                name => format!("profiles.{name}."),
+            };
+            for key in compress.take_user_only() {
+                ignored.push(format!("{prefix}compress.{key}"));
+            }
+            for key in extract.take_user_only() {
+                ignored.push(format!("{prefix}extract.{key}"));
+            }
+        }
+        if !ignored.is_empty() {
+            eprintln!(
+                "warning: ignoring {} from {}; set these in the user config or on the command line",
+                ignored.join(", "),
+                path.display()
+            );
+        }
+    }
+}
+
+impl Config {
+    /// discover and merge the user and project-local config files
+    pub fn load(profile: Option<&str>) -> Result<Self> {
+        let mut files = Vec::new();
+        if let Some(path) = user_config_path().filter(|path| path.is_file()) {
+            files.push((ConfigFile::load(&path)?, path));
+        }
+        let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+        if let Some(path) = local_config_path(&cwd) {
+            let mut file = ConfigFile::load(&path)?;
+            file.ignore_user_only(&path);
+            files.push((file, path));
+        }
+        Self::merge_files(files, profile)
+    }
+
+    /// merge the given config files in order, then apply the selected profile
+    pub fn from_files(paths: &[PathBuf], profile: Option<&str>) -> Result<Self> {
+        let files = paths
+            .iter()
+            .map(|path| Ok((ConfigFile::load(path)?, path.clone())))
+            .collect::<Result<_>>()?;
+        Self::merge_files(files, profile)
+    }
+
+    fn merge_files(files: Vec<(ConfigFile, PathBuf)>, profile: Option<&str>) -> Result<Self> {
+        let mut config = Config {
+            profile: profile.map(str::to_string),
+            ..Default::default()
+        };
+        let mut profile_found = false;
+        let mut profile_settings = Profile::default();
+
+        for (file, path) in files {
+            config.compress.merge(&file.compress);
+            config.extract.merge(&file.extract);
+            if let Some(selected) = file.profiles.get(profile.unwrap_or_default()) {
+                profile_found = true;
+                profile_settings.compress.merge(&selected.compress);
+                profile_settings.extract.merge(&selected.extract);
+            }
+            config.sources.push(path);
+        }
+
+        if let Some(name) = profile {
+            if !profile_found {
+                return Err(anyhow::anyhow!(
+                    "profile '{name}' not found in config files"
+                ));
+            }
+            config.compress.merge(&profile_settings.compress);
+            config.extract.merge(&profile_settings.extract);
+        }
+
+        Ok(config)
+    }
+
+    /// render the effective settings, with built-in defaults filled in, as TOML
+    pub fn render(&self) -> Result<String> {
+        let mut compress = CompressDefaults::builtin();
+        compress.merge(&self.compress);
+        let mut extract = ExtractDefaults::builtin();
+        extract.merge(&self.extract);
+
+        let effective = Profile { compress, extract };
+        let mut output = String::new();
+        if self.sources.is_empty() {
+            output.push_str("# no config files loaded\n");
+        }
+        for source in &self.sources {
+            output.push_str(&format!("# loaded: {}\n", source.display()));
+        }
+        if let Some(profile) = &self.profile {
+            output.push_str(&format!("# profile: {profile}\n"));
+        }
+        output.push('\n');
+        output.push_str(&toml::to_string_pretty(&effective).context("Failed to render config")?);
+        Ok(output)
+    }
+}
+
+/// read the reproducible-builds epoch from `SOURCE_DATE_EPOCH`, if set
+pub fn source_date_epoch() -> Result<Option<u64>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("invalid SOURCE_DATE_EPOCH '{value}'")),
+        _ => Ok(None),
+    }
+}
+
+/// path of the user config file (`$XDG_CONFIG_HOME/zzz/config.toml`)
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("zzz").join("config.toml"))
+}
+
+/// nearest project-local config file at or above `start`
+pub fn local_config_path(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(LOCAL_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_local_overrides_user_and_profile_overrides_both() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let user = temp_dir.path().join("config.toml");
+        let local = temp_dir.path().join(".zzz.toml");
+        fs::write(
+            &user,
+            "[compress]\nlevel = 5\nexclude = [\"*.tmp\"]\n\n[profiles.backup.compress]\nformat = \"7z\"\n",
+        )?;
+        fs::write(
+            &local,
+            "[compress]\nlevel = 9\nexclude = [\"*.log\"]\n\n[profiles.backup.compress]\nredact = true\n",
+        )?;
+
+        let config = Config::from_files(&[user.clone(), local.clone()], None)?;
+        assert_eq!(config.compress.level, Some(9));
+        assert_eq!(config.compress.exclude, vec!["*.tmp", "*.log"]);
+        assert_eq!(config.compress.format, None);
+
+        let config = Config::from_files(&[user, local], Some("backup"))?;
+        assert_eq!(config.compress.level, Some(9));
+        assert_eq!(config.compress.format.as_deref(), Some("7z"));
+        assert_eq!(config.compress.redact, Some(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_profile_is_an_error() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let local = temp_dir.path().join(".zzz.toml");
+        fs::write(&local, "[compress]\nlevel = 3\n")?;
+
+        let err = Config::from_files(&[local], Some("nightly")).unwrap_err();
+        assert!(err.to_string().contains("profile 'nightly' not found"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let local = temp_dir.path().join(".zzz.toml");
+
+        fs::write(&local, "[compress]\nlevel = 40\n")?;
+        assert!(ConfigFile::load(&local).is_err());
+
+        fs::write(&local, "[compress]\nformat = \"cab\"\n")?;
+        assert!(ConfigFile::load(&local).is_err());
+
+        fs::write(&local, "[compress]\nlevle = 3\n")?;
+        assert!(ConfigFile::load(&local).is_err());
+
+        Ok(())
+    }
+}
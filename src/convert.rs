@@ -0,0 +1,759 @@
+//! archive-to-archive conversion, streaming entries without touching the disk
+
+use crate::filter::FileFilter;
+use crate::formats::{
+    dedup::{self, DedupWriter},
+    sevenz, zip as zip_format, CompressionOptions, ExtractionOptions, FinishWrite, Format,
+    StreamPayload,
+};
+use crate::volumes::{self, OutputFile, VolumeSet};
+use crate::{compress, utils, Result};
+use anyhow::{bail, Context};
+use sevenz_rust::{SeqReader, SevenZArchiveEntry, SevenZWriter, SourceReader};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+/// modes given to entries without one, or when permissions are normalized
+const NORMALIZED_FILE_MODE: u32 = 0o644;
+const NORMALIZED_DIR_MODE: u32 = 0o755;
+const SYMLINK_MODE: u32 = 0o777;
+
+/// file type bits of a unix mode, and the type of a symlink
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 7-Zip's flag for a unix mode kept in the high half of the windows attributes
+const UNIX_EXTENSION_ATTRIBUTE: u32 = 0x8000;
+
+/// 7z solid blocks are buffered in memory while converting, so they are capped by default
+const DEFAULT_SOLID_BLOCK: u64 = 64 * 1024 * 1024;
+
+/// summary of a conversion
+pub struct ConvertStats {
+    pub entries: u64,
+    pub input_size: u64,
+    pub output_size: u64,
+}
+
+pub(crate) enum EntryKind {
+    File,
+    Directory,
+    Symlink(String),
+}
+
+/// one entry as read from the source archive
+pub(crate) struct Entry {
+    pub(crate) path: String,
+    pub(crate) kind: EntryKind,
+    /// content size, when the source records it up front
+    pub(crate) size: Option<u64>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) mtime: Option<SystemTime>,
+    pub(crate) owner: Option<(u64, u64)>,
+}
+
+impl Entry {
+    fn default_mode(&self) -> u32 {
+        match self.kind {
+            EntryKind::File => NORMALIZED_FILE_MODE,
+            EntryKind::Directory => NORMALIZED_DIR_MODE,
+            EntryKind::Symlink(_) => SYMLINK_MODE,
+        }
+    }
+}
+
+/// entries and metadata the target could not hold, reported once at the end
+#[derive(Default)]
+struct Losses {
+    modes: u64,
+    ownership: u64,
+    symlinks: u64,
+    hard_links: u64,
+    special: u64,
+}
+
+impl Losses {
+    fn report(&self, target: Format) {
+        let name = target.name();
+        if self.symlinks > 0 {
+            eprintln!(
+                "warning: skipped {} symlink(s): {name} archives cannot store symlinks",
+                self.symlinks
+            );
+        }
+        if self.modes > 0 {
+            eprintln!(
+                "warning: dropped the permissions of {} entries: {name} archives do not store unix modes",
+                self.modes
+            );
+        }
+        if self.ownership > 0 {
+            eprintln!(
+                "warning: dropped the ownership of {} entries: {name} archives do not store uid/gid",
+                self.ownership
+            );
+        }
+        if self.hard_links > 0 {
+            eprintln!(
+                "warning: skipped {} hard link(s), which cannot be converted",
+                self.hard_links
+            );
+        }
+        if self.special > 0 {
+            eprintln!(
+                "warning: skipped {} device or fifo entries, which cannot be converted",
+                self.special
+            );
+        }
+    }
+}
+
+/// a target archive being written
+pub(crate) trait Sink {
+    /// write one entry; `data` holds the contents of files
+    fn add(&mut self, entry: &Entry, data: &mut dyn Read) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// applies the filter and options to each source entry before handing it to the sink
+struct Converter<'a> {
+    sink: Box<dyn Sink + 'a>,
+    filter: &'a FileFilter,
+    options: &'a CompressionOptions,
+    losses: Losses,
+    /// what the target can represent: unix modes, uid/gid and symlinks
+    modes: bool,
+    ownership: bool,
+    symlinks: bool,
+    entries: u64,
+    verbose: bool,
+}
+
+impl Converter<'_> {
+    fn add(&mut self, mut entry: Entry, data: &mut dyn Read) -> Result<()> {
+        if self.included(&entry.path) {
+            self.normalize(&mut entry);
+            if matches!(entry.kind, EntryKind::Symlink(_)) && !self.symlinks {
+                self.losses.symlinks += 1;
+            } else {
+                if self.verbose {
+                    println!("  adding: {}", entry.path);
+                }
+                self.sink.add(&entry, data)?;
+                self.entries += 1;
+            }
+        }
+        // solid sources decode entries back to back, so each one is read to its end
+        std::io::copy(data, &mut std::io::sink())?;
+        Ok(())
+    }
+
+    /// entries are excluded along with everything below an excluded directory
+    fn included(&self, path: &str) -> bool {
+        Path::new(path)
+            .ancestors()
+            .all(|ancestor| self.filter.should_include_relative(ancestor))
+    }
+
+    fn normalize(&mut self, entry: &mut Entry) {
+        entry.mtime = match entry.mtime {
+            _ if self.options.strip_timestamps => None,
+            Some(mtime) => Some(self.options.clamp_mtime(mtime)),
+            None => None,
+        };
+
+        let default_mode = entry.default_mode();
+        entry.mode = if self.options.normalize_permissions {
+            None
+        } else {
+            entry.mode.map(|mode| mode & 0o7777)
+        };
+        if !self.modes && entry.mode.is_some_and(|mode| mode != default_mode) {
+            self.losses.modes += 1;
+        }
+
+        if self.options.normalize_ownership {
+            entry.owner = None;
+        }
+        if !self.ownership && entry.owner.is_some_and(|owner| owner != (0, 0)) {
+            self.losses.ownership += 1;
+        }
+    }
+}
+
+/// convert `input_path` into `output_path`, in the format given or implied by its name
+pub fn convert(
+    input_path: &Path,
+    output_path: &Path,
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+    filter: &FileFilter,
+    format_override: Option<Format>,
+    verbose: bool,
+) -> Result<ConvertStats> {
+    let source = Format::detect(input_path)?;
+    let target = match format_override {
+        Some(format) => format,
+        None => compress::detect_output_format(output_path)?,
+    };
+    if target == Format::Rar {
+        bail!("RAR compression is not supported");
+    }
+    ensure_distinct(input_path, output_path)?;
+    if options.password.is_some()
+        && !matches!(target, Format::Zstd | Format::SevenZ | Format::Dedup)
+    {
+        bail!(
+            "Password protection is not supported for {} format. Use zst, 7z or zzd for password protection.",
+            target.name()
+        );
+    }
+    // unrar only tells encrypted entries apart once it reads them; a password means it needed one
+    let encrypted = match source {
+        Format::Rar => extract_options.password.is_some(),
+        _ => source.is_encrypted(input_path)?,
+    };
+    if encrypted && options.password.is_none() {
+        eprintln!(
+            "warning: {} is encrypted but {} will not be; pass --output-password to encrypt it",
+            input_path.display(),
+            output_path.display()
+        );
+    }
+
+    if verbose {
+        println!(
+            "converting {} ({}) to {} ({})",
+            input_path.display(),
+            source.name(),
+            output_path.display(),
+            target.name()
+        );
+    }
+
+    let tarball = !matches!(target, Format::Zip | Format::SevenZ | Format::Dedup);
+    let mut converter = Converter {
+        sink: open_sink(target, output_path, options)?,
+        filter,
+        options,
+        losses: Losses::default(),
+        modes: target != Format::SevenZ,
+        ownership: tarball,
+        symlinks: !matches!(target, Format::SevenZ | Format::Dedup),
+        entries: 0,
+        verbose,
+    };
+
+    match source {
+        Format::Zip => read_zip(input_path, &mut converter)?,
+        Format::SevenZ => read_sevenz(input_path, extract_options, &mut converter)?,
+        Format::Rar => read_rar(input_path, extract_options, &mut converter)?,
+        Format::Dedup => dedup::read_entries(input_path, extract_options, |entry, data| {
+            converter.add(entry, data)
+        })?,
+        _ => read_stream(source, input_path, extract_options, &mut converter)?,
+    }
+
+    let Converter {
+        sink,
+        losses,
+        entries,
+        ..
+    } = converter;
+    sink.finish()?;
+    losses.report(target);
+
+    Ok(ConvertStats {
+        entries,
+        input_size: volumes::open(input_path)?.len(),
+        output_size: volumes::written_size(output_path, options.split_size)?,
+    })
+}
+
+/// refuse to write over the archive (or any volume of it) being read
+fn ensure_distinct(input_path: &Path, output_path: &Path) -> Result<()> {
+    let Ok(output) = output_path.canonicalize() else {
+        return Ok(());
+    };
+    let members = match VolumeSet::detect(input_path)? {
+        Some(set) => set.volumes,
+        None => vec![input_path.to_path_buf()],
+    };
+    for member in members {
+        if member.canonicalize().is_ok_and(|member| member == output) {
+            bail!(
+                "output file '{}' is the archive being converted",
+                output_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn open_sink<'a>(
+    target: Format,
+    output_path: &Path,
+    options: &'a CompressionOptions,
+) -> Result<Box<dyn Sink + 'a>> {
+    if let Some(writer) = target.tar_writer(output_path, options)? {
+        return Ok(Box::new(TarSink {
+            builder: tar::Builder::new(writer),
+        }));
+    }
+    match target {
+        Format::Zip => {
+            let file = volumes::create_zip(output_path, options.split_size)?;
+            Ok(Box::new(ZipSink {
+                writer: ZipWriter::new(BufWriter::new(file)),
+                options,
+            }))
+        }
+        Format::SevenZ => Ok(Box::new(SevenZSink::create(output_path, options)?)),
+        Format::Dedup => Ok(Box::new(DedupWriter::create(output_path, options, None)?)),
+        _ => unreachable!("single-stream formats are written through tar_writer"),
+    }
+}
+
+/// read a tarball or raw file from a single-stream format
+fn read_stream(
+    source: Format,
+    input_path: &Path,
+    options: &ExtractionOptions,
+    converter: &mut Converter,
+) -> Result<()> {
+    let (payload, mut reader) = source
+        .stream_reader(input_path, options)?
+        .expect("single-stream format");
+
+    if payload == StreamPayload::Raw {
+        // a raw stream holds one file, named after the archive like `zzz x` would
+        let archive_name = match VolumeSet::detect(input_path)? {
+            Some(set) => set.archive_name(),
+            None => input_path.to_path_buf(),
+        };
+        let entry = Entry {
+            path: utils::raw_output_name(&archive_name, &[]),
+            kind: EntryKind::File,
+            size: None,
+            mode: None,
+            mtime: std::fs::metadata(input_path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            owner: None,
+        };
+        return converter.add(entry, &mut reader);
+    }
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let kind = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+            tar::EntryType::Directory => EntryKind::Directory,
+            tar::EntryType::Symlink => EntryKind::Symlink(
+                entry
+                    .link_name()?
+                    .map(|target| target.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            tar::EntryType::Link => {
+                converter.losses.hard_links += 1;
+                continue;
+            }
+            _ => {
+                converter.losses.special += 1;
+                continue;
+            }
+        };
+        let converted = Entry {
+            path: entry
+                .path()?
+                .to_string_lossy()
+                .trim_end_matches('/')
+                .to_string(),
+            kind,
+            size: Some(entry.size()),
+            mode: header.mode().ok(),
+            mtime: header
+                .mtime()
+                .ok()
+                .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime)),
+            owner: header.uid().ok().zip(header.gid().ok()),
+        };
+        converter.add(converted, &mut entry)?;
+    }
+    Ok(())
+}
+
+fn read_zip(input_path: &Path, converter: &mut Converter) -> Result<()> {
+    let file = volumes::open(input_path)
+        .with_context(|| format!("Failed to open archive file {}", input_path.display()))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read ZIP archive from {}", input_path.display()))?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let kind = if file.is_dir() {
+            EntryKind::Directory
+        } else if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            EntryKind::Symlink(target)
+        } else {
+            EntryKind::File
+        };
+        let entry = Entry {
+            path: file.name().trim_end_matches('/').to_string(),
+            kind,
+            size: Some(file.size()),
+            mode: file.unix_mode(),
+            mtime: file
+                .last_modified()
+                .and_then(|dt| OffsetDateTime::try_from(dt).ok())
+                .map(SystemTime::from),
+            owner: None,
+        };
+        converter.add(entry, &mut file)?;
+    }
+    Ok(())
+}
+
+fn read_sevenz(
+    input_path: &Path,
+    options: &ExtractionOptions,
+    converter: &mut Converter,
+) -> Result<()> {
+    sevenz::for_each_entry(input_path, options.password.as_deref(), |entry, data| {
+        converter.add(sevenz_entry(entry, data)?, data)
+    })
+}
+
+/// describe a 7z entry, reading a symlink's target from its contents
+///
+/// 7-Zip and p7zip keep the unix mode (and with it the symlink type) above the
+/// windows attributes; archives made on windows have neither.
+fn sevenz_entry(entry: &SevenZArchiveEntry, data: &mut dyn Read) -> Result<Entry> {
+    let mode = (entry.has_windows_attributes
+        && entry.windows_attributes & UNIX_EXTENSION_ATTRIBUTE != 0)
+        .then_some(entry.windows_attributes >> 16);
+    let kind = if entry.is_directory {
+        EntryKind::Directory
+    } else if mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+        let mut target = String::new();
+        data.read_to_string(&mut target)?;
+        EntryKind::Symlink(target)
+    } else {
+        EntryKind::File
+    };
+    Ok(Entry {
+        path: entry.name.replace('\\', "/"),
+        kind,
+        size: Some(entry.size),
+        mode,
+        mtime: entry
+            .has_last_modified_date
+            .then(|| SystemTime::from(entry.last_modified_date)),
+        owner: None,
+    })
+}
+
+#[cfg(feature = "rar")]
+fn read_rar(
+    input_path: &Path,
+    options: &ExtractionOptions,
+    converter: &mut Converter,
+) -> Result<()> {
+    use crate::formats::rar;
+
+    let path = rar::path_str(input_path)?;
+    let password = options.password.as_deref();
+    let error = |e| rar::rar_error(password, e);
+    let mut archive = rar::archive(&path, password)
+        .open_for_processing()
+        .map_err(error)?;
+    // unrar only hands out whole entries in memory, so each file is extracted to a spool first
+    let spool_dir = tempfile::tempdir()?;
+    let spool = spool_dir.path().join("entry");
+
+    while let Some(header) = archive.read_header().map_err(error)? {
+        let entry = header.entry();
+        let time = entry.file_time;
+        let converted = Entry {
+            path: entry.filename.to_string_lossy().replace('\\', "/"),
+            kind: if entry.is_directory() {
+                EntryKind::Directory
+            } else {
+                EntryKind::File
+            },
+            size: Some(entry.unpacked_size),
+            mode: None,
+            mtime: zip::DateTime::try_from_msdos((time >> 16) as u16, time as u16)
+                .ok()
+                .and_then(|dt| OffsetDateTime::try_from(dt).ok())
+                .map(SystemTime::from),
+            owner: None,
+        };
+        if entry.is_file() {
+            archive = header.extract_to(&spool).map_err(error)?;
+            let mut data = File::open(&spool)?;
+            converter.add(converted, &mut data)?;
+            std::fs::remove_file(&spool)?;
+        } else {
+            converter.add(converted, &mut std::io::empty())?;
+            archive = header.skip().map_err(error)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "rar"))]
+fn read_rar(
+    _input_path: &Path,
+    _options: &ExtractionOptions,
+    _converter: &mut Converter,
+) -> Result<()> {
+    Err(crate::formats::rar::rar_not_enabled_error())
+}
+
+/// tar entries in one of the single-stream formats
+pub(crate) struct TarSink {
+    pub(crate) builder: tar::Builder<Box<dyn FinishWrite>>,
+}
+
+impl Sink for TarSink {
+    fn add(&mut self, entry: &Entry, data: &mut dyn Read) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(entry.mode.unwrap_or(entry.default_mode()));
+        let (uid, gid) = entry.owner.unwrap_or_default();
+        header.set_uid(uid);
+        header.set_gid(gid);
+        let mtime = entry
+            .mtime
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |mtime| mtime.as_secs());
+        header.set_mtime(mtime);
+
+        match &entry.kind {
+            EntryKind::File => {
+                header.set_entry_type(tar::EntryType::Regular);
+                match entry.size {
+                    Some(size) => {
+                        header.set_size(size);
+                        self.builder.append_data(&mut header, &entry.path, data)?;
+                    }
+                    None => {
+                        // tar needs the size up front; only raw streams lack it, and they can
+                        // be any size, so they are spooled to a temporary file
+                        let mut spool = tempfile::tempfile()?;
+                        let size = std::io::copy(data, &mut spool)?;
+                        spool.rewind()?;
+                        header.set_size(size);
+                        self.builder.append_data(&mut header, &entry.path, spool)?;
+                    }
+                }
+            }
+            EntryKind::Directory => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                self.builder.append_data(
+                    &mut header,
+                    format!("{}/", entry.path),
+                    std::io::empty(),
+                )?;
+            }
+            EntryKind::Symlink(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                self.builder.append_link(&mut header, &entry.path, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.builder.into_inner()?.finish()
+    }
+}
+
+/// where a [`ZipSink`] writes, completed once the central directory is in place
+pub(crate) trait ZipOutput: Write + Seek {
+    fn complete(self, deterministic: bool) -> Result<()>;
+}
+
+impl ZipOutput for File {
+    fn complete(mut self, deterministic: bool) -> Result<()> {
+        if deterministic {
+            zip_format::normalize_host_fields(&mut self)?;
+        }
+        Ok(())
+    }
+}
+
+impl ZipOutput for BufWriter<OutputFile> {
+    fn complete(self, deterministic: bool) -> Result<()> {
+        let mut output = self.into_inner()?;
+        if deterministic {
+            zip_format::normalize_host_fields(&mut output)?;
+        }
+        output.finish()
+    }
+}
+
+pub(crate) struct ZipSink<'a, W: ZipOutput> {
+    pub(crate) writer: ZipWriter<W>,
+    pub(crate) options: &'a CompressionOptions,
+}
+
+impl<W: ZipOutput> Sink for ZipSink<'_, W> {
+    fn add(&mut self, entry: &Entry, data: &mut dyn Read) -> Result<()> {
+        let last_modified = entry
+            .mtime
+            .and_then(|mtime| zip::DateTime::try_from(OffsetDateTime::from(mtime)).ok())
+            .unwrap_or_default();
+        let file_options = FileOptions::<()>::default()
+            .last_modified_time(last_modified)
+            .unix_permissions(entry.mode.unwrap_or(entry.default_mode()));
+
+        match &entry.kind {
+            EntryKind::File => {
+                // the start of the file decides whether it is stored or compressed
+                let mut sample = Vec::new();
+                data.take(utils::ENTROPY_SAMPLE_SIZE as u64)
+                    .read_to_end(&mut sample)?;
+                let precompressed = utils::looks_precompressed(Path::new(&entry.path), &sample);
+                let (method, level) = zip_format::compression_for(precompressed, self.options);
+                let file_options = file_options
+                    .compression_method(method)
+                    .compression_level(level)
+                    .large_file(entry.size.is_none_or(|size| size > u64::from(u32::MAX)));
+                self.writer.start_file(entry.path.as_str(), file_options)?;
+                self.writer.write_all(&sample)?;
+                std::io::copy(data, &mut self.writer)?;
+            }
+            EntryKind::Directory => {
+                self.writer
+                    .add_directory(format!("{}/", entry.path), file_options)?;
+            }
+            EntryKind::Symlink(target) => {
+                self.writer
+                    .add_symlink(entry.path.as_str(), target, file_options)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.finish()?.complete(self.options.deterministic)
+    }
+}
+
+/// 7z entries, with small files gathered into solid blocks held in memory
+pub(crate) struct SevenZSink<'a> {
+    writer: SevenZWriter<OutputFile>,
+    output_path: PathBuf,
+    options: &'a CompressionOptions,
+    block: Vec<(SevenZArchiveEntry, Vec<u8>)>,
+    block_size: u64,
+}
+
+impl<'a> SevenZSink<'a> {
+    pub(crate) fn create(output_path: &Path, options: &'a CompressionOptions) -> Result<Self> {
+        let output = volumes::create(output_path, options.split_size)?;
+        let mut writer = SevenZWriter::new(output).with_context(|| {
+            format!(
+                "Failed to create 7-Zip writer for {}",
+                output_path.display()
+            )
+        })?;
+        // the total size is unknown up front, so the preset's full dictionary is kept
+        writer.set_content_methods(sevenz::content_methods(options, u64::from(u32::MAX)));
+        Ok(Self {
+            writer,
+            output_path: output_path.to_path_buf(),
+            options,
+            block: Vec::new(),
+            block_size: 0,
+        })
+    }
+
+    /// write an entry as given; files without a stream and directories carry no data
+    pub(crate) fn push(&mut self, entry: SevenZArchiveEntry, data: &mut dyn Read) -> Result<()> {
+        if entry.is_directory || !entry.has_stream {
+            self.writer
+                .push_archive_entry(entry, None::<std::io::Empty>)?;
+            return Ok(());
+        }
+
+        if !self.options.sevenz_solid {
+            self.writer.push_archive_entry(entry, Some(data))?;
+            return Ok(());
+        }
+
+        let limit = self
+            .options
+            .sevenz_solid_block_size
+            .unwrap_or(DEFAULT_SOLID_BLOCK);
+        let mut contents = Vec::new();
+        data.take(limit + 1).read_to_end(&mut contents)?;
+        let size = contents.len() as u64;
+        if size == 0 {
+            // empty files carry no stream, so they stay out of solid blocks
+            self.writer
+                .push_archive_entry(entry, None::<std::io::Empty>)?;
+        } else if size > limit {
+            // too large to buffer: the file becomes a block of its own, in order
+            self.push_block()?;
+            self.writer
+                .push_archive_entry(entry, Some(Cursor::new(contents).chain(data)))?;
+        } else {
+            if self.block_size + size > limit {
+                self.push_block()?;
+            }
+            self.block.push((entry, contents));
+            self.block_size += size;
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self) -> Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let (entries, contents): (Vec<_>, Vec<_>) = self.block.drain(..).unzip();
+        let readers = contents
+            .into_iter()
+            .map(|contents| SourceReader::new(Cursor::new(contents)))
+            .collect();
+        self.writer
+            .push_archive_entries(entries, SeqReader::new(readers))?;
+        self.block_size = 0;
+        Ok(())
+    }
+}
+
+impl Sink for SevenZSink<'_> {
+    fn add(&mut self, entry: &Entry, data: &mut dyn Read) -> Result<()> {
+        let mut archive_entry = SevenZArchiveEntry::new();
+        archive_entry.name = entry.path.clone();
+        if let Some(date) = entry
+            .mtime
+            .and_then(|mtime| sevenz::entry_date(Ok(mtime), self.options))
+        {
+            archive_entry.last_modified_date = date;
+            archive_entry.has_last_modified_date = true;
+        }
+        archive_entry.is_directory = !matches!(entry.kind, EntryKind::File);
+        archive_entry.has_stream = !archive_entry.is_directory;
+        self.push(archive_entry, data)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.push_block()?;
+        sevenz::finish_writer(self.writer, &self.output_path, self.options)
+    }
+}
@@ -0,0 +1,70 @@
+//! zstd dictionary training and loading
+
+use crate::Result;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// train a zstd dictionary from sample files; directories are walked recursively
+pub fn train(samples: &[PathBuf], output: &Path, max_size: u64, verbose: bool) -> Result<()> {
+    let files = collect_samples(samples)?;
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("no sample files found"));
+    }
+
+    if verbose {
+        println!("training dictionary from {} samples", files.len());
+    }
+
+    let max_size = usize::try_from(max_size).context("dictionary size too large")?;
+    let dictionary = zstd::dict::from_files(&files, max_size).with_context(|| {
+        format!(
+            "failed to train zstd dictionary from {} samples (zstd needs many small samples, ideally ~100x the dictionary size in total)",
+            files.len()
+        )
+    })?;
+
+    std::fs::write(output, &dictionary)
+        .with_context(|| format!("failed to write dictionary {}", output.display()))?;
+
+    println!(
+        "trained dictionary {} ({}) from {} samples: {}",
+        describe_id(dictionary_id(&dictionary)),
+        crate::utils::format_bytes(dictionary.len() as u64),
+        files.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// read a dictionary file passed with `--dict`
+pub fn load(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read dictionary {}", path.display()))
+}
+
+/// ID recorded in a trained dictionary; raw content dictionaries have none
+pub fn dictionary_id(dictionary: &[u8]) -> Option<u32> {
+    zstd::zstd_safe::get_dict_id_from_dict(dictionary).map(|id| id.get())
+}
+
+/// human-readable dictionary ID for messages
+pub fn describe_id(id: Option<u32>) -> String {
+    match id {
+        Some(id) => format!("ID {id}"),
+        None => "no ID (raw content)".to_string(),
+    }
+}
+
+fn collect_samples(samples: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for sample in samples {
+        for entry in WalkDir::new(sample).sort_by_file_name() {
+            let entry =
+                entry.with_context(|| format!("failed to read sample {}", sample.display()))?;
+            if entry.file_type().is_file() {
+                files.push(entry.into_path());
+            }
+        }
+    }
+    Ok(files)
+}
@@ -13,7 +13,7 @@ use std::io::{self, ErrorKind, Read};
 
 // AES-GCM imports
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng as AeadOsRng},
+    aead::{Aead, KeyInit, OsRng as AeadOsRng, Payload},
     Aes256Gcm, Nonce,
 };
 
@@ -318,6 +318,49 @@ impl<R: Read> Read for DecryptingReader<R> {
     }
 }
 
+/// Encrypt one self-contained block with AES-256-GCM, authenticating `aad` along with it
+///
+/// The block is written as: [nonce: 12 bytes][ciphertext_with_tag: variable]
+pub fn seal_block(key: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| anyhow!("Failed to initialize AES-GCM cipher: {e}"))?;
+
+    let mut block = vec![0u8; NONCE_SIZE];
+    AeadOsRng
+        .try_fill_bytes(&mut block)
+        .context("Failed to generate nonce for encryption")?;
+    let ciphertext_with_tag = cipher
+        .encrypt(
+            Nonce::from_slice(&block),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|e| anyhow!("AES-GCM encryption failed: {e}"))?;
+    block.extend_from_slice(&ciphertext_with_tag);
+    Ok(block)
+}
+
+/// Decrypt a block written by [`seal_block`] with the same `aad`
+pub fn open_block(key: &[u8], aad: &[u8], block: &[u8]) -> Result<Vec<u8>> {
+    if block.len() < NONCE_SIZE + TAG_SIZE {
+        bail!("Encrypted block too short: {} bytes", block.len());
+    }
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| anyhow!("Failed to initialize AES-GCM cipher: {e}"))?;
+    let (nonce, ciphertext_with_tag) = block.split_at(NONCE_SIZE);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext_with_tag,
+                aad,
+            },
+        )
+        .map_err(|e| anyhow!("AES-GCM decryption failed (data integrity or key error): {e}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -441,4 +484,21 @@ mod tests {
         assert!(err.to_string().contains("Invalid ciphertext length"));
         Ok(())
     }
+
+    #[test]
+    fn test_seal_block_roundtrip() {
+        let (key, _) = derive_key("test_password", Some(&[7u8; ARGON2_SALT_LEN])).unwrap();
+        let block = seal_block(&key, b"chunk 1", b"secret data").unwrap();
+        assert_eq!(block.len(), NONCE_SIZE + b"secret data".len() + TAG_SIZE);
+        assert_eq!(
+            open_block(&key, b"chunk 1", &block).unwrap(),
+            b"secret data"
+        );
+
+        // the block is bound to its associated data
+        assert!(open_block(&key, b"chunk 2", &block).is_err());
+        let mut tampered = block.clone();
+        tampered[NONCE_SIZE] ^= 1;
+        assert!(open_block(&key, b"chunk 1", &tampered).is_err());
+    }
 }
@@ -1,8 +1,9 @@
 //! extraction orchestration
 
 use crate::formats::{
-    gz::GzipFormat, rar::RarFormat, sevenz::SevenZFormat, xz::XzFormat, zip::ZipFormat,
-    zstd::ZstdFormat, CompressionFormat, ExtractionOptions, Format,
+    br::BrotliFormat, bz2::Bzip2Format, dedup::DedupFormat, gz::GzipFormat, lz4::Lz4Format,
+    lzma::LzmaFormat, rar::RarFormat, sevenz::SevenZFormat, tar::TarFormat, xz::XzFormat,
+    zip::ZipFormat, zstd::ZstdFormat, CompressionFormat, ExtractionOptions, Format,
 };
 use crate::progress::Progress;
 use crate::Result;
@@ -31,6 +32,10 @@ pub fn extract(
         println!("detected {} format", format.name());
     }
 
+    if !options.entries.is_empty() && matches!(format, Format::Zip | Format::SevenZ | Format::Rar) {
+        anyhow::bail!("--entry is only supported for tar-based archives");
+    }
+
     // ensure output directory exists
     if !output_dir.exists() {
         std::fs::create_dir_all(output_dir)?;
@@ -38,10 +43,15 @@ pub fn extract(
 
     // create progress tracker (enabled when requested or verbose)
     let progress = match format {
-        Format::Zstd | Format::Gzip | Format::Xz => {
-            Progress::new(show_progress || verbose, 0, verbose)
-        }
-        Format::Zip | Format::SevenZ | Format::Rar => {
+        Format::Zstd
+        | Format::Gzip
+        | Format::Xz
+        | Format::Tar
+        | Format::Bzip2
+        | Format::Lz4
+        | Format::Lzma
+        | Format::Brotli => Progress::new(show_progress || verbose, 0, verbose),
+        Format::Zip | Format::SevenZ | Format::Rar | Format::Dedup => {
             Progress::new_items(show_progress || verbose, 0, verbose)
         }
     };
@@ -56,6 +66,14 @@ pub fn extract(
             SevenZFormat::extract(archive_path, output_dir, &options, Some(&progress))?
         }
         Format::Rar => RarFormat::extract(archive_path, output_dir, &options, Some(&progress))?,
+        Format::Tar => TarFormat::extract(archive_path, output_dir, &options, Some(&progress))?,
+        Format::Bzip2 => Bzip2Format::extract(archive_path, output_dir, &options, Some(&progress))?,
+        Format::Lz4 => Lz4Format::extract(archive_path, output_dir, &options, Some(&progress))?,
+        Format::Lzma => LzmaFormat::extract(archive_path, output_dir, &options, Some(&progress))?,
+        Format::Brotli => {
+            BrotliFormat::extract(archive_path, output_dir, &options, Some(&progress))?
+        }
+        Format::Dedup => DedupFormat::extract(archive_path, output_dir, &options, Some(&progress))?,
     }
 
     progress.finish_with_message("extraction completed");
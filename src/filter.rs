@@ -1,9 +1,10 @@
 //! file filtering system with comprehensive garbage file exclusion
 
 use crate::Result;
+use anyhow::Context;
 use glob::Pattern;
 use once_cell::sync::Lazy;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 /// comprehensive list of garbage files to exclude by default
@@ -125,16 +126,73 @@ pub const SENSITIVE_FILES: &[&str] = &[
     ".sops.json",
 ];
 
-static COMPILED_GARBAGE_PATTERNS: Lazy<Vec<Pattern>> = Lazy::new(|| {
+/// where an exclusion rule came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcludeSource {
+    /// built-in garbage file list (`GARBAGE_FILES`)
+    Default,
+    /// sensitive file list enabled by `--redact` (`SENSITIVE_FILES`)
+    Redact,
+    /// pattern passed with `-e/--exclude`
+    User,
+    /// pattern read from an ignore file (`--exclude-from`)
+    IgnoreFile(PathBuf),
+}
+
+impl std::fmt::Display for ExcludeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExcludeSource::Default => write!(f, "default list"),
+            ExcludeSource::Redact => write!(f, "redact list"),
+            ExcludeSource::User => write!(f, "user"),
+            ExcludeSource::IgnoreFile(path) => write!(f, "ignore file {}", path.display()),
+        }
+    }
+}
+
+/// a compiled exclusion pattern together with its source
+#[derive(Debug, Clone)]
+pub struct ExcludeRule {
+    pub pattern: Pattern,
+    pub source: ExcludeSource,
+}
+
+/// the rule responsible for excluding a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludeMatch {
+    pub pattern: String,
+    pub source: ExcludeSource,
+}
+
+impl From<&ExcludeRule> for ExcludeMatch {
+    fn from(rule: &ExcludeRule) -> Self {
+        Self {
+            pattern: rule.pattern.as_str().to_string(),
+            source: rule.source.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ExcludeMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} pattern '{}'", self.source, self.pattern)
+    }
+}
+
+static COMPILED_GARBAGE_RULES: Lazy<Vec<ExcludeRule>> = Lazy::new(|| {
     GARBAGE_FILES
         .iter()
         .filter_map(|s| Pattern::new(s).ok())
+        .map(|pattern| ExcludeRule {
+            pattern,
+            source: ExcludeSource::Default,
+        })
         .collect()
 });
 
 pub struct FileFilter {
     use_defaults: bool,
-    custom_excludes: Vec<Pattern>,
+    custom_excludes: Vec<ExcludeRule>,
 }
 
 impl FileFilter {
@@ -148,9 +206,15 @@ impl FileFilter {
         parts.join("/")
     }
 
-    fn matches_patterns(patterns: &[Pattern], filename: &str, relative_path: &Path) -> bool {
-        if !filename.is_empty() && patterns.iter().any(|pattern| pattern.matches(filename)) {
-            return true;
+    fn matching_rule<'a>(
+        rules: &'a [ExcludeRule],
+        filename: &str,
+        relative_path: &Path,
+    ) -> Option<&'a ExcludeRule> {
+        if !filename.is_empty() {
+            if let Some(rule) = rules.iter().find(|rule| rule.pattern.matches(filename)) {
+                return Some(rule);
+            }
         }
 
         let components: Vec<String> = relative_path
@@ -161,11 +225,10 @@ impl FileFilter {
             })
             .collect();
 
-        if components
-            .iter()
-            .any(|component| patterns.iter().any(|pattern| pattern.matches(component)))
-        {
-            return true;
+        for component in &components {
+            if let Some(rule) = rules.iter().find(|rule| rule.pattern.matches(component)) {
+                return Some(rule);
+            }
         }
 
         for ancestor in relative_path.ancestors() {
@@ -173,56 +236,90 @@ impl FileFilter {
             if ancestor_str.is_empty() {
                 continue;
             }
-            if patterns
+            if let Some(rule) = rules
                 .iter()
-                .any(|pattern| pattern.matches(&ancestor_str))
+                .find(|rule| rule.pattern.matches(&ancestor_str))
             {
-                return true;
+                return Some(rule);
             }
         }
 
-        false
+        None
     }
 
     /// create new file filter with optional custom patterns
     pub fn new(use_defaults: bool, custom_patterns: &[String]) -> Result<Self> {
-        let mut custom_excludes = Vec::new();
-        for pattern in custom_patterns {
-            custom_excludes.push(Pattern::new(pattern)?);
-        }
-        Ok(Self {
+        let mut filter = Self {
             use_defaults,
-            custom_excludes,
-        })
+            custom_excludes: Vec::new(),
+        };
+        filter.add_patterns(custom_patterns, ExcludeSource::User)?;
+        Ok(filter)
     }
 
-    /// check if a path should be excluded from archiving
-    pub fn should_exclude(&self, path: &Path) -> bool {
+    /// add exclusion patterns attributed to the given source
+    pub fn add_patterns<S: AsRef<str>>(
+        &mut self,
+        patterns: &[S],
+        source: ExcludeSource,
+    ) -> Result<()> {
+        for pattern in patterns {
+            self.custom_excludes.push(ExcludeRule {
+                pattern: Pattern::new(pattern.as_ref())?,
+                source: source.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// add the sensitive file patterns used by redact mode
+    pub fn add_redact_patterns(&mut self) -> Result<()> {
+        self.add_patterns(SENSITIVE_FILES, ExcludeSource::Redact)
+    }
+
+    /// add patterns from an ignore file (one glob per line, `#` starts a comment)
+    pub fn add_ignore_file(&mut self, path: &Path) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ignore file '{}'", path.display()))?;
+        let patterns: Vec<&str> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        self.add_patterns(&patterns, ExcludeSource::IgnoreFile(path.to_path_buf()))
+            .with_context(|| format!("Invalid pattern in ignore file '{}'", path.display()))
+    }
+
+    /// explain why a path would be excluded, matching against its filename only
+    pub fn explain(&self, path: &Path) -> Option<ExcludeMatch> {
         let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
         // check custom patterns first (against filename only for consistency)
-        for pattern in &self.custom_excludes {
-            if pattern.matches(filename) {
-                return true;
-            }
+        if let Some(rule) = self
+            .custom_excludes
+            .iter()
+            .find(|rule| rule.pattern.matches(filename))
+        {
+            return Some(rule.into());
         }
 
         // check default garbage files if enabled
         if self.use_defaults {
-            for pattern in &*COMPILED_GARBAGE_PATTERNS {
-                if pattern.matches(filename) {
-                    return true;
-                }
+            if let Some(rule) = COMPILED_GARBAGE_RULES
+                .iter()
+                .find(|rule| rule.pattern.matches(filename))
+            {
+                return Some(rule.into());
             }
         }
 
-        false
+        None
     }
 
-    /// check if a relative path should be excluded from archiving
-    pub fn should_exclude_relative(&self, relative_path: &Path) -> bool {
+    /// explain why a relative path would be excluded from archiving
+    pub fn explain_relative(&self, relative_path: &Path) -> Option<ExcludeMatch> {
         if relative_path.as_os_str().is_empty() {
-            return false;
+            return None;
         }
 
         let filename = relative_path
@@ -230,17 +327,35 @@ impl FileFilter {
             .and_then(|n| n.to_str())
             .unwrap_or("");
 
-        if Self::matches_patterns(&self.custom_excludes, filename, relative_path) {
-            return true;
+        if let Some(rule) = Self::matching_rule(&self.custom_excludes, filename, relative_path) {
+            return Some(rule.into());
         }
 
-        if self.use_defaults
-            && Self::matches_patterns(&COMPILED_GARBAGE_PATTERNS, filename, relative_path)
-        {
-            return true;
+        if self.use_defaults {
+            if let Some(rule) =
+                Self::matching_rule(&COMPILED_GARBAGE_RULES, filename, relative_path)
+            {
+                return Some(rule.into());
+            }
         }
 
-        false
+        None
+    }
+
+    /// explain why a path would be excluded, based on its relative path to a root
+    pub fn explain_path(&self, root: &Path, path: &Path) -> Option<ExcludeMatch> {
+        let relative_path = path.strip_prefix(root).unwrap_or(path);
+        self.explain_relative(relative_path)
+    }
+
+    /// check if a path should be excluded from archiving
+    pub fn should_exclude(&self, path: &Path) -> bool {
+        self.explain(path).is_some()
+    }
+
+    /// check if a relative path should be excluded from archiving
+    pub fn should_exclude_relative(&self, relative_path: &Path) -> bool {
+        self.explain_relative(relative_path).is_some()
     }
 
     /// check if a path should be excluded, based on its relative path to a root
@@ -283,6 +398,28 @@ impl FileFilter {
             .into_iter()
             .filter_entry(move |entry| self.should_include_path(root, entry.path()))
     }
+
+    /// walk a directory tree and collect the pruned entries with the rule that excluded them
+    pub fn excluded_entries(
+        &self,
+        root: &Path,
+        follow_links: bool,
+    ) -> Vec<(PathBuf, ExcludeMatch)> {
+        let mut excluded = Vec::new();
+        let walker = WalkDir::new(root)
+            .follow_links(follow_links)
+            .into_iter()
+            .filter_entry(|entry| match self.explain_path(root, entry.path()) {
+                Some(rule) => {
+                    excluded.push((entry.path().to_path_buf(), rule));
+                    false
+                }
+                None => true,
+            });
+        // errors are reported by the archiving walk itself
+        for _ in walker {}
+        excluded
+    }
 }
 
 #[cfg(test)]
@@ -412,6 +549,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_explain_reports_rule_source() -> Result<()> {
+        let mut filter = FileFilter::new(true, &["*.log".to_string()])?;
+        filter.add_redact_patterns()?;
+
+        let user = filter.explain_relative(Path::new("logs/debug.log"));
+        assert_eq!(
+            user,
+            Some(ExcludeMatch {
+                pattern: "*.log".to_string(),
+                source: ExcludeSource::User,
+            })
+        );
+
+        let redact = filter.explain_relative(Path::new("config/.env"));
+        assert_eq!(redact.map(|m| m.source), Some(ExcludeSource::Redact));
+
+        let default = filter.explain_relative(Path::new("vendor/.git/config"));
+        assert_eq!(
+            default,
+            Some(ExcludeMatch {
+                pattern: ".git".to_string(),
+                source: ExcludeSource::Default,
+            })
+        );
+
+        assert_eq!(filter.explain_relative(Path::new("src/main.rs")), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_file_patterns() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let ignore_path = temp_dir.path().join(".zzzignore");
+        std::fs::write(&ignore_path, "# build outputs\n\ndist\n*.bin\n")?;
+
+        let mut filter = FileFilter::new(false, &[])?;
+        filter.add_ignore_file(&ignore_path)?;
+
+        let matched = filter.explain_relative(Path::new("project/dist/app.js"));
+        assert_eq!(
+            matched,
+            Some(ExcludeMatch {
+                pattern: "dist".to_string(),
+                source: ExcludeSource::IgnoreFile(ignore_path.clone()),
+            })
+        );
+        assert!(filter.should_exclude_relative(Path::new("firmware.bin")));
+        assert!(!filter.should_exclude_relative(Path::new("README.md")));
+
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_pattern() {
         // Test that invalid glob patterns return an error
@@ -0,0 +1,85 @@
+//! Brotli format support (tar.br and raw .br)
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        stream::{self, StreamCodec},
+        ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+    },
+    progress::Progress,
+    Result,
+};
+use brotli::{CompressorWriter, Decompressor};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+const BROTLI_BUFFER_SIZE: usize = 64 * 1024;
+const BROTLI_WINDOW_LOG: u32 = 22;
+
+pub struct BrotliFormat;
+
+pub(crate) struct BrotliCodec;
+
+impl StreamCodec for BrotliCodec {
+    const NAME: &'static str = "Brotli";
+    const TAR_SUFFIXES: &'static [&'static str] = &[".tar.br"];
+    const RAW_SUFFIX: Option<&'static str> = Some(".br");
+
+    type Encoder<W: Write> = CompressorWriter<W>;
+
+    fn encoder<W: Write>(writer: W, level: i32) -> Result<Self::Encoder<W>> {
+        // Map compression level (1-22) to brotli quality (0-11)
+        let quality = ((level.clamp(1, 22) - 1) * 11 / 21) as u32;
+        Ok(CompressorWriter::new(
+            writer,
+            BROTLI_BUFFER_SIZE,
+            quality,
+            BROTLI_WINDOW_LOG,
+        ))
+    }
+
+    fn finish<W: Write>(mut encoder: Self::Encoder<W>) -> Result<W> {
+        // surface write errors before into_inner, which finishes the stream silently
+        encoder.flush()?;
+        Ok(encoder.into_inner())
+    }
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(Decompressor::new(reader, BROTLI_BUFFER_SIZE)))
+    }
+}
+
+impl CompressionFormat for BrotliFormat {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        stream::compress::<BrotliCodec>(input_path, output_path, options, filter, progress)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        stream::extract::<BrotliCodec>(archive_path, output_dir, options, progress)
+    }
+
+    fn extension() -> &'static str {
+        "tar.br"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        stream::list::<BrotliCodec>(archive_path, options)
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        stream::test_integrity::<BrotliCodec>(archive_path, options)
+    }
+}
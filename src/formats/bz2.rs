@@ -0,0 +1,76 @@
+//! bzip2 format support (tar.bz2/tbz2 and raw .bz2)
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        stream::{self, StreamCodec},
+        ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+    },
+    progress::Progress,
+    Result,
+};
+use bzip2::{read::MultiBzDecoder, write::BzEncoder, Compression};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+pub struct Bzip2Format;
+
+pub(crate) struct Bzip2Codec;
+
+impl StreamCodec for Bzip2Codec {
+    const NAME: &'static str = "bzip2";
+    const TAR_SUFFIXES: &'static [&'static str] = &[".tar.bz2", ".tbz2", ".tbz"];
+    const RAW_SUFFIX: Option<&'static str> = Some(".bz2");
+
+    type Encoder<W: Write> = BzEncoder<W>;
+
+    fn encoder<W: Write>(writer: W, level: i32) -> Result<Self::Encoder<W>> {
+        // Map compression level (1-22) to bzip2 block size (1-9)
+        let bz_level = ((level.clamp(1, 22) - 1) * 8 / 21 + 1) as u32;
+        Ok(BzEncoder::new(writer, Compression::new(bz_level)))
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> Result<W> {
+        Ok(encoder.finish()?)
+    }
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+        // parallel bzip2 tools emit concatenated streams
+        Ok(Box::new(MultiBzDecoder::new(reader)))
+    }
+}
+
+impl CompressionFormat for Bzip2Format {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        stream::compress::<Bzip2Codec>(input_path, output_path, options, filter, progress)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        stream::extract::<Bzip2Codec>(archive_path, output_dir, options, progress)
+    }
+
+    fn extension() -> &'static str {
+        "tbz2"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        stream::list::<Bzip2Codec>(archive_path, options)
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        stream::test_integrity::<Bzip2Codec>(archive_path, options)
+    }
+}
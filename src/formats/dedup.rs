@@ -0,0 +1,1143 @@
+//! deduplicating archives: content-defined chunks, each stored once
+//!
+//! file contents are cut with FastCDC, a gear-hash rolling chunker, so an edit only changes
+//! the chunks around it. every distinct chunk is compressed with zstd (and encrypted with
+//! AES-256-GCM when a password is given) and stored once; the index at the end maps each
+//! file to its list of chunks.
+//!
+//! layout: header (magic, version, flags, salt when encrypted), chunk blobs, index blob,
+//! trailer (index offset and length, magic). all integers are little-endian.
+
+use super::{
+    ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+};
+use crate::convert::{Entry, EntryKind, Sink};
+use crate::encryption::{self, ARGON2_SALT_LEN};
+use crate::filter::FileFilter;
+use crate::progress::Progress;
+use crate::volumes::{self, OutputFile, VolumeReader};
+use crate::{modify, utils, Result};
+use anyhow::{anyhow, bail, Context};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct DedupFormat;
+
+const MAGIC: &[u8; 8] = b"ZZZDEDUP";
+const VERSION: u8 = 1;
+const FLAG_ENCRYPTED: u8 = 1;
+/// index offset, index length and magic
+const TRAILER_SIZE: u64 = 24;
+
+/// chunk size bounds; cutting gets easier once a chunk passes the average
+const MIN_CHUNK: usize = 16 * 1024;
+const AVG_CHUNK: usize = 64 * 1024;
+const MAX_CHUNK: usize = 256 * 1024;
+/// normalized chunking: two more mask bits than the 64K average below it, two fewer above
+const MASK_SMALL: u64 = u64::MAX << (64 - 18);
+const MASK_LARGE: u64 = u64::MAX << (64 - 14);
+const GEAR: [u64; 256] = gear_table();
+
+/// associated data of the encrypted index; chunks use their number
+const INDEX_AAD: u64 = u64::MAX;
+
+const KIND_FILE: u8 = 0;
+const KIND_DIRECTORY: u8 = 1;
+const HAS_MODE: u8 = 1;
+const HAS_MTIME: u8 = 2;
+
+/// unique chunks compressed together on the worker threads, per thread
+const CHUNKS_PER_THREAD: usize = 8;
+
+/// random gear values for the rolling hash, fixed so chunk boundaries are reproducible
+const fn gear_table() -> [u64; 256] {
+    // splitmix64
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x5a5a_5a5a_5a5a_5a5a;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// length of the chunk at the start of `data`
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let normal = AVG_CHUNK.min(data.len());
+    let max = MAX_CHUNK.min(data.len());
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(max).skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[usize::from(byte)]);
+        let mask = if i < normal { MASK_SMALL } else { MASK_LARGE };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    max
+}
+
+/// cut everything `reader` yields into content-defined chunks
+fn for_each_chunk(
+    reader: &mut dyn Read,
+    mut chunk: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<u64> {
+    let mut buffer = Vec::with_capacity(2 * MAX_CHUNK);
+    let mut eof = false;
+    let mut total = 0;
+    loop {
+        // keep a whole maximum chunk buffered so cut points do not depend on read sizes
+        while !eof && buffer.len() < MAX_CHUNK {
+            let filled = buffer.len();
+            buffer.resize(MAX_CHUNK, 0);
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => {
+                    buffer.truncate(filled);
+                    eof = true;
+                }
+                Ok(read) => buffer.truncate(filled + read),
+                Err(e) if e.kind() == ErrorKind::Interrupted => buffer.truncate(filled),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if buffer.is_empty() {
+            return Ok(total);
+        }
+        let len = cut_point(&buffer);
+        chunk(&buffer[..len])?;
+        total += len as u64;
+        buffer.drain(..len);
+    }
+}
+
+/// how much deduplication saved
+#[derive(Debug, Default, Clone)]
+pub struct DedupStats {
+    pub files: u64,
+    /// file contents before deduplication
+    pub logical_size: u64,
+    pub chunks: u64,
+    pub unique_chunks: u64,
+    /// contents of the distinct chunks
+    pub unique_size: u64,
+    /// the distinct chunks after compression (and encryption)
+    pub stored_size: u64,
+}
+
+impl DedupStats {
+    /// file contents per byte of distinct chunks
+    pub fn ratio(&self) -> f64 {
+        if self.unique_size == 0 {
+            1.0
+        } else {
+            self.logical_size as f64 / self.unique_size as f64
+        }
+    }
+}
+
+impl fmt::Display for DedupStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dedup: {} files, {} in {} chunks, {} in {} unique chunks (ratio {:.2}), {} stored",
+            self.files,
+            utils::format_bytes(self.logical_size),
+            self.chunks,
+            utils::format_bytes(self.unique_size),
+            self.unique_chunks,
+            self.ratio(),
+            utils::format_bytes(self.stored_size)
+        )
+    }
+}
+
+/// where a distinct chunk is stored
+#[derive(Debug, Clone, PartialEq)]
+struct ChunkRecord {
+    offset: u64,
+    stored_len: u32,
+    raw_len: u32,
+    sha256: [u8; 32],
+}
+
+/// a file or directory in the index
+#[derive(Debug, Clone, PartialEq)]
+struct DedupEntry {
+    path: String,
+    directory: bool,
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+    size: u64,
+    chunks: Vec<u32>,
+}
+
+/// compress a chunk or the index, then encrypt it when there is a key
+fn seal(data: &[u8], level: i32, key: Option<&[u8]>, aad: u64) -> Result<Vec<u8>> {
+    let compressed = zstd::bulk::compress(data, level).context("zstd compression failed")?;
+    encrypt(compressed, key, aad)
+}
+
+/// encrypt compressed data when there is a key
+fn encrypt(compressed: Vec<u8>, key: Option<&[u8]>, aad: u64) -> Result<Vec<u8>> {
+    match key {
+        Some(key) => encryption::seal_block(key, &aad.to_le_bytes(), &compressed),
+        None => Ok(compressed),
+    }
+}
+
+/// the compressed data in a blob, decrypted when there is a key
+fn decrypt(blob: Vec<u8>, key: Option<&[u8]>, aad: u64) -> Result<Vec<u8>> {
+    match key {
+        Some(key) => encryption::open_block(key, &aad.to_le_bytes(), &blob),
+        None => Ok(blob),
+    }
+}
+
+/// undo `seal`
+fn unseal(blob: Vec<u8>, key: Option<&[u8]>, aad: u64) -> Result<Vec<u8>> {
+    let compressed = decrypt(blob, key, aad)?;
+    zstd::stream::decode_all(compressed.as_slice()).context("zstd decompression failed")
+}
+
+/// an archive being written; entries are added through [`Sink`]
+pub(crate) struct DedupWriter<'a> {
+    file: BufWriter<OutputFile>,
+    offset: u64,
+    level: i32,
+    key: Option<Vec<u8>>,
+    threads: usize,
+    known: HashMap<[u8; 32], u32>,
+    chunks: Vec<ChunkRecord>,
+    /// distinct chunks waiting to be compressed, by number
+    pending: Vec<(u32, Vec<u8>)>,
+    entries: Vec<DedupEntry>,
+    stats: DedupStats,
+    progress: Option<&'a Progress>,
+    /// an existing archive whose chunks are copied instead of compressed again
+    reference: Option<Reference>,
+}
+
+/// the chunks of an existing archive, by hash
+struct Reference {
+    archive: DedupArchive,
+    chunks: HashMap<[u8; 32], u32>,
+}
+
+impl<'a> DedupWriter<'a> {
+    pub(crate) fn create(
+        output_path: &Path,
+        options: &CompressionOptions,
+        progress: Option<&'a Progress>,
+    ) -> Result<Self> {
+        let mut file = BufWriter::new(volumes::create(output_path, options.split_size)?);
+
+        let password = options
+            .password
+            .as_deref()
+            .filter(|password| !password.is_empty());
+        let flags = if password.is_some() {
+            FLAG_ENCRYPTED
+        } else {
+            0
+        };
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION, flags])?;
+        let mut offset = (MAGIC.len() + 2) as u64;
+        let key = match password {
+            Some(password) => {
+                let (key, salt) = encryption::derive_key(password, None)?;
+                file.write_all(&salt)?;
+                offset += salt.len() as u64;
+                Some(key)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            file,
+            offset,
+            level: if options.level == 0 { 3 } else { options.level },
+            key,
+            threads: utils::resolved_thread_count(options.threads) as usize,
+            known: HashMap::new(),
+            chunks: Vec::new(),
+            pending: Vec::new(),
+            entries: Vec::new(),
+            stats: DedupStats::default(),
+            progress,
+            reference: None,
+        })
+    }
+
+    /// reuse the stored chunks of `archive` for data it already holds
+    fn with_reference(mut self, archive: DedupArchive) -> Self {
+        let chunks = archive
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(number, chunk)| (chunk.sha256, number as u32))
+            .collect();
+        self.reference = Some(Reference { archive, chunks });
+        self
+    }
+
+    /// the number of the chunk holding `data`, storing it if it is new
+    fn add_chunk(&mut self, data: &[u8]) -> Result<u32> {
+        self.stats.chunks += 1;
+        let sha256: [u8; 32] = Sha256::digest(data).into();
+        if let Some(&number) = self.known.get(&sha256) {
+            return Ok(number);
+        }
+        let stored = self
+            .reference
+            .as_ref()
+            .and_then(|reference| reference.chunks.get(&sha256).copied());
+        if let Some(stored) = stored {
+            return self.copy_chunk(stored);
+        }
+
+        let number = u32::try_from(self.chunks.len()).context("too many chunks")?;
+        self.known.insert(sha256, number);
+        self.chunks.push(ChunkRecord {
+            offset: 0,
+            stored_len: 0,
+            raw_len: data.len() as u32,
+            sha256,
+        });
+        self.stats.unique_chunks += 1;
+        self.stats.unique_size += data.len() as u64;
+        self.pending.push((number, data.to_vec()));
+        if self.pending.len() >= self.threads * CHUNKS_PER_THREAD {
+            self.write_pending()?;
+        }
+        Ok(number)
+    }
+
+    /// store chunk `stored` of the reference archive without compressing it again
+    fn copy_chunk(&mut self, stored: u32) -> Result<u32> {
+        let reference = self.reference.as_mut().expect("copying needs a reference");
+        let record = reference.archive.chunks[stored as usize].clone();
+        if let Some(&number) = self.known.get(&record.sha256) {
+            return Ok(number);
+        }
+        let (compressed, _) = reference.archive.read_stored(stored)?;
+
+        let number = u32::try_from(self.chunks.len()).context("too many chunks")?;
+        let blob = encrypt(compressed, self.key.as_deref(), u64::from(number))?;
+        self.known.insert(record.sha256, number);
+        self.chunks.push(ChunkRecord {
+            offset: self.offset,
+            stored_len: u32::try_from(blob.len()).context("chunk too large")?,
+            ..record
+        });
+        self.file.write_all(&blob)?;
+        self.offset += blob.len() as u64;
+        self.stats.unique_chunks += 1;
+        self.stats.unique_size += u64::from(record.raw_len);
+        self.stats.stored_size += blob.len() as u64;
+        Ok(number)
+    }
+
+    /// add an entry of the reference archive, copying its chunks as they are stored
+    fn copy_entry(&mut self, entry: DedupEntry) -> Result<()> {
+        let chunks = entry
+            .chunks
+            .iter()
+            .map(|&stored| self.copy_chunk(stored))
+            .collect::<Result<_>>()?;
+        if !entry.directory {
+            self.stats.files += 1;
+            self.stats.logical_size += entry.size;
+            self.stats.chunks += entry.chunks.len() as u64;
+        }
+        self.entries.push(DedupEntry { chunks, ..entry });
+        Ok(())
+    }
+
+    /// compress the pending chunks on worker threads, then write them in order
+    fn write_pending(&mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        let (level, key) = (self.level, self.key.as_deref());
+        let batch = pending.len().div_ceil(self.threads).max(1);
+        let blobs: Vec<Result<Vec<u8>>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = pending
+                .chunks(batch)
+                .map(|chunks| {
+                    scope.spawn(move || {
+                        chunks
+                            .iter()
+                            .map(|(number, data)| seal(data, level, key, u64::from(*number)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("chunk worker panicked"))
+                .collect()
+        });
+
+        for ((number, _), blob) in pending.iter().zip(blobs) {
+            let blob = blob?;
+            let record = &mut self.chunks[*number as usize];
+            record.offset = self.offset;
+            record.stored_len = u32::try_from(blob.len()).context("chunk too large")?;
+            self.file.write_all(&blob)?;
+            self.offset += blob.len() as u64;
+            self.stats.stored_size += blob.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// write the index and trailer, returning what deduplication saved
+    pub(crate) fn complete(mut self) -> Result<DedupStats> {
+        self.write_pending()?;
+        let index = encode_index(&self.chunks, &self.entries);
+        let blob = seal(&index, self.level, self.key.as_deref(), INDEX_AAD)?;
+        self.file.write_all(&blob)?;
+        self.file.write_all(&self.offset.to_le_bytes())?;
+        self.file.write_all(&(blob.len() as u64).to_le_bytes())?;
+        self.file.write_all(MAGIC)?;
+        self.file.flush()?;
+        Ok(self.stats)
+    }
+}
+
+impl Sink for DedupWriter<'_> {
+    fn add(&mut self, entry: &Entry, data: &mut dyn Read) -> Result<()> {
+        let directory = match entry.kind {
+            EntryKind::File => false,
+            EntryKind::Directory => true,
+            EntryKind::Symlink(_) => bail!("dedup archives cannot store symlinks"),
+        };
+
+        let mut chunks = Vec::new();
+        let mut size = 0;
+        if !directory {
+            self.stats.files += 1;
+            size = for_each_chunk(data, |chunk| {
+                chunks.push(self.add_chunk(chunk)?);
+                self.stats.logical_size += chunk.len() as u64;
+                if let Some(progress) = self.progress {
+                    progress.update(self.stats.logical_size);
+                }
+                Ok(())
+            })?;
+        }
+        self.entries.push(DedupEntry {
+            path: entry.path.clone(),
+            directory,
+            mode: entry.mode,
+            mtime: entry.mtime,
+            size,
+            chunks,
+        });
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.complete().map(drop)
+    }
+}
+
+fn encode_index(chunks: &[ChunkRecord], entries: &[DedupEntry]) -> Vec<u8> {
+    let mut index = Vec::new();
+    index.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for chunk in chunks {
+        index.extend_from_slice(&chunk.offset.to_le_bytes());
+        index.extend_from_slice(&chunk.stored_len.to_le_bytes());
+        index.extend_from_slice(&chunk.raw_len.to_le_bytes());
+        index.extend_from_slice(&chunk.sha256);
+    }
+
+    index.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries {
+        index.push(if entry.directory {
+            KIND_DIRECTORY
+        } else {
+            KIND_FILE
+        });
+        let mtime = entry
+            .mtime
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok());
+        let mut flags = 0;
+        if entry.mode.is_some() {
+            flags |= HAS_MODE;
+        }
+        if mtime.is_some() {
+            flags |= HAS_MTIME;
+        }
+        index.push(flags);
+        index.extend_from_slice(&entry.mode.unwrap_or_default().to_le_bytes());
+        let mtime = mtime.unwrap_or_default();
+        index.extend_from_slice(&mtime.as_secs().to_le_bytes());
+        index.extend_from_slice(&mtime.subsec_nanos().to_le_bytes());
+        index.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+        index.extend_from_slice(entry.path.as_bytes());
+        index.extend_from_slice(&entry.size.to_le_bytes());
+        index.extend_from_slice(&(entry.chunks.len() as u32).to_le_bytes());
+        for chunk in &entry.chunks {
+            index.extend_from_slice(&chunk.to_le_bytes());
+        }
+    }
+    index
+}
+
+/// reads the fields of a decoded index, failing on truncation
+struct IndexReader<'a> {
+    data: &'a [u8],
+}
+
+impl IndexReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let (bytes, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .ok_or_else(|| anyhow!("truncated index"))?;
+        self.data = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+}
+
+fn decode_index(index: &[u8]) -> Result<(Vec<ChunkRecord>, Vec<DedupEntry>)> {
+    let mut reader = IndexReader { data: index };
+
+    let chunk_count = reader.u32()?;
+    let mut chunks = Vec::new();
+    for _ in 0..chunk_count {
+        chunks.push(ChunkRecord {
+            offset: reader.u64()?,
+            stored_len: reader.u32()?,
+            raw_len: reader.u32()?,
+            sha256: reader.take()?,
+        });
+    }
+
+    let entry_count = reader.u32()?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        let directory = match reader.u8()? {
+            KIND_FILE => false,
+            KIND_DIRECTORY => true,
+            kind => bail!("unknown entry kind {kind}"),
+        };
+        let flags = reader.u8()?;
+        let mode = reader.u32()?;
+        let (seconds, nanos) = (reader.u64()?, reader.u32()?);
+        let path_len = reader.u32()? as usize;
+        if path_len > reader.data.len() {
+            bail!("truncated index");
+        }
+        let (path, rest) = reader.data.split_at(path_len);
+        reader.data = rest;
+        let path = String::from_utf8(path.to_vec()).context("entry path is not UTF-8")?;
+        let size = reader.u64()?;
+        let chunk_refs = reader.u32()?;
+        let mut refs = Vec::new();
+        for _ in 0..chunk_refs {
+            let chunk = reader.u32()?;
+            if chunk >= chunk_count {
+                bail!("entry '{path}' refers to missing chunk {chunk}");
+            }
+            refs.push(chunk);
+        }
+        let mtime = if flags & HAS_MTIME != 0 {
+            // a crafted time must not overflow SystemTime
+            let mtime = (nanos < 1_000_000_000)
+                .then(|| UNIX_EPOCH.checked_add(Duration::new(seconds, nanos)))
+                .flatten()
+                .ok_or_else(|| anyhow!("corrupt index: entry '{path}' has an invalid mtime"))?;
+            Some(mtime)
+        } else {
+            None
+        };
+        entries.push(DedupEntry {
+            path,
+            directory,
+            mode: (flags & HAS_MODE != 0).then_some(mode),
+            mtime,
+            size,
+            chunks: refs,
+        });
+    }
+    if !reader.data.is_empty() {
+        bail!("trailing data after the index");
+    }
+    Ok((chunks, entries))
+}
+
+/// an archive opened for reading, with its index loaded
+struct DedupArchive {
+    reader: VolumeReader,
+    key: Option<Vec<u8>>,
+    chunks: Vec<ChunkRecord>,
+    entries: Vec<DedupEntry>,
+}
+
+impl DedupArchive {
+    fn open(archive_path: &Path, password: Option<&str>) -> Result<Self> {
+        let mut reader = volumes::open(archive_path)
+            .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+        let mut header = [0u8; MAGIC.len() + 2];
+        reader
+            .read_exact(&mut header)
+            .ok()
+            .filter(|()| header.starts_with(MAGIC))
+            .ok_or_else(|| anyhow!("'{}' is not a dedup archive", archive_path.display()))?;
+        let (version, flags) = (header[MAGIC.len()], header[MAGIC.len() + 1]);
+        if version != VERSION {
+            bail!(
+                "unsupported dedup archive version {version} in '{}'",
+                archive_path.display()
+            );
+        }
+
+        let key = if flags & FLAG_ENCRYPTED != 0 {
+            let password = password.ok_or_else(|| {
+                anyhow!(
+                    "Encrypted archive '{}' requires a password.",
+                    archive_path.display()
+                )
+            })?;
+            if password.is_empty() {
+                bail!(
+                    "Password cannot be empty for encrypted archive '{}'.",
+                    archive_path.display()
+                );
+            }
+            let mut salt = [0u8; ARGON2_SALT_LEN];
+            reader.read_exact(&mut salt)?;
+            Some(encryption::derive_key(password, Some(&salt))?.0)
+        } else {
+            None
+        };
+
+        let corrupt = || {
+            anyhow!(
+                "'{}' is a truncated or corrupt dedup archive",
+                archive_path.display()
+            )
+        };
+        let len = reader.len();
+        if len < TRAILER_SIZE {
+            return Err(corrupt());
+        }
+        let mut trailer = [0u8; TRAILER_SIZE as usize];
+        reader.seek(SeekFrom::Start(len - TRAILER_SIZE))?;
+        reader.read_exact(&mut trailer)?;
+        if &trailer[16..] != MAGIC {
+            return Err(corrupt());
+        }
+        let index_offset = u64::from_le_bytes(trailer[..8].try_into().expect("8 bytes"));
+        let index_len = u64::from_le_bytes(trailer[8..16].try_into().expect("8 bytes"));
+        if index_offset.checked_add(index_len) != Some(len - TRAILER_SIZE) {
+            return Err(corrupt());
+        }
+
+        let mut blob = vec![0u8; index_len as usize];
+        reader.seek(SeekFrom::Start(index_offset))?;
+        reader.read_exact(&mut blob)?;
+        let index = unseal(blob, key.as_deref(), INDEX_AAD).map_err(|e| {
+            if key.is_some() {
+                anyhow!("Failed to decrypt archive (invalid password)")
+            } else {
+                e.context(corrupt())
+            }
+        })?;
+        let (chunks, entries) = decode_index(&index)
+            .with_context(|| format!("Invalid index in '{}'", archive_path.display()))?;
+        // chunks lie between the header and the index; their lengths are read from the archive
+        if chunks.iter().any(|chunk| {
+            chunk
+                .offset
+                .checked_add(u64::from(chunk.stored_len))
+                .is_none_or(|end| end > index_offset)
+        }) {
+            return Err(corrupt());
+        }
+
+        Ok(Self {
+            reader,
+            key,
+            chunks,
+            entries,
+        })
+    }
+
+    /// contents of a chunk, checked against its hash
+    fn read_chunk(&mut self, number: u32) -> Result<Vec<u8>> {
+        Ok(self.read_stored(number)?.1)
+    }
+
+    /// a chunk as compressed in the archive, with its contents checked against its hash
+    ///
+    /// `open` has checked that the chunk lies within the archive
+    fn read_stored(&mut self, number: u32) -> Result<(Vec<u8>, Vec<u8>)> {
+        let record = &self.chunks[number as usize];
+        let mut blob = vec![0u8; record.stored_len as usize];
+        self.reader.seek(SeekFrom::Start(record.offset))?;
+        self.reader.read_exact(&mut blob)?;
+        let corrupt = || format!("chunk {number} is corrupt");
+        let compressed =
+            decrypt(blob, self.key.as_deref(), u64::from(number)).with_context(corrupt)?;
+        let data = zstd::stream::decode_all(compressed.as_slice())
+            .context("zstd decompression failed")
+            .with_context(corrupt)?;
+        if data.len() != record.raw_len as usize || Sha256::digest(&data)[..] != record.sha256 {
+            bail!("chunk {number} is corrupt (checksum mismatch)");
+        }
+        Ok((compressed, data))
+    }
+
+    /// the contents of entry `index`, read chunk by chunk
+    fn entry_reader(&mut self, index: usize) -> EntryReader<'_> {
+        EntryReader {
+            chunks: self.entries[index].chunks.clone().into_iter(),
+            archive: self,
+            buffer: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// write the contents of entry `index` to `writer`
+    fn copy_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()> {
+        let written = std::io::copy(&mut self.entry_reader(index), writer)?;
+        let entry = &self.entries[index];
+        if written != entry.size {
+            bail!(
+                "'{}' has {written} bytes, expected {}",
+                entry.path,
+                entry.size
+            );
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> DedupStats {
+        let files = self.entries.iter().filter(|entry| !entry.directory);
+        DedupStats {
+            files: files.clone().count() as u64,
+            logical_size: files.clone().map(|entry| entry.size).sum(),
+            chunks: files.map(|entry| entry.chunks.len() as u64).sum(),
+            unique_chunks: self.chunks.len() as u64,
+            unique_size: self
+                .chunks
+                .iter()
+                .map(|chunk| u64::from(chunk.raw_len))
+                .sum(),
+            stored_size: self
+                .chunks
+                .iter()
+                .map(|chunk| u64::from(chunk.stored_len))
+                .sum(),
+        }
+    }
+}
+
+struct EntryReader<'a> {
+    archive: &'a mut DedupArchive,
+    chunks: std::vec::IntoIter<u32>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.buffer.len() {
+            let Some(number) = self.chunks.next() else {
+                return Ok(0);
+            };
+            self.buffer = self
+                .archive
+                .read_chunk(number)
+                .map_err(std::io::Error::other)?;
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// whether the chunks of an archive are encrypted
+pub(crate) fn is_encrypted(archive_path: &Path) -> Result<bool> {
+    let mut reader = volumes::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+    let mut header = [0u8; MAGIC.len() + 2];
+    reader.read_exact(&mut header)?;
+    Ok(header.starts_with(MAGIC) && header[MAGIC.len() + 1] & FLAG_ENCRYPTED != 0)
+}
+
+/// hand every entry of an archive, with its contents, to `visit`
+pub(crate) fn read_entries(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+    mut visit: impl FnMut(Entry, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let mut archive = DedupArchive::open(archive_path, options.password.as_deref())?;
+    for index in 0..archive.entries.len() {
+        let entry = &archive.entries[index];
+        let converted = Entry {
+            path: entry.path.clone(),
+            kind: if entry.directory {
+                EntryKind::Directory
+            } else {
+                EntryKind::File
+            },
+            size: Some(entry.size),
+            mode: entry.mode,
+            mtime: entry.mtime,
+            owner: None,
+        };
+        visit(converted, &mut archive.entry_reader(index))?;
+    }
+    Ok(())
+}
+
+/// rewrite an archive with its entries renamed or dropped, followed by `entries`
+///
+/// kept entries are copied with their chunks as stored, and new files only compress the
+/// chunks the old archive does not already hold.
+pub(crate) fn rewrite(
+    archive_path: &Path,
+    output_path: &Path,
+    rename: modify::Rename,
+    entries: &[modify::NewEntry],
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+) -> Result<()> {
+    let archive = DedupArchive::open(archive_path, extract_options.password.as_deref())?;
+    let kept: Vec<_> = archive
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let path = rename(&entry.path)?;
+            Some(DedupEntry {
+                path,
+                ..entry.clone()
+            })
+        })
+        .collect();
+
+    let mut writer = DedupWriter::create(output_path, options, None)?.with_reference(archive);
+    for entry in kept {
+        writer.copy_entry(entry)?;
+    }
+    modify::add_entries(&mut writer, entries, options, None)?;
+    writer.complete().map(drop)
+}
+
+/// check every chunk and file of an archive, returning what deduplication saved
+pub fn test_archive(archive_path: &Path, options: &ExtractionOptions) -> Result<DedupStats> {
+    let mut archive = DedupArchive::open(archive_path, options.password.as_deref())?;
+    // every stored chunk once, then every file's size against its chunk list
+    for number in 0..archive.chunks.len() as u32 {
+        archive.read_chunk(number)?;
+    }
+    for entry in &archive.entries {
+        let size: u64 = entry
+            .chunks
+            .iter()
+            .map(|&number| u64::from(archive.chunks[number as usize].raw_len))
+            .sum();
+        if size != entry.size {
+            bail!(
+                "'{}' has {size} bytes of chunks, expected {}",
+                entry.path,
+                entry.size
+            );
+        }
+    }
+    Ok(archive.stats())
+}
+
+impl CompressionFormat for DedupFormat {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        let entries = modify::collect_inputs(&[input_path.to_path_buf()], options, filter)?;
+        let mut writer = DedupWriter::create(output_path, options, progress)?;
+        modify::add_entries(&mut writer, &entries, options, None)?;
+        let dedup = writer.complete()?;
+
+        let output_size = volumes::written_size(output_path, options.split_size)?;
+        let mut stats = CompressionStats::new(dedup.logical_size, output_size);
+        stats.dedup = Some(dedup);
+        Ok(stats)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        let mut archive = DedupArchive::open(archive_path, options.password.as_deref())?;
+        std::fs::create_dir_all(output_dir)?;
+        if let Some(progress) = progress {
+            progress.set_length(archive.entries.len() as u64);
+        }
+
+        let mut selected = false;
+        for index in 0..archive.entries.len() {
+            let entry = archive.entries[index].clone();
+            if !utils::entry_selected(&entry.path, &options.entries) {
+                continue;
+            }
+            selected = true;
+            let Some(target_path) = utils::extract_entry_to_path(
+                output_dir,
+                Path::new(&entry.path),
+                options.strip_components,
+                options.overwrite,
+                entry.directory,
+            )?
+            else {
+                continue;
+            };
+
+            if let Some(progress) = progress {
+                if progress.is_verbose() {
+                    let action = if entry.directory {
+                        "creating"
+                    } else {
+                        "extracting"
+                    };
+                    println!("  {action}: {}", entry.path);
+                }
+            }
+
+            if let Some(parent) = target_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if entry.directory {
+                std::fs::create_dir_all(&target_path)?;
+            } else {
+                let file = File::create(&target_path).with_context(|| {
+                    format!("Failed to create output file {}", target_path.display())
+                })?;
+                let mut output = BufWriter::new(file);
+                archive.copy_entry(index, &mut output)?;
+                output.flush()?;
+                drop(output);
+                if let Some(mtime) = entry.mtime.filter(|_| !options.strip_timestamps) {
+                    utils::apply_mtime(&target_path, mtime)?;
+                }
+            }
+            if let Some(mode) = entry.mode.filter(|_| options.preserve_permissions) {
+                utils::apply_permissions(&target_path, mode)?;
+            }
+
+            if let Some(progress) = progress {
+                progress.set_position((index + 1) as u64);
+            }
+        }
+
+        if !selected && !options.entries.is_empty() {
+            return Err(super::tarball::no_matching_entries(&options.entries));
+        }
+        Ok(())
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        let archive = DedupArchive::open(archive_path, options.password.as_deref())?;
+        Ok(archive
+            .entries
+            .into_iter()
+            .map(|entry| ArchiveEntry {
+                path: entry.path,
+                size: entry.size,
+                is_file: !entry.directory,
+                method: None,
+            })
+            .collect())
+    }
+
+    fn extension() -> &'static str {
+        "zzd"
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        test_archive(archive_path, options).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// deterministic pseudo-random bytes
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn chunk_lengths(data: &[u8]) -> Vec<usize> {
+        let mut lengths = Vec::new();
+        for_each_chunk(&mut &data[..], |chunk| {
+            lengths.push(chunk.len());
+            Ok(())
+        })
+        .unwrap();
+        lengths
+    }
+
+    #[test]
+    fn test_chunk_sizes_stay_in_bounds() {
+        let data = noise(4 * 1024 * 1024, 1);
+        let lengths = chunk_lengths(&data);
+        assert_eq!(lengths.iter().sum::<usize>(), data.len());
+        let (last, rest) = lengths.split_last().unwrap();
+        assert!(*last <= MAX_CHUNK);
+        assert!(rest
+            .iter()
+            .all(|&len| (MIN_CHUNK..=MAX_CHUNK).contains(&len)));
+        // roughly the average, not pinned to either bound
+        let average = data.len() / lengths.len();
+        assert!(
+            (AVG_CHUNK / 2..AVG_CHUNK * 2).contains(&average),
+            "{average}"
+        );
+    }
+
+    #[test]
+    fn test_chunks_resync_after_an_insertion() {
+        let data = noise(2 * 1024 * 1024, 2);
+        let mut edited = data[..500_000].to_vec();
+        edited.extend_from_slice(b"inserted bytes");
+        edited.extend_from_slice(&data[500_000..]);
+
+        let chunks = |data: &[u8]| {
+            let mut hashes = Vec::new();
+            for_each_chunk(&mut &data[..], |chunk| {
+                hashes.push(<[u8; 32]>::from(Sha256::digest(chunk)));
+                Ok(())
+            })
+            .unwrap();
+            hashes
+        };
+        let original = chunks(&data);
+        let edited = chunks(&edited);
+        let shared = edited.iter().filter(|hash| original.contains(hash)).count();
+        // only the chunks around the edit differ
+        assert!(
+            shared + 3 >= original.len(),
+            "{shared} of {}",
+            original.len()
+        );
+    }
+
+    #[test]
+    fn test_index_roundtrip() -> Result<()> {
+        let chunks = vec![ChunkRecord {
+            offset: 10,
+            stored_len: 20,
+            raw_len: 30,
+            sha256: [7; 32],
+        }];
+        let entries = vec![
+            DedupEntry {
+                path: "dir".to_string(),
+                directory: true,
+                mode: None,
+                mtime: None,
+                size: 0,
+                chunks: Vec::new(),
+            },
+            DedupEntry {
+                path: "dir/ünïcode.txt".to_string(),
+                directory: false,
+                mode: Some(0o640),
+                mtime: Some(UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+                size: 60,
+                chunks: vec![0, 0],
+            },
+        ];
+        let index = encode_index(&chunks, &entries);
+        assert_eq!(decode_index(&index)?, (chunks, entries));
+        assert!(decode_index(&index[..index.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_mtimes() {
+        for (seconds, nanos) in [(u64::MAX, 0), (u64::MAX, 999_999_999), (0, u32::MAX)] {
+            let mut index = 0u32.to_le_bytes().to_vec();
+            index.extend_from_slice(&1u32.to_le_bytes());
+            index.extend_from_slice(&[KIND_FILE, HAS_MTIME]);
+            index.extend_from_slice(&0u32.to_le_bytes());
+            index.extend_from_slice(&seconds.to_le_bytes());
+            index.extend_from_slice(&nanos.to_le_bytes());
+            index.extend_from_slice(&1u32.to_le_bytes());
+            index.push(b'a');
+            index.extend_from_slice(&0u64.to_le_bytes());
+            index.extend_from_slice(&0u32.to_le_bytes());
+
+            let err = decode_index(&index).expect_err("an out-of-range mtime");
+            assert!(err.to_string().contains("corrupt index"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_chunks_past_the_index() -> Result<()> {
+        let chunks = vec![ChunkRecord {
+            offset: 10,
+            stored_len: u32::MAX,
+            raw_len: 30,
+            sha256: [7; 32],
+        }];
+        let index = seal(&encode_index(&chunks, &[]), 1, None, INDEX_AAD)?;
+        let mut archive = MAGIC.to_vec();
+        archive.extend_from_slice(&[VERSION, 0]);
+        let index_offset = archive.len() as u64;
+        archive.extend_from_slice(&index);
+        archive.extend_from_slice(&index_offset.to_le_bytes());
+        archive.extend_from_slice(&(index.len() as u64).to_le_bytes());
+        archive.extend_from_slice(MAGIC);
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("bad.zzd");
+        std::fs::write(&path, archive)?;
+        let err = DedupArchive::open(&path, None)
+            .err()
+            .expect("a corrupt archive");
+        assert!(err.to_string().contains("corrupt"), "{err}");
+        Ok(())
+    }
+}
@@ -3,17 +3,19 @@
 use crate::{
     filter::FileFilter,
     formats::{
-        tarball, ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats,
-        ExtractionOptions,
+        parallel_gzip::ParallelGzEncoder, tarball, ArchiveEntry, CompressionFormat,
+        CompressionOptions, CompressionStats, ExtractionOptions, FinishWrite, StreamPayload,
     },
     progress::{Progress, ProgressReader},
-    utils, Result,
+    utils,
+    volumes::{self, OutputFile},
+    Result,
 };
 use anyhow::Context;
-use flate2::{read::GzDecoder, write::GzEncoder, Compression, GzBuilder};
+use flate2::{read::GzDecoder, Compression};
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Read},
+    io::{BufReader, BufWriter, Read, Write},
     path::Path,
 };
 
@@ -35,15 +37,8 @@ fn is_raw_gz(path: &Path) -> bool {
     name.ends_with(".gz") && !is_targz(path)
 }
 
-fn raw_output_name(path: &Path) -> Option<String> {
-    let name = path.file_name()?.to_string_lossy();
-    let lower = name.to_lowercase();
-    if lower.ends_with(".gz") {
-        let new_len = name.len().saturating_sub(3);
-        return Some(name[..new_len].to_string());
-    }
-    None
-}
+/// suffixes stripped to name the file held by a raw gzip stream
+const RAW_SUFFIXES: &[&str] = &[".gz", ".tgz"];
 
 fn gzip_mtime(path: &Path, options: &CompressionOptions) -> u32 {
     if options.strip_timestamps {
@@ -56,13 +51,89 @@ fn gzip_mtime(path: &Path, options: &CompressionOptions) -> u32 {
     let Ok(modified) = metadata.modified() else {
         return 0;
     };
-    let Ok(duration) = modified.duration_since(std::time::UNIX_EPOCH) else {
+    let Ok(duration) = options
+        .clamp_mtime(modified)
+        .duration_since(std::time::UNIX_EPOCH)
+    else {
         return 0;
     };
 
     duration.as_secs().min(u64::from(u32::MAX)) as u32
 }
 
+/// map the compression level (1-22) to a gzip level (0-9)
+fn gzip_level(options: &CompressionOptions) -> u32 {
+    (((options.level as f32 / 22.0) * 9.0) as u32).clamp(0, 9)
+}
+
+/// gzip output, deflated in fixed-size blocks on up to `-j` workers
+///
+/// the blocks do not depend on the thread count, so every `-j` yields the same bytes
+struct GzipWriter(ParallelGzEncoder<BufWriter<OutputFile>>);
+
+impl GzipWriter {
+    fn create(
+        output_path: &Path,
+        level: Compression,
+        mtime: u32,
+        options: &CompressionOptions,
+    ) -> Result<Self> {
+        let output_file = volumes::create(output_path, options.split_size)?;
+        let threads = utils::resolved_thread_count(options.threads);
+        Ok(Self(ParallelGzEncoder::new(
+            BufWriter::new(output_file),
+            level,
+            mtime,
+            threads,
+        )?))
+    }
+
+    fn finish(self) -> Result<()> {
+        drop(self.0.finish()?);
+        Ok(())
+    }
+}
+
+impl Write for GzipWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl FinishWrite for GzipWriter {
+    fn finish(self: Box<Self>) -> Result<()> {
+        GzipWriter::finish(*self)
+    }
+}
+
+/// encoder for a tar stream assembled entry by entry
+pub fn stream_writer(
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<Box<dyn FinishWrite>> {
+    let level = Compression::new(gzip_level(options));
+    Ok(Box::new(GzipWriter::create(
+        output_path,
+        level,
+        0,
+        options,
+    )?))
+}
+
+/// decoded content of an archive and whether it holds a tarball
+pub fn stream_reader(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<(StreamPayload, Box<dyn Read>)> {
+    let file = volumes::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+    tarball::detect_payload(GzDecoder::new(BufReader::new(file)), options.payload)
+}
+
 impl CompressionFormat for GzipFormat {
     fn compress(
         input_path: &Path,
@@ -78,8 +149,7 @@ impl CompressionFormat for GzipFormat {
             options.allow_symlink_escape,
         )?;
 
-        // Map compression level (1-22) to gzip level (0-9)
-        let gzip_level = (((options.level as f32 / 22.0) * 9.0) as u32).clamp(0, 9);
+        let gzip_level = gzip_level(options);
 
         if let Some(progress) = progress {
             progress.set_length(input_size);
@@ -92,71 +162,31 @@ impl CompressionFormat for GzipFormat {
             ));
         }
 
-        if input_path.is_file() {
-            if is_raw_gz(output_path) {
-                let filename = input_path.file_name();
-                if let Some(filename) = filename {
-                    if !filter.should_include_relative(Path::new(filename)) {
-                        let output_file = File::create(output_path).with_context(|| {
-                            format!("Failed to create output file {}", output_path.display())
-                        })?;
-                        let buf_writer = BufWriter::new(output_file);
-                        let encoder = GzBuilder::new()
-                            .mtime(0)
-                            .write(buf_writer, Compression::new(gzip_level));
-                        encoder.finish()?;
-                        let output_size = std::fs::metadata(output_path)?.len();
-                        return Ok(CompressionStats::new(input_size, output_size));
-                    }
-                }
-
-                let mtime = gzip_mtime(input_path, options);
-                let output_file = File::create(output_path).with_context(|| {
-                    format!("Failed to create output file {}", output_path.display())
-                })?;
-                let buf_writer = BufWriter::new(output_file);
-                let mut encoder = GzBuilder::new()
-                    .mtime(mtime)
-                    .write(buf_writer, Compression::new(gzip_level));
-
-                let mut input_file = File::open(input_path).with_context(|| {
-                    format!("Failed to open input file {}", input_path.display())
-                })?;
-                std::io::copy(&mut input_file, &mut encoder)?;
-                encoder.finish()?;
-            } else {
-                let output_file = File::create(output_path).with_context(|| {
-                    format!("Failed to create output file {}", output_path.display())
-                })?;
-                let buf_writer = BufWriter::new(output_file);
-                let encoder = GzEncoder::new(buf_writer, Compression::new(gzip_level));
-                let encoder = tarball::build_tarball(
-                    encoder,
-                    input_path,
-                    options,
-                    filter,
-                    progress,
-                    tarball::BuildOptions {
-                        normalize_ownership: options.normalize_ownership,
-                        apply_filter_to_single_file: true,
-                        directory_slash: false,
-                        set_mtime_for_single_file: true,
-                    },
-                )?;
-                encoder.finish()?;
+        let level = Compression::new(gzip_level);
+        if input_path.is_file() && is_raw_gz(output_path) {
+            let included = input_path
+                .file_name()
+                .is_none_or(|filename| filter.should_include_relative(Path::new(filename)));
+            if !included {
+                GzipWriter::create(output_path, level, 0, options)?.finish()?;
+                let output_size = volumes::written_size(output_path, options.split_size)?;
+                return Ok(CompressionStats::new(input_size, output_size));
             }
+
+            let mtime = gzip_mtime(input_path, options);
+            let mut encoder = GzipWriter::create(output_path, level, mtime, options)?;
+            let mut input_file = File::open(input_path)
+                .with_context(|| format!("Failed to open input file {}", input_path.display()))?;
+            std::io::copy(&mut input_file, &mut encoder)?;
+            encoder.finish()?;
         } else {
-            if is_raw_gz(output_path) {
+            if !input_path.is_file() && is_raw_gz(output_path) {
                 return Err(anyhow::anyhow!(
                     "Directory input requires a .tgz or .tar.gz output"
                 ));
             }
 
-            let output_file = File::create(output_path).with_context(|| {
-                format!("Failed to create output file {}", output_path.display())
-            })?;
-            let buf_writer = BufWriter::new(output_file);
-            let encoder = GzEncoder::new(buf_writer, Compression::new(gzip_level));
+            let encoder = GzipWriter::create(output_path, level, 0, options)?;
             let encoder = tarball::build_tarball(
                 encoder,
                 input_path,
@@ -173,7 +203,7 @@ impl CompressionFormat for GzipFormat {
             encoder.finish()?;
         }
 
-        let output_size = std::fs::metadata(output_path)?.len();
+        let output_size = volumes::written_size(output_path, options.split_size)?;
         Ok(CompressionStats::new(input_size, output_size))
     }
 
@@ -183,123 +213,98 @@ impl CompressionFormat for GzipFormat {
         options: &ExtractionOptions,
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()> {
-        let archive_size = std::fs::metadata(archive_path)
-            .with_context(|| format!("Failed to read archive metadata {}", archive_path.display()))?
-            .len();
+        let file = volumes::open(archive_path)
+            .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
         if let Some(progress) = progress {
-            progress.set_length(archive_size);
+            progress.set_length(file.len());
         }
 
-        if is_raw_gz(archive_path) {
-            let output_name = raw_output_name(archive_path)
-                .ok_or_else(|| anyhow::anyhow!("Failed to determine output filename"))?;
-            let target_path = match crate::utils::prepare_extract_target(
-                output_dir,
-                Path::new(&output_name),
-                options.strip_components,
-                options.overwrite,
-                false,
-            )? {
-                crate::utils::ExtractTarget::Target(target_path) => target_path,
-                crate::utils::ExtractTarget::SkipStrip => return Ok(()),
-                crate::utils::ExtractTarget::SkipExisting(target_path) => {
-                    return Err(anyhow::anyhow!(
-                        "output file '{}' already exists. Use --overwrite to replace.",
-                        target_path.display()
-                    ));
-                }
-            };
-
-            if let Some(parent) = target_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-
-            let file = File::open(archive_path).with_context(|| {
-                format!("Failed to open archive file {}", archive_path.display())
-            })?;
-            let mut decoder = GzDecoder::new(ProgressReader::new(file, progress));
-            let mtime = if options.strip_timestamps {
-                None
-            } else {
-                decoder
-                    .header()
-                    .and_then(|header| header.mtime_as_datetime())
-            };
-            let mut output_file = File::create(&target_path)?;
-            std::io::copy(&mut decoder, &mut output_file)?;
-            drop(output_file);
-            if let Some(mtime) = mtime {
-                utils::apply_mtime(&target_path, mtime)?;
-            }
-            return Ok(());
-        }
-
-        let file = File::open(archive_path)
-            .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
         let buf_reader = BufReader::new(file);
         let decoder = GzDecoder::new(ProgressReader::new(buf_reader, progress));
+        let mtime = if options.strip_timestamps {
+            None
+        } else {
+            decoder
+                .header()
+                .and_then(|header| header.mtime_as_datetime())
+        };
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
 
-        tarball::extract_tarball(decoder, output_dir, options, progress)
-    }
+        if payload == StreamPayload::Tar {
+            return tarball::extract_tarball(reader, output_dir, options, progress);
+        }
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
-        if is_raw_gz(archive_path) {
-            let output_name = raw_output_name(archive_path)
-                .ok_or_else(|| anyhow::anyhow!("Failed to determine output filename"))?;
-            let file = File::open(archive_path)?;
-            let mut decoder = GzDecoder::new(file);
-            let mut size = 0u64;
-            let mut buffer = [0u8; 8192];
-            loop {
-                let read = decoder.read(&mut buffer)?;
-                if read == 0 {
-                    break;
-                }
-                size += read as u64;
+        let output_name = utils::raw_output_name(archive_path, RAW_SUFFIXES);
+        let target_path = match utils::prepare_extract_target(
+            output_dir,
+            Path::new(&output_name),
+            options.strip_components,
+            options.overwrite,
+            false,
+        )? {
+            utils::ExtractTarget::Target(target_path) => target_path,
+            utils::ExtractTarget::SkipStrip => return Ok(()),
+            utils::ExtractTarget::SkipExisting(target_path) => {
+                return Err(anyhow::anyhow!(
+                    "output file '{}' already exists. Use --overwrite to replace.",
+                    target_path.display()
+                ));
             }
-            return Ok(vec![ArchiveEntry {
-                path: output_name,
-                size,
-                is_file: true,
-            }]);
+        };
+
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
 
-        let file = File::open(archive_path).with_context(|| {
+        let mut output_file = File::create(&target_path)?;
+        std::io::copy(&mut reader, &mut output_file)?;
+        drop(output_file);
+        if let Some(mtime) = mtime {
+            utils::apply_mtime(&target_path, mtime)?;
+        }
+        Ok(())
+    }
+
+    fn extension() -> &'static str {
+        "tgz"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        let file = volumes::open(archive_path).with_context(|| {
             format!(
                 "Failed to open archive for listing {}",
                 archive_path.display()
             )
         })?;
-        let buf_reader = BufReader::new(file);
-        let decoder = GzDecoder::new(buf_reader);
+        let decoder = GzDecoder::new(BufReader::new(file));
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
 
-        tarball::list_tarball(decoder)
-    }
+        if payload == StreamPayload::Tar {
+            return tarball::list_tarball(reader);
+        }
 
-    fn extension() -> &'static str {
-        "tgz"
+        let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+        Ok(vec![ArchiveEntry {
+            path: utils::raw_output_name(archive_path, RAW_SUFFIXES),
+            size,
+            is_file: true,
+            method: None,
+        }])
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
-        // For Gzip, try to read the whole stream to check for corruption.
-        // If it's a .tgz (tar.gz), list contents using the tar crate.
-        use flate2::read::GzDecoder;
-        use std::fs::File;
-        use std::io::Read;
-        use tar::Archive;
-
-        let file = File::open(archive_path)?;
-        if is_targz(archive_path) {
-            let gz_decoder = GzDecoder::new(file);
-            let mut archive = Archive::new(gz_decoder);
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        // decode the whole stream; tarballs are also walked entry by entry
+        let file = volumes::open(archive_path)?;
+        let decoder = GzDecoder::new(BufReader::new(file));
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
+
+        if payload == StreamPayload::Tar {
+            let mut archive = tar::Archive::new(reader);
             for entry in archive.entries()? {
-                let _entry = entry?; // Access entry to check for errors
+                std::io::copy(&mut entry?, &mut std::io::sink())?;
             }
         } else {
-            // Single .gz file
-            let mut gz_decoder = GzDecoder::new(file);
-            let mut buffer = Vec::new();
-            gz_decoder.read_to_end(&mut buffer)?;
+            std::io::copy(&mut reader, &mut std::io::sink())?;
         }
         Ok(())
     }
@@ -0,0 +1,74 @@
+//! LZ4 frame format support (tar.lz4 and raw .lz4)
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        stream::{self, StreamCodec},
+        ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+    },
+    progress::Progress,
+    Result,
+};
+use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+pub struct Lz4Format;
+
+pub(crate) struct Lz4Codec;
+
+impl StreamCodec for Lz4Codec {
+    const NAME: &'static str = "LZ4";
+    const TAR_SUFFIXES: &'static [&'static str] = &[".tar.lz4"];
+    const RAW_SUFFIX: Option<&'static str> = Some(".lz4");
+
+    type Encoder<W: Write> = FrameEncoder<W>;
+
+    fn encoder<W: Write>(writer: W, _level: i32) -> Result<Self::Encoder<W>> {
+        // lz4_flex has a single (fast) compression level
+        Ok(FrameEncoder::new(writer))
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> Result<W> {
+        Ok(encoder.finish()?)
+    }
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(FrameDecoder::new(reader)))
+    }
+}
+
+impl CompressionFormat for Lz4Format {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        stream::compress::<Lz4Codec>(input_path, output_path, options, filter, progress)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        stream::extract::<Lz4Codec>(archive_path, output_dir, options, progress)
+    }
+
+    fn extension() -> &'static str {
+        "tar.lz4"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        stream::list::<Lz4Codec>(archive_path, options)
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        stream::test_integrity::<Lz4Codec>(archive_path, options)
+    }
+}
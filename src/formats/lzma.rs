@@ -0,0 +1,81 @@
+//! legacy LZMA (lzma-alone) format support (tar.lzma and raw .lzma)
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        stream::{self, StreamCodec},
+        ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+    },
+    progress::Progress,
+    Result,
+};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+use xz2::{
+    read::XzDecoder,
+    stream::{LzmaOptions, Stream},
+    write::XzEncoder,
+};
+
+pub struct LzmaFormat;
+
+pub(crate) struct LzmaCodec;
+
+impl StreamCodec for LzmaCodec {
+    const NAME: &'static str = "LZMA";
+    const TAR_SUFFIXES: &'static [&'static str] = &[".tar.lzma", ".tlz"];
+    const RAW_SUFFIX: Option<&'static str> = Some(".lzma");
+
+    type Encoder<W: Write> = XzEncoder<W>;
+
+    fn encoder<W: Write>(writer: W, level: i32) -> Result<Self::Encoder<W>> {
+        // Map compression level (1-22) to lzma preset (0-9)
+        let preset = (((level as f32 / 22.0) * 9.0) as u32).clamp(0, 9);
+        let stream = Stream::new_lzma_encoder(&LzmaOptions::new_preset(preset)?)?;
+        Ok(XzEncoder::new_stream(writer, stream))
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> Result<W> {
+        Ok(encoder.finish()?)
+    }
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+        let stream = Stream::new_lzma_decoder(u64::MAX)?;
+        Ok(Box::new(XzDecoder::new_stream(reader, stream)))
+    }
+}
+
+impl CompressionFormat for LzmaFormat {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        stream::compress::<LzmaCodec>(input_path, output_path, options, filter, progress)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        stream::extract::<LzmaCodec>(archive_path, output_dir, options, progress)
+    }
+
+    fn extension() -> &'static str {
+        "tar.lzma"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        stream::list::<LzmaCodec>(archive_path, options)
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        stream::test_integrity::<LzmaCodec>(archive_path, options)
+    }
+}
@@ -1,12 +1,24 @@
 //! compression format abstraction
 
+use crate::volumes::{VolumeKind, VolumeSet};
 use crate::Result;
 use anyhow::Context;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
+pub mod br;
+pub mod bz2;
+pub mod dedup;
 pub mod gz;
+pub mod lz4;
+pub mod lzma;
+pub mod parallel_gzip;
+pub mod parallel_xz;
 pub mod rar;
+pub mod seekable;
 pub mod sevenz;
+pub mod stream;
+pub mod tar;
 pub mod tarball;
 pub mod xz;
 pub mod zip;
@@ -17,6 +29,10 @@ pub struct CompressionStats {
     pub input_size: u64,
     pub output_size: u64,
     pub compression_ratio: f64,
+    /// files written when the output was split into volumes
+    pub volumes: Vec<PathBuf>,
+    /// what deduplication saved, for dedup archives
+    pub dedup: Option<dedup::DedupStats>,
 }
 
 impl CompressionStats {
@@ -30,6 +46,8 @@ impl CompressionStats {
             input_size,
             output_size,
             compression_ratio,
+            volumes: Vec::new(),
+            dedup: None,
         }
     }
 }
@@ -39,20 +57,40 @@ pub struct ArchiveEntry {
     pub path: String,
     pub size: u64,
     pub is_file: bool,
+    /// compression method, for formats that record one per entry
+    pub method: Option<String>,
 }
 
 /// compression options for creating archives
 #[derive(Debug, Clone)]
 pub struct CompressionOptions {
-    pub level: i32,                  // 1-22, default 19
-    pub threads: u32,                // 0 = auto-detect available parallelism
-    pub normalize_permissions: bool, // security: normalize permissions
-    pub normalize_ownership: bool,   // security: normalize ownership (uid/gid)
-    pub strip_xattrs: bool,          // security: strip extended attributes (xattrs)
-    pub strip_timestamps: bool,      // security: strip filesystem timestamps
-    pub follow_symlinks: bool,       // follow symlinks when walking input
-    pub allow_symlink_escape: bool,  // allow symlink targets outside input root
-    pub deterministic: bool,         // sort files for reproducible archives
+    pub level: i32,                           // 1-22, default 19
+    pub threads: u32,                         // 0 = auto-detect available parallelism
+    pub normalize_permissions: bool,          // security: normalize permissions
+    pub normalize_ownership: bool,            // security: normalize ownership (uid/gid)
+    pub strip_xattrs: bool,                   // security: strip extended attributes (xattrs)
+    pub strip_timestamps: bool,               // security: strip filesystem timestamps
+    pub follow_symlinks: bool,                // follow symlinks when walking input
+    pub allow_symlink_escape: bool,           // allow symlink targets outside input root
+    pub deterministic: bool,                  // sort files for reproducible archives
+    pub mtime_clamp: Option<u64>,             // clamp entry mtimes to this unix epoch
+    pub zip_method: ZipMethod,                // compression method for zip entries
+    pub auto_store: bool,                     // store precompressed zip inputs as-is
+    pub zstd_long: Option<u32>,               // zstd long-distance matching window log
+    pub zstd_window_log: Option<u32>,         // explicit zstd window log
+    pub zstd_strategy: Option<ZstdStrategy>,  // zstd match finder strategy
+    pub zstd_target_block_size: Option<u32>,  // target compressed zstd block size
+    pub zstd_checksum: bool,                  // add content checksums to zstd frames
+    pub zstd_ultra: bool,                     // allow zstd window logs above 27
+    pub zstd_dict: Option<PathBuf>,           // zstd dictionary file
+    pub zstd_seekable: Option<u32>,           // seekable zstd frame size
+    pub xz_block_size: Option<u64>,           // uncompressed size of xz blocks
+    pub sevenz_method: SevenZMethod,          // compression method for 7z content
+    pub sevenz_solid: bool,                   // pack 7z files into shared blocks
+    pub sevenz_solid_block_size: Option<u64>, // uncompressed size cap of solid blocks
+    pub sevenz_encrypt_headers: bool,         // refuse 7z headers left unencrypted
+    pub split_size: Option<u64>,              // cut the output into volumes of this size
+    pub snapshot: Option<PathBuf>,            // incremental backup state file
     pub password: Option<String>,
 }
 
@@ -68,11 +106,130 @@ impl Default for CompressionOptions {
             follow_symlinks: false,
             allow_symlink_escape: false,
             deterministic: true,
+            mtime_clamp: None,
+            zip_method: ZipMethod::default(),
+            auto_store: true,
+            zstd_long: None,
+            zstd_window_log: None,
+            zstd_strategy: None,
+            zstd_target_block_size: None,
+            zstd_checksum: false,
+            zstd_ultra: false,
+            zstd_dict: None,
+            zstd_seekable: None,
+            xz_block_size: None,
+            sevenz_method: SevenZMethod::default(),
+            sevenz_solid: false,
+            sevenz_solid_block_size: None,
+            sevenz_encrypt_headers: false,
+            split_size: None,
+            snapshot: None,
             password: None,
         }
     }
 }
 
+impl CompressionOptions {
+    /// apply the mtime clamp (if any) to a filesystem timestamp
+    pub fn clamp_mtime(&self, mtime: std::time::SystemTime) -> std::time::SystemTime {
+        match self.mtime_clamp {
+            Some(epoch) => mtime.min(std::time::UNIX_EPOCH + std::time::Duration::from_secs(epoch)),
+            None => mtime,
+        }
+    }
+}
+
+/// compression method used for zip entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZipMethod {
+    Store,
+    #[default]
+    Deflate,
+    Bzip2,
+    Zstd,
+}
+
+impl ZipMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZipMethod::Store => "store",
+            ZipMethod::Deflate => "deflate",
+            ZipMethod::Bzip2 => "bzip2",
+            ZipMethod::Zstd => "zstd",
+        }
+    }
+
+    /// map our 1-22 level onto the method's native range
+    pub fn native_level(&self, level: i32) -> Option<i64> {
+        let level = i64::from(level.clamp(1, 22));
+        match self {
+            ZipMethod::Store => None,
+            // deflate and bzip2 both take 1-9
+            ZipMethod::Deflate | ZipMethod::Bzip2 => Some((level - 1) * 8 / 21 + 1),
+            ZipMethod::Zstd => Some(level),
+        }
+    }
+}
+
+/// compression method used for 7z content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SevenZMethod {
+    #[default]
+    Lzma2,
+    Lzma,
+}
+
+impl SevenZMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SevenZMethod::Lzma2 => "lzma2",
+            SevenZMethod::Lzma => "lzma",
+        }
+    }
+}
+
+/// zstd match finder strategy, from fastest to strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZstdStrategy {
+    Fast,
+    Dfast,
+    Greedy,
+    Lazy,
+    Lazy2,
+    Btlazy2,
+    Btopt,
+    Btultra,
+    Btultra2,
+}
+
+impl ZstdStrategy {
+    pub const ALL: [ZstdStrategy; 9] = [
+        ZstdStrategy::Fast,
+        ZstdStrategy::Dfast,
+        ZstdStrategy::Greedy,
+        ZstdStrategy::Lazy,
+        ZstdStrategy::Lazy2,
+        ZstdStrategy::Btlazy2,
+        ZstdStrategy::Btopt,
+        ZstdStrategy::Btultra,
+        ZstdStrategy::Btultra2,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZstdStrategy::Fast => "fast",
+            ZstdStrategy::Dfast => "dfast",
+            ZstdStrategy::Greedy => "greedy",
+            ZstdStrategy::Lazy => "lazy",
+            ZstdStrategy::Lazy2 => "lazy2",
+            ZstdStrategy::Btlazy2 => "btlazy2",
+            ZstdStrategy::Btopt => "btopt",
+            ZstdStrategy::Btultra => "btultra",
+            ZstdStrategy::Btultra2 => "btultra2",
+        }
+    }
+}
+
 /// extraction options for extracting archives
 #[derive(Debug, Clone)]
 pub struct ExtractionOptions {
@@ -83,6 +240,10 @@ pub struct ExtractionOptions {
     pub preserve_permissions: bool,
     pub preserve_ownership: bool,
     pub password: Option<String>,
+    pub payload: StreamPayload,
+    pub zstd_dict: Option<PathBuf>,
+    pub entries: Vec<String>,
+    pub threads: u32,
 }
 
 impl Default for ExtractionOptions {
@@ -95,10 +256,37 @@ impl Default for ExtractionOptions {
             preserve_permissions: false,
             preserve_ownership: false,
             password: None,
+            payload: StreamPayload::Auto,
+            zstd_dict: None,
+            entries: Vec::new(),
+            threads: 0,
         }
     }
 }
 
+/// what a single-stream format (gz, xz, zst, bz2, lz4, lzma, br) holds once decoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamPayload {
+    /// sniff the decoded stream for a tar header
+    #[default]
+    Auto,
+    Tar,
+    Raw,
+}
+
+/// a compressing writer that has to be finished to complete the archive
+pub trait FinishWrite: Write {
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// an uncompressed file only needs its buffer flushed
+impl FinishWrite for std::io::BufWriter<std::fs::File> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+
 /// Supported compression formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
@@ -108,11 +296,71 @@ pub enum Format {
     Zip,
     SevenZ,
     Rar,
+    Tar,
+    Bzip2,
+    Lz4,
+    Lzma,
+    Brotli,
+    Dedup,
 }
 
 impl Format {
+    /// encoder for a tar stream assembled entry by entry, for the single-stream formats
+    ///
+    /// `None` for zip, 7z, rar and dedup, which have their own layout
+    pub fn tar_writer(
+        &self,
+        output_path: &Path,
+        options: &CompressionOptions,
+    ) -> Result<Option<Box<dyn FinishWrite>>> {
+        Ok(Some(match self {
+            Format::Zstd => zstd::stream_writer(output_path, options)?,
+            Format::Gzip => gz::stream_writer(output_path, options)?,
+            Format::Xz => xz::stream_writer(output_path, options)?,
+            Format::Bzip2 => stream::stream_writer::<bz2::Bzip2Codec>(output_path, options)?,
+            Format::Lz4 => stream::stream_writer::<lz4::Lz4Codec>(output_path, options)?,
+            Format::Lzma => stream::stream_writer::<lzma::LzmaCodec>(output_path, options)?,
+            Format::Brotli => stream::stream_writer::<br::BrotliCodec>(output_path, options)?,
+            Format::Tar => stream::stream_writer::<tar::TarCodec>(output_path, options)?,
+            Format::Zip | Format::SevenZ | Format::Rar | Format::Dedup => return Ok(None),
+        }))
+    }
+
+    /// decoded content of a single-stream format and whether it holds a tarball
+    ///
+    /// `None` for zip, 7z, rar and dedup, which have their own layout
+    pub fn stream_reader(
+        &self,
+        archive_path: &Path,
+        options: &ExtractionOptions,
+    ) -> Result<Option<(StreamPayload, Box<dyn Read>)>> {
+        Ok(Some(match self {
+            Format::Zstd => zstd::stream_reader(archive_path, options)?,
+            Format::Gzip => gz::stream_reader(archive_path, options)?,
+            Format::Xz => xz::stream_reader(archive_path, options)?,
+            Format::Bzip2 => stream::stream_reader::<bz2::Bzip2Codec>(archive_path, options)?,
+            Format::Lz4 => stream::stream_reader::<lz4::Lz4Codec>(archive_path, options)?,
+            Format::Lzma => stream::stream_reader::<lzma::LzmaCodec>(archive_path, options)?,
+            Format::Brotli => stream::stream_reader::<br::BrotliCodec>(archive_path, options)?,
+            Format::Tar => stream::stream_reader::<tar::TarCodec>(archive_path, options)?,
+            Format::Zip | Format::SevenZ | Format::Rar | Format::Dedup => return Ok(None),
+        }))
+    }
+
     /// Detect format from file path, with magic number validation
     pub fn detect(path: &Path) -> Result<Self> {
+        // any member of a volume set stands for the whole archive
+        if let Some(set) = VolumeSet::detect(path)? {
+            return match set.kind {
+                VolumeKind::Rar => Ok(Format::Rar),
+                VolumeKind::Zip => Ok(Format::Zip),
+                VolumeKind::Split => Self::from_magic(set.first())
+                    .ok()
+                    .or_else(|| Self::from_extension(&set.archive_name()))
+                    .ok_or_else(|| anyhow::anyhow!("unsupported archive format")),
+            };
+        }
+
         // Try magic number detection first (most reliable)
         if let Ok(format) = Self::from_magic(path) {
             return Ok(format);
@@ -130,7 +378,8 @@ impl Format {
     pub fn from_extension(path: &Path) -> Option<Self> {
         let filename = path.file_name()?.to_str()?.to_lowercase();
 
-        if filename.ends_with(".zst") || filename.ends_with(".zstd") {
+        if filename.ends_with(".zst") || filename.ends_with(".zstd") || filename.ends_with(".tzst")
+        {
             Some(Format::Zstd)
         } else if filename.ends_with(".tgz")
             || filename.ends_with(".tar.gz")
@@ -142,12 +391,27 @@ impl Format {
             || filename.ends_with(".xz")
         {
             Some(Format::Xz)
+        } else if filename.ends_with(".tbz2")
+            || filename.ends_with(".tbz")
+            || filename.ends_with(".bz2")
+        {
+            Some(Format::Bzip2)
+        } else if filename.ends_with(".lz4") {
+            Some(Format::Lz4)
+        } else if filename.ends_with(".tlz") || filename.ends_with(".lzma") {
+            Some(Format::Lzma)
+        } else if filename.ends_with(".br") {
+            Some(Format::Brotli)
+        } else if filename.ends_with(".tar") {
+            Some(Format::Tar)
         } else if filename.ends_with(".zip") {
             Some(Format::Zip)
         } else if filename.ends_with(".7z") {
             Some(Format::SevenZ)
         } else if filename.ends_with(".rar") {
             Some(Format::Rar)
+        } else if filename.ends_with(".zzd") {
+            Some(Format::Dedup)
         } else {
             None
         }
@@ -156,7 +420,6 @@ impl Format {
     /// Detect format using magic number detection
     fn from_magic(path: &Path) -> Result<Self> {
         use std::fs::File;
-        use std::io::Read;
 
         let mut file = File::open(path).with_context(|| {
             format!(
@@ -164,8 +427,9 @@ impl Format {
                 path.display()
             )
         })?;
-        let mut buffer = [0u8; 16];
-        let bytes_read = file.read(&mut buffer)?;
+        // large enough to reach the ustar magic of a tar header
+        let mut buffer = [0u8; 512];
+        let bytes_read = read_prefix(&mut file, &mut buffer)?;
 
         if bytes_read >= 4 {
             // Check magic numbers
@@ -176,10 +440,20 @@ impl Format {
                 [0x50, 0x4B, 0x03, 0x04] | [0x50, 0x4B, 0x05, 0x06] | [0x50, 0x4B, 0x07, 0x08] => {
                     return Ok(Format::Zip); // ZIP
                 }
+                [b'B', b'Z', b'h', b'1'..=b'9'] => return Ok(Format::Bzip2), // bzip2
+                [0x04, 0x22, 0x4D, 0x18] => return Ok(Format::Lz4),          // LZ4 frame
                 _ => {}
             }
         }
 
+        // lzma-alone has no magic; accept the usual properties byte with a sane dictionary size
+        if bytes_read >= 13 && buffer[0] == 0x5D {
+            let dict_size = u32::from_le_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]);
+            if dict_size >= 4096 && dict_size.is_power_of_two() {
+                return Ok(Format::Lzma);
+            }
+        }
+
         if bytes_read >= 6 && &buffer[..6] == b"7z\xBC\xAF\x27\x1C" {
             return Ok(Format::SevenZ); // 7-Zip
         }
@@ -192,6 +466,14 @@ impl Format {
             return Ok(Format::Rar); // RAR v5
         }
 
+        if bytes_read >= 8 && &buffer[..8] == b"ZZZDEDUP" {
+            return Ok(Format::Dedup);
+        }
+
+        if bytes_read >= 262 && &buffer[257..262] == b"ustar" {
+            return Ok(Format::Tar); // POSIX or GNU tar
+        }
+
         // Use tree_magic_mini as final fallback
         match tree_magic_mini::from_filepath(path) {
             Some(mime_type) => match mime_type {
@@ -201,6 +483,10 @@ impl Format {
                 "application/zip" => Ok(Format::Zip),
                 "application/x-7z-compressed" => Ok(Format::SevenZ),
                 "application/x-rar-compressed" | "application/vnd.rar" => Ok(Format::Rar),
+                "application/x-tar" => Ok(Format::Tar),
+                "application/x-bzip2" | "application/x-bzip" => Ok(Format::Bzip2),
+                "application/x-lz4" => Ok(Format::Lz4),
+                "application/x-lzma" => Ok(Format::Lzma),
                 _ => Err(anyhow::anyhow!(
                     "unsupported archive format (unknown mime type from tree_magic_mini: {mime_type})"
                 )),
@@ -220,9 +506,31 @@ impl Format {
             Format::Zip => "zip",
             Format::SevenZ => "7z",
             Format::Rar => "rar",
+            Format::Tar => "tar",
+            Format::Bzip2 => "tbz2",
+            Format::Lz4 => "tar.lz4",
+            Format::Lzma => "tar.lzma",
+            Format::Brotli => "tar.br",
+            Format::Dedup => "zzd",
         }
     }
 
+    /// whether an archive is encrypted; always false for formats without encryption
+    pub fn is_encrypted(&self, archive_path: &Path) -> Result<bool> {
+        Ok(match self {
+            Format::Zstd => zstd::is_encrypted_archive(archive_path)?,
+            Format::SevenZ => sevenz::is_encrypted(archive_path),
+            Format::Dedup => dedup::is_encrypted(archive_path)?,
+            _ => false,
+        })
+    }
+
+    /// whether the compression level changes the output
+    pub fn has_levels(&self) -> bool {
+        // lz4_flex has a single (fast) level and plain tar is not compressed
+        !matches!(self, Format::Lz4 | Format::Tar)
+    }
+
     /// Get format name for display
     pub fn name(&self) -> &'static str {
         match self {
@@ -232,8 +540,28 @@ impl Format {
             Format::Zip => "ZIP",
             Format::SevenZ => "7-Zip",
             Format::Rar => "RAR",
+            Format::Tar => "tar",
+            Format::Bzip2 => "bzip2",
+            Format::Lz4 => "LZ4",
+            Format::Lzma => "LZMA",
+            Format::Brotli => "Brotli",
+            Format::Dedup => "dedup",
+        }
+    }
+}
+
+/// read up to `buffer.len()` bytes, stopping early only at end of file
+fn read_prefix(reader: &mut impl std::io::Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
         }
     }
+    Ok(filled)
 }
 
 /// trait for compression formats
@@ -253,9 +581,9 @@ pub trait CompressionFormat {
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()>;
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>>;
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>>;
 
     fn extension() -> &'static str;
 
-    fn test_integrity(archive_path: &Path) -> Result<()>;
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()>;
 }
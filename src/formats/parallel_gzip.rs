@@ -0,0 +1,174 @@
+//! block-parallel gzip compression (pigz-style)
+//!
+//! input is cut into fixed-size blocks that are deflated independently on worker threads.
+//! every block ends with a sync flush, which byte-aligns it, so the compressed blocks
+//! concatenate into one raw deflate stream. the result is a single standard gzip member
+//! whose CRC32 is combined from the per-block checksums. blocks are cut at fixed offsets,
+//! so the output is the same for any number of threads.
+
+use flate2::{Compress, Compression, FlushCompress};
+use std::io::{self, Write};
+
+/// uncompressed bytes deflated per block; blocks share no history, so they stay large
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// a gzip writer that deflates blocks on `threads` workers
+pub struct ParallelGzEncoder<W: Write> {
+    inner: W,
+    level: Compression,
+    threads: usize,
+    block_size: usize,
+    buffer: Vec<u8>,
+    crc: crc32fast::Hasher,
+    total_in: u64,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    /// write the gzip header; `mtime` goes into the header like `GzBuilder::mtime`
+    pub fn new(mut inner: W, level: Compression, mtime: u32, threads: u32) -> io::Result<Self> {
+        // same header flate2 writes: no flags, unknown OS
+        let xfl = match level.level() {
+            9.. => 2,
+            0..=1 => 4,
+            _ => 0,
+        };
+        let mut header = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, xfl, 255];
+        header[4..8].copy_from_slice(&mtime.to_le_bytes());
+        inner.write_all(&header)?;
+
+        Ok(Self {
+            inner,
+            level,
+            threads: threads.max(1) as usize,
+            block_size: DEFAULT_BLOCK_SIZE,
+            buffer: Vec::new(),
+            crc: crc32fast::Hasher::new(),
+            total_in: 0,
+        })
+    }
+
+    /// deflate the first `len` buffered bytes, one block per worker at a time
+    fn compress_buffered(&mut self, len: usize) -> io::Result<()> {
+        let level = self.level;
+        for batch in self.buffer[..len].chunks(self.block_size * self.threads) {
+            let results = std::thread::scope(|scope| {
+                let workers: Vec<_> = batch
+                    .chunks(self.block_size)
+                    .map(|block| scope.spawn(move || compress_block(block, level)))
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().expect("gzip worker panicked"))
+                    .collect::<Vec<_>>()
+            });
+
+            for result in results {
+                let (compressed, crc) = result?;
+                self.inner.write_all(&compressed)?;
+                self.crc.combine(&crc);
+            }
+        }
+
+        self.total_in += len as u64;
+        self.buffer.drain(..len);
+        Ok(())
+    }
+
+    /// compress the remaining input, write the final block and the gzip trailer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress_buffered(self.buffer.len())?;
+
+        // an empty final block marks the end of the deflate stream
+        let mut compress = Compress::new(self.level, false);
+        let mut last_block = Vec::with_capacity(16);
+        compress.compress_vec(&[], &mut last_block, FlushCompress::Finish)?;
+        self.inner.write_all(&last_block)?;
+
+        self.inner
+            .write_all(&self.crc.clone().finalize().to_le_bytes())?;
+        // ISIZE is the input length modulo 2^32
+        self.inner
+            .write_all(&(self.total_in as u32).to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        let batch_size = self.block_size * self.threads;
+        if self.buffer.len() >= batch_size {
+            let len = self.buffer.len() / batch_size * batch_size;
+            self.compress_buffered(len)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // blocks are only cut at `block_size`; partial data stays buffered until finish
+        self.inner.flush()
+    }
+}
+
+/// deflate one block ending in a sync flush, returning the data and its CRC32
+fn compress_block(block: &[u8], level: Compression) -> io::Result<(Vec<u8>, crc32fast::Hasher)> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(block);
+
+    let mut compress = Compress::new(level, false);
+    let mut output = Vec::with_capacity(block.len() / 2 + 64);
+    loop {
+        let consumed = compress.total_in() as usize;
+        if output.capacity() - output.len() < 64 {
+            output.reserve(block.len() / 4 + 64);
+        }
+        compress.compress_vec(&block[consumed..], &mut output, FlushCompress::Sync)?;
+        // the flush is complete once all input is consumed with output space left over
+        if compress.total_in() as usize == block.len() && output.len() < output.capacity() {
+            break;
+        }
+    }
+
+    Ok((output, crc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_parallel_gzip_is_single_member() {
+        let data: Vec<u8> = (0..3_000_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 27) as u8)
+            .collect();
+
+        let mut encoder =
+            ParallelGzEncoder::new(Vec::new(), Compression::new(6), 1_700_000_000, 4).unwrap();
+        // uneven writes exercise the block buffering
+        for chunk in data.chunks(70_001) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+
+        // GzDecoder stops after the first member, so this checks the whole stream is one
+        let mut decoder = GzDecoder::new(compressed.as_slice());
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(decoder.header().unwrap().mtime(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_parallel_gzip_empty_input() {
+        let encoder = ParallelGzEncoder::new(Vec::new(), Compression::new(6), 0, 2).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut decoded = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert!(decoded.is_empty());
+    }
+}
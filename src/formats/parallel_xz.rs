@@ -0,0 +1,374 @@
+//! parallel decoding of multi-block xz files
+//!
+//! the multithreaded encoder splits its output into independent blocks and records their
+//! sizes in the stream index. liblzma 5.2 has no threaded decoder, so each block is wrapped
+//! in a minimal single-block stream of its own and decoded on a worker thread.
+
+use std::collections::VecDeque;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use xz2::read::XzDecoder;
+
+const HEADER_MAGIC: &[u8; 6] = b"\xFD7zXZ\0";
+const FOOTER_MAGIC: &[u8; 2] = b"YZ";
+/// stream header and footer are both 12 bytes
+const HEADER_SIZE: u64 = 12;
+const FOOTER_SIZE: u64 = 12;
+/// decoded bytes held per batch; files with larger blocks are decoded sequentially
+const MEMORY_LIMIT: u64 = 512 << 20;
+
+/// one block of the stream, as recorded in the index
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    unpadded_size: u64,
+    uncompressed_size: u64,
+}
+
+impl Block {
+    /// size on disk, including the padding to a multiple of four bytes
+    fn padded_size(&self) -> Option<u64> {
+        self.unpadded_size.div_ceil(4).checked_mul(4)
+    }
+}
+
+/// block layout of a single-stream xz file
+#[derive(Debug)]
+pub struct XzIndex {
+    header: [u8; HEADER_SIZE as usize],
+    blocks: Vec<Block>,
+}
+
+impl XzIndex {
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+}
+
+/// read the block index of `file`
+///
+/// returns `None` for anything but a single stream without padding (e.g. concatenated
+/// `.xz` files) and for blocks over `MEMORY_LIMIT`, which the sequential decoder handles
+/// instead
+pub fn read_index(file: &mut (impl Read + Seek)) -> io::Result<Option<XzIndex>> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if file_len < HEADER_SIZE + FOOTER_SIZE {
+        return Ok(None);
+    }
+
+    let mut header = [0u8; HEADER_SIZE as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    let mut footer = [0u8; FOOTER_SIZE as usize];
+    file.seek(SeekFrom::Start(file_len - FOOTER_SIZE))?;
+    file.read_exact(&mut footer)?;
+
+    // stream flags must agree, which also rules out trailing stream padding
+    if &header[..6] != HEADER_MAGIC
+        || &footer[10..] != FOOTER_MAGIC
+        || header[6..8] != footer[8..10]
+    {
+        return Ok(None);
+    }
+
+    let backward_size = u64::from(u32::from_le_bytes(footer[4..8].try_into().unwrap()));
+    let index_size = (backward_size + 1) * 4;
+    let Some(index_start) = (file_len - FOOTER_SIZE).checked_sub(index_size) else {
+        return Ok(None);
+    };
+    if index_start < HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let mut index = vec![0u8; index_size as usize];
+    file.seek(SeekFrom::Start(index_start))?;
+    file.read_exact(&mut index)?;
+    let Some(blocks) = parse_index(&index) else {
+        return Ok(None);
+    };
+    if blocks
+        .iter()
+        .any(|block| block.uncompressed_size > MEMORY_LIMIT)
+    {
+        return Ok(None);
+    }
+
+    // a second stream before this one would leave bytes unaccounted for
+    let blocks_size = blocks.iter().try_fold(HEADER_SIZE, |total, block| {
+        total.checked_add(block.padded_size()?)
+    });
+    if blocks_size != Some(index_start) {
+        return Ok(None);
+    }
+
+    Ok(Some(XzIndex { header, blocks }))
+}
+
+fn parse_index(index: &[u8]) -> Option<Vec<Block>> {
+    let (body, crc) = index.split_at(index.len().checked_sub(4)?);
+    if crc32fast::hash(body).to_le_bytes() != crc || body.first() != Some(&0) {
+        return None;
+    }
+
+    let mut rest = &body[1..];
+    let count = read_varint(&mut rest)?;
+    let mut blocks = Vec::new();
+    for _ in 0..count {
+        let unpadded_size = read_varint(&mut rest)?;
+        let uncompressed_size = read_varint(&mut rest)?;
+        blocks.push(Block {
+            unpadded_size,
+            uncompressed_size,
+        });
+    }
+
+    rest.iter().all(|&byte| byte == 0).then_some(blocks)
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7F) << (i * 7);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// decode one block by giving it a stream header, index and footer of its own
+fn decode_block(header: &[u8], block: Block, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut index = vec![0];
+    write_varint(&mut index, 1);
+    write_varint(&mut index, block.unpadded_size);
+    write_varint(&mut index, block.uncompressed_size);
+    index.resize(index.len().div_ceil(4) * 4, 0);
+    let index_crc = crc32fast::hash(&index);
+    index.extend_from_slice(&index_crc.to_le_bytes());
+
+    let backward_size = (index.len() / 4 - 1) as u32;
+    let mut footer_fields = backward_size.to_le_bytes().to_vec();
+    footer_fields.extend_from_slice(&header[6..8]);
+
+    let mut stream = Vec::with_capacity(data.len() + index.len() + 24);
+    stream.extend_from_slice(header);
+    stream.extend_from_slice(data);
+    stream.extend_from_slice(&index);
+    stream.extend_from_slice(&crc32fast::hash(&footer_fields).to_le_bytes());
+    stream.extend_from_slice(&footer_fields);
+    stream.extend_from_slice(FOOTER_MAGIC);
+
+    let mut output = Vec::with_capacity(block.uncompressed_size.min(MEMORY_LIMIT) as usize);
+    XzDecoder::new(stream.as_slice()).read_to_end(&mut output)?;
+    if output.len() as u64 != block.uncompressed_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "xz block size does not match the index",
+        ));
+    }
+    Ok(output)
+}
+
+/// decodes a multi-block xz file, `threads` blocks at a time
+pub struct ParallelXzReader<R: Read> {
+    input: R,
+    header: [u8; HEADER_SIZE as usize],
+    blocks: VecDeque<Block>,
+    threads: usize,
+    decoded: VecDeque<Vec<u8>>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl<R: Read> ParallelXzReader<R> {
+    /// `input` must be positioned at the start of the file described by `index`
+    pub fn new(mut input: R, index: XzIndex, threads: u32) -> io::Result<Self> {
+        input.read_exact(&mut [0u8; HEADER_SIZE as usize])?;
+        Ok(Self {
+            input,
+            header: index.header,
+            blocks: index.blocks.into(),
+            threads: threads.max(1) as usize,
+            decoded: VecDeque::new(),
+            current: Cursor::new(Vec::new()),
+        })
+    }
+
+    /// decode up to `threads` blocks, fewer once their output would pass `MEMORY_LIMIT`
+    fn decode_batch(&mut self) -> io::Result<()> {
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+        while batch.len() < self.threads {
+            let Some(&block) = self.blocks.front() else {
+                break;
+            };
+            batch_size += block.uncompressed_size;
+            if !batch.is_empty() && batch_size > MEMORY_LIMIT {
+                break;
+            }
+            self.blocks.pop_front();
+            // read_index checked that the padded sizes add up to the file
+            let mut data = vec![0u8; block.padded_size().unwrap_or_default() as usize];
+            self.input.read_exact(&mut data)?;
+            batch.push((block, data));
+        }
+
+        let header = &self.header;
+        let results = std::thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .iter()
+                .map(|(block, data)| scope.spawn(move || decode_block(header, *block, data)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("xz worker panicked"))
+                .collect::<Vec<_>>()
+        });
+        for result in results {
+            self.decoded.push_back(result?);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ParallelXzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            if self.decoded.is_empty() {
+                if self.blocks.is_empty() {
+                    return Ok(0);
+                }
+                self.decode_batch()?;
+            }
+            if let Some(next) = self.decoded.pop_front() {
+                self.current = Cursor::new(next);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parallel_decode_of_multi_block_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.xz");
+        let data: Vec<u8> = (0..600_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 26) as u8)
+            .collect();
+
+        let stream = xz2::stream::MtStreamBuilder::new()
+            .threads(2)
+            .block_size(100_000)
+            .preset(1)
+            .encoder()
+            .unwrap();
+        let mut encoder = xz2::write::XzEncoder::new_stream(File::create(&path).unwrap(), stream);
+        encoder.write_all(&data).unwrap();
+        encoder.finish().unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let index = read_index(&mut file).unwrap().unwrap();
+        assert_eq!(index.block_count(), 6);
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut decoded = Vec::new();
+        ParallelXzReader::new(file, index, 4)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    /// a stream with zero-filled blocks of the given sizes and a valid index
+    fn write_stream(path: &Path, blocks: &[Block]) {
+        let mut index = vec![0];
+        write_varint(&mut index, blocks.len() as u64);
+        for block in blocks {
+            write_varint(&mut index, block.unpadded_size);
+            write_varint(&mut index, block.uncompressed_size);
+        }
+        index.resize(index.len().div_ceil(4) * 4, 0);
+        let index_crc = crc32fast::hash(&index);
+        index.extend_from_slice(&index_crc.to_le_bytes());
+
+        let mut bytes = HEADER_MAGIC.to_vec();
+        bytes.extend_from_slice(&[0, 4, 0, 0, 0, 0]);
+        for block in blocks {
+            let padded_size = block.padded_size().unwrap_or(4).min(1 << 20);
+            bytes.resize(bytes.len() + padded_size as usize, 0);
+        }
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&((index.len() / 4 - 1) as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0, 4]);
+        bytes.extend_from_slice(FOOTER_MAGIC);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_untrusted_block_sizes_are_not_indexed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("crafted.xz");
+        let block = Block {
+            unpadded_size: 16,
+            uncompressed_size: 1,
+        };
+
+        write_stream(&path, &[block, block]);
+        assert!(read_index(&mut File::open(&path).unwrap())
+            .unwrap()
+            .is_some());
+
+        let huge = Block {
+            uncompressed_size: u64::MAX,
+            ..block
+        };
+        write_stream(&path, &[block, huge]);
+        assert!(read_index(&mut File::open(&path).unwrap())
+            .unwrap()
+            .is_none());
+
+        let overflowing = Block {
+            unpadded_size: u64::MAX - 1,
+            ..block
+        };
+        write_stream(&path, &[block, overflowing]);
+        assert!(read_index(&mut File::open(&path).unwrap())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_concatenated_streams_are_not_indexed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("joined.xz");
+        let mut bytes = Vec::new();
+        for part in [b"first".as_slice(), b"second"] {
+            let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 1);
+            encoder.write_all(part).unwrap();
+            bytes.extend(encoder.finish().unwrap());
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(read_index(&mut File::open(&path).unwrap())
+            .unwrap()
+            .is_none());
+    }
+}
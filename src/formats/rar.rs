@@ -10,6 +10,48 @@ use std::path::Path;
 #[cfg(feature = "rar")]
 pub struct RarFormat;
 
+/// unrar needs the archive path as a string; volume sets are opened from their first part
+#[cfg(feature = "rar")]
+pub(crate) fn path_str(archive_path: &Path) -> Result<String> {
+    crate::volumes::first_volume(archive_path)?
+        .into_os_string()
+        .into_string()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "RAR archive path is not valid UTF-8: {}",
+                archive_path.display()
+            )
+        })
+}
+
+/// an unrar handle that decrypts with `password` when one is given
+///
+/// the password covers both encrypted file data and encrypted headers
+#[cfg(feature = "rar")]
+pub(crate) fn archive<'a>(path: &'a str, password: Option<&'a str>) -> unrar::Archive<'a> {
+    match password {
+        Some(password) => unrar::Archive::with_password(path, password),
+        None => unrar::Archive::new(path),
+    }
+}
+
+/// map unrar's password failures to the messages the 7z path uses
+#[cfg(feature = "rar")]
+pub(crate) fn rar_error(password: Option<&str>, e: unrar::error::UnrarError) -> anyhow::Error {
+    use unrar::error::Code;
+    match e.code {
+        Code::MissingPassword => {
+            anyhow::anyhow!("Archive is password protected but no password was provided")
+        }
+        Code::BadPassword => anyhow::anyhow!("Failed to decrypt archive (invalid password)"),
+        // RAR4 stores no password check, so a wrong password looks the same as corrupt data
+        Code::BadData if password.is_some() => {
+            anyhow::anyhow!("Archive data failed its checksum (wrong password or corrupt data)")
+        }
+        _ => e.into(),
+    }
+}
+
 #[cfg(feature = "rar")]
 impl CompressionFormat for RarFormat {
     fn compress(
@@ -28,18 +70,15 @@ impl CompressionFormat for RarFormat {
         options: &ExtractionOptions,
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()> {
-        use unrar::Archive;
-
-        let archive_path_str = archive_path.to_str().ok_or_else(|| {
-            anyhow::anyhow!(
-                "RAR archive path is not valid UTF-8: {}",
-                archive_path.display()
-            )
-        })?;
-        let mut archive = Archive::new(archive_path_str).open_for_processing()?;
+        let path = path_str(archive_path)?;
+        let password = options.password.as_deref();
+        let error = |e| rar_error(password, e);
+        let mut archive = archive(&path, password)
+            .open_for_processing()
+            .map_err(error)?;
 
         let mut entry_count = 0u64;
-        while let Some(header) = archive.read_header()? {
+        while let Some(header) = archive.read_header().map_err(error)? {
             let entry = header.entry();
 
             if entry.is_file() {
@@ -51,7 +90,7 @@ impl CompressionFormat for RarFormat {
                     false,
                 )?;
                 let Some(output_path) = output_path else {
-                    archive = header.skip()?;
+                    archive = header.skip().map_err(error)?;
                     continue;
                 };
 
@@ -59,9 +98,9 @@ impl CompressionFormat for RarFormat {
                     std::fs::create_dir_all(parent)?;
                 }
 
-                archive = header.extract_to(output_path)?;
+                archive = header.extract_to(output_path).map_err(error)?;
             } else {
-                archive = header.skip()?;
+                archive = header.skip().map_err(error)?;
             }
 
             // Update progress
@@ -74,26 +113,22 @@ impl CompressionFormat for RarFormat {
         Ok(())
     }
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
-        use unrar::Archive;
-
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
         let mut entries = Vec::new();
-        let archive_path_str = archive_path.to_str().ok_or_else(|| {
-            anyhow::anyhow!(
-                "RAR archive path is not valid UTF-8: {}",
-                archive_path.display()
-            )
-        })?;
-        let mut archive = Archive::new(archive_path_str).open_for_listing()?;
+        let path = path_str(archive_path)?;
+        let password = options.password.as_deref();
+        let error = |e| rar_error(password, e);
+        let mut archive = archive(&path, password).open_for_listing().map_err(error)?;
 
-        while let Some(header) = archive.read_header()? {
+        while let Some(header) = archive.read_header().map_err(error)? {
             let entry = header.entry();
             entries.push(ArchiveEntry {
                 path: entry.filename.to_string_lossy().to_string(),
                 size: entry.unpacked_size,
                 is_file: entry.is_file(),
+                method: None,
             });
-            archive = header.skip()?;
+            archive = header.skip().map_err(error)?;
         }
 
         Ok(entries)
@@ -103,19 +138,16 @@ impl CompressionFormat for RarFormat {
         "rar"
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
-        use unrar::Archive;
-
-        let archive_path_str = archive_path.to_str().ok_or_else(|| {
-            anyhow::anyhow!(
-                "RAR archive path is not valid UTF-8: {}",
-                archive_path.display()
-            )
-        })?;
-        let mut archive = Archive::new(archive_path_str).open_for_processing()?;
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        let path = path_str(archive_path)?;
+        let password = options.password.as_deref();
+        let error = |e| rar_error(password, e);
+        let mut archive = archive(&path, password)
+            .open_for_processing()
+            .map_err(error)?;
 
-        while let Some(header) = archive.read_header()? {
-            archive = header.test()?;
+        while let Some(header) = archive.read_header().map_err(error)? {
+            archive = header.test().map_err(error)?;
         }
 
         Ok(())
@@ -146,7 +178,10 @@ impl crate::formats::CompressionFormat for RarFormat {
         Err(rar_not_enabled_error())
     }
 
-    fn list(_archive_path: &std::path::Path) -> crate::Result<Vec<crate::formats::ArchiveEntry>> {
+    fn list(
+        _archive_path: &std::path::Path,
+        _options: &crate::formats::ExtractionOptions,
+    ) -> crate::Result<Vec<crate::formats::ArchiveEntry>> {
         Err(rar_not_enabled_error())
     }
 
@@ -154,12 +189,15 @@ impl crate::formats::CompressionFormat for RarFormat {
         "rar"
     }
 
-    fn test_integrity(_archive_path: &std::path::Path) -> crate::Result<()> {
+    fn test_integrity(
+        _archive_path: &std::path::Path,
+        _options: &crate::formats::ExtractionOptions,
+    ) -> crate::Result<()> {
         Err(rar_not_enabled_error())
     }
 }
 
 #[cfg(not(feature = "rar"))]
-fn rar_not_enabled_error() -> anyhow::Error {
+pub(crate) fn rar_not_enabled_error() -> anyhow::Error {
     anyhow::anyhow!("RAR support not enabled - compile with --features rar")
 }
@@ -0,0 +1,711 @@
+//! zstd seekable format: independent frames followed by a seek table
+//!
+//! layout (see zstd's `contrib/seekable_format`):
+//!
+//! ```text
+//! [frame 0] [frame 1] ... [frame n-1] [tar index] [seek table]
+//! ```
+//!
+//! every data frame is an ordinary zstd frame, and the tar index and seek table are
+//! skippable frames, so `zstd -d` still decodes the archive as one stream. the tar index
+//! maps entry paths to uncompressed offsets and is listed in the seek table as a frame
+//! with no decompressed bytes.
+
+use crate::{formats::ArchiveEntry, utils, volumes::VolumeReader, Result};
+use anyhow::Context;
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Seek, SeekFrom, Write},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::JoinHandle,
+};
+
+/// skippable frame magic reserved for the seek table
+const SEEK_TABLE_MAGIC: u32 = 0x184D_2A5E;
+/// magic at the very end of a seekable archive
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+/// skippable frame magic used for our tar index
+const INDEX_FRAME_MAGIC: u32 = 0x184D_2A5D;
+/// identifies the payload of the tar index frame
+const INDEX_MAGIC: &[u8; 8] = b"ZZZTIDX1";
+/// skippable frame header: magic + frame size
+const SKIPPABLE_HEADER_SIZE: u64 = 8;
+/// seek table footer: frame count + descriptor + magic
+const FOOTER_SIZE: u64 = 9;
+/// descriptor bit marking per-frame checksums in the seek table
+const CHECKSUM_FLAG: u8 = 0x80;
+
+/// default amount of uncompressed data per frame
+pub const DEFAULT_FRAME_SIZE: u64 = 1 << 20;
+/// largest frame the seekable format allows
+pub const MAX_FRAME_SIZE: u64 = 1 << 30;
+
+/// one seek table entry: compressed and decompressed frame sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSize {
+    pub compressed: u32,
+    pub decompressed: u32,
+}
+
+/// a frame located within the archive
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    compressed_offset: u64,
+    compressed_size: u32,
+    decompressed_offset: u64,
+    decompressed_size: u32,
+}
+
+impl Frame {
+    fn decompressed_end(&self) -> u64 {
+        self.decompressed_offset + u64::from(self.decompressed_size)
+    }
+}
+
+/// a tar entry and the uncompressed byte range holding its headers and data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub path: String,
+    pub size: u64,
+    pub is_file: bool,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl From<&IndexEntry> for ArchiveEntry {
+    fn from(entry: &IndexEntry) -> Self {
+        ArchiveEntry {
+            path: entry.path.clone(),
+            size: entry.size,
+            is_file: entry.is_file,
+            method: None,
+        }
+    }
+}
+
+/// splits written data into independently compressed frames of `frame_size` bytes
+pub struct SeekableWriter<W: Write, F: FnMut(&[u8]) -> io::Result<Vec<u8>>> {
+    inner: W,
+    buffer: Vec<u8>,
+    frame_size: usize,
+    frames: Vec<FrameSize>,
+    compress: F,
+}
+
+impl<W: Write, F: FnMut(&[u8]) -> io::Result<Vec<u8>>> SeekableWriter<W, F> {
+    /// `compress` turns one chunk of uncompressed data into a complete zstd frame
+    pub fn new(inner: W, frame_size: u32, compress: F) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+            frame_size: frame_size as usize,
+            frames: Vec::new(),
+            compress,
+        }
+    }
+
+    fn write_frame(&mut self, len: usize) -> io::Result<()> {
+        let frame = (self.compress)(&self.buffer[..len])?;
+        self.inner.write_all(&frame)?;
+        self.frames.push(FrameSize {
+            compressed: u32::try_from(frame.len()).map_err(io::Error::other)?,
+            decompressed: len as u32,
+        });
+        self.buffer.drain(..len);
+        Ok(())
+    }
+
+    /// compress any buffered data and return the writer with the frames written so far
+    pub fn finish(mut self) -> io::Result<(W, Vec<FrameSize>)> {
+        if !self.buffer.is_empty() {
+            self.write_frame(self.buffer.len())?;
+        }
+        Ok((self.inner, self.frames))
+    }
+}
+
+impl<W: Write, F: FnMut(&[u8]) -> io::Result<Vec<u8>>> Write for SeekableWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() >= self.frame_size {
+            self.write_frame(self.frame_size)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // frames are only cut at `frame_size`; flushing must not produce tiny frames
+        self.inner.flush()
+    }
+}
+
+/// index every entry of a decoded tarball by its uncompressed byte range
+pub fn build_index<R: Read>(reader: R) -> Result<Vec<IndexEntry>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    // an entry starts where the previous one ended, so gnu long-name and pax
+    // headers in front of it fall inside its range
+    let mut start = 0u64;
+
+    for entry in archive
+        .entries()
+        .context("failed to read tarball for index")?
+    {
+        let entry = entry.context("failed to read tarball for index")?;
+        let header = entry.header();
+        let size = header.size()?;
+        let end = entry.raw_file_position() + header.entry_size()?.div_ceil(512) * 512;
+
+        entries.push(IndexEntry {
+            path: entry.path()?.to_string_lossy().to_string(),
+            size,
+            is_file: header.entry_type().is_file(),
+            start,
+            end,
+        });
+        start = end;
+    }
+
+    Ok(entries)
+}
+
+/// passes a tarball through to `inner` while indexing it on a parser thread
+///
+/// the index is built from the bytes as they are written, so nothing has to be
+/// decoded again once the frames are on disk
+pub struct IndexingWriter<W: Write> {
+    inner: W,
+    sender: Option<SyncSender<Vec<u8>>>,
+    parser: JoinHandle<Result<Vec<IndexEntry>>>,
+}
+
+impl<W: Write> IndexingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        // a few chunks in flight keep the parser from holding up compression
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let parser = std::thread::spawn(move || {
+            build_index(ChannelReader {
+                receiver,
+                chunk: Vec::new(),
+                offset: 0,
+            })
+        });
+        Self {
+            inner,
+            sender: Some(sender),
+            parser,
+        }
+    }
+
+    /// return the inner writer and the index of everything written
+    pub fn finish(mut self) -> Result<(W, Vec<IndexEntry>)> {
+        // closing the channel ends the parser's input
+        self.sender = None;
+        let index = self
+            .parser
+            .join()
+            .map_err(|_| anyhow::anyhow!("tar index thread panicked"))??;
+        Ok((self.inner, index))
+    }
+}
+
+impl<W: Write> Write for IndexingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(sender) = &self.sender {
+            // the parser hangs up after the end-of-archive blocks; the rest is padding
+            let _ = sender.send(buf[..written].to_vec());
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// the receiving end of an [`IndexingWriter`], read as one stream
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let count = buf.len().min(self.chunk.len() - self.offset);
+        buf[..count].copy_from_slice(&self.chunk[self.offset..self.offset + count]);
+        self.offset += count;
+        Ok(count)
+    }
+}
+
+/// write the tar index as a skippable frame, returning its seek table entry
+pub fn write_index<W: Write>(writer: &mut W, entries: &[IndexEntry]) -> io::Result<FrameSize> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(INDEX_MAGIC);
+    payload.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for entry in entries {
+        payload.extend_from_slice(&entry.start.to_le_bytes());
+        payload.extend_from_slice(&entry.end.to_le_bytes());
+        payload.extend_from_slice(&entry.size.to_le_bytes());
+        payload.push(u8::from(entry.is_file));
+        payload.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+        payload.extend_from_slice(entry.path.as_bytes());
+    }
+
+    let payload_len = u32::try_from(payload.len()).map_err(io::Error::other)?;
+    writer.write_all(&INDEX_FRAME_MAGIC.to_le_bytes())?;
+    writer.write_all(&payload_len.to_le_bytes())?;
+    writer.write_all(&payload)?;
+
+    Ok(FrameSize {
+        compressed: payload_len + SKIPPABLE_HEADER_SIZE as u32,
+        decompressed: 0,
+    })
+}
+
+/// write the seek table skippable frame that ends a seekable archive
+pub fn write_seek_table<W: Write>(writer: &mut W, frames: &[FrameSize]) -> io::Result<()> {
+    let frame_count = u32::try_from(frames.len()).map_err(io::Error::other)?;
+    let table_size = frame_count * 8 + FOOTER_SIZE as u32;
+
+    writer.write_all(&SEEK_TABLE_MAGIC.to_le_bytes())?;
+    writer.write_all(&table_size.to_le_bytes())?;
+    for frame in frames {
+        writer.write_all(&frame.compressed.to_le_bytes())?;
+        writer.write_all(&frame.decompressed.to_le_bytes())?;
+    }
+    writer.write_all(&frame_count.to_le_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(&SEEKABLE_MAGIC.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn read_exact_at(file: &mut VolumeReader, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; len];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// read the seek table at the end of `file`; `None` when the archive is not seekable
+fn read_seek_table(file: &mut VolumeReader) -> Result<Option<Vec<Frame>>> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if file_len < SKIPPABLE_HEADER_SIZE + FOOTER_SIZE {
+        return Ok(None);
+    }
+
+    let footer = read_exact_at(file, file_len - FOOTER_SIZE, FOOTER_SIZE as usize)?;
+    if read_u32(&footer[5..]) != SEEKABLE_MAGIC {
+        return Ok(None);
+    }
+    let frame_count = u64::from(read_u32(&footer));
+    let entry_size = if footer[4] & CHECKSUM_FLAG != 0 {
+        12
+    } else {
+        8
+    };
+
+    let table_size = frame_count * entry_size + FOOTER_SIZE;
+    let table_start = file_len
+        .checked_sub(table_size + SKIPPABLE_HEADER_SIZE)
+        .context("seek table is larger than the archive")?;
+    let table = read_exact_at(
+        file,
+        table_start,
+        (table_size + SKIPPABLE_HEADER_SIZE) as usize,
+    )?;
+    if read_u32(&table) != SEEK_TABLE_MAGIC || u64::from(read_u32(&table[4..])) != table_size {
+        anyhow::bail!("corrupt zstd seek table");
+    }
+
+    let mut frames = Vec::with_capacity(frame_count as usize);
+    let (mut compressed_offset, mut decompressed_offset) = (0u64, 0u64);
+    for entry in table[SKIPPABLE_HEADER_SIZE as usize..]
+        .chunks_exact(entry_size as usize)
+        .take(frame_count as usize)
+    {
+        let frame = Frame {
+            compressed_offset,
+            compressed_size: read_u32(entry),
+            decompressed_offset,
+            decompressed_size: read_u32(&entry[4..]),
+        };
+        compressed_offset += u64::from(frame.compressed_size);
+        decompressed_offset = frame.decompressed_end();
+        frames.push(frame);
+    }
+    if compressed_offset != table_start {
+        anyhow::bail!("zstd seek table does not match the archive size");
+    }
+
+    Ok(Some(frames))
+}
+
+/// find and parse the tar index frame, if the archive has one
+fn read_index(file: &mut VolumeReader, frames: &[Frame]) -> Result<Option<Vec<IndexEntry>>> {
+    let Some(frame) = frames
+        .iter()
+        .rev()
+        .find(|frame| frame.decompressed_size == 0)
+    else {
+        return Ok(None);
+    };
+
+    let bytes = read_exact_at(
+        file,
+        frame.compressed_offset,
+        frame.compressed_size as usize,
+    )?;
+    let corrupt = || anyhow::anyhow!("corrupt tar index in seekable zstd archive");
+    if bytes.len() < SKIPPABLE_HEADER_SIZE as usize {
+        return Err(corrupt());
+    }
+    let payload = &bytes[SKIPPABLE_HEADER_SIZE as usize..];
+    if read_u32(&bytes) != INDEX_FRAME_MAGIC || !payload.starts_with(INDEX_MAGIC) {
+        return Ok(None);
+    }
+
+    let mut rest = &payload[INDEX_MAGIC.len()..];
+    let mut take = |len: usize| -> Result<&[u8]> {
+        if rest.len() < len {
+            return Err(corrupt());
+        }
+        let (head, tail) = rest.split_at(len);
+        rest = tail;
+        Ok(head)
+    };
+
+    let count = read_u64(take(8)?);
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let start = read_u64(take(8)?);
+        let end = read_u64(take(8)?);
+        let size = read_u64(take(8)?);
+        let is_file = take(1)?[0] != 0;
+        let path_len = read_u32(take(4)?) as usize;
+        let path = String::from_utf8(take(path_len)?.to_vec()).map_err(|_| corrupt())?;
+        entries.push(IndexEntry {
+            path,
+            size,
+            is_file,
+            start,
+            end,
+        });
+    }
+
+    Ok(Some(entries))
+}
+
+/// random access to the uncompressed contents of a seekable zstd archive
+pub struct SeekableArchive {
+    file: VolumeReader,
+    frames: Vec<Frame>,
+    index: Option<Vec<IndexEntry>>,
+    decompressor: zstd::bulk::Decompressor<'static>,
+    cached: Option<(usize, Vec<u8>)>,
+    position: u64,
+    len: u64,
+}
+
+impl SeekableArchive {
+    /// open `file` if it ends with a seek table; `None` for ordinary zstd archives
+    pub fn open(mut file: VolumeReader) -> Result<Option<Self>> {
+        let Some(frames) = read_seek_table(&mut file)? else {
+            return Ok(None);
+        };
+        let index = read_index(&mut file, &frames)?;
+        let len = frames.last().map_or(0, Frame::decompressed_end);
+
+        Ok(Some(Self {
+            file,
+            frames,
+            index,
+            decompressor: new_decompressor(&[])?,
+            cached: None,
+            position: 0,
+            len,
+        }))
+    }
+
+    /// entries of the tar index, if the archive holds a tarball
+    pub fn index(&self) -> Option<&[IndexEntry]> {
+        self.index.as_deref()
+    }
+
+    /// uncompressed size of the frames the archive was cut into
+    ///
+    /// only the first frame is full-sized; an archive of a single frame may have been
+    /// written with frames of any larger size, so the default is assumed for it
+    pub fn frame_size(&self) -> u32 {
+        let sizes: Vec<_> = self
+            .frames
+            .iter()
+            .map(|frame| frame.decompressed_size)
+            .filter(|&size| size > 0)
+            .take(2)
+            .collect();
+        match sizes[..] {
+            [first, _] => first,
+            [first] => first.max(DEFAULT_FRAME_SIZE as u32),
+            _ => DEFAULT_FRAME_SIZE as u32,
+        }
+    }
+
+    /// dictionary ID recorded in the first frame header
+    pub fn frame_dict_id(&mut self) -> Result<Option<u32>> {
+        let header_len = self
+            .frames
+            .first()
+            .map_or(0, |frame| frame.compressed_size.min(18));
+        let header = read_exact_at(&mut self.file, 0, header_len as usize)?;
+        Ok(zstd::zstd_safe::get_dict_id_from_frame(&header).map(|id| id.get()))
+    }
+
+    /// decode frames with `dictionary` from now on
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<()> {
+        self.decompressor = new_decompressor(dictionary)?;
+        self.cached = None;
+        Ok(())
+    }
+
+    fn load_frame(&mut self, index: usize) -> io::Result<&[u8]> {
+        if self
+            .cached
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != index)
+        {
+            let frame = self.frames[index];
+            let compressed = read_exact_at(
+                &mut self.file,
+                frame.compressed_offset,
+                frame.compressed_size as usize,
+            )?;
+            let data = self
+                .decompressor
+                .decompress(&compressed, frame.decompressed_size as usize)?;
+            if data.len() != frame.decompressed_size as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "zstd frame size does not match the seek table",
+                ));
+            }
+            self.cached = Some((index, data));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+fn new_decompressor(dictionary: &[u8]) -> Result<zstd::bulk::Decompressor<'static>> {
+    let mut decompressor = zstd::bulk::Decompressor::with_dictionary(dictionary)
+        .context("failed to create zstd decompressor")?;
+    decompressor
+        .set_parameter(zstd::stream::raw::DParameter::WindowLogMax(31))
+        .context("failed to raise zstd decoder window limit")?;
+    Ok(decompressor)
+}
+
+impl Read for SeekableArchive {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        let position = self.position;
+        let index = self
+            .frames
+            .partition_point(|frame| frame.decompressed_end() <= position);
+        let frame_offset = self.frames[index].decompressed_offset;
+        let data = self.load_frame(index)?;
+        let start = (position - frame_offset) as usize;
+        let count = buf.len().min(data.len() - start);
+        buf[..count].copy_from_slice(&data[start..start + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for SeekableArchive {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+        Ok(self.position)
+    }
+}
+
+/// uncompressed byte ranges of the entries picked by `--entry`, merging neighbours
+pub fn selected_ranges(index: &[IndexEntry], selections: &[String]) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for entry in index
+        .iter()
+        .filter(|entry| utils::entry_selected(&entry.path, selections))
+    {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == entry.start => *end = entry.end,
+            _ => ranges.push((entry.start, entry.end)),
+        }
+    }
+    ranges
+}
+
+/// reads the given uncompressed ranges back to back, seeking between them
+pub struct RangeReader {
+    archive: SeekableArchive,
+    ranges: VecDeque<(u64, u64)>,
+    remaining: u64,
+}
+
+impl RangeReader {
+    pub fn new(archive: SeekableArchive, ranges: Vec<(u64, u64)>) -> Self {
+        Self {
+            archive,
+            ranges: ranges.into(),
+            remaining: 0,
+        }
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((start, end)) = self.ranges.pop_front() else {
+                return Ok(0);
+            };
+            self.archive.seek(SeekFrom::Start(start))?;
+            self.remaining = end - start;
+        }
+
+        let len = buf.len().min(self.remaining as usize);
+        let read = self.archive.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::volumes;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn write_archive(path: &std::path::Path, data: &[u8], frame_size: u32) -> Vec<FrameSize> {
+        let file = File::create(path).unwrap();
+        let mut writer =
+            SeekableWriter::new(file, frame_size, |chunk| zstd::bulk::compress(chunk, 3));
+        writer.write_all(data).unwrap();
+        let (mut file, frames) = writer.finish().unwrap();
+        write_seek_table(&mut file, &frames).unwrap();
+        frames
+    }
+
+    #[test]
+    fn test_seekable_random_access_and_plain_decode() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.zst");
+        let data: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let frames = write_archive(&path, &data, 64 * 1024);
+        assert_eq!(frames.len(), 7);
+
+        // the seek table is a skippable frame, so a plain decoder sees only the data
+        let plain = zstd::stream::decode_all(File::open(&path).unwrap()).unwrap();
+        assert_eq!(plain, data);
+
+        let mut archive = SeekableArchive::open(volumes::open(&path).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(archive.index().is_none());
+        archive.seek(SeekFrom::Start(200_000)).unwrap();
+        let mut chunk = vec![0u8; 100_000];
+        archive.read_exact(&mut chunk).unwrap();
+        assert_eq!(chunk, data[200_000..300_000]);
+    }
+
+    #[test]
+    fn test_index_is_built_while_writing() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, size) in [("a.txt", 10usize), ("dir/b.bin", 70_000)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(size as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, &vec![7u8; size][..])
+                .unwrap();
+        }
+        let tarball = builder.into_inner().unwrap();
+
+        let mut writer = IndexingWriter::new(Vec::new());
+        for chunk in tarball.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let (written, index) = writer.finish().unwrap();
+        assert_eq!(written, tarball);
+        assert_eq!(index, build_index(&tarball[..]).unwrap());
+        assert_eq!(index[1].path, "dir/b.bin");
+    }
+
+    #[test]
+    fn test_truncated_index_frame_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("corrupt.zst");
+        let mut file = File::create(&path).unwrap();
+        let data = zstd::bulk::compress(b"some data", 3).unwrap();
+        file.write_all(&data).unwrap();
+        // an "index" frame too short to hold even a skippable frame header
+        file.write_all(&[0u8; 4]).unwrap();
+        let frames = [
+            FrameSize {
+                compressed: data.len() as u32,
+                decompressed: 9,
+            },
+            FrameSize {
+                compressed: 4,
+                decompressed: 0,
+            },
+        ];
+        write_seek_table(&mut file, &frames).unwrap();
+        drop(file);
+
+        let err = SeekableArchive::open(volumes::open(&path).unwrap())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("corrupt tar index"));
+    }
+
+    #[test]
+    fn test_plain_zstd_is_not_seekable() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("plain.zst");
+        std::fs::write(&path, zstd::bulk::compress(b"plain data", 3).unwrap()).unwrap();
+        assert!(SeekableArchive::open(volumes::open(&path).unwrap())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -4,22 +4,321 @@ use crate::{
     filter::FileFilter,
     formats::{
         ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+        SevenZMethod,
     },
     progress::Progress,
-    utils, Result,
+    utils,
+    volumes::{self, OutputFile, VolumeReader},
+    Result,
 };
 use anyhow::Context;
-use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader, SevenZWriter};
-use std::{fs::File, path::Path};
+use sevenz_rust::{
+    lzma::LZMA2Options, AesEncoderOptions, MethodOptions, Password, SeqReader, SevenZArchiveEntry,
+    SevenZMethodConfiguration, SevenZReader, SevenZWriter, SourceReader,
+};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
 
 pub struct SevenZFormat;
 
-fn sanitize_entry_timestamps(entry: &mut SevenZArchiveEntry, options: &CompressionOptions) {
+/// map our 1-22 level onto an LZMA preset, shrinking the dictionary to fit the input
+fn lzma_options(level: i32, input_size: u64) -> LZMA2Options {
+    let preset = (((level as f32 / 22.0) * 9.0) as u32).clamp(0, 9);
+    let mut lzma = LZMA2Options::with_preset(preset);
+    // a dictionary larger than the input only costs memory, on both ends
+    let fitted = input_size.next_power_of_two().clamp(
+        u64::from(sevenz_rust::lzma::DICT_SIZE_MIN),
+        u64::from(u32::MAX),
+    );
+    lzma.dict_size = lzma.dict_size.min(fitted as u32);
+    lzma
+}
+
+/// coder chain for entry contents: the selected method, encrypted when a password is set
+pub(crate) fn content_methods(
+    options: &CompressionOptions,
+    input_size: u64,
+) -> Vec<SevenZMethodConfiguration> {
+    let method = match options.sevenz_method {
+        SevenZMethod::Lzma2 => sevenz_rust::SevenZMethod::LZMA2,
+        SevenZMethod::Lzma => sevenz_rust::SevenZMethod::LZMA,
+    };
+    let lzma = lzma_options(options.level, input_size);
+
+    let mut methods = Vec::new();
+    if let Some(password) = &options.password {
+        methods.push(AesEncoderOptions::new(Password::from(password.as_str())).into());
+    }
+    methods.push(SevenZMethodConfiguration::new(method).with_options(MethodOptions::LZMA2(lzma)));
+    methods
+}
+
+/// describe one coder the way 7-Zip does, e.g. `LZMA2:24` (dictionary as a power of two)
+fn coder_method(id: &[u8], props: &[u8]) -> String {
+    let Some(method) = sevenz_rust::SevenZMethod::by_id(id) else {
+        return id.iter().map(|byte| format!("{byte:02x}")).collect();
+    };
+    let dict_size = match method.id() {
+        // property 40 is the 4 GiB dictionary, which overflows a u32
+        sevenz_rust::SevenZMethod::ID_LZMA2 if !props.is_empty() => {
+            let prop = u64::from(props[0].min(40));
+            Some((2 | (prop & 1)) << (prop / 2 + 11))
+        }
+        sevenz_rust::SevenZMethod::ID_LZMA if props.len() >= 5 => Some(u64::from(
+            u32::from_le_bytes(props[1..5].try_into().unwrap()),
+        )),
+        _ => None,
+    };
+    match dict_size {
+        Some(size) if size.is_power_of_two() => {
+            format!("{}:{}", method.name(), size.trailing_zeros())
+        }
+        Some(size) if size.is_multiple_of(1 << 20) => format!("{}:{}m", method.name(), size >> 20),
+        Some(size) => format!("{}:{}k", method.name(), size.div_ceil(1 << 10)),
+        None => method.name().to_string(),
+    }
+}
+
+/// a file opened on first read, so a solid block holds one descriptor at a time
+struct LazyFile {
+    path: PathBuf,
+    file: Option<File>,
+    done: bool,
+}
+
+impl LazyFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: None,
+            done: false,
+        }
+    }
+}
+
+impl Read for LazyFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(File::open(&self.path).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to open file for archiving {}: {e}",
+                        self.path.display()
+                    ),
+                )
+            })?),
+        };
+        let read = file.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.file = None;
+            self.done = true;
+        }
+        Ok(read)
+    }
+}
+
+/// compress the pending files as one solid block
+fn push_solid_block(
+    sz: &mut SevenZWriter<OutputFile>,
+    block: &mut Vec<(SevenZArchiveEntry, PathBuf)>,
+) -> Result<()> {
+    if block.is_empty() {
+        return Ok(());
+    }
+    let (entries, paths): (Vec<_>, Vec<_>) = block.drain(..).unzip();
+    let readers = paths
+        .into_iter()
+        .map(|path| SourceReader::new(LazyFile::new(path)))
+        .collect();
+    sz.push_archive_entries(entries, SeqReader::new(readers))?;
+    Ok(())
+}
+
+/// convert a filesystem timestamp into a 7z date, honoring the mtime clamp
+pub(crate) fn entry_date(
+    time: std::io::Result<std::time::SystemTime>,
+    options: &CompressionOptions,
+) -> Option<sevenz_rust::nt_time::FileTime> {
+    let date: sevenz_rust::nt_time::FileTime = options.clamp_mtime(time.ok()?).try_into().ok()?;
+    (date.to_raw() > 0).then_some(date)
+}
+
+/// build an archive entry from explicit metadata
+///
+/// unlike `SevenZArchiveEntry::from_path` this only records what the options ask for:
+/// deterministic archives carry the (clamped) mtime and nothing else from disk.
+fn archive_entry(
+    name: String,
+    metadata: &std::fs::Metadata,
+    options: &CompressionOptions,
+) -> SevenZArchiveEntry {
+    let mut entry = SevenZArchiveEntry::new();
+    entry.name = name;
+    entry.has_stream = metadata.is_file();
+    entry.is_directory = metadata.is_dir();
+
     if options.strip_timestamps {
-        entry.has_creation_date = false;
-        entry.has_last_modified_date = false;
-        entry.has_access_date = false;
+        return entry;
+    }
+
+    if let Some(date) = entry_date(metadata.modified(), options) {
+        entry.last_modified_date = date;
+        entry.has_last_modified_date = true;
+    }
+    if !options.deterministic {
+        if let Some(date) = entry_date(metadata.created(), options) {
+            entry.creation_date = date;
+            entry.has_creation_date = true;
+        }
+        if let Some(date) = entry_date(metadata.accessed(), options) {
+            entry.access_date = date;
+            entry.has_access_date = true;
+        }
+    }
+    entry
+}
+
+/// map a reader error to the password messages shared by all commands
+pub(crate) fn reader_error(
+    archive_path: &Path,
+    password: Option<&str>,
+    e: sevenz_rust::Error,
+) -> anyhow::Error {
+    // Check if this looks like a password-related error
+    let error_msg = format!("{e}");
+    if error_msg.contains("MaybeBadPassword")
+        || (password.is_some()
+            && (error_msg.contains("password")
+                || error_msg.contains("decrypt")
+                || error_msg.contains("encrypted")))
+    {
+        anyhow::anyhow!("Failed to decrypt archive (invalid password)")
+    } else if error_msg.contains("PasswordRequired")
+        || (password.is_none()
+            && (error_msg.contains("password")
+                || error_msg.contains("AES")
+                || error_msg.contains("encrypted")))
+    {
+        anyhow::anyhow!("Archive is password protected but no password was provided")
+    } else {
+        anyhow::anyhow!(
+            "Failed to open 7-Zip archive {}: {}",
+            archive_path.display(),
+            e
+        )
+    }
+}
+
+pub(crate) fn open_reader(
+    archive_path: &Path,
+    password: Option<&str>,
+) -> Result<SevenZReader<VolumeReader>> {
+    let reader = volumes::open(archive_path)?;
+    let len = reader.len();
+    SevenZReader::new(
+        reader,
+        len,
+        password.map_or(Password::empty(), Password::from),
+    )
+    .map_err(|e| reader_error(archive_path, password, e))
+}
+
+/// visit every entry in archive order, with `data` positioned at its contents
+///
+/// decoding errors go back to the reader, which blames a wrong password when one was given;
+/// errors from `visit` itself stop the walk and are returned as they are
+pub(crate) fn for_each_entry(
+    archive_path: &Path,
+    password: Option<&str>,
+    mut visit: impl FnMut(&SevenZArchiveEntry, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let mut reader = open_reader(archive_path, password)?;
+    let mut failure = None;
+    reader
+        .for_each_entries(|entry, data| {
+            match visit(entry, data).map_err(|e| e.downcast::<std::io::Error>()) {
+                Ok(()) => Ok(true),
+                Err(Ok(e)) => Err(e.into()),
+                Err(Err(e)) => {
+                    failure = Some(e);
+                    Ok(false)
+                }
+            }
+        })
+        .map_err(|e| reader_error(archive_path, password, e))?;
+    failure.map_or(Ok(()), Err)
+}
+
+/// first byte of a header stored as it is; an encoded (compressed or encrypted) one differs
+const K_HEADER: u8 = 0x01;
+
+/// write the header and complete the output
+///
+/// sevenz-rust encrypts the header along with the contents, but stores it as it is when
+/// compressing it saves 20 bytes or less. with `sevenz_encrypt_headers` that would leave the
+/// file names readable, so the output is removed and an error returned instead.
+pub(crate) fn finish_writer(
+    writer: SevenZWriter<OutputFile>,
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<()> {
+    let mut output = writer
+        .finish()
+        .with_context(|| format!("Failed to finalize 7-Zip archive {}", output_path.display()))?;
+    if options.sevenz_encrypt_headers && options.password.is_some() {
+        let plain = header_is_plain(&mut output).with_context(|| {
+            format!(
+                "Failed to read back 7-Zip archive {}",
+                output_path.display()
+            )
+        })?;
+        if plain {
+            drop(output);
+            for volume in volumes::written(output_path, options.split_size) {
+                let _ = std::fs::remove_file(volume);
+            }
+            anyhow::bail!(
+                "the 7z header of {} is too small for sevenz-rust to encrypt, which would leave \
+                 its file names readable; add more files or drop --encrypt-headers",
+                output_path.display()
+            );
+        }
     }
+    output.finish()
+}
+
+/// whether the header a writer finished with was stored without encoding
+fn header_is_plain(archive: &mut (impl Read + Seek)) -> std::io::Result<bool> {
+    // the start header holds the offset of the next header, counted from its end
+    let mut start = [0; 32];
+    archive.seek(SeekFrom::Start(0))?;
+    archive.read_exact(&mut start)?;
+    let offset = u64::from_le_bytes(start[12..20].try_into().expect("eight bytes"));
+    archive.seek(SeekFrom::Start(32 + offset))?;
+    let mut kind = [0];
+    archive.read_exact(&mut kind)?;
+    Ok(kind[0] == K_HEADER)
+}
+
+/// whether the header or any folder of an archive is encrypted
+pub(crate) fn is_encrypted(archive_path: &Path) -> bool {
+    let Ok(reader) = open_reader(archive_path, None) else {
+        // only an encrypted header stops the archive from opening without a password
+        return true;
+    };
+    reader.archive().folders.iter().any(|folder| {
+        folder.coders.iter().any(|coder| {
+            coder.decompression_method_id() == sevenz_rust::SevenZMethod::ID_AES256SHA256
+        })
+    })
 }
 
 impl CompressionFormat for SevenZFormat {
@@ -37,21 +336,17 @@ impl CompressionFormat for SevenZFormat {
             options.allow_symlink_escape,
         )?;
 
-        let mut sz = SevenZWriter::create(output_path).with_context(|| {
+        // `-j` is not honored here: sevenz-rust has no multithreaded LZMA2 encoder and its
+        // writer compresses every folder itself, so entries cannot be compressed ahead
+        let output = volumes::create(output_path, options.split_size)?;
+        let mut sz = SevenZWriter::new(output).with_context(|| {
             format!(
                 "Failed to create 7-Zip writer for {}",
                 output_path.display()
             )
         })?;
 
-        // Set password encryption if provided
-        if let Some(password) = &options.password {
-            use sevenz_rust::{AesEncoderOptions, SevenZMethod};
-            sz.set_content_methods(vec![
-                AesEncoderOptions::new(Password::from(password.as_str())).into(),
-                SevenZMethod::LZMA2.into(),
-            ]);
-        }
+        sz.set_content_methods(content_methods(options, input_size));
 
         if let Some(progress) = progress {
             progress.set_length(input_size);
@@ -69,14 +364,7 @@ impl CompressionFormat for SevenZFormat {
                 sz.finish().with_context(|| {
                     format!("Failed to finalize 7-Zip archive {}", output_path.display())
                 })?;
-                let output_size = std::fs::metadata(output_path)
-                    .with_context(|| {
-                        format!(
-                            "Failed to read metadata for output file {}",
-                            output_path.display()
-                        )
-                    })?
-                    .len();
+                let output_size = volumes::written_size(output_path, options.split_size)?;
                 return Ok(CompressionStats::new(input_size, output_size));
             }
 
@@ -87,8 +375,13 @@ impl CompressionFormat for SevenZFormat {
                 )
             })?;
 
-            let mut entry = SevenZArchiveEntry::from_path(input_path, filename.to_string());
-            sanitize_entry_timestamps(&mut entry, options);
+            let metadata = std::fs::metadata(input_path).with_context(|| {
+                format!(
+                    "Failed to read metadata for input file {}",
+                    input_path.display()
+                )
+            })?;
+            let entry = archive_entry(filename.to_string(), &metadata, options);
             sz.push_archive_entry(
                 entry,
                 Some(File::open(input_path).with_context(|| {
@@ -130,26 +423,45 @@ impl CompressionFormat for SevenZFormat {
             }
 
             let mut processed_size = 0u64;
+            // files waiting to be compressed together, and their total size
+            let mut block = Vec::new();
+            let mut block_size = 0u64;
 
             for entry in entries {
                 let path = entry.path();
                 let relative_path = path.strip_prefix(base_path)?;
-                let path_str = relative_path.to_string_lossy().to_string();
+                let path_str = utils::normalize_archive_path(relative_path);
 
                 if path.is_file() {
-                    let mut archive_entry = SevenZArchiveEntry::from_path(path, path_str);
-                    sanitize_entry_timestamps(&mut archive_entry, options);
-                    sz.push_archive_entry(
-                        archive_entry,
-                        Some(File::open(path).with_context(|| {
-                            format!("Failed to open file for archiving {}", path.display())
-                        })?),
-                    )?;
-
                     let metadata = entry.metadata().with_context(|| {
                         format!("Failed to read metadata for {}", path.display())
                     })?;
-                    processed_size += metadata.len();
+                    let archive_entry = archive_entry(path_str, &metadata, options);
+                    let size = metadata.len();
+
+                    if !options.sevenz_solid {
+                        sz.push_archive_entry(
+                            archive_entry,
+                            Some(File::open(path).with_context(|| {
+                                format!("Failed to open file for archiving {}", path.display())
+                            })?),
+                        )?;
+                    } else if size == 0 {
+                        // empty files carry no stream, so they stay out of solid blocks
+                        sz.push_archive_entry(archive_entry, None::<std::io::Empty>)?;
+                    } else {
+                        if options
+                            .sevenz_solid_block_size
+                            .is_some_and(|limit| block_size + size > limit)
+                        {
+                            push_solid_block(&mut sz, &mut block)?;
+                            block_size = 0;
+                        }
+                        block.push((archive_entry, path.to_path_buf()));
+                        block_size += size;
+                    }
+
+                    processed_size += size;
 
                     if let Some(progress) = progress {
                         progress.set_position(processed_size);
@@ -162,20 +474,12 @@ impl CompressionFormat for SevenZFormat {
                     sz.push_archive_entry(archive_entry, None::<std::io::Empty>)?;
                 }
             }
+            push_solid_block(&mut sz, &mut block)?;
         }
 
-        sz.finish().with_context(|| {
-            format!("Failed to finalize 7-Zip archive {}", output_path.display())
-        })?;
+        finish_writer(sz, output_path, options)?;
 
-        let output_size = std::fs::metadata(output_path)
-            .with_context(|| {
-                format!(
-                    "Failed to read metadata for output file {}",
-                    output_path.display()
-                )
-            })?
-            .len();
+        let output_size = volumes::written_size(output_path, options.split_size)?;
         Ok(CompressionStats::new(input_size, output_size))
     }
 
@@ -185,35 +489,7 @@ impl CompressionFormat for SevenZFormat {
         options: &ExtractionOptions,
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()> {
-        let password = options
-            .password
-            .as_ref()
-            .map_or(Password::empty(), |p| Password::from(p.as_str()));
-        let mut sz = SevenZReader::open(archive_path, password).map_err(|e| {
-            // Check if this looks like a password-related error
-            let error_msg = format!("{e}");
-            if error_msg.contains("MaybeBadPassword")
-                || (options.password.is_some()
-                    && (error_msg.contains("password")
-                        || error_msg.contains("decrypt")
-                        || error_msg.contains("encrypted")))
-            {
-                anyhow::anyhow!("Failed to decrypt archive (invalid password)")
-            } else if error_msg.contains("PasswordRequired")
-                || (options.password.is_none()
-                    && (error_msg.contains("password")
-                        || error_msg.contains("AES")
-                        || error_msg.contains("encrypted")))
-            {
-                anyhow::anyhow!("Archive is password protected but no password was provided")
-            } else {
-                anyhow::anyhow!(
-                    "Failed to open 7-Zip archive {}: {}",
-                    archive_path.display(),
-                    e
-                )
-            }
-        })?;
+        let mut sz = open_reader(archive_path, options.password.as_deref())?;
 
         std::fs::create_dir_all(output_dir).with_context(|| {
             format!("Failed to create output directory {}", output_dir.display())
@@ -276,26 +552,51 @@ impl CompressionFormat for SevenZFormat {
             }
 
             Ok(true)
+        })
+        .map_err(|e| match e {
+            // content encryption is only noticed once the first stream is decoded
+            sevenz_rust::Error::PasswordRequired | sevenz_rust::Error::MaybeBadPassword(_) => {
+                reader_error(archive_path, options.password.as_deref(), e)
+            }
+            e => e.into(),
         })?;
 
         Ok(())
     }
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
-        let sz = SevenZReader::open(archive_path, Password::empty())?;
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        let sz = open_reader(archive_path, options.password.as_deref())?;
         let archive = sz.archive();
 
         let mut entries = Vec::new();
 
-        for file in &archive.files {
+        for (index, file) in archive.files.iter().enumerate() {
             let path = file.name.clone();
             let size = file.size;
             let is_file = !file.is_directory();
+            let method = archive
+                .stream_map
+                .file_folder_index
+                .get(index)
+                .copied()
+                .flatten()
+                .and_then(|folder| archive.folders.get(folder))
+                .map(|folder| {
+                    folder
+                        .coders
+                        .iter()
+                        .map(|coder| {
+                            coder_method(coder.decompression_method_id(), &coder.properties)
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                });
 
             entries.push(ArchiveEntry {
                 path,
                 size,
                 is_file,
+                method,
             });
         }
 
@@ -306,30 +607,39 @@ impl CompressionFormat for SevenZFormat {
         "7z"
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
-        // The sevenz-rust crate has a way to iterate and test entries.
-        // For now, we'll just try to open and list entries.
-        let mut sz = sevenz_rust::SevenZReader::open(archive_path, sevenz_rust::Password::empty())
-            .map_err(|e| {
-                // Provide consistent error messages for password-protected archives
-                let error_msg = format!("{e}");
-                if error_msg.contains("PasswordRequired")
-                    || error_msg.contains("password")
-                    || error_msg.contains("encrypted")
-                {
-                    anyhow::anyhow!(
-                        "Failed to open 7-Zip archive {}: archive is password protected",
-                        archive_path.display()
-                    )
-                } else {
-                    anyhow::anyhow!(
-                        "Failed to open 7-Zip archive {}: {}",
-                        archive_path.display(),
-                        e
-                    )
-                }
-            })?;
-        sz.for_each_entries(|_entry, _reader| Ok(true))?; // This iterates and reads entry headers
-        Ok(())
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        let password = options.password.as_deref();
+        let test = || -> Result<()> {
+            let mut sz = open_reader(archive_path, password)?;
+            // decode every entry; the reader verifies CRCs as the streams end
+            sz.for_each_entries(|_entry, reader| {
+                std::io::copy(reader, &mut std::io::sink())?;
+                Ok(true)
+            })
+            .map_err(|e| reader_error(archive_path, password, e))
+        };
+        test().map_err(|e| {
+            if password.is_none() && e.to_string().contains("no password was provided") {
+                anyhow::anyhow!(
+                    "Failed to open 7-Zip archive {}: archive is password protected",
+                    archive_path.display()
+                )
+            } else {
+                e
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coder_method_dictionary_names() {
+        let lzma2 = sevenz_rust::SevenZMethod::ID_LZMA2;
+        assert_eq!(coder_method(lzma2, &[16]), "LZMA2:20");
+        assert_eq!(coder_method(lzma2, &[19]), "LZMA2:3m");
+        assert_eq!(coder_method(lzma2, &[40]), "LZMA2:32");
     }
 }
@@ -0,0 +1,289 @@
+//! Shared tar-or-raw handling for single-stream codecs (bz2, lz4, lzma, br, plain tar)
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        tarball, ArchiveEntry, CompressionOptions, CompressionStats, ExtractionOptions,
+        FinishWrite, StreamPayload,
+    },
+    progress::{Progress, ProgressReader},
+    utils,
+    volumes::{self, OutputFile},
+    Result,
+};
+use anyhow::Context;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// a streaming codec that can wrap a tarball or a single raw file
+pub trait StreamCodec {
+    /// display name used in error messages
+    const NAME: &'static str;
+    /// suffixes of tarballs compressed with this codec (lowercase, longest first)
+    const TAR_SUFFIXES: &'static [&'static str];
+    /// suffix of a single compressed file, if the codec supports raw mode
+    const RAW_SUFFIX: Option<&'static str>;
+
+    type Encoder<W: Write>: Write;
+
+    /// create an encoder; `level` is our 1-22 scale
+    fn encoder<W: Write>(writer: W, level: i32) -> Result<Self::Encoder<W>>;
+
+    /// flush the encoder and return the inner writer
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> Result<W>;
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>>;
+}
+
+fn file_name_lower(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+pub fn is_tar<C: StreamCodec>(path: &Path) -> bool {
+    let name = file_name_lower(path);
+    C::TAR_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+pub fn is_raw<C: StreamCodec>(path: &Path) -> bool {
+    let name = file_name_lower(path);
+    C::RAW_SUFFIX.is_some_and(|suffix| name.ends_with(suffix)) && !is_tar::<C>(path)
+}
+
+fn raw_output_name<C: StreamCodec>(path: &Path) -> String {
+    let suffixes: Vec<&str> = C::RAW_SUFFIX
+        .into_iter()
+        .chain(C::TAR_SUFFIXES.iter().copied())
+        .collect();
+    utils::raw_output_name(path, &suffixes)
+}
+
+/// open a decoded stream and resolve its payload; codecs without a raw mode are always tar
+fn open_payload<'a, C: StreamCodec, R: Read + 'a>(
+    reader: R,
+    options: &ExtractionOptions,
+) -> Result<(StreamPayload, Box<dyn Read + 'a>)> {
+    let decoder = C::decoder(reader)?;
+    if C::RAW_SUFFIX.is_none() {
+        return Ok((StreamPayload::Tar, decoder));
+    }
+    tarball::detect_payload(decoder, options.payload)
+}
+
+/// decoded content of an archive and whether it holds a tarball
+pub fn stream_reader<C: StreamCodec>(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<(StreamPayload, Box<dyn Read>)> {
+    let file = volumes::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+    open_payload::<C, _>(BufReader::new(file), options)
+}
+
+/// an encoder finished through its codec
+struct CodecWriter<C: StreamCodec>(C::Encoder<BufWriter<OutputFile>>);
+
+impl<C: StreamCodec> Write for CodecWriter<C> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<C: StreamCodec> FinishWrite for CodecWriter<C> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        finish_encoder::<C>(self.0)
+    }
+}
+
+/// encoder for a tar stream assembled entry by entry
+pub fn stream_writer<C: StreamCodec + 'static>(
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<Box<dyn FinishWrite>>
+where
+    C::Encoder<BufWriter<OutputFile>>: 'static,
+{
+    Ok(Box::new(CodecWriter::<C>(create_encoder::<C>(
+        output_path,
+        options,
+    )?)))
+}
+
+fn create_encoder<C: StreamCodec>(
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<C::Encoder<BufWriter<OutputFile>>> {
+    if options.password.is_some() {
+        return Err(anyhow::anyhow!(
+            "Password protection is not supported for {} format. Use 7z format for password protection.",
+            C::NAME
+        ));
+    }
+    let output_file = volumes::create(output_path, options.split_size)?;
+    C::encoder(BufWriter::new(output_file), options.level)
+}
+
+fn finish_encoder<C: StreamCodec>(encoder: C::Encoder<BufWriter<OutputFile>>) -> Result<()> {
+    let mut writer = C::finish(encoder)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn compress<C: StreamCodec>(
+    input_path: &Path,
+    output_path: &Path,
+    options: &CompressionOptions,
+    filter: &FileFilter,
+    progress: Option<&Progress>,
+) -> Result<CompressionStats> {
+    let input_size = utils::calculate_directory_size(
+        input_path,
+        filter,
+        options.follow_symlinks,
+        options.allow_symlink_escape,
+    )?;
+
+    if let Some(progress) = progress {
+        progress.set_length(input_size);
+    }
+
+    if is_raw::<C>(output_path) {
+        if !input_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Directory input requires a {} output",
+                C::TAR_SUFFIXES.join(" or ")
+            ));
+        }
+
+        let mut encoder = create_encoder::<C>(output_path, options)?;
+        let included = input_path
+            .file_name()
+            .is_none_or(|filename| filter.should_include_relative(Path::new(filename)));
+        if included {
+            let mut input_file = File::open(input_path)
+                .with_context(|| format!("Failed to open input file {}", input_path.display()))?;
+            std::io::copy(&mut input_file, &mut encoder)?;
+        }
+        finish_encoder::<C>(encoder)?;
+    } else {
+        let encoder = create_encoder::<C>(output_path, options)?;
+        let encoder = tarball::build_tarball(
+            encoder,
+            input_path,
+            options,
+            filter,
+            progress,
+            tarball::BuildOptions {
+                normalize_ownership: options.normalize_ownership,
+                apply_filter_to_single_file: true,
+                directory_slash: false,
+                set_mtime_for_single_file: true,
+            },
+        )?;
+        finish_encoder::<C>(encoder)?;
+    }
+
+    let output_size = volumes::written_size(output_path, options.split_size)?;
+    Ok(CompressionStats::new(input_size, output_size))
+}
+
+pub fn extract<C: StreamCodec>(
+    archive_path: &Path,
+    output_dir: &Path,
+    options: &ExtractionOptions,
+    progress: Option<&Progress>,
+) -> Result<()> {
+    let file = volumes::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+    if let Some(progress) = progress {
+        progress.set_length(file.len());
+    }
+
+    let buf_reader = BufReader::new(file);
+    let (payload, mut reader) =
+        open_payload::<C, _>(ProgressReader::new(buf_reader, progress), options)?;
+
+    if payload == StreamPayload::Tar {
+        return tarball::extract_tarball(reader, output_dir, options, progress);
+    }
+
+    let output_name = raw_output_name::<C>(archive_path);
+    let target_path = match utils::prepare_extract_target(
+        output_dir,
+        Path::new(&output_name),
+        options.strip_components,
+        options.overwrite,
+        false,
+    )? {
+        utils::ExtractTarget::Target(target_path) => target_path,
+        utils::ExtractTarget::SkipStrip => return Ok(()),
+        utils::ExtractTarget::SkipExisting(target_path) => {
+            return Err(anyhow::anyhow!(
+                "output file '{}' already exists. Use --overwrite to replace.",
+                target_path.display()
+            ));
+        }
+    };
+
+    if let Some(parent) = target_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut output_file = File::create(&target_path)?;
+    std::io::copy(&mut reader, &mut output_file)?;
+    Ok(())
+}
+
+pub fn list<C: StreamCodec>(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<Vec<ArchiveEntry>> {
+    let file = volumes::open(archive_path).with_context(|| {
+        format!(
+            "Failed to open archive for listing {}",
+            archive_path.display()
+        )
+    })?;
+    let (payload, mut reader) = open_payload::<C, _>(BufReader::new(file), options)?;
+
+    if payload == StreamPayload::Tar {
+        return tarball::list_tarball(reader);
+    }
+
+    let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+    Ok(vec![ArchiveEntry {
+        path: raw_output_name::<C>(archive_path),
+        size,
+        is_file: true,
+        method: None,
+    }])
+}
+
+pub fn test_integrity<C: StreamCodec>(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<()> {
+    let file = volumes::open(archive_path)?;
+    let (payload, mut reader) = open_payload::<C, _>(BufReader::new(file), options)?;
+
+    if payload == StreamPayload::Tar {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry.with_context(|| format!("Failed to read {} tarball", C::NAME))?;
+            std::io::copy(&mut entry, &mut std::io::sink())
+                .with_context(|| format!("Failed to decode {} stream", C::NAME))?;
+        }
+    } else {
+        std::io::copy(&mut reader, &mut std::io::sink())
+            .with_context(|| format!("Failed to decode {} stream", C::NAME))?;
+    }
+    Ok(())
+}
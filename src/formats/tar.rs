@@ -0,0 +1,72 @@
+//! uncompressed tar format support
+
+use crate::{
+    filter::FileFilter,
+    formats::{
+        stream::{self, StreamCodec},
+        ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+    },
+    progress::Progress,
+    Result,
+};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+pub struct TarFormat;
+
+pub(crate) struct TarCodec;
+
+impl StreamCodec for TarCodec {
+    const NAME: &'static str = "tar";
+    const TAR_SUFFIXES: &'static [&'static str] = &[".tar"];
+    const RAW_SUFFIX: Option<&'static str> = None;
+
+    type Encoder<W: Write> = W;
+
+    fn encoder<W: Write>(writer: W, _level: i32) -> Result<Self::Encoder<W>> {
+        Ok(writer)
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> Result<W> {
+        Ok(encoder)
+    }
+
+    fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(reader))
+    }
+}
+
+impl CompressionFormat for TarFormat {
+    fn compress(
+        input_path: &Path,
+        output_path: &Path,
+        options: &CompressionOptions,
+        filter: &FileFilter,
+        progress: Option<&Progress>,
+    ) -> Result<CompressionStats> {
+        stream::compress::<TarCodec>(input_path, output_path, options, filter, progress)
+    }
+
+    fn extract(
+        archive_path: &Path,
+        output_dir: &Path,
+        options: &ExtractionOptions,
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        stream::extract::<TarCodec>(archive_path, output_dir, options, progress)
+    }
+
+    fn extension() -> &'static str {
+        "tar"
+    }
+
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        stream::list::<TarCodec>(archive_path, options)
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        stream::test_integrity::<TarCodec>(archive_path, options)
+    }
+}
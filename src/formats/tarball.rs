@@ -2,7 +2,7 @@
 
 use crate::{
     filter::FileFilter,
-    formats::{ArchiveEntry, CompressionOptions, ExtractionOptions},
+    formats::{ArchiveEntry, CompressionOptions, ExtractionOptions, StreamPayload},
     progress::Progress,
     utils, Result,
 };
@@ -73,6 +73,7 @@ fn append_xattrs<W: Write>(_builder: &mut Builder<W>, _path: &Path) -> Result<()
 fn apply_header_normalization(
     header: &mut tar::Header,
     metadata: &std::fs::Metadata,
+    options: &CompressionOptions,
     normalize_ownership: bool,
     set_mtime: bool,
 ) -> Result<()> {
@@ -91,6 +92,7 @@ fn apply_header_normalization(
 
     if set_mtime {
         if let Ok(mtime) = metadata.modified() {
+            let mtime = options.clamp_mtime(mtime);
             if let Ok(duration) = mtime.duration_since(std::time::UNIX_EPOCH) {
                 header.set_mtime(duration.as_secs());
             }
@@ -121,7 +123,13 @@ fn create_file_header(
         }
     });
 
-    apply_header_normalization(&mut header, metadata, normalize_ownership, set_mtime)?;
+    apply_header_normalization(
+        &mut header,
+        metadata,
+        options,
+        normalize_ownership,
+        set_mtime,
+    )?;
     header.set_cksum();
     Ok(header)
 }
@@ -148,7 +156,13 @@ fn create_dir_header(
         }
     });
 
-    apply_header_normalization(&mut header, metadata, normalize_ownership, set_mtime)?;
+    apply_header_normalization(
+        &mut header,
+        metadata,
+        options,
+        normalize_ownership,
+        set_mtime,
+    )?;
     header.set_cksum();
     Ok(header)
 }
@@ -326,6 +340,39 @@ pub fn build_tarball<W: Write>(
     Ok(tar_builder.into_inner()?)
 }
 
+/// check whether `block` starts with a valid tar header (non-empty, matching checksum)
+pub fn is_tar_header(block: &[u8]) -> bool {
+    const CHECKSUM: std::ops::Range<usize> = 148..156;
+
+    let Some(header) = block.get(..512) else {
+        return false;
+    };
+    if header.iter().all(|&byte| byte == 0) {
+        return false;
+    }
+
+    let stored = header[CHECKSUM]
+        .iter()
+        .skip_while(|&&byte| byte == b' ')
+        .take_while(|&&byte| (b'0'..=b'7').contains(&byte))
+        .try_fold(0u32, |acc, &byte| {
+            acc.checked_mul(8)?.checked_add(u32::from(byte - b'0'))
+        });
+    let computed: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            if CHECKSUM.contains(&i) {
+                u32::from(b' ')
+            } else {
+                u32::from(byte)
+            }
+        })
+        .sum();
+
+    stored == Some(computed)
+}
+
 pub fn extract_tarball<R: Read>(
     reader: R,
     output_dir: &Path,
@@ -340,9 +387,15 @@ pub fn extract_tarball<R: Read>(
     std::fs::create_dir_all(output_dir)?;
 
     let mut entry_count = 0u64;
+    let mut matched = false;
     for entry in archive.entries()? {
         let mut entry = entry?;
         let path = entry.path()?;
+        if !utils::entry_selected(&path.to_string_lossy(), &options.entries) {
+            continue;
+        }
+        matched = true;
+
         let target_path = match utils::prepare_extract_target(
             output_dir,
             &path,
@@ -384,9 +437,18 @@ pub fn extract_tarball<R: Read>(
         }
     }
 
+    if !matched && !options.entries.is_empty() {
+        return Err(no_matching_entries(&options.entries));
+    }
+
     Ok(())
 }
 
+/// error for an `--entry` selection that matched nothing
+pub fn no_matching_entries(selections: &[String]) -> anyhow::Error {
+    anyhow::anyhow!("no archive entries match {}", selections.join(", "))
+}
+
 pub fn list_tarball<R: Read>(reader: R) -> Result<Vec<ArchiveEntry>> {
     let mut archive = Archive::new(reader);
     let mut entries = Vec::new();
@@ -401,8 +463,33 @@ pub fn list_tarball<R: Read>(reader: R) -> Result<Vec<ArchiveEntry>> {
             path,
             size,
             is_file,
+            method: None,
         });
     }
 
     Ok(entries)
 }
+
+/// resolve what a decoded stream holds, returning a reader positioned at its start
+///
+/// `StreamPayload::Auto` checks whether the first decoded block is a valid tar header;
+/// a stream of exactly two zero blocks is an empty tarball (e.g. a single excluded
+/// input file). the result is always `Tar` or `Raw`.
+pub fn detect_payload<'a>(
+    mut reader: impl Read + 'a,
+    payload: StreamPayload,
+) -> Result<(StreamPayload, Box<dyn Read + 'a>)> {
+    if payload != StreamPayload::Auto {
+        return Ok((payload, Box::new(reader)));
+    }
+
+    let mut block = Vec::with_capacity(1025);
+    (&mut reader).take(1025).read_to_end(&mut block)?;
+    let empty_tarball = block.len() == 1024 && block.iter().all(|&byte| byte == 0);
+    let payload = if empty_tarball || is_tar_header(&block) {
+        StreamPayload::Tar
+    } else {
+        StreamPayload::Raw
+    };
+    Ok((payload, Box::new(std::io::Cursor::new(block).chain(reader))))
+}
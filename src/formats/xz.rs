@@ -3,20 +3,27 @@
 use crate::{
     filter::FileFilter,
     formats::{
+        parallel_xz::{self, ParallelXzReader},
         tarball, ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats,
-        ExtractionOptions,
+        ExtractionOptions, FinishWrite, StreamPayload,
     },
     progress::{Progress, ProgressReader},
-    utils, Result,
+    utils,
+    volumes::{self, OutputFile, VolumeReader},
+    Result,
 };
 use anyhow::Context;
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Read},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
-use xz2::{read::XzDecoder, write::XzEncoder};
+use xz2::{
+    read::XzDecoder,
+    stream::{Check, MtStreamBuilder},
+    write::XzEncoder,
+};
 
 pub struct XzFormat;
 
@@ -36,14 +43,91 @@ fn is_raw_xz(path: &Path) -> bool {
     name.ends_with(".xz") && !is_tarxz(path)
 }
 
-fn raw_output_name(path: &Path) -> Option<String> {
-    let name = path.file_name()?.to_string_lossy();
-    let lower = name.to_lowercase();
-    if lower.ends_with(".xz") {
-        let new_len = name.len().saturating_sub(3);
-        return Some(name[..new_len].to_string());
+/// suffixes stripped to name the file held by a raw xz stream
+const RAW_SUFFIXES: &[&str] = &[".xz", ".txz"];
+
+/// map the compression level (1-22) to an xz preset (0-9)
+fn xz_level(options: &CompressionOptions) -> u32 {
+    (((options.level as f32 / 22.0) * 9.0) as u32).clamp(0, 9)
+}
+
+/// xz encoder for `output_path`, always liblzma's multithreaded block encoder
+///
+/// blocks are cut at `--xz-block-size`, or at liblzma's default for the preset, and
+/// never by thread count, so every `-j` yields the same bytes
+fn xz_encoder(
+    output_path: &Path,
+    xz_level: u32,
+    options: &CompressionOptions,
+) -> Result<XzEncoder<BufWriter<OutputFile>>> {
+    let output_file = volumes::create(output_path, options.split_size)?;
+    let buf_writer = BufWriter::new(output_file);
+
+    let threads = utils::resolved_thread_count(options.threads);
+    let stream = MtStreamBuilder::new()
+        .threads(threads)
+        // 0 picks liblzma's default: three times the preset's dictionary size
+        .block_size(options.xz_block_size.unwrap_or(0))
+        .preset(xz_level)
+        .check(Check::Crc64)
+        .encoder()
+        .with_context(|| format!("failed to create xz encoder with {threads} threads"))?;
+    Ok(XzEncoder::new_stream(buf_writer, stream))
+}
+
+fn open_archive(archive_path: &Path) -> Result<VolumeReader> {
+    volumes::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))
+}
+
+/// decoder for an xz file; files with several blocks are decoded in parallel under `-j`
+fn xz_decoder<'a>(
+    mut file: VolumeReader,
+    options: &ExtractionOptions,
+    progress: Option<&'a Progress>,
+) -> Result<Box<dyn Read + 'a>> {
+    let threads = utils::resolved_thread_count(options.threads);
+    if threads > 1 {
+        let index = parallel_xz::read_index(&mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+        if let Some(index) = index.filter(|index| index.block_count() > 1) {
+            let input = ProgressReader::new(BufReader::new(file), progress);
+            return Ok(Box::new(ParallelXzReader::new(input, index, threads)?));
+        }
     }
-    None
+
+    Ok(Box::new(XzDecoder::new(ProgressReader::new(
+        BufReader::new(file),
+        progress,
+    ))))
+}
+
+impl FinishWrite for XzEncoder<BufWriter<OutputFile>> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        (*self).finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// encoder for a tar stream assembled entry by entry
+pub fn stream_writer(
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<Box<dyn FinishWrite>> {
+    Ok(Box::new(xz_encoder(
+        output_path,
+        xz_level(options),
+        options,
+    )?))
+}
+
+/// decoded content of an archive and whether it holds a tarball
+pub fn stream_reader(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<(StreamPayload, Box<dyn Read>)> {
+    let decoder = xz_decoder(open_archive(archive_path)?, options, None)?;
+    tarball::detect_payload(decoder, options.payload)
 }
 
 impl CompressionFormat for XzFormat {
@@ -61,73 +145,36 @@ impl CompressionFormat for XzFormat {
             options.allow_symlink_escape,
         )?;
 
-        // Map compression level (1-22) to xz level (0-9)
-        let xz_level = (((options.level as f32 / 22.0) * 9.0) as u32).clamp(0, 9);
+        let xz_level = xz_level(options);
 
         if let Some(progress) = progress {
             progress.set_length(input_size);
         }
 
-        if input_path.is_file() {
-            if is_raw_xz(output_path) {
-                let filename = input_path.file_name();
-                if let Some(filename) = filename {
-                    if !filter.should_include_relative(Path::new(filename)) {
-                        let output_file = File::create(output_path).with_context(|| {
-                            format!("Failed to create output file {}", output_path.display())
-                        })?;
-                        let buf_writer = BufWriter::new(output_file);
-                        let encoder = XzEncoder::new(buf_writer, xz_level);
-                        encoder.finish()?;
-                        let output_size = std::fs::metadata(output_path)?.len();
-                        return Ok(CompressionStats::new(input_size, output_size));
-                    }
-                }
-
-                let output_file = File::create(output_path).with_context(|| {
-                    format!("Failed to create output file {}", output_path.display())
-                })?;
-                let buf_writer = BufWriter::new(output_file);
-                let mut encoder = XzEncoder::new(buf_writer, xz_level);
-
-                let mut input_file = File::open(input_path).with_context(|| {
-                    format!("Failed to open input file {}", input_path.display())
-                })?;
-                std::io::copy(&mut input_file, &mut encoder)?;
-                encoder.finish()?;
-            } else {
-                let output_file = File::create(output_path).with_context(|| {
-                    format!("Failed to create output file {}", output_path.display())
-                })?;
-                let buf_writer = BufWriter::new(output_file);
-                let encoder = XzEncoder::new(buf_writer, xz_level);
-                let encoder = tarball::build_tarball(
-                    encoder,
-                    input_path,
-                    options,
-                    filter,
-                    progress,
-                    tarball::BuildOptions {
-                        normalize_ownership: options.normalize_ownership,
-                        apply_filter_to_single_file: true,
-                        directory_slash: false,
-                        set_mtime_for_single_file: true,
-                    },
-                )?;
+        if input_path.is_file() && is_raw_xz(output_path) {
+            let encoder = xz_encoder(output_path, xz_level, options)?;
+            let included = input_path
+                .file_name()
+                .is_none_or(|filename| filter.should_include_relative(Path::new(filename)));
+            if !included {
                 encoder.finish()?;
+                let output_size = volumes::written_size(output_path, options.split_size)?;
+                return Ok(CompressionStats::new(input_size, output_size));
             }
+
+            let mut encoder = encoder;
+            let mut input_file = File::open(input_path)
+                .with_context(|| format!("Failed to open input file {}", input_path.display()))?;
+            std::io::copy(&mut input_file, &mut encoder)?;
+            encoder.finish()?;
         } else {
-            if is_raw_xz(output_path) {
+            if !input_path.is_file() && is_raw_xz(output_path) {
                 return Err(anyhow::anyhow!(
                     "Directory input requires a .txz or .tar.xz output"
                 ));
             }
 
-            let output_file = File::create(output_path).with_context(|| {
-                format!("Failed to create output file {}", output_path.display())
-            })?;
-            let buf_writer = BufWriter::new(output_file);
-            let encoder = XzEncoder::new(buf_writer, xz_level);
+            let encoder = xz_encoder(output_path, xz_level, options)?;
             let encoder = tarball::build_tarball(
                 encoder,
                 input_path,
@@ -144,7 +191,7 @@ impl CompressionFormat for XzFormat {
             encoder.finish()?;
         }
 
-        let output_size = std::fs::metadata(output_path)?.len();
+        let output_size = volumes::written_size(output_path, options.split_size)?;
         Ok(CompressionStats::new(input_size, output_size))
     }
 
@@ -154,112 +201,78 @@ impl CompressionFormat for XzFormat {
         options: &ExtractionOptions,
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()> {
-        let archive_size = std::fs::metadata(archive_path)
-            .with_context(|| format!("Failed to read archive metadata {}", archive_path.display()))?
-            .len();
+        let file = open_archive(archive_path)?;
         if let Some(progress) = progress {
-            progress.set_length(archive_size);
+            progress.set_length(file.len());
         }
 
-        if is_raw_xz(archive_path) {
-            let output_name = raw_output_name(archive_path)
-                .ok_or_else(|| anyhow::anyhow!("Failed to determine output filename"))?;
-            let target_path = match crate::utils::prepare_extract_target(
-                output_dir,
-                Path::new(&output_name),
-                options.strip_components,
-                options.overwrite,
-                false,
-            )? {
-                crate::utils::ExtractTarget::Target(target_path) => target_path,
-                crate::utils::ExtractTarget::SkipStrip => return Ok(()),
-                crate::utils::ExtractTarget::SkipExisting(target_path) => {
-                    return Err(anyhow::anyhow!(
-                        "output file '{}' already exists. Use --overwrite to replace.",
-                        target_path.display()
-                    ));
-                }
-            };
-
-            if let Some(parent) = target_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+        let decoder = xz_decoder(file, options, progress)?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
 
-            let file = File::open(archive_path).with_context(|| {
-                format!("Failed to open archive file {}", archive_path.display())
-            })?;
-            let mut decoder = XzDecoder::new(ProgressReader::new(file, progress));
-            let mut output_file = File::create(&target_path)?;
-            std::io::copy(&mut decoder, &mut output_file)?;
-            return Ok(());
+        if payload == StreamPayload::Tar {
+            return tarball::extract_tarball(reader, output_dir, options, progress);
         }
 
-        let file = File::open(archive_path)
-            .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
-        let buf_reader = BufReader::new(file);
-        let decoder = XzDecoder::new(ProgressReader::new(buf_reader, progress));
-
-        tarball::extract_tarball(decoder, output_dir, options, progress)
-    }
-
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
-        if is_raw_xz(archive_path) {
-            let output_name = raw_output_name(archive_path)
-                .ok_or_else(|| anyhow::anyhow!("Failed to determine output filename"))?;
-            let file = File::open(archive_path)?;
-            let mut decoder = XzDecoder::new(file);
-            let mut size = 0u64;
-            let mut buffer = [0u8; 8192];
-            loop {
-                let read = decoder.read(&mut buffer)?;
-                if read == 0 {
-                    break;
-                }
-                size += read as u64;
+        let output_name = utils::raw_output_name(archive_path, RAW_SUFFIXES);
+        let target_path = match utils::prepare_extract_target(
+            output_dir,
+            Path::new(&output_name),
+            options.strip_components,
+            options.overwrite,
+            false,
+        )? {
+            utils::ExtractTarget::Target(target_path) => target_path,
+            utils::ExtractTarget::SkipStrip => return Ok(()),
+            utils::ExtractTarget::SkipExisting(target_path) => {
+                return Err(anyhow::anyhow!(
+                    "output file '{}' already exists. Use --overwrite to replace.",
+                    target_path.display()
+                ));
             }
-            return Ok(vec![ArchiveEntry {
-                path: output_name,
-                size,
-                is_file: true,
-            }]);
-        }
+        };
 
-        let file = File::open(archive_path).with_context(|| {
-            format!(
-                "Failed to open archive for listing {}",
-                archive_path.display()
-            )
-        })?;
-        let buf_reader = BufReader::new(file);
-        let decoder = XzDecoder::new(buf_reader);
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
 
-        tarball::list_tarball(decoder)
+        let mut output_file = File::create(&target_path)?;
+        std::io::copy(&mut reader, &mut output_file)?;
+        Ok(())
     }
 
     fn extension() -> &'static str {
         "txz"
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
-        // Similar to Gzip, for .txz (tar.xz), list contents.
-        // For a raw .xz stream, try to decompress fully.
-        use std::fs::File;
-        use std::io::Read;
-        use tar::Archive;
-        use xz2::read::XzDecoder;
-
-        let file = File::open(archive_path)?;
-        if is_tarxz(archive_path) {
-            let xz_decoder = XzDecoder::new(file);
-            let mut archive = Archive::new(xz_decoder);
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        let decoder = xz_decoder(open_archive(archive_path)?, options, None)?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
+
+        if payload == StreamPayload::Tar {
+            return tarball::list_tarball(reader);
+        }
+
+        let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+        Ok(vec![ArchiveEntry {
+            path: utils::raw_output_name(archive_path, RAW_SUFFIXES),
+            size,
+            is_file: true,
+            method: None,
+        }])
+    }
+
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
+        // decode the whole stream; tarballs are also walked entry by entry
+        let decoder = xz_decoder(open_archive(archive_path)?, options, None)?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
+
+        if payload == StreamPayload::Tar {
+            let mut archive = tar::Archive::new(reader);
             for entry in archive.entries()? {
-                let _entry = entry?;
+                std::io::copy(&mut entry?, &mut std::io::sink())?;
             }
         } else {
-            // Single .xz file
-            let mut xz_decoder = XzDecoder::new(file);
-            let mut buffer = Vec::new();
-            xz_decoder.read_to_end(&mut buffer)?;
+            std::io::copy(&mut reader, &mut std::io::sink())?;
         }
         Ok(())
     }
@@ -4,17 +4,20 @@ use crate::{
     filter::FileFilter,
     formats::{
         ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats, ExtractionOptions,
+        ZipMethod,
     },
     progress::Progress,
-    utils, Result,
+    utils, volumes, Result,
 };
 use anyhow::Context;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::{
-    fs::File,
-    io::{BufReader, BufWriter},
-    path::Path,
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
     time::SystemTime,
 };
 use time::OffsetDateTime;
@@ -22,18 +25,345 @@ use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 pub struct ZipFormat;
 
-fn zip_last_modified(metadata: &std::fs::Metadata, strip_timestamps: bool) -> zip::DateTime {
-    if strip_timestamps {
+fn zip_last_modified(metadata: &std::fs::Metadata, options: &CompressionOptions) -> zip::DateTime {
+    if options.strip_timestamps {
         return zip::DateTime::default();
     }
 
     let Ok(modified) = metadata.modified() else {
         return zip::DateTime::default();
     };
-    let dt = OffsetDateTime::from(modified);
+    let dt = OffsetDateTime::from(options.clamp_mtime(modified));
     zip::DateTime::try_from(dt).unwrap_or_default()
 }
 
+/// pick the compression method and native level for one file
+fn entry_compression(
+    path: &Path,
+    options: &CompressionOptions,
+) -> Result<(CompressionMethod, Option<i64>)> {
+    let precompressed = options.auto_store
+        && options.zip_method != ZipMethod::Store
+        && utils::is_precompressed(path)?;
+    Ok(compression_for(precompressed, options))
+}
+
+/// the compression method and native level for a file, storing it if `precompressed`
+pub(crate) fn compression_for(
+    precompressed: bool,
+    options: &CompressionOptions,
+) -> (CompressionMethod, Option<i64>) {
+    let method = if precompressed && options.auto_store {
+        ZipMethod::Store
+    } else {
+        options.zip_method
+    };
+
+    let zip_method = match method {
+        ZipMethod::Store => CompressionMethod::Stored,
+        ZipMethod::Deflate => CompressionMethod::Deflated,
+        ZipMethod::Bzip2 => CompressionMethod::Bzip2,
+        ZipMethod::Zstd => CompressionMethod::Zstd,
+    };
+    (zip_method, method.native_level(options.level))
+}
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const EOCD64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+pub(crate) const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const UNIX_HOST: u8 = 3;
+
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// the end of central directory record (with its comment) and its offset in `reader`
+pub(crate) fn read_eocd(reader: &mut (impl Read + Seek)) -> Result<(u64, Vec<u8>)> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    // EOCD is 22 bytes plus an optional comment of up to 64 KiB
+    let tail_len = len.min(22 + u64::from(u16::MAX));
+    let tail_start = len - tail_len;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.seek(SeekFrom::Start(tail_start))?;
+    reader.read_exact(&mut tail)?;
+
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| read_u32(&tail, pos) == EOCD_SIGNATURE)
+        .ok_or_else(|| anyhow::anyhow!("end of central directory not found"))?;
+    Ok((tail_start + eocd as u64, tail.split_off(eocd)))
+}
+
+/// the zip64 end of central directory locator right before the EOCD record, if there is one
+pub(crate) fn read_eocd64_locator(
+    reader: &mut (impl Read + Seek),
+    eocd_offset: u64,
+) -> Result<Option<[u8; 20]>> {
+    let Some(start) = eocd_offset.checked_sub(20) else {
+        return Ok(None);
+    };
+    let mut locator = [0u8; 20];
+    reader.seek(SeekFrom::Start(start))?;
+    reader.read_exact(&mut locator)?;
+    Ok((read_u32(&locator, 0) == EOCD64_LOCATOR_SIGNATURE).then_some(locator))
+}
+
+/// locate the central directory, returning (offset, size)
+fn central_directory_range(file: &mut (impl Read + Seek)) -> Result<(u64, u64)> {
+    let (eocd_offset, eocd) = read_eocd(file)?;
+    let cd_size = read_u32(&eocd, 12);
+    let cd_offset = read_u32(&eocd, 16);
+    if cd_size != u32::MAX && cd_offset != u32::MAX {
+        return Ok((u64::from(cd_offset), u64::from(cd_size)));
+    }
+
+    let locator = read_eocd64_locator(file, eocd_offset)?
+        .ok_or_else(|| anyhow::anyhow!("zip64 end of central directory locator not found"))?;
+    let mut eocd64 = [0u8; 56];
+    file.seek(SeekFrom::Start(read_u64(&locator, 8)))?;
+    file.read_exact(&mut eocd64)?;
+    Ok((read_u64(&eocd64, 48), read_u64(&eocd64, 40)))
+}
+
+/// read the central directory, returning its offset and contents
+fn read_central_directory(file: &mut (impl Read + Seek)) -> Result<(u64, Vec<u8>)> {
+    let (cd_offset, cd_size) = central_directory_range(file)?;
+    let mut cd = vec![0u8; cd_size as usize];
+    file.seek(SeekFrom::Start(cd_offset))?;
+    file.read_exact(&mut cd)?;
+    Ok((cd_offset, cd))
+}
+
+/// offsets of the central directory headers, in archive order
+pub(crate) fn central_headers(cd: &[u8]) -> Vec<usize> {
+    let mut headers = Vec::new();
+    let mut pos = 0;
+    while pos + 46 <= cd.len() && read_u32(cd, pos) == CENTRAL_HEADER_SIGNATURE {
+        headers.push(pos);
+        let name_len = usize::from(read_u16(cd, pos + 28));
+        let extra_len = usize::from(read_u16(cd, pos + 30));
+        let comment_len = usize::from(read_u16(cd, pos + 32));
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    headers
+}
+
+fn open_for_update(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to reopen ZIP archive {}", path.display()))
+}
+
+/// rewrite host-dependent central directory fields so output does not depend on the build os
+///
+/// the zip writer records DOS as the host system (plus DOS attribute bits) on windows and
+/// unix everywhere else; deterministic archives always claim a unix host.
+pub(crate) fn normalize_host_fields(file: &mut (impl Read + Write + Seek)) -> Result<()> {
+    let (cd_offset, mut cd) = read_central_directory(file)?;
+
+    let mut changed = false;
+    for pos in central_headers(&cd) {
+        if cd[pos + 5] != UNIX_HOST {
+            cd[pos + 5] = UNIX_HOST;
+            // keep the unix mode in the high half, drop the DOS attribute bits
+            let mode = read_u32(&cd, pos + 38) & 0xffff_0000;
+            cd[pos + 38..pos + 42].copy_from_slice(&mode.to_le_bytes());
+            changed = true;
+        }
+    }
+
+    if changed {
+        file.seek(SeekFrom::Start(cd_offset))?;
+        file.write_all(&cd)?;
+    }
+    Ok(())
+}
+
+/// the "version made by" and external attributes of every entry, in archive order
+pub(crate) fn host_fields(path: &Path) -> Result<Vec<(u16, u32)>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open ZIP archive {}", path.display()))?;
+    let (_, cd) = read_central_directory(&mut file)?;
+    Ok(central_headers(&cd)
+        .into_iter()
+        .map(|pos| (read_u16(&cd, pos + 4), read_u32(&cd, pos + 38)))
+        .collect())
+}
+
+/// put back the host fields of leading entries copied from another archive
+///
+/// copying an entry raw re-derives these fields from the unix mode, which loses the DOS
+/// attributes of archives made on windows.
+pub(crate) fn restore_host_fields(path: &Path, fields: &[(u16, u32)]) -> Result<()> {
+    let mut file = open_for_update(path)?;
+    let (cd_offset, mut cd) = read_central_directory(&mut file)?;
+    for (pos, (made_by, attributes)) in central_headers(&cd).into_iter().zip(fields) {
+        cd[pos + 4..pos + 6].copy_from_slice(&made_by.to_le_bytes());
+        cd[pos + 38..pos + 42].copy_from_slice(&attributes.to_le_bytes());
+    }
+    file.seek(SeekFrom::Start(cd_offset))?;
+    file.write_all(&cd)?;
+    Ok(())
+}
+
+/// files at least this large are compressed in place rather than buffered by a worker
+const PARALLEL_ENTRY_LIMIT: u64 = 64 * 1024 * 1024;
+/// total size of the files handed to workers but not yet written, whatever `-j` is
+const PARALLEL_BUFFER_BUDGET: u64 = 256 * 1024 * 1024;
+
+/// one archive entry, prepared before anything is written so files can be compressed ahead
+enum EntryJob {
+    Directory {
+        name: String,
+        options: FileOptions<'static, ()>,
+    },
+    File {
+        path: PathBuf,
+        name: String,
+        options: FileOptions<'static, ()>,
+        size: u64,
+    },
+}
+
+fn write_file<W: Write + Seek>(
+    zip_writer: &mut ZipWriter<W>,
+    path: &Path,
+    name: &str,
+    file_options: FileOptions<'static, ()>,
+) -> Result<()> {
+    zip_writer.start_file(name, file_options)?;
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open file for archiving {}", path.display()))?;
+    std::io::copy(&mut file, zip_writer)?;
+    Ok(())
+}
+
+/// compress one file into a single-entry zip held in memory
+fn compress_entry(
+    path: &Path,
+    name: &str,
+    file_options: FileOptions<'static, ()>,
+) -> Result<Vec<u8>> {
+    let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+    write_file(&mut zip_writer, path, name, file_options)?;
+    Ok(zip_writer.finish()?.into_inner())
+}
+
+/// write entries in order; with several threads, files are compressed by workers ahead of
+/// time and their compressed data copied in as-is, so the archive matches a sequential run
+///
+/// `threads` workers share one queue, and files are only queued while the ones waiting to
+/// be written stay within [`PARALLEL_BUFFER_BUDGET`].
+fn write_entries<W: Write + Seek>(
+    zip_writer: &mut ZipWriter<W>,
+    jobs: &[EntryJob],
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Result<()> {
+    let parallel_size = |job: &EntryJob| match job {
+        EntryJob::File { size, .. } if threads > 1 && *size < PARALLEL_ENTRY_LIMIT => Some(*size),
+        _ => None,
+    };
+
+    let (job_sender, job_receiver) = mpsc::channel::<usize>();
+    let (result_sender, result_receiver) = mpsc::channel();
+    let job_receiver = Mutex::new(job_receiver);
+    std::thread::scope(|scope| {
+        // owned by the writer loop, so the queue closes and workers exit when it returns
+        let job_sender = job_sender;
+        if threads > 1 {
+            for _ in 0..threads {
+                let (job_receiver, result_sender) = (&job_receiver, result_sender.clone());
+                scope.spawn(move || loop {
+                    // the queue closes once every job is written or writing failed
+                    let Ok(index) = job_receiver.lock().unwrap().recv() else {
+                        return;
+                    };
+                    let EntryJob::File {
+                        path,
+                        name,
+                        options,
+                        ..
+                    } = &jobs[index]
+                    else {
+                        unreachable!("directories are not compressed");
+                    };
+                    if result_sender
+                        .send((index, compress_entry(path, name, *options)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                });
+            }
+        }
+        drop(result_sender);
+
+        let mut queued = 0;
+        let mut buffered = 0u64;
+        let mut finished = HashMap::new();
+        let mut processed_size = 0u64;
+        for (index, job) in jobs.iter().enumerate() {
+            // queue ahead while the budget allows; the next entry always fits an empty budget
+            while let Some(next) = jobs.get(queued) {
+                if let Some(size) = parallel_size(next) {
+                    if buffered > 0 && buffered + size > PARALLEL_BUFFER_BUDGET {
+                        break;
+                    }
+                    buffered += size;
+                    job_sender.send(queued)?;
+                }
+                queued += 1;
+            }
+
+            match job {
+                EntryJob::Directory { name, options } => {
+                    zip_writer.add_directory(name.as_str(), *options)?;
+                }
+                EntryJob::File {
+                    path,
+                    name,
+                    options,
+                    size,
+                } => {
+                    if parallel_size(job).is_some() {
+                        let entry = loop {
+                            if let Some(entry) = finished.remove(&index) {
+                                break entry;
+                            }
+                            let (done, entry) = result_receiver
+                                .recv()
+                                .map_err(|_| anyhow::anyhow!("zip workers stopped early"))?;
+                            finished.insert(done, entry);
+                        }?;
+                        let mut entry = ZipArchive::new(Cursor::new(entry))?;
+                        zip_writer.raw_copy_file(entry.by_index_raw(0)?)?;
+                        buffered -= size;
+                    } else {
+                        write_file(zip_writer, path, name, *options)?;
+                    }
+
+                    processed_size += size;
+                    if let Some(progress) = progress {
+                        progress.set_position(processed_size);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
 impl CompressionFormat for ZipFormat {
     fn compress(
         input_path: &Path,
@@ -49,16 +379,11 @@ impl CompressionFormat for ZipFormat {
             options.allow_symlink_escape,
         )?;
 
-        let output_file = File::create(output_path)
-            .with_context(|| format!("Failed to create output file {}", output_path.display()))?;
+        let output_file = volumes::create_zip(output_path, options.split_size)?;
         let buf_writer = BufWriter::new(output_file);
         let mut zip_writer = ZipWriter::new(buf_writer);
 
-        // Map compression level (1-22) to zip level (0-9)
-        let zip_level = (((options.level as f32 / 22.0) * 9.0) as i64).clamp(0, 9);
-        let base_file_options = FileOptions::<()>::default()
-            .compression_method(CompressionMethod::Deflated)
-            .compression_level(Some(zip_level));
+        let base_file_options = FileOptions::<()>::default();
 
         if let Some(progress) = progress {
             progress.set_length(input_size);
@@ -77,7 +402,7 @@ impl CompressionFormat for ZipFormat {
                     input_path.display()
                 )
             })?;
-            let zip_time = zip_last_modified(&metadata, options.strip_timestamps);
+            let zip_time = zip_last_modified(&metadata, options);
             let permissions = if options.normalize_permissions {
                 0o644
             } else {
@@ -101,8 +426,8 @@ impl CompressionFormat for ZipFormat {
                 )
             })?;
             if !filter.should_include_relative(Path::new(filename_os)) {
-                zip_writer.finish()?;
-                let output_size = std::fs::metadata(output_path)?.len();
+                zip_writer.finish()?.into_inner()?.finish()?;
+                let output_size = volumes::written_size(output_path, options.split_size)?;
                 return Ok(CompressionStats::new(input_size, output_size));
             }
 
@@ -112,11 +437,11 @@ impl CompressionFormat for ZipFormat {
                     input_path.display()
                 )
             })?;
-            zip_writer.start_file(filename, current_file_options)?;
-
-            let mut file = File::open(input_path)
-                .with_context(|| format!("Failed to open input file {}", input_path.display()))?;
-            std::io::copy(&mut file, &mut zip_writer)?;
+            let (method, level) = entry_compression(input_path, options)?;
+            let current_file_options = current_file_options
+                .compression_method(method)
+                .compression_level(level);
+            write_file(&mut zip_writer, input_path, filename, current_file_options)?;
         } else {
             // Directory compression
             // Password protection is not supported for ZIP format
@@ -156,44 +481,40 @@ impl CompressionFormat for ZipFormat {
                 entries.sort_by(|a, b| a.path().cmp(b.path()));
             }
 
-            let mut processed_size = 0u64;
-
+            let mut jobs = Vec::with_capacity(entries.len());
             for entry in entries {
                 let path = entry.path();
                 let relative_path = path.strip_prefix(base_path)?;
                 let path_str = utils::normalize_archive_path(relative_path);
 
                 let metadata = entry.metadata()?;
-                let zip_time = zip_last_modified(&metadata, options.strip_timestamps);
-                let permissions = if options.normalize_permissions {
-                    0o644
-                } else {
-                    #[cfg(unix)]
-                    {
-                        metadata.permissions().mode()
-                    }
-                    #[cfg(not(unix))]
-                    {
-                        0o644
-                    }
-                };
-                let current_file_options = base_file_options
-                    .last_modified_time(zip_time)
-                    .unix_permissions(permissions);
+                let zip_time = zip_last_modified(&metadata, options);
 
                 if path.is_file() {
-                    zip_writer.start_file(path_str.as_str(), current_file_options)?;
-
-                    let mut file = File::open(path).with_context(|| {
-                        format!("Failed to open file for archiving {}", path.display())
-                    })?;
-                    std::io::copy(&mut file, &mut zip_writer)?;
-
-                    processed_size += metadata.len();
-
-                    if let Some(progress) = progress {
-                        progress.set_position(processed_size);
-                    }
+                    let permissions = if options.normalize_permissions {
+                        0o644
+                    } else {
+                        #[cfg(unix)]
+                        {
+                            metadata.permissions().mode()
+                        }
+                        #[cfg(not(unix))]
+                        {
+                            0o644
+                        }
+                    };
+                    let (method, level) = entry_compression(path, options)?;
+                    let current_file_options = base_file_options
+                        .last_modified_time(zip_time)
+                        .unix_permissions(permissions)
+                        .compression_method(method)
+                        .compression_level(level);
+                    jobs.push(EntryJob::File {
+                        path: path.to_path_buf(),
+                        name: path_str,
+                        options: current_file_options,
+                        size: metadata.len(),
+                    });
                 } else if path.is_dir() {
                     let permissions = if options.normalize_permissions {
                         0o755
@@ -212,15 +533,24 @@ impl CompressionFormat for ZipFormat {
                         .unix_permissions(permissions);
 
                     // Add directory entry with trailing slash
-                    let dir_path = format!("{path_str}/");
-                    zip_writer.add_directory(&dir_path, current_file_options)?;
+                    jobs.push(EntryJob::Directory {
+                        name: format!("{path_str}/"),
+                        options: current_file_options,
+                    });
                 }
             }
+
+            let threads = utils::resolved_thread_count(options.threads) as usize;
+            write_entries(&mut zip_writer, &jobs, threads, progress)?;
         }
 
-        zip_writer.finish()?;
+        let mut output = zip_writer.finish()?.into_inner()?;
+        if options.deterministic {
+            normalize_host_fields(&mut output)?;
+        }
+        output.finish()?;
 
-        let output_size = std::fs::metadata(output_path)?.len();
+        let output_size = volumes::written_size(output_path, options.split_size)?;
         Ok(CompressionStats::new(input_size, output_size))
     }
 
@@ -235,7 +565,7 @@ impl CompressionFormat for ZipFormat {
             return Err(anyhow::anyhow!("Password protection is not supported for ZIP format. Use 7z format for password protection."));
         }
 
-        let file = File::open(archive_path)
+        let file = volumes::open(archive_path)
             .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
         let buf_reader = BufReader::new(file);
         let mut archive = ZipArchive::new(buf_reader).with_context(|| {
@@ -318,8 +648,8 @@ impl CompressionFormat for ZipFormat {
         Ok(())
     }
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
-        let file = File::open(archive_path)?;
+    fn list(archive_path: &Path, _options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
+        let file = volumes::open(archive_path)?;
         let buf_reader = BufReader::new(file);
         let mut archive = ZipArchive::new(buf_reader)?;
 
@@ -335,6 +665,7 @@ impl CompressionFormat for ZipFormat {
                 path,
                 size,
                 is_file,
+                method: None,
             });
         }
 
@@ -345,11 +676,10 @@ impl CompressionFormat for ZipFormat {
         "zip"
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
-        use std::fs::File;
+    fn test_integrity(archive_path: &Path, _options: &ExtractionOptions) -> Result<()> {
         use zip::ZipArchive;
 
-        let file = File::open(archive_path)?;
+        let file = volumes::open(archive_path)?;
         let mut archive = ZipArchive::new(file)?;
         for i in 0..archive.len() {
             let mut entry = archive.by_index(i)?;
@@ -360,3 +690,38 @@ impl CompressionFormat for ZipFormat {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn central_header_offset(bytes: &[u8]) -> usize {
+        (0..bytes.len() - 4)
+            .find(|&pos| read_u32(bytes, pos) == CENTRAL_HEADER_SIGNATURE)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_normalize_host_fields_rewrites_dos_entries() -> Result<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::<()>::default()
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(0o644);
+        writer.start_file("file.txt", options)?;
+        writer.write_all(b"hello")?;
+        let expected = writer.finish()?.into_inner();
+
+        // simulate an archive written on windows: DOS host byte plus attribute bits
+        let mut dos = expected.clone();
+        let pos = central_header_offset(&dos);
+        dos[pos + 5] = 0;
+        dos[pos + 38] |= 0x20;
+
+        let mut dos = Cursor::new(dos);
+        normalize_host_fields(&mut dos)?;
+
+        assert_eq!(dos.into_inner(), expected);
+        Ok(())
+    }
+}
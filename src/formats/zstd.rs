@@ -5,34 +5,78 @@ use crate::encryption::{
     ENCRYPTED_ZSTD_MAGIC,
 };
 use crate::filter::FileFilter;
+use crate::formats::seekable::{self, IndexEntry, IndexingWriter, SeekableArchive, SeekableWriter};
 use crate::formats::{
     tarball, ArchiveEntry, CompressionFormat, CompressionOptions, CompressionStats,
-    ExtractionOptions,
+    ExtractionOptions, FinishWrite, StreamPayload, ZstdStrategy,
 };
 use crate::progress::{Progress, ProgressReader};
-use crate::Result;
+use crate::volumes::{self, OutputFile, VolumeReader};
+use crate::{dict, utils, Result};
 use anyhow::{anyhow, bail, Context};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 pub struct ZstdFormat;
 
-fn resolved_thread_count(requested_threads: u32) -> u32 {
-    if requested_threads == 0 {
-        std::thread::available_parallelism()
-            .map(|parallelism| parallelism.get() as u32)
-            .unwrap_or(1)
-    } else {
-        requested_threads
+/// largest window log decoders accept without opting in (128 MiB)
+const DEFAULT_WINDOW_LOG_LIMIT: u32 = 27;
+
+/// largest window log zstd supports on 64-bit targets (2 GiB)
+const MAX_WINDOW_LOG: u32 = 31;
+
+/// longest possible zstd frame header, enough to read the dictionary ID
+const FRAME_HEADER_SIZE_MAX: usize = 18;
+
+/// window log requested by `--window-log` or `--long`, if any
+fn requested_window_log(options: &CompressionOptions) -> Option<u32> {
+    options.zstd_window_log.or(options.zstd_long)
+}
+
+/// reject windows that stock decoders refuse unless `--ultra` was given
+fn check_window_log(options: &CompressionOptions) -> Result<()> {
+    match requested_window_log(options) {
+        Some(log) if log > DEFAULT_WINDOW_LOG_LIMIT && !options.zstd_ultra => bail!(
+            "zstd window log {log} exceeds {DEFAULT_WINDOW_LOG_LIMIT}; pass --ultra to allow it (other decoders then need --long={log} or --memory)"
+        ),
+        _ => Ok(()),
     }
 }
 
-fn configure_threads<W: Write>(
+fn configure_encoder<W: Write>(
     encoder: &mut zstd::Encoder<'_, W>,
-    requested_threads: u32,
+    options: &CompressionOptions,
 ) -> Result<()> {
-    let thread_count = resolved_thread_count(requested_threads);
+    if options.zstd_long.is_some() {
+        encoder
+            .long_distance_matching(true)
+            .context("failed to enable zstd long-distance matching")?;
+    }
+    if let Some(log) = requested_window_log(options) {
+        encoder
+            .window_log(log)
+            .with_context(|| format!("failed to set zstd window log {log}"))?;
+    }
+    if let Some(strategy) = options.zstd_strategy {
+        encoder
+            .set_parameter(zstd::stream::raw::CParameter::Strategy(zstd_strategy(
+                strategy,
+            )))
+            .with_context(|| format!("failed to set zstd strategy {}", strategy.name()))?;
+    }
+    if let Some(size) = options.zstd_target_block_size {
+        encoder
+            .set_parameter(zstd::stream::raw::CParameter::TargetCBlockSize(size))
+            .with_context(|| format!("failed to set zstd target block size {size}"))?;
+    }
+    if options.zstd_checksum {
+        encoder
+            .include_checksum(true)
+            .context("failed to enable zstd checksums")?;
+    }
+
+    let thread_count = utils::resolved_thread_count(options.threads);
 
     // Preserve `-j1` as a single-threaded request. zstd's `NbWorkers=1`
     // still offloads compression onto a background worker thread.
@@ -47,33 +91,461 @@ fn configure_threads<W: Write>(
     Ok(())
 }
 
-fn compress_tarball<W: Write>(
+fn zstd_strategy(strategy: ZstdStrategy) -> zstd::zstd_safe::Strategy {
+    use zstd::zstd_safe::Strategy;
+    match strategy {
+        ZstdStrategy::Fast => Strategy::ZSTD_fast,
+        ZstdStrategy::Dfast => Strategy::ZSTD_dfast,
+        ZstdStrategy::Greedy => Strategy::ZSTD_greedy,
+        ZstdStrategy::Lazy => Strategy::ZSTD_lazy,
+        ZstdStrategy::Lazy2 => Strategy::ZSTD_lazy2,
+        ZstdStrategy::Btlazy2 => Strategy::ZSTD_btlazy2,
+        ZstdStrategy::Btopt => Strategy::ZSTD_btopt,
+        ZstdStrategy::Btultra => Strategy::ZSTD_btultra,
+        ZstdStrategy::Btultra2 => Strategy::ZSTD_btultra2,
+    }
+}
+
+fn file_name_lower(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// names that always get a tarball when compressing (`.tar.zst`, `.tzst`)
+fn is_tar_zst(path: &Path) -> bool {
+    let name = file_name_lower(path);
+    name.ends_with(".tar.zst") || name.ends_with(".tar.zstd") || name.ends_with(".tzst")
+}
+
+/// names that may hold a raw stream; plain `.zst` is also used for tarballs of directories
+fn is_plain_zst(path: &Path) -> bool {
+    let name = file_name_lower(path);
+    (name.ends_with(".zst") || name.ends_with(".zstd")) && !is_tar_zst(path)
+}
+
+/// suffixes stripped to name the file held by a raw zstd stream
+const RAW_SUFFIXES: &[&str] = &[".zst", ".zstd", ".tzst"];
+
+/// dictionary from `--dict`, or an empty one
+fn load_dictionary(options: &CompressionOptions) -> Result<Vec<u8>> {
+    match &options.zstd_dict {
+        Some(path) => dict::load(path),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn create_encoder<W: Write>(
+    writer: W,
+    zstd_level: i32,
+    options: &CompressionOptions,
+    dictionary: &[u8],
+) -> Result<zstd::Encoder<'static, W>> {
+    let mut zstd_encoder = zstd::Encoder::with_dictionary(writer, zstd_level, dictionary)
+        .context("Failed to create ZSTD encoder")?;
+    configure_encoder(&mut zstd_encoder, options)?;
+    Ok(zstd_encoder)
+}
+
+/// write the tarball or raw file contents to `writer`, handing it back afterwards
+fn write_payload<W: Write>(
+    writer: W,
+    input_path: &Path,
+    options: &CompressionOptions,
+    filter: &FileFilter,
+    progress: Option<&Progress>,
+    payload: StreamPayload,
+) -> Result<W> {
+    match payload {
+        StreamPayload::Auto | StreamPayload::Tar => tarball::build_tarball(
+            writer,
+            input_path,
+            options,
+            filter,
+            progress,
+            tarball::BuildOptions {
+                normalize_ownership: options.normalize_ownership,
+                apply_filter_to_single_file: true,
+                directory_slash: true,
+                set_mtime_for_single_file: true,
+            },
+        ),
+        StreamPayload::Raw => {
+            let mut writer = writer;
+            let included = input_path
+                .file_name()
+                .is_none_or(|filename| filter.should_include_relative(Path::new(filename)));
+            if included {
+                let mut input_file = File::open(input_path).with_context(|| {
+                    format!("failed to open input file: {}", input_path.display())
+                })?;
+                std::io::copy(&mut input_file, &mut writer)?;
+            }
+            Ok(writer)
+        }
+    }
+}
+
+fn compress_stream<W: Write>(
     writer: W,
     input_path: &Path,
     zstd_level: i32,
     options: &CompressionOptions,
     filter: &FileFilter,
     progress: Option<&Progress>,
-    build_options: tarball::BuildOptions,
+    payload: StreamPayload,
 ) -> Result<()> {
-    let mut zstd_encoder =
-        zstd::Encoder::new(writer, zstd_level).context("Failed to create ZSTD encoder")?;
-    configure_threads(&mut zstd_encoder, options.threads)?;
-
-    let zstd_encoder = tarball::build_tarball(
-        zstd_encoder,
-        input_path,
-        options,
-        filter,
-        progress,
-        build_options,
-    )?;
+    let dictionary = load_dictionary(options)?;
+    let zstd_encoder = create_encoder(writer, zstd_level, options, &dictionary)?;
+    let zstd_encoder = write_payload(zstd_encoder, input_path, options, filter, progress, payload)?;
 
     drop(zstd_encoder.finish()?);
 
     Ok(())
 }
 
+/// compresses one chunk of a seekable archive into a complete zstd frame
+type FrameCompressor = Box<dyn FnMut(&[u8]) -> std::io::Result<Vec<u8>>>;
+
+/// tarball written as independent frames of `frame_size` bytes while it is indexed
+type SeekableTarWriter<W> = IndexingWriter<SeekableWriter<W, FrameCompressor>>;
+
+fn frame_compressor(zstd_level: i32, options: &CompressionOptions) -> Result<FrameCompressor> {
+    let dictionary = load_dictionary(options)?;
+    let options = options.clone();
+    Ok(Box::new(move |chunk| {
+        let mut zstd_encoder = create_encoder(Vec::new(), zstd_level, &options, &dictionary)
+            .map_err(std::io::Error::other)?;
+        zstd_encoder.write_all(chunk)?;
+        zstd_encoder.finish()
+    }))
+}
+
+/// finish the frames, then write the tar index (if any) and the seek table
+fn finish_seekable<W: Write>(
+    writer: SeekableWriter<W, FrameCompressor>,
+    index: Option<Vec<IndexEntry>>,
+) -> Result<()> {
+    let (mut writer, mut frames) = writer.finish()?;
+    if let Some(index) = index {
+        frames.push(seekable::write_index(&mut writer, &index)?);
+    }
+
+    seekable::write_seek_table(&mut writer, &frames)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// write independent frames of `frame_size` bytes, the tar index and the seek table
+#[allow(clippy::too_many_arguments)]
+fn compress_seekable(
+    output_file: OutputFile,
+    input_path: &Path,
+    zstd_level: i32,
+    frame_size: u32,
+    options: &CompressionOptions,
+    filter: &FileFilter,
+    progress: Option<&Progress>,
+    payload: StreamPayload,
+) -> Result<()> {
+    let writer = SeekableWriter::new(
+        BufWriter::new(output_file),
+        frame_size,
+        frame_compressor(zstd_level, options)?,
+    );
+    // raw payloads have no entries to index
+    if payload == StreamPayload::Raw {
+        let writer = write_payload(writer, input_path, options, filter, progress, payload)?;
+        return finish_seekable(writer, None);
+    }
+    let writer = IndexingWriter::new(writer);
+    let (writer, index) =
+        write_payload(writer, input_path, options, filter, progress, payload)?.finish()?;
+    finish_seekable(writer, Some(index))
+}
+
+impl FinishWrite for SeekableTarWriter<BufWriter<OutputFile>> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        let (writer, index) = (*self).finish()?;
+        finish_seekable(writer, Some(index))
+    }
+}
+
+/// frame size of a seekable archive, or `None` for an ordinary zstd stream
+pub(crate) fn seekable_frame_size(archive_path: &Path) -> Result<Option<u32>> {
+    let mut archive_file = volumes::open(archive_path)
+        .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
+    if is_encrypted(&mut archive_file)? {
+        return Ok(None);
+    }
+    let archive = SeekableArchive::open(archive_file)
+        .with_context(|| format!("failed to read seek table of {}", archive_path.display()))?;
+    Ok(archive.map(|archive| archive.frame_size()))
+}
+
+/// ID of the dictionary the archive was compressed with, if any
+pub(crate) fn dictionary_id(archive_path: &Path, password: Option<&str>) -> Result<Option<u32>> {
+    let (input, _) = open_archive(archive_path, password, None)?;
+    let mut header = Vec::with_capacity(FRAME_HEADER_SIZE_MAX);
+    input
+        .take(FRAME_HEADER_SIZE_MAX as u64)
+        .read_to_end(&mut header)?;
+    Ok(zstd::zstd_safe::get_dict_id_from_frame(&header).map(|id| id.get()))
+}
+
+/// open a seekable archive that carries a tar index, with a copy of the index
+fn open_indexed(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<Option<(SeekableArchive, Vec<IndexEntry>)>> {
+    if options.payload == StreamPayload::Raw {
+        return Ok(None);
+    }
+
+    let mut archive_file = volumes::open(archive_path)
+        .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
+    if is_encrypted(&mut archive_file)? {
+        return Ok(None);
+    }
+
+    let Some(archive) = SeekableArchive::open(archive_file)
+        .with_context(|| format!("failed to read seek table of {}", archive_path.display()))?
+    else {
+        return Ok(None);
+    };
+    Ok(archive
+        .index()
+        .map(|index| index.to_vec())
+        .map(|index| (archive, index)))
+}
+
+/// open an archive, unwrapping the encryption layer when present
+///
+/// returns the (possibly decrypting) reader and the size of the compressed data it reads
+fn open_archive<'a>(
+    archive_path: &Path,
+    password: Option<&str>,
+    progress: Option<&'a Progress>,
+) -> Result<(Box<dyn Read + 'a>, u64)> {
+    let mut archive_file = volumes::open(archive_path)
+        .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
+    let archive_size = archive_file.len();
+
+    if !is_encrypted(&mut archive_file)? {
+        if password.is_some_and(|password| !password.is_empty()) {
+            eprintln!(
+                "warning: Password provided, but archive '{}' does not appear to be in the expected encrypted format. Attempting standard extraction.",
+                archive_path.display()
+            );
+        }
+        return Ok((
+            Box::new(ProgressReader::new(archive_file, progress)),
+            archive_size,
+        ));
+    }
+
+    let password = password.ok_or_else(|| {
+        anyhow!(
+            "Encrypted archive '{}' requires a password.",
+            archive_path.display()
+        )
+    })?;
+
+    if password.is_empty() {
+        bail!(
+            "Password cannot be empty for encrypted archive '{}'.",
+            archive_path.display()
+        );
+    }
+
+    // Read the salt
+    let mut salt = vec![0u8; ARGON2_SALT_LEN];
+    archive_file
+        .read_exact(&mut salt)
+        .context("Failed to read salt from encrypted archive")?;
+
+    // Derive the decryption key
+    let (derived_key, _used_salt) =
+        encryption::derive_key(password, Some(&salt)).context("Failed to derive decryption key")?;
+
+    // Create decrypting reader
+    let decrypting_reader =
+        DecryptingReader::new(ProgressReader::new(archive_file, progress), &derived_key)
+            .context("Failed to create DecryptingReader for ZSTD")?;
+
+    let header_size = (ENCRYPTED_ZSTD_MAGIC.len() + ARGON2_SALT_LEN) as u64;
+    Ok((
+        Box::new(decrypting_reader),
+        archive_size.saturating_sub(header_size),
+    ))
+}
+
+/// whether an archive starts with the encryption header
+pub(crate) fn is_encrypted_archive(archive_path: &Path) -> Result<bool> {
+    let mut archive_file = volumes::open(archive_path)
+        .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
+    is_encrypted(&mut archive_file)
+}
+
+/// check for the encryption magic header, leaving the file positioned after it if present
+fn is_encrypted(archive_file: &mut VolumeReader) -> Result<bool> {
+    let mut magic_buffer = [0u8; ENCRYPTED_ZSTD_MAGIC.len()];
+    let bytes_read = archive_file
+        .read(&mut magic_buffer)
+        .context("Failed to read initial bytes from archive for encryption check")?;
+
+    if bytes_read == ENCRYPTED_ZSTD_MAGIC.len() && magic_buffer == *ENCRYPTED_ZSTD_MAGIC {
+        return Ok(true);
+    }
+
+    archive_file
+        .seek(SeekFrom::Start(0))
+        .context("Failed to rewind archive file for standard processing")?;
+    Ok(false)
+}
+
+/// pick the dictionary to decode with, checking it against the ID in the frame header
+fn resolve_dictionary(
+    archive_path: &Path,
+    frame_dict_id: Option<u32>,
+    dict_path: Option<&Path>,
+) -> Result<Vec<u8>> {
+    let Some(dict_path) = dict_path else {
+        if let Some(id) = frame_dict_id {
+            bail!(
+                "archive '{}' was compressed with zstd dictionary ID {id}; pass it with --dict",
+                archive_path.display()
+            );
+        }
+        return Ok(Vec::new());
+    };
+
+    let dictionary = dict::load(dict_path)?;
+    let dict_id = dict::dictionary_id(&dictionary);
+    match (frame_dict_id, dict_id) {
+        (Some(expected), actual) if actual != Some(expected) => bail!(
+            "wrong zstd dictionary: archive '{}' needs dictionary ID {expected}, but {} has {}",
+            archive_path.display(),
+            dict_path.display(),
+            dict::describe_id(actual)
+        ),
+        // trained dictionaries always leave their ID in the frame, so this archive
+        // was made without one; decoding with it would corrupt the output
+        (None, Some(_)) => {
+            eprintln!(
+                "warning: archive '{}' does not use a zstd dictionary; ignoring --dict",
+                archive_path.display()
+            );
+            Ok(Vec::new())
+        }
+        _ => Ok(dictionary),
+    }
+}
+
+fn decoder<'a>(
+    archive_path: &Path,
+    mut input: Box<dyn Read + 'a>,
+    dict_path: Option<&Path>,
+) -> Result<impl Read + 'a> {
+    let mut header = Vec::with_capacity(FRAME_HEADER_SIZE_MAX);
+    (&mut input)
+        .take(FRAME_HEADER_SIZE_MAX as u64)
+        .read_to_end(&mut header)?;
+    let frame_dict_id = zstd::zstd_safe::get_dict_id_from_frame(&header).map(|id| id.get());
+    let dictionary = resolve_dictionary(archive_path, frame_dict_id, dict_path)?;
+
+    let input = std::io::BufReader::new(std::io::Cursor::new(header).chain(input));
+    let mut decoder = zstd::Decoder::with_dictionary(input, &dictionary).with_context(|| {
+        format!(
+            "failed to create zstd decoder for: {}",
+            archive_path.display()
+        )
+    })?;
+    // accept archives written with --long or --ultra windows
+    decoder
+        .window_log_max(MAX_WINDOW_LOG)
+        .context("failed to raise zstd decoder window limit")?;
+    Ok(decoder)
+}
+
+impl<W: Write> FinishWrite for zstd::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        (*self).finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// encoder for a tar stream assembled entry by entry
+pub fn stream_writer(
+    output_path: &Path,
+    options: &CompressionOptions,
+) -> Result<Box<dyn FinishWrite>> {
+    check_window_log(options)?;
+    let encrypt = options
+        .password
+        .as_deref()
+        .is_some_and(|password| !password.is_empty());
+    if options.zstd_seekable.is_some() && encrypt {
+        bail!("--seekable cannot be combined with --password");
+    }
+    let mut underlying_file = volumes::create(output_path, options.split_size)
+        .with_context(|| format!("failed to create output file: {}", output_path.display()))?;
+    let zstd_level = if options.level == 0 { 3 } else { options.level };
+
+    if let Some(frame_size) = options.zstd_seekable {
+        let writer = SeekableWriter::new(
+            BufWriter::new(underlying_file),
+            frame_size,
+            frame_compressor(zstd_level, options)?,
+        );
+        return Ok(Box::new(IndexingWriter::new(writer)));
+    }
+
+    let dictionary = load_dictionary(options)?;
+
+    let Some(password) = options
+        .password
+        .as_deref()
+        .filter(|password| !password.is_empty())
+    else {
+        let writer = BufWriter::new(underlying_file);
+        return Ok(Box::new(create_encoder(
+            writer,
+            zstd_level,
+            options,
+            &dictionary,
+        )?));
+    };
+
+    let (derived_key, salt) = encryption::derive_key(password, None)
+        .context("Failed to derive encryption key for ZSTD compression")?;
+    underlying_file
+        .write_all(ENCRYPTED_ZSTD_MAGIC)
+        .context("Failed to write encryption magic header")?;
+    underlying_file
+        .write_all(&salt)
+        .context("Failed to write encryption salt")?;
+    let encrypting_writer =
+        EncryptingWriter::new(underlying_file, &derived_key, DEFAULT_ENCRYPTION_CHUNK_SIZE)
+            .context("Failed to create EncryptingWriter for ZSTD")?;
+    Ok(Box::new(create_encoder(
+        encrypting_writer,
+        zstd_level,
+        options,
+        &dictionary,
+    )?))
+}
+
+/// decoded content of an archive and whether it holds a tarball
+pub fn stream_reader(
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<(StreamPayload, Box<dyn Read>)> {
+    let (input_stream, _) = open_archive(archive_path, options.password.as_deref(), None)?;
+    let decoder = decoder(archive_path, input_stream, options.zstd_dict.as_deref())?;
+    tarball::detect_payload(decoder, options.payload)
+}
+
 impl CompressionFormat for ZstdFormat {
     fn compress(
         input_path: &Path,
@@ -82,6 +554,15 @@ impl CompressionFormat for ZstdFormat {
         filter: &FileFilter,
         progress: Option<&Progress>,
     ) -> Result<CompressionStats> {
+        check_window_log(options)?;
+        let encrypt = options
+            .password
+            .as_deref()
+            .is_some_and(|password| !password.is_empty());
+        if options.zstd_seekable.is_some() && encrypt {
+            bail!("--seekable cannot be combined with --password");
+        }
+
         // calculate input size for progress and stats
         let input_size = crate::utils::calculate_directory_size(
             input_path,
@@ -90,19 +571,35 @@ impl CompressionFormat for ZstdFormat {
             options.allow_symlink_escape,
         )?;
 
+        // a single file named `<file>.zst` becomes a raw stream, like `zstd file`;
+        // other names keep the tarball so the original filename survives extraction
+        let payload = if input_path.is_file()
+            && is_plain_zst(output_path)
+            && input_path.file_name().and_then(|name| name.to_str())
+                == Some(utils::raw_output_name(output_path, RAW_SUFFIXES).as_str())
+        {
+            StreamPayload::Raw
+        } else {
+            StreamPayload::Tar
+        };
+
         // create output file
-        let mut underlying_file = File::create(output_path)
+        let mut underlying_file = volumes::create(output_path, options.split_size)
             .with_context(|| format!("failed to create output file: {}", output_path.display()))?;
         let zstd_level = if options.level == 0 { 3 } else { options.level };
-        let build_options = tarball::BuildOptions {
-            normalize_ownership: options.normalize_ownership,
-            apply_filter_to_single_file: true,
-            directory_slash: true,
-            set_mtime_for_single_file: true,
-        };
 
-        // Handle password-based encryption
-        if let Some(password) = options
+        if let Some(frame_size) = options.zstd_seekable {
+            compress_seekable(
+                underlying_file,
+                input_path,
+                zstd_level,
+                frame_size,
+                options,
+                filter,
+                progress,
+                payload,
+            )?;
+        } else if let Some(password) = options
             .password
             .as_deref()
             .filter(|password| !password.is_empty())
@@ -121,28 +618,28 @@ impl CompressionFormat for ZstdFormat {
             let encrypting_writer =
                 EncryptingWriter::new(underlying_file, &derived_key, DEFAULT_ENCRYPTION_CHUNK_SIZE)
                     .context("Failed to create EncryptingWriter for ZSTD")?;
-            compress_tarball(
+            compress_stream(
                 encrypting_writer,
                 input_path,
                 zstd_level,
                 options,
                 filter,
                 progress,
-                build_options,
+                payload,
             )?;
         } else {
-            compress_tarball(
+            compress_stream(
                 underlying_file,
                 input_path,
                 zstd_level,
                 options,
                 filter,
                 progress,
-                build_options,
+                payload,
             )?;
         }
 
-        let output_size = std::fs::metadata(output_path)?.len();
+        let output_size = volumes::written_size(output_path, options.split_size)?;
 
         // finalize progress
         if let Some(progress) = progress {
@@ -158,148 +655,108 @@ impl CompressionFormat for ZstdFormat {
         options: &ExtractionOptions,
         progress: Option<&crate::progress::Progress>,
     ) -> Result<()> {
-        // open archive file
-        let mut archive_file = File::open(archive_path)
-            .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
-        let archive_size = std::fs::metadata(archive_path)
-            .with_context(|| {
-                format!(
-                    "failed to read archive metadata: {}",
-                    archive_path.display()
-                )
-            })?
-            .len();
-
-        // Check for encryption magic header
-        let mut magic_buffer = [0u8; ENCRYPTED_ZSTD_MAGIC.len()];
-        let bytes_read = archive_file
-            .read(&mut magic_buffer)
-            .context("Failed to read initial bytes from archive for encryption check")?;
-
-        // Determine if this is an encrypted archive and set up the input stream
-        let (input_stream, bytes_offset): (Box<dyn Read>, u64) = if bytes_read
-            == ENCRYPTED_ZSTD_MAGIC.len()
-            && magic_buffer == *ENCRYPTED_ZSTD_MAGIC
-        {
-            // This is an encrypted archive
-            let password = options.password.as_deref().ok_or_else(|| {
-                anyhow!(
-                    "Encrypted archive '{}' requires a password.",
-                    archive_path.display()
-                )
-            })?;
-
-            if password.is_empty() {
-                bail!(
-                    "Password cannot be empty for encrypted archive '{}'.",
-                    archive_path.display()
-                );
+        // a seekable archive lets `--entry` decode only the frames holding the selection
+        if !options.entries.is_empty() {
+            if let Some((mut archive, index)) = open_indexed(archive_path, options)? {
+                let frame_dict_id = archive.frame_dict_id()?;
+                archive.set_dictionary(&resolve_dictionary(
+                    archive_path,
+                    frame_dict_id,
+                    options.zstd_dict.as_deref(),
+                )?)?;
+                let ranges = seekable::selected_ranges(&index, &options.entries);
+                let reader = seekable::RangeReader::new(archive, ranges);
+                return tarball::extract_tarball(reader, output_dir, options, progress);
             }
+        }
 
-            // Read the salt
-            let mut salt = vec![0u8; ARGON2_SALT_LEN];
-            archive_file
-                .read_exact(&mut salt)
-                .context("Failed to read salt from encrypted archive")?;
+        let (input_stream, compressed_size) =
+            open_archive(archive_path, options.password.as_deref(), progress)?;
+        if let Some(progress) = progress {
+            progress.set_length(compressed_size);
+        }
 
-            // Derive the decryption key
-            let (derived_key, _used_salt) = encryption::derive_key(password, Some(&salt))
-                .context("Failed to derive decryption key")?;
+        // create zstd decoder with the appropriate input stream
+        let decoder = decoder(archive_path, input_stream, options.zstd_dict.as_deref())?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
 
-            // Create decrypting reader
-            let decrypting_reader =
-                DecryptingReader::new(ProgressReader::new(archive_file, progress), &derived_key)
-                    .context("Failed to create DecryptingReader for ZSTD")?;
+        if payload == StreamPayload::Tar {
+            return tarball::extract_tarball(reader, output_dir, options, progress);
+        }
 
-            (
-                Box::new(decrypting_reader),
-                (ENCRYPTED_ZSTD_MAGIC.len() + ARGON2_SALT_LEN) as u64,
-            )
-        } else {
-            // This is a standard (unencrypted) archive
-            archive_file
-                .seek(SeekFrom::Start(0))
-                .context("Failed to rewind archive file for standard processing")?;
-
-            if options.password.is_some()
-                && !options.password.as_deref().unwrap_or_default().is_empty()
-            {
-                eprintln!(
-                    "warning: Password provided, but archive '{}' does not appear to be in the expected encrypted format. Attempting standard extraction.",
-                    archive_path.display()
+        let output_name = utils::raw_output_name(archive_path, RAW_SUFFIXES);
+        let target_path = match utils::prepare_extract_target(
+            output_dir,
+            Path::new(&output_name),
+            options.strip_components,
+            options.overwrite,
+            false,
+        )? {
+            utils::ExtractTarget::Target(target_path) => target_path,
+            utils::ExtractTarget::SkipStrip => return Ok(()),
+            utils::ExtractTarget::SkipExisting(target_path) => {
+                bail!(
+                    "output file '{}' already exists. Use --overwrite to replace.",
+                    target_path.display()
                 );
             }
-
-            (Box::new(ProgressReader::new(archive_file, progress)), 0)
         };
 
-        if let Some(progress) = progress {
-            progress.set_length(archive_size.saturating_sub(bytes_offset));
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
 
-        // create zstd decoder with the appropriate input stream
-        let decoder = zstd::Decoder::new(input_stream).with_context(|| {
-            format!(
-                "failed to create zstd decoder for: {}",
-                archive_path.display()
-            )
-        })?;
+        let mut output_file = File::create(&target_path)?;
+        std::io::copy(&mut reader, &mut output_file)?;
+        Ok(())
+    }
 
-        tarball::extract_tarball(decoder, output_dir, options, progress)
+    fn extension() -> &'static str {
+        "zst"
     }
 
-    fn list(archive_path: &Path) -> Result<Vec<ArchiveEntry>> {
+    fn list(archive_path: &Path, options: &ExtractionOptions) -> Result<Vec<ArchiveEntry>> {
         // open archive file
-        let mut archive_file = File::open(archive_path)
+        let mut archive_file = volumes::open(archive_path)
             .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
 
-        // Check for encryption magic header
-        let mut magic_buffer = [0u8; ENCRYPTED_ZSTD_MAGIC.len()];
-        let bytes_read = archive_file
-            .read(&mut magic_buffer)
-            .context("Failed to read initial bytes from archive for encryption check")?;
-
         // If this is an encrypted archive, we can't list it without a password
-        if bytes_read == ENCRYPTED_ZSTD_MAGIC.len() && magic_buffer == *ENCRYPTED_ZSTD_MAGIC {
+        if is_encrypted(&mut archive_file)? && options.password.is_none() {
             return Err(anyhow!(
-                "Cannot list encrypted ZSTD archive '{}' - password required. Use the extract command with --password to access contents.",
+                "Cannot list encrypted ZSTD archive '{}' - password required. Use --password to list its contents.",
                 archive_path.display()
             ));
         }
 
-        // This is a standard archive, proceed normally
-        archive_file
-            .seek(SeekFrom::Start(0))
-            .context("Failed to rewind archive file for standard processing")?;
+        // the tar index lists a seekable archive without decoding it
+        if let Some((_, index)) = open_indexed(archive_path, options)? {
+            return Ok(index.iter().map(ArchiveEntry::from).collect());
+        }
 
-        // create zstd decoder
-        let decoder = zstd::Decoder::new(archive_file).with_context(|| {
-            format!(
-                "failed to create zstd decoder for: {}",
-                archive_path.display()
-            )
-        })?;
+        let (input_stream, _) = open_archive(archive_path, options.password.as_deref(), None)?;
+        let decoder = decoder(archive_path, input_stream, options.zstd_dict.as_deref())?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
 
-        tarball::list_tarball(decoder)
-    }
+        if payload == StreamPayload::Tar {
+            return tarball::list_tarball(reader);
+        }
 
-    fn extension() -> &'static str {
-        "zst"
+        let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+        Ok(vec![ArchiveEntry {
+            path: utils::raw_output_name(archive_path, RAW_SUFFIXES),
+            size,
+            is_file: true,
+            method: None,
+        }])
     }
 
-    fn test_integrity(archive_path: &Path) -> Result<()> {
+    fn test_integrity(archive_path: &Path, options: &ExtractionOptions) -> Result<()> {
         // open archive file
-        let mut archive_file = File::open(archive_path)
+        let mut archive_file = volumes::open(archive_path)
             .with_context(|| format!("failed to open archive file: {}", archive_path.display()))?;
 
-        // Check for encryption magic header
-        let mut magic_buffer = [0u8; ENCRYPTED_ZSTD_MAGIC.len()];
-        let bytes_read = archive_file
-            .read(&mut magic_buffer)
-            .context("Failed to read initial bytes from archive for encryption check")?;
-
-        // If this is an encrypted archive, we can only verify the header format
-        if bytes_read == ENCRYPTED_ZSTD_MAGIC.len() && magic_buffer == *ENCRYPTED_ZSTD_MAGIC {
+        // Without a password, an encrypted archive can only have its header format verified
+        if is_encrypted(&mut archive_file)? && options.password.is_none() {
             // For encrypted archives, we can check if the salt is readable
             let mut salt = vec![0u8; ARGON2_SALT_LEN];
             archive_file
@@ -307,34 +764,27 @@ impl CompressionFormat for ZstdFormat {
                 .context("Failed to read salt from encrypted archive")?;
 
             // If we got here, the header format is valid
-            // Full integrity testing would require a password, but basic format is OK
             return Ok(());
         }
 
-        // This is a standard archive, proceed with full integrity testing
-        archive_file
-            .seek(SeekFrom::Start(0))
-            .context("Failed to rewind archive file for standard processing")?;
-
-        // Check if this is a tar.zst or single .zst file
-        if archive_path
-            .extension()
-            .is_some_and(|ext| ext == "tzst" || ext == "tar.zst")
-            || archive_path
-                .file_name()
-                .is_some_and(|name| name.to_string_lossy().ends_with(".tar.zst"))
-        {
-            // This is a tar.zst archive - test by reading all tar entries
-            let zstd_decoder = zstd::stream::read::Decoder::new(archive_file)?;
-            let mut archive = tar::Archive::new(zstd_decoder);
-            for entry in archive.entries()? {
-                let _entry = entry?; // This will fail if data is corrupted
+        // decrypt if needed, then proceed with full integrity testing
+        let (input_stream, _) = open_archive(archive_path, options.password.as_deref(), None)?;
+        let decoder = decoder(archive_path, input_stream, options.zstd_dict.as_deref())?;
+        let (payload, mut reader) = tarball::detect_payload(decoder, options.payload)?;
+
+        match payload {
+            StreamPayload::Auto | StreamPayload::Tar => {
+                // test by reading all tar entries and their data
+                let mut archive = tar::Archive::new(reader);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    std::io::copy(&mut entry, &mut std::io::sink())?;
+                }
+            }
+            StreamPayload::Raw => {
+                // test by decompressing fully
+                std::io::copy(&mut reader, &mut std::io::sink())?;
             }
-        } else {
-            // Single .zst file - test by decompressing fully
-            let mut zstd_decoder = zstd::stream::read::Decoder::new(archive_file)?;
-            let mut buffer = Vec::new();
-            zstd_decoder.read_to_end(&mut buffer)?;
         }
 
         Ok(())
@@ -0,0 +1,635 @@
+//! incremental backups: snapshot files and restoring a chain of archives
+//!
+//! a snapshot records every path the last run saw. the next run only archives what is new
+//! or changed since then, and lists the paths that disappeared in a manifest entry written
+//! first in the archive. `zzz restore` replays a full backup and its incrementals in order.
+
+use crate::convert::{self, Entry, EntryKind};
+use crate::filter::FileFilter;
+use crate::formats::{
+    dedup, sevenz, CompressionOptions, CompressionStats, ExtractionOptions, Format, StreamPayload,
+};
+use crate::modify::{self, NewEntry};
+use crate::progress::Progress;
+use crate::{extract, volumes, Result};
+use anyhow::{anyhow, bail, Context};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+/// name of the manifest entry at the root of every incremental archive
+pub const MANIFEST_NAME: &str = ".zzz-incremental.json";
+
+/// version of the snapshot and manifest layout
+const VERSION: i64 = 1;
+
+/// what the last run saw of one path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Record {
+    path: String,
+    #[serde(rename = "type")]
+    kind: RecordKind,
+    size: u64,
+    mtime: i64,
+    mtime_nsec: u32,
+    inode: u64,
+    /// permission bits; missing from snapshots written before they were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    /// content hash of files
+    sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RecordKind {
+    File,
+    Directory,
+}
+
+/// the state file given to `--snapshot`
+#[derive(Debug, PartialEq, Deserialize)]
+struct Snapshot {
+    level: u64,
+    #[serde(rename = "entries")]
+    records: Vec<Record>,
+}
+
+/// the manifest entry of an archive: its level and the paths deleted since the last run
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Manifest {
+    level: u64,
+    deleted: Vec<String>,
+}
+
+/// a manifest as written, led by the layout version
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    version: i64,
+    #[serde(flatten)]
+    contents: &'a T,
+}
+
+/// what a restore did
+#[derive(Debug, Default)]
+pub struct RestoreStats {
+    pub archives: u64,
+    pub deleted: u64,
+}
+
+/// archive what changed since the run recorded in the snapshot file, then update it
+///
+/// without a snapshot file this is a full (level 0) backup of everything.
+pub(crate) fn compress(
+    input_path: &Path,
+    output_path: &Path,
+    format: Format,
+    options: &CompressionOptions,
+    filter: &FileFilter,
+    progress: Option<&Progress>,
+    verbose: bool,
+) -> Result<CompressionStats> {
+    let snapshot_path = options
+        .snapshot
+        .as_deref()
+        .expect("incremental backups need a snapshot path");
+    if format == Format::Rar {
+        bail!("RAR compression is not supported");
+    }
+    if options.zstd_seekable.is_some() {
+        bail!("--seekable cannot be combined with --snapshot");
+    }
+
+    let previous = if snapshot_path.exists() {
+        Some(Snapshot::load(snapshot_path)?)
+    } else {
+        None
+    };
+    let level = previous.as_ref().map_or(0, |snapshot| snapshot.level + 1);
+    let known: HashMap<&str, &Record> = previous
+        .iter()
+        .flat_map(|snapshot| &snapshot.records)
+        .map(|record| (record.path.as_str(), record))
+        .collect();
+
+    // the snapshot may live inside the tree being backed up
+    let snapshot_file = std::fs::canonicalize(snapshot_path).ok();
+    let mut records = Vec::new();
+    let mut changed = Vec::new();
+    for entry in modify::collect_inputs(&[input_path.to_path_buf()], options, filter)? {
+        if snapshot_file.is_some()
+            && entry.source.file_name() == snapshot_path.file_name()
+            && std::fs::canonicalize(&entry.source).ok() == snapshot_file
+        {
+            continue;
+        }
+        let old = known.get(entry.name.as_str()).copied();
+        let (record, unchanged) = record_entry(&entry, old)?;
+        if !unchanged {
+            if verbose {
+                let action = if old.is_some() { "changed" } else { "adding" };
+                println!("  {action}: {}", entry.name);
+            }
+            changed.push(entry);
+        }
+        records.push(record);
+    }
+
+    // paths that are gone, or whose type changed, are removed before the archive is extracted
+    let current: HashMap<&str, RecordKind> = records
+        .iter()
+        .map(|record| (record.path.as_str(), record.kind))
+        .collect();
+    let deleted: Vec<String> = previous
+        .iter()
+        .flat_map(|snapshot| &snapshot.records)
+        .filter(|record| current.get(record.path.as_str()) != Some(&record.kind))
+        .map(|record| record.path.clone())
+        .collect();
+    if verbose {
+        for path in &deleted {
+            println!("  deleted: {path}");
+        }
+        println!(
+            "  level {level}: {} archived, {} deleted",
+            changed.len(),
+            deleted.len()
+        );
+    }
+
+    let input_size = changed
+        .iter()
+        .filter(|entry| entry.metadata.is_file())
+        .map(|entry| entry.metadata.len())
+        .sum();
+    if let Some(progress) = progress {
+        progress.set_length(input_size);
+    }
+
+    let manifest = Manifest { level, deleted }.to_json()?;
+    let mut sink = convert::open_sink(format, output_path, options)?;
+    sink.add(
+        &Entry {
+            path: MANIFEST_NAME.to_string(),
+            kind: EntryKind::File,
+            size: Some(manifest.len() as u64),
+            mode: None,
+            mtime: None,
+            owner: None,
+        },
+        &mut manifest.as_bytes(),
+    )?;
+    modify::add_entries(sink.as_mut(), &changed, options, progress)?;
+    sink.finish()?;
+    let output_size = volumes::written_size(output_path, options.split_size)?;
+
+    // only a complete archive, every volume written, may advance the snapshot
+    Snapshot { level, records }.save(snapshot_path)?;
+    Ok(CompressionStats::new(input_size, output_size))
+}
+
+/// describe a path on disk, and whether it is unchanged since `old`
+///
+/// files whose size, mtime and inode match are taken as unchanged without reading them;
+/// otherwise the content hash decides.
+fn record_entry(entry: &NewEntry, old: Option<&Record>) -> Result<(Record, bool)> {
+    let metadata = &entry.metadata;
+    let (mtime, mtime_nsec) = match metadata
+        .modified()
+        .ok()
+        .map(|mtime| mtime.duration_since(std::time::UNIX_EPOCH))
+    {
+        Some(Ok(since)) => (since.as_secs() as i64, since.subsec_nanos()),
+        Some(Err(before)) => (-(before.duration().as_secs() as i64), 0),
+        None => (0, 0),
+    };
+    #[cfg(unix)]
+    let (inode, mode) = (metadata.ino(), Some(metadata.permissions().mode() & 0o7777));
+    #[cfg(not(unix))]
+    let (inode, mode) = (0, None);
+
+    let kind = if metadata.is_dir() {
+        RecordKind::Directory
+    } else {
+        RecordKind::File
+    };
+    let mut record = Record {
+        path: entry.name.clone(),
+        kind,
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        mtime,
+        mtime_nsec,
+        inode,
+        mode,
+        sha256: None,
+    };
+    let Some(old) = old.filter(|old| old.kind == kind) else {
+        if kind == RecordKind::File {
+            record.sha256 = Some(hash_file(&entry.source)?);
+        }
+        return Ok((record, false));
+    };
+    // a chmod alone changes the archived entry too
+    let same_mode = old.mode.is_none() || old.mode == record.mode;
+    if kind == RecordKind::Directory {
+        return Ok((record, same_mode));
+    }
+
+    if old.size == record.size
+        && old.mtime == record.mtime
+        && old.mtime_nsec == record.mtime_nsec
+        && old.inode == record.inode
+        && old.sha256.is_some()
+    {
+        record.sha256 = old.sha256.clone();
+        return Ok((record, same_mode));
+    }
+    record.sha256 = Some(hash_file(&entry.source)?);
+    let unchanged = same_mode && old.size == record.size && old.sha256 == record.sha256;
+    Ok((record, unchanged))
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open file for hashing {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// extract a full backup and the incrementals made after it, in order, into `output_dir`
+///
+/// every archive's manifest is checked before anything is written. the paths an archive
+/// deleted are removed before it is extracted.
+pub fn restore(
+    archives: &[PathBuf],
+    output_dir: &Path,
+    options: &ExtractionOptions,
+    verbose: bool,
+) -> Result<RestoreStats> {
+    let mut manifests = Vec::new();
+    let mut last_level = None;
+    for archive in archives {
+        let manifest = read_manifest(archive, options)?;
+        match last_level {
+            None if manifest.level != 0 => bail!(
+                "'{}' is a level {} incremental backup; a restore starts from a full (level 0) backup",
+                archive.display(),
+                manifest.level
+            ),
+            Some(last) if manifest.level == 0 || manifest.level > last + 1 => bail!(
+                "'{}' is a level {} backup and cannot follow a level {last} backup",
+                archive.display(),
+                manifest.level
+            ),
+            _ => last_level = Some(manifest.level),
+        }
+        manifests.push(manifest);
+    }
+
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create output directory {}", output_dir.display()))?;
+    let mut stats = RestoreStats::default();
+    for (archive, manifest) in archives.iter().zip(manifests) {
+        if verbose {
+            println!("restoring {} (level {})", archive.display(), manifest.level);
+        }
+        for path in &manifest.deleted {
+            if remove_path(output_dir, path, archive)? {
+                stats.deleted += 1;
+                if verbose {
+                    println!("  deleting: {path}");
+                }
+            }
+        }
+        let options = ExtractionOptions {
+            overwrite: true,
+            ..options.clone()
+        };
+        let strip_components = options.strip_components;
+        extract::extract(archive, output_dir, options, false, verbose)?;
+        // the manifest sits at the root, so stripped components or picked entries skip it
+        if strip_components == 0 {
+            match std::fs::remove_file(output_dir.join(MANIFEST_NAME)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to remove {MANIFEST_NAME} from {}",
+                            output_dir.display()
+                        )
+                    });
+                }
+                _ => {}
+            }
+        }
+        stats.archives += 1;
+    }
+    Ok(stats)
+}
+
+/// remove a deleted path below `output_dir`, returning whether it was there
+fn remove_path(output_dir: &Path, name: &str, archive: &Path) -> Result<bool> {
+    let relative = Path::new(name);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!(
+            "unsafe path '{name}' in the deletion list of '{}'",
+            archive.display()
+        );
+    }
+
+    // never follow a symlink out of the restore directory
+    let path = output_dir.join(relative);
+    for ancestor in relative.ancestors().skip(1) {
+        if ancestor.as_os_str().is_empty() {
+            break;
+        }
+        match std::fs::symlink_metadata(output_dir.join(ancestor)) {
+            Ok(metadata) if metadata.file_type().is_symlink() => bail!(
+                "refusing to delete '{name}' through the symlink '{}'",
+                ancestor.display()
+            ),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let metadata = match std::fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(&path)
+    } else {
+        std::fs::remove_file(&path)
+    }
+    .with_context(|| format!("Failed to delete {}", path.display()))?;
+    Ok(true)
+}
+
+/// read the manifest entry of an incremental archive without extracting anything
+fn read_manifest(archive_path: &Path, options: &ExtractionOptions) -> Result<Manifest> {
+    let format = Format::detect(archive_path)?;
+    let not_incremental = || {
+        anyhow!(
+            "'{}' is not an incremental backup (it has no {MANIFEST_NAME} entry)",
+            archive_path.display()
+        )
+    };
+
+    let mut contents = String::new();
+    match format {
+        Format::Zip => {
+            let file = volumes::open(archive_path).with_context(|| {
+                format!("Failed to open archive file {}", archive_path.display())
+            })?;
+            let mut archive = ZipArchive::new(BufReader::new(file)).with_context(|| {
+                format!("Failed to read ZIP archive from {}", archive_path.display())
+            })?;
+            let Ok(mut entry) = archive.by_name(MANIFEST_NAME) else {
+                return Err(not_incremental());
+            };
+            entry.read_to_string(&mut contents)?;
+        }
+        Format::SevenZ => {
+            let password = options.password.as_deref();
+            let mut reader = sevenz::open_reader(archive_path, password)?;
+            let mut found = false;
+            reader
+                .for_each_entries(|entry, data| {
+                    if entry.name() != MANIFEST_NAME {
+                        return Ok(true);
+                    }
+                    found = true;
+                    data.read_to_string(&mut contents)?;
+                    Ok(false)
+                })
+                .map_err(|e| sevenz::reader_error(archive_path, password, e))?;
+            if !found {
+                return Err(not_incremental());
+            }
+        }
+        Format::Dedup => {
+            let mut found = false;
+            dedup::read_entries(archive_path, options, |entry, data| {
+                if entry.path == MANIFEST_NAME {
+                    found = true;
+                    data.read_to_string(&mut contents)?;
+                }
+                Ok(())
+            })?;
+            if !found {
+                return Err(not_incremental());
+            }
+        }
+        Format::Rar => return Err(not_incremental()),
+        _ => {
+            let (payload, reader) = format
+                .stream_reader(archive_path, options)?
+                .expect("single-stream format");
+            if payload == StreamPayload::Raw {
+                return Err(not_incremental());
+            }
+            // the manifest is always written first
+            let mut archive = tar::Archive::new(reader);
+            let mut entry = match archive.entries()?.next() {
+                Some(entry) => entry?,
+                None => return Err(not_incremental()),
+            };
+            if entry.path()?.to_str() != Some(MANIFEST_NAME) {
+                return Err(not_incremental());
+            }
+            entry.read_to_string(&mut contents)?;
+        }
+    }
+    parse::<Manifest>(&contents)
+        .with_context(|| format!("Invalid {MANIFEST_NAME} in '{}'", archive_path.display()))
+}
+
+impl Snapshot {
+    fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot file {}", path.display()))?;
+        parse(&contents).with_context(|| format!("Invalid snapshot file {}", path.display()))
+    }
+
+    /// write the snapshot through a temporary file so a failed run keeps the old one
+    fn save(&self, path: &Path) -> Result<()> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid snapshot path {}", path.display()))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(".zzz-tmp");
+        let temp_path = path.with_file_name(temp_name);
+        let json = self.to_json()?;
+        // sync before the rename so a crash cannot leave an empty snapshot behind
+        File::create(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(json.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temp_path, path))
+            .with_context(|| format!("Failed to write snapshot file {}", path.display()))
+    }
+
+    /// one line per entry keeps snapshots diffable
+    fn to_json(&self) -> Result<String> {
+        let mut json = format!(
+            "{{\n  \"version\": {VERSION},\n  \"level\": {},\n  \"entries\": [",
+            self.level
+        );
+        for (i, record) in self.records.iter().enumerate() {
+            json.push_str(if i == 0 { "\n    " } else { ",\n    " });
+            json.push_str(&serde_json::to_string(record)?);
+        }
+        json.push_str("\n  ]\n}\n");
+        Ok(json)
+    }
+}
+
+impl Manifest {
+    fn to_json(&self) -> Result<String> {
+        let versioned = Versioned {
+            version: VERSION,
+            contents: self,
+        };
+        Ok(serde_json::to_string(&versioned)? + "\n")
+    }
+}
+
+/// parse a snapshot or manifest, checking its layout version first
+fn parse<T: DeserializeOwned>(contents: &str) -> Result<T> {
+    #[derive(Deserialize)]
+    struct Header {
+        version: i64,
+    }
+
+    let Header { version } = serde_json::from_str(contents)?;
+    if version != VERSION {
+        bail!("unsupported version {version} (this zzz reads version {VERSION})");
+    }
+    Ok(serde_json::from_str(contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, sha256: Option<&str>) -> Record {
+        Record {
+            path: path.to_string(),
+            kind: if sha256.is_none() {
+                RecordKind::Directory
+            } else {
+                RecordKind::File
+            },
+            size: 12,
+            mtime: 1_700_000_000,
+            mtime_nsec: 123_456_789,
+            inode: 42,
+            mode: Some(0o644),
+            sha256: sha256.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot = Snapshot {
+            level: 3,
+            records: vec![
+                record("docs", None),
+                record("docs/\"quoted\" \\ tab\t.txt", Some("ab12")),
+                record("docs/ünïcode ✓.txt", Some("cd34")),
+            ],
+        };
+        let json = snapshot.to_json().unwrap();
+        assert_eq!(parse::<Snapshot>(&json).unwrap(), snapshot);
+        // one line per entry keeps snapshots diffable
+        assert_eq!(json.lines().count(), 9);
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let manifest = Manifest {
+            level: 1,
+            deleted: vec!["docs/old.txt".to_string(), "docs/gone".to_string()],
+        };
+        assert_eq!(
+            parse::<Manifest>(&manifest.to_json().unwrap()).unwrap(),
+            manifest
+        );
+
+        let empty = Manifest {
+            level: 0,
+            deleted: Vec::new(),
+        };
+        assert_eq!(parse::<Manifest>(&empty.to_json().unwrap()).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_reads_snapshots_without_modes() {
+        let json = r#"{"version": 1, "level": 0, "entries": [
+            {"path": "a.txt", "type": "file", "size": 12, "mtime": 1700000000, "mtime_nsec": 0, "inode": 42, "sha256": "ab12"}
+        ]}"#;
+        let snapshot = parse::<Snapshot>(json).unwrap();
+        assert_eq!(snapshot.records[0].mode, None);
+        assert_eq!(snapshot.records[0].kind, RecordKind::File);
+    }
+
+    #[test]
+    fn test_restore_with_stripped_components() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let data = temp_dir.path().join("data");
+        std::fs::create_dir(&data)?;
+        std::fs::write(data.join("notes.txt"), "hello")?;
+        let archive = temp_dir.path().join("full.tar.zst");
+        let options = CompressionOptions {
+            snapshot: Some(temp_dir.path().join("state.json")),
+            ..Default::default()
+        };
+        crate::compress::compress(
+            &data,
+            &archive,
+            options,
+            FileFilter::new(false, &[])?,
+            false,
+            false,
+            None,
+        )?;
+
+        let restored = temp_dir.path().join("restored");
+        let options = ExtractionOptions {
+            strip_components: 1,
+            ..Default::default()
+        };
+        restore(&[archive], &restored, &options, false)?;
+        assert_eq!(
+            std::fs::read_to_string(restored.join("notes.txt"))?,
+            "hello"
+        );
+        assert!(!restored.join(MANIFEST_NAME).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        let err = parse::<Manifest>(r#"{"version": 2, "level": 0, "deleted": []}"#).unwrap_err();
+        assert!(err.to_string().contains("unsupported version 2"));
+    }
+}
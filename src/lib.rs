@@ -1,18 +1,24 @@
 //! zzz - A simple, fast compression multitool
 //!
 //! This library provides functionality for creating and extracting archives in multiple
-//! formats (zst, tgz, txz, zip, 7z) with smart file filtering and security features.
+//! formats (zst, tgz, txz, zip, 7z, tar, tbz2, lz4, lzma, br, zzd) with smart file filtering and security features.
 
 pub mod cli;
 pub mod compress;
+pub mod config;
+pub mod convert;
+pub mod dict;
 pub mod encryption;
 pub mod error;
 pub mod extract;
 pub mod filter;
 pub mod formats;
+pub mod incremental;
 pub mod list;
+pub mod modify;
 pub mod progress;
 pub mod utils;
+pub mod volumes;
 
 // re-export main types for convenience
 pub use error::Result;
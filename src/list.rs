@@ -1,14 +1,15 @@
 //! archive listing functionality
 
 use crate::formats::{
-    gz::GzipFormat, rar::RarFormat, sevenz::SevenZFormat, xz::XzFormat, zip::ZipFormat,
-    zstd::ZstdFormat, CompressionFormat, Format,
+    br::BrotliFormat, bz2::Bzip2Format, dedup::DedupFormat, gz::GzipFormat, lz4::Lz4Format,
+    lzma::LzmaFormat, rar::RarFormat, sevenz::SevenZFormat, tar::TarFormat, tarball, xz::XzFormat,
+    zip::ZipFormat, zstd::ZstdFormat, CompressionFormat, ExtractionOptions, Format,
 };
 use crate::Result;
 use std::path::Path;
 
 /// list contents of an archive using auto-detected format
-pub fn list(archive_path: &Path, verbose: bool) -> Result<()> {
+pub fn list(archive_path: &Path, options: &ExtractionOptions, verbose: bool) -> Result<()> {
     if verbose {
         println!("listing contents of {}", archive_path.display());
     }
@@ -22,14 +23,34 @@ pub fn list(archive_path: &Path, verbose: bool) -> Result<()> {
 
     // dispatch to appropriate format implementation
     let entries = match format {
-        Format::Zstd => ZstdFormat::list(archive_path)?,
-        Format::Gzip => GzipFormat::list(archive_path)?,
-        Format::Xz => XzFormat::list(archive_path)?,
-        Format::Zip => ZipFormat::list(archive_path)?,
-        Format::SevenZ => SevenZFormat::list(archive_path)?,
-        Format::Rar => RarFormat::list(archive_path)?,
+        Format::Zstd => ZstdFormat::list(archive_path, options)?,
+        Format::Gzip => GzipFormat::list(archive_path, options)?,
+        Format::Xz => XzFormat::list(archive_path, options)?,
+        Format::Zip => ZipFormat::list(archive_path, options)?,
+        Format::SevenZ => SevenZFormat::list(archive_path, options)?,
+        Format::Rar => RarFormat::list(archive_path, options)?,
+        Format::Tar => TarFormat::list(archive_path, options)?,
+        Format::Bzip2 => Bzip2Format::list(archive_path, options)?,
+        Format::Lz4 => Lz4Format::list(archive_path, options)?,
+        Format::Lzma => LzmaFormat::list(archive_path, options)?,
+        Format::Brotli => BrotliFormat::list(archive_path, options)?,
+        Format::Dedup => DedupFormat::list(archive_path, options)?,
     };
 
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| crate::utils::entry_selected(&entry.path, &options.entries))
+        .collect();
+    if entries.is_empty() && !options.entries.is_empty() {
+        return Err(tarball::no_matching_entries(&options.entries));
+    }
+
+    // only formats that record a method per entry get the extra column
+    let method_width = entries
+        .iter()
+        .filter_map(|entry| entry.method.as_ref().map(String::len))
+        .max();
+
     for entry in entries {
         if verbose {
             // detailed listing with sizes
@@ -38,7 +59,12 @@ pub fn list(archive_path: &Path, verbose: bool) -> Result<()> {
             } else {
                 "dir".to_string()
             };
-            println!("{:>10} {}", size_str, entry.path);
+            if let Some(width) = method_width {
+                let method = entry.method.as_deref().unwrap_or("-");
+                println!("{:>10} {:<width$} {}", size_str, method, entry.path);
+            } else {
+                println!("{:>10} {}", size_str, entry.path);
+            }
         } else {
             // simple listing
             println!("{}", entry.path);
@@ -1,13 +1,19 @@
 //! zzz - simple, fast compression multitool
 
 use clap::Parser;
+use std::path::PathBuf;
 use std::process;
 use zzz_arc::{
-    cli::{Cli, Commands},
-    compress, extract,
-    filter::FileFilter,
-    formats::{CompressionFormat, CompressionOptions, ExtractionOptions},
-    list,
+    cli::{AddArgs, Cli, Commands, ConfigAction, DictAction},
+    compress,
+    config::{CompressDefaults, Config, ExtractDefaults},
+    convert, dict, extract,
+    formats::{
+        br::BrotliFormat, bz2::Bzip2Format, gz::GzipFormat, lz4::Lz4Format, lzma::LzmaFormat,
+        rar::RarFormat, sevenz::SevenZFormat, tar::TarFormat, xz::XzFormat, zip::ZipFormat,
+        zstd::ZstdFormat, CompressionFormat, CompressionOptions, ExtractionOptions, Format,
+    },
+    incremental, list, modify,
 };
 
 fn main() {
@@ -20,13 +26,20 @@ fn main() {
 }
 
 fn run(cli: Cli) -> zzz_arc::Result<()> {
+    let config = if cli.no_config {
+        Config::default()
+    } else {
+        Config::load(cli.profile.as_deref())?
+    };
+
     match cli.command {
         Commands::Compress {
             input,
             output,
             level,
             progress,
-            mut exclude,
+            exclude,
+            exclude_from,
             keep_xattrs,
             keep_permissions,
             keep_ownership,
@@ -34,21 +47,93 @@ fn run(cli: Cli) -> zzz_arc::Result<()> {
             allow_symlink_escape,
             redact,
             strip_timestamps,
+            mtime_clamp,
+            zip_method,
+            no_auto_store,
+            long,
+            window_log,
+            strategy,
+            target_block_size,
+            checksum,
+            ultra,
+            dict,
+            seekable,
+            xz_block_size,
+            sevenz_method,
+            solid,
+            solid_block_size,
+            split_size,
+            snapshot,
             no_default_excludes,
             format,
             overwrite,
             password,
+            encrypt_headers,
         } => {
-            let output_path = Cli::get_output_path(&input, output, format);
+            // command line flags take precedence over config files and profiles
+            let settings = config.compress.overlay(&CompressDefaults {
+                format: None,
+                level,
+                threads: cli.threads,
+                exclude,
+                exclude_from,
+                progress,
+                keep_xattrs,
+                keep_permissions,
+                keep_ownership,
+                follow_symlinks,
+                allow_symlink_escape,
+                redact,
+                strip_timestamps,
+                mtime_clamp,
+                zip_method: zip_method.map(|method| method.name().to_string()),
+                no_auto_store,
+                long,
+                window_log,
+                strategy: strategy.map(|strategy| strategy.name().to_string()),
+                target_block_size: target_block_size.map(|size| size.to_string()),
+                checksum,
+                ultra,
+                dict,
+                seekable: seekable.map(|size| size.to_string()),
+                xz_block_size: xz_block_size.map(|size| size.to_string()),
+                sevenz_method: sevenz_method.map(|method| method.name().to_string()),
+                solid,
+                solid_block_size: solid_block_size.map(|size| size.to_string()),
+                split_size: split_size.map(|size| size.to_string()),
+                no_default_excludes,
+                overwrite,
+            });
+            let format = match format {
+                Some(format) => Some(format),
+                None => settings.output_format(output.as_deref())?,
+            };
+            let options = CompressionOptions {
+                password,
+                snapshot,
+                sevenz_encrypt_headers: encrypt_headers,
+                ..settings.compression_options()?
+            };
+            let filter = settings.file_filter()?;
 
-            if allow_symlink_escape && !follow_symlinks {
-                return Err(anyhow::anyhow!(
-                    "--allow-symlink-escape requires --follow-symlinks"
-                ));
+            let output_path = Cli::get_output_path(&input, output, format);
+            let output_format = format.or_else(|| Format::from_extension(&output_path));
+            if let Some(output_format) = output_format.filter(|f| !f.has_levels()) {
+                if settings.level.is_some() {
+                    eprintln!(
+                        "warning: {} has no compression levels; --level is ignored",
+                        output_format.name()
+                    );
+                }
             }
 
             // check if output already exists and prompt user
-            if output_path.exists() && !overwrite {
+            let first_volume = options
+                .split_size
+                .map(|_| zzz_arc::volumes::split_volume_path(&output_path, 1));
+            if (output_path.exists() || first_volume.is_some_and(|volume| volume.exists()))
+                && !settings.overwrite.unwrap_or_default()
+            {
                 let prompt_message = format!(
                     "output file '{}' already exists. overwrite?",
                     output_path.display()
@@ -59,51 +144,37 @@ fn run(cli: Cli) -> zzz_arc::Result<()> {
                 }
             }
 
-            let mut options = CompressionOptions {
-                level,
-                threads: cli.threads,
-                password,
-                normalize_permissions: !keep_permissions,
-                normalize_ownership: !keep_ownership,
-                strip_xattrs: !keep_xattrs,
-                strip_timestamps,
-                follow_symlinks,
-                allow_symlink_escape,
-                ..Default::default()
-            };
-            if redact {
-                options.normalize_permissions = true;
-                options.normalize_ownership = true;
-                options.strip_xattrs = true;
-                options.strip_timestamps = true;
-                options.deterministic = true;
-                exclude.extend(
-                    zzz_arc::filter::SENSITIVE_FILES
-                        .iter()
-                        .map(|pattern| (*pattern).to_string()),
-                );
-            }
-
-            let filter = FileFilter::new(!no_default_excludes, &exclude)?;
-
             let stats = compress::compress(
                 &input,
                 &output_path,
                 options,
                 filter,
-                progress,
+                settings.progress.unwrap_or_default(),
                 cli.verbose,
                 format,
             )?;
 
             if !cli.verbose {
+                let output = match stats.volumes.as_slice() {
+                    [] => output_path.display().to_string(),
+                    [volume] => volume.display().to_string(),
+                    [first, .., last] => format!(
+                        "{} … {} in {} volumes",
+                        first.display(),
+                        last.display(),
+                        stats.volumes.len()
+                    ),
+                };
                 println!(
                     "compressed {} ({}) -> {} ({})",
                     input.display(),
                     zzz_arc::utils::format_bytes(stats.input_size),
-                    output_path.display(),
+                    output,
                     zzz_arc::utils::format_bytes(stats.output_size)
                 );
+                if let Some(dedup) = &stats.dedup {
+                    println!("{dedup}");
+                }
             }
         }
 
@@ -119,54 +190,368 @@ fn run(cli: Cli) -> zzz_arc::Result<()> {
             keep_ownership,
             overwrite,
             password,
+            tar,
+            raw,
+            dict,
+            entries,
         } => {
+            let settings = config.extract.overlay(&ExtractDefaults {
+                progress,
+                strip_components,
+                keep_xattrs,
+                strip_timestamps,
+                keep_permissions,
+                keep_ownership,
+                overwrite,
+            });
             let extract_dir = Cli::get_extract_dir(destination, directory);
 
             let options = ExtractionOptions {
-                overwrite,
-                strip_components,
-                strip_xattrs: !keep_xattrs,
-                strip_timestamps,
-                preserve_permissions: keep_permissions,
-                preserve_ownership: keep_ownership,
                 password,
+                payload: Cli::stream_payload(tar, raw),
+                zstd_dict: dict,
+                entries,
+                threads: cli.threads.unwrap_or(0),
+                ..settings.extraction_options()
             };
 
-            extract::extract(&archive, &extract_dir, options, progress, cli.verbose)?;
+            extract::extract(
+                &archive,
+                &extract_dir,
+                options,
+                settings.progress.unwrap_or_default(),
+                cli.verbose,
+            )?;
         }
 
-        Commands::List { archive } => {
-            list::list(&archive, cli.verbose)?;
+        Commands::Restore {
+            archives,
+            directory,
+            keep_permissions,
+            keep_ownership,
+            password,
+        } => {
+            let settings = config.extract.overlay(&ExtractDefaults {
+                keep_permissions,
+                keep_ownership,
+                ..Default::default()
+            });
+            let options = ExtractionOptions {
+                preserve_permissions: settings.keep_permissions.unwrap_or_default(),
+                preserve_ownership: settings.keep_ownership.unwrap_or_default(),
+                password,
+                threads: cli.threads.unwrap_or(0),
+                ..Default::default()
+            };
+            let stats = incremental::restore(&archives, &directory, &options, cli.verbose)?;
+            println!(
+                "restored {} archives into {} ({} paths deleted)",
+                stats.archives,
+                directory.display(),
+                stats.deleted
+            );
+        }
+
+        Commands::List {
+            archive,
+            tar,
+            raw,
+            dict,
+            entries,
+            password,
+        } => {
+            let options = ExtractionOptions {
+                password,
+                payload: Cli::stream_payload(tar, raw),
+                zstd_dict: dict,
+                entries,
+                threads: cli.threads.unwrap_or(0),
+                ..ExtractionOptions::default()
+            };
+            list::list(&archive, &options, cli.verbose)?;
         }
 
-        Commands::Test { archive } => {
+        Commands::Test {
+            archive,
+            tar,
+            raw,
+            dict,
+            password,
+        } => {
+            let options = ExtractionOptions {
+                password,
+                payload: Cli::stream_payload(tar, raw),
+                zstd_dict: dict,
+                threads: cli.threads.unwrap_or(0),
+                ..ExtractionOptions::default()
+            };
+
             // Detect format and test integrity
-            let format = zzz_arc::formats::Format::detect(&archive)?;
+            let format = Format::detect(&archive)?;
 
+            let mut dedup = None;
             match format {
-                zzz_arc::formats::Format::Zip => {
-                    zzz_arc::formats::zip::ZipFormat::test_integrity(&archive)?
-                }
-                zzz_arc::formats::Format::SevenZ => {
-                    zzz_arc::formats::sevenz::SevenZFormat::test_integrity(&archive)?
-                }
-                zzz_arc::formats::Format::Gzip => {
-                    zzz_arc::formats::gz::GzipFormat::test_integrity(&archive)?
-                }
-                zzz_arc::formats::Format::Xz => {
-                    zzz_arc::formats::xz::XzFormat::test_integrity(&archive)?
-                }
-                zzz_arc::formats::Format::Zstd => {
-                    zzz_arc::formats::zstd::ZstdFormat::test_integrity(&archive)?
-                }
-                zzz_arc::formats::Format::Rar => {
-                    zzz_arc::formats::rar::RarFormat::test_integrity(&archive)?
+                Format::Zip => ZipFormat::test_integrity(&archive, &options)?,
+                Format::SevenZ => SevenZFormat::test_integrity(&archive, &options)?,
+                Format::Gzip => GzipFormat::test_integrity(&archive, &options)?,
+                Format::Xz => XzFormat::test_integrity(&archive, &options)?,
+                Format::Zstd => ZstdFormat::test_integrity(&archive, &options)?,
+                Format::Rar => RarFormat::test_integrity(&archive, &options)?,
+                Format::Tar => TarFormat::test_integrity(&archive, &options)?,
+                Format::Bzip2 => Bzip2Format::test_integrity(&archive, &options)?,
+                Format::Lz4 => Lz4Format::test_integrity(&archive, &options)?,
+                Format::Lzma => LzmaFormat::test_integrity(&archive, &options)?,
+                Format::Brotli => BrotliFormat::test_integrity(&archive, &options)?,
+                // dedup also reports how much its chunks were shared
+                Format::Dedup => {
+                    dedup = Some(zzz_arc::formats::dedup::test_archive(&archive, &options)?)
                 }
             }
 
             println!("{} integrity: OK", archive.display());
+            if let Some(dedup) = dedup {
+                println!("{dedup}");
+            }
         }
+
+        Commands::Convert {
+            input,
+            output,
+            format,
+            level,
+            exclude,
+            exclude_from,
+            no_default_excludes,
+            redact,
+            strip_timestamps,
+            overwrite,
+            password,
+            output_password,
+        } => {
+            let settings = config.compress.overlay(&CompressDefaults {
+                level,
+                threads: cli.threads,
+                exclude,
+                exclude_from,
+                no_default_excludes,
+                redact,
+                strip_timestamps,
+                overwrite,
+                ..Default::default()
+            });
+
+            if output.exists() && !settings.overwrite.unwrap_or_default() {
+                let prompt_message = format!(
+                    "output file '{}' already exists. overwrite?",
+                    output.display()
+                );
+                if !zzz_arc::utils::prompt_yes_no(&prompt_message) {
+                    println!("operation cancelled");
+                    return Ok(());
+                }
+            }
+
+            // entries keep the metadata the source recorded unless redacting
+            let redact = settings.redact.unwrap_or_default();
+            let options = CompressionOptions {
+                normalize_permissions: redact,
+                normalize_ownership: redact,
+                password: output_password,
+                // a configured split size is for `compress`; converted archives are one file
+                split_size: None,
+                ..settings.compression_options()?
+            };
+            let extract_options = ExtractionOptions {
+                password,
+                threads: cli.threads.unwrap_or(0),
+                ..Default::default()
+            };
+            let filter = settings.file_filter()?;
+
+            let stats = convert::convert(
+                &input,
+                &output,
+                &options,
+                &extract_options,
+                &filter,
+                format,
+                cli.verbose,
+            )?;
+
+            println!(
+                "converted {} ({}) -> {} ({}), {} entries",
+                input.display(),
+                zzz_arc::utils::format_bytes(stats.input_size),
+                output.display(),
+                zzz_arc::utils::format_bytes(stats.output_size),
+                stats.entries
+            );
+        }
+
+        Commands::Add(args) => add(args, false, &config, cli.threads, cli.verbose)?,
+
+        Commands::Update(args) => add(args, true, &config, cli.threads, cli.verbose)?,
+
+        Commands::Remove {
+            archive,
+            patterns,
+            level,
+            password,
+            dict,
+        } => {
+            let (options, extract_options) =
+                rewrite_options(level, password, dict, &config, cli.threads);
+            let removed =
+                modify::remove(&archive, &patterns, &options, &extract_options, cli.verbose)?;
+            println!("{}: removed {} entries", archive.display(), removed);
+        }
+
+        Commands::Rename {
+            archive,
+            from,
+            to,
+            level,
+            password,
+            dict,
+        } => {
+            let (options, extract_options) =
+                rewrite_options(level, password, dict, &config, cli.threads);
+            let renamed = modify::rename(
+                &archive,
+                &from,
+                &to,
+                &options,
+                &extract_options,
+                cli.verbose,
+            )?;
+            println!("{}: renamed {} entries", archive.display(), renamed);
+        }
+
+        Commands::CheckIgnore {
+            paths,
+            exclude,
+            exclude_from,
+            redact,
+            no_default_excludes,
+        } => {
+            let filter = config
+                .compress
+                .overlay(&CompressDefaults {
+                    exclude,
+                    exclude_from,
+                    redact,
+                    no_default_excludes,
+                    ..Default::default()
+                })
+                .file_filter()?;
+
+            for path in paths {
+                match filter.explain_relative(&path) {
+                    Some(rule) => println!("{}: excluded by {}", path.display(), rule),
+                    None if cli.verbose => println!("{}: included", path.display()),
+                    None => {}
+                }
+            }
+        }
+
+        Commands::Dict { action } => match action {
+            DictAction::Train {
+                samples,
+                output,
+                max_size,
+            } => dict::train(&samples, &output, max_size, cli.verbose)?,
+        },
+
+        Commands::Config { action } => match action {
+            ConfigAction::Show => print!("{}", config.render()?),
+        },
     }
 
     Ok(())
 }
+
+/// add files to an existing archive; `only_newer` replaces only outdated entries
+fn add(
+    args: AddArgs,
+    only_newer: bool,
+    config: &Config,
+    threads: Option<u32>,
+    verbose: bool,
+) -> zzz_arc::Result<()> {
+    let settings = config.compress.overlay(&CompressDefaults {
+        level: args.level,
+        threads,
+        exclude: args.exclude,
+        exclude_from: args.exclude_from,
+        keep_permissions: args.keep_permissions,
+        keep_ownership: args.keep_ownership,
+        follow_symlinks: args.follow_symlinks,
+        allow_symlink_escape: args.allow_symlink_escape,
+        redact: args.redact,
+        strip_timestamps: args.strip_timestamps,
+        mtime_clamp: args.mtime_clamp,
+        zip_method: args.zip_method.map(|method| method.name().to_string()),
+        no_auto_store: args.no_auto_store,
+        no_default_excludes: args.no_default_excludes,
+        ..Default::default()
+    });
+    let options = CompressionOptions {
+        password: args.password.clone(),
+        zstd_dict: args.dict.clone(),
+        // the archive is modified as one file, never split
+        split_size: None,
+        ..settings.compression_options()?
+    };
+    let extract_options = ExtractionOptions {
+        password: args.password,
+        zstd_dict: args.dict,
+        threads: threads.unwrap_or(0),
+        ..Default::default()
+    };
+    let filter = settings.file_filter()?;
+
+    let stats = modify::add(
+        &args.archive,
+        &args.paths,
+        args.directory.as_deref(),
+        &options,
+        &extract_options,
+        &filter,
+        only_newer,
+        verbose,
+    )?;
+    println!(
+        "{}: {} added, {} replaced",
+        args.archive.display(),
+        stats.added,
+        stats.replaced
+    );
+    Ok(())
+}
+
+/// options for rewriting an archive in place, reading and writing it with the same password
+fn rewrite_options(
+    level: Option<i32>,
+    password: Option<String>,
+    dict: Option<PathBuf>,
+    config: &Config,
+    threads: Option<u32>,
+) -> (CompressionOptions, ExtractionOptions) {
+    let defaults = &config.compress;
+    let options = CompressionOptions {
+        level: level
+            .or(defaults.level)
+            .unwrap_or(CompressionOptions::default().level),
+        threads: threads.or(defaults.threads).unwrap_or(0),
+        password: password.clone(),
+        zstd_dict: dict.clone(),
+        ..Default::default()
+    };
+    let extract_options = ExtractionOptions {
+        password,
+        threads: threads.unwrap_or(0),
+        zstd_dict: dict,
+        ..Default::default()
+    };
+    (options, extract_options)
+}
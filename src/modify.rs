@@ -0,0 +1,865 @@
+//! adding, removing and renaming entries of existing archives
+//!
+//! every change is written to a temporary file next to the archive, which replaces it once
+//! complete. zip and plain tar archives are copied and appended to; everything else is
+//! rewritten, copying the existing entries as they are.
+
+use crate::convert::{Entry, EntryKind, SevenZSink, Sink, TarSink, ZipSink};
+use crate::filter::FileFilter;
+use crate::formats::{
+    dedup, sevenz, tarball, zip as zip_format, zstd as zstd_format, CompressionOptions,
+    ExtractionOptions, FinishWrite, Format, StreamPayload,
+};
+use crate::progress::Progress;
+use crate::volumes::{self, VolumeSet};
+use crate::{compress, utils, Result};
+use anyhow::{bail, Context};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
+use zip::{ZipArchive, ZipWriter};
+
+/// what an add or update changed
+#[derive(Debug, Default)]
+pub struct ModifyStats {
+    pub added: u64,
+    pub replaced: u64,
+}
+
+/// a file or directory on disk and the name it gets in the archive
+pub(crate) struct NewEntry {
+    pub(crate) source: PathBuf,
+    pub(crate) name: String,
+    pub(crate) metadata: std::fs::Metadata,
+}
+
+/// one tarball member, with the GNU long-name and pax headers written in front of it
+struct Member {
+    path: String,
+    /// target of a link, resolved the same way as the path
+    link: Option<String>,
+    mtime: Option<u64>,
+    prefix: Vec<(tar::Header, Vec<u8>)>,
+}
+
+/// the name an existing entry keeps when the archive is rewritten, or `None` to drop it
+pub(crate) type Rename<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// add `inputs` to an existing archive, replacing entries of the same name
+///
+/// entries are named by the paths as given, which are read from `directory` when it is set.
+/// with `only_newer`, archived files are only replaced by files modified after them.
+/// directories already in the archive are left alone.
+#[allow(clippy::too_many_arguments)]
+pub fn add(
+    archive_path: &Path,
+    inputs: &[PathBuf],
+    directory: Option<&Path>,
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+    filter: &FileFilter,
+    only_newer: bool,
+    verbose: bool,
+) -> Result<ModifyStats> {
+    let format = modifiable_format(archive_path, options)?;
+    let base = directory.unwrap_or(Path::new(""));
+    let sources: Vec<PathBuf> = inputs.iter().map(|input| base.join(input)).collect();
+    for source in &sources {
+        compress::ensure_output_outside_input(source, archive_path)?;
+    }
+
+    let archived = archived_mtimes(format, archive_path, extract_options)?;
+    // zip stores times in 2-second steps
+    let resolution = if format == Format::Zip { 2 } else { 1 };
+
+    let mut stats = ModifyStats::default();
+    let mut replaced = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    let mut collected = Vec::new();
+    for source in &sources {
+        collect_input(source, base, options, filter, &mut collected)?;
+    }
+    for entry in collected {
+        if !seen.insert(entry.name.clone()) {
+            continue;
+        }
+        match archived.get(&entry.name) {
+            None => stats.added += 1,
+            Some(_) if entry.metadata.is_dir() => continue,
+            Some(&archived_mtime) => {
+                if only_newer && !is_newer(&entry.metadata, archived_mtime, resolution) {
+                    continue;
+                }
+                replaced.insert(entry.name.clone());
+                stats.replaced += 1;
+            }
+        }
+        if verbose {
+            let action = if replaced.contains(&entry.name) {
+                "replacing"
+            } else {
+                "adding"
+            };
+            println!("  {action}: {}", entry.name);
+        }
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return Ok(stats);
+    }
+
+    match format {
+        Format::Zip if replaced.is_empty() => append_zip(archive_path, &entries, options)?,
+        Format::Tar if replaced.is_empty() => append_tar(archive_path, &entries, options)?,
+        _ => rewrite_archive(
+            format,
+            archive_path,
+            &|name| (!replaced.contains(name)).then(|| name.to_string()),
+            &entries,
+            options,
+            extract_options,
+        )?,
+    }
+    Ok(stats)
+}
+
+/// delete every entry matching one of `patterns`, returning how many were removed
+///
+/// patterns follow the exclude rules: `.env` matches at any depth and a directory takes
+/// everything below it along.
+pub fn remove(
+    archive_path: &Path,
+    patterns: &[String],
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+    verbose: bool,
+) -> Result<u64> {
+    let format = modifiable_format(archive_path, options)?;
+    let matcher = FileFilter::new(false, patterns)?;
+    let removed = |name: &str| matcher.should_exclude_relative(Path::new(name));
+
+    let mut names: Vec<_> = archived_mtimes(format, archive_path, extract_options)?
+        .into_keys()
+        .filter(|name| removed(name))
+        .collect();
+    if names.is_empty() {
+        return Err(tarball::no_matching_entries(patterns));
+    }
+    if verbose {
+        names.sort();
+        for name in &names {
+            println!("  deleting: {name}");
+        }
+    }
+
+    rewrite_archive(
+        format,
+        archive_path,
+        &|name| (!removed(name)).then(|| name.to_string()),
+        &[],
+        options,
+        extract_options,
+    )?;
+    Ok(names.len() as u64)
+}
+
+/// rename the entry `from`, and everything below it when it is a directory, to `to`
+pub fn rename(
+    archive_path: &Path,
+    from: &str,
+    to: &str,
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+    verbose: bool,
+) -> Result<u64> {
+    let format = modifiable_format(archive_path, options)?;
+    let (from, to) = (from.trim_end_matches('/'), to.trim_end_matches('/'));
+    if to.is_empty() {
+        bail!("the new name cannot be empty");
+    }
+    let moved = |name: &str| {
+        if name == from {
+            Some(to.to_string())
+        } else {
+            let rest = name.strip_prefix(from)?.strip_prefix('/')?;
+            Some(format!("{to}/{rest}"))
+        }
+    };
+
+    let archived = archived_mtimes(format, archive_path, extract_options)?;
+    let mut renamed: Vec<_> = archived
+        .keys()
+        .filter_map(|name| Some((name.as_str(), moved(name)?)))
+        .collect();
+    if renamed.is_empty() {
+        return Err(tarball::no_matching_entries(&[from.to_string()]));
+    }
+    renamed.sort();
+    for (name, new_name) in &renamed {
+        if archived.contains_key(new_name) && moved(new_name).is_none() {
+            bail!("'{new_name}' already exists in the archive");
+        }
+        if verbose {
+            println!("  renaming: {name} -> {new_name}");
+        }
+    }
+
+    rewrite_archive(
+        format,
+        archive_path,
+        &|name| Some(moved(name).unwrap_or_else(|| name.to_string())),
+        &[],
+        options,
+        extract_options,
+    )?;
+    Ok(renamed.len() as u64)
+}
+
+/// the format of an archive that can be modified with the given options
+fn modifiable_format(archive_path: &Path, options: &CompressionOptions) -> Result<Format> {
+    let format = Format::detect(archive_path)?;
+    if VolumeSet::detect(archive_path)?.is_some() {
+        bail!(
+            "cannot modify multi-volume archive '{}'",
+            archive_path.display()
+        );
+    }
+    match format {
+        Format::Rar => bail!("RAR archives cannot be modified"),
+        Format::Zstd | Format::SevenZ | Format::Dedup => {}
+        _ if options.password.is_some() => bail!(
+            "Password protection is not supported for {} format. Use 7z format for password protection.",
+            format.name()
+        ),
+        _ => {}
+    }
+    Ok(format)
+}
+
+/// rewrite the archive with its entries renamed or dropped, followed by `entries`
+fn rewrite_archive(
+    format: Format,
+    archive_path: &Path,
+    rename: Rename,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+) -> Result<()> {
+    // the rewritten archive is encrypted exactly when the original was
+    let mut options = options.clone();
+    if !format.is_encrypted(archive_path)? {
+        options.password = None;
+    }
+    // and keeps its seek table, tar index and dictionary
+    if format == Format::Zstd {
+        options.zstd_seekable = zstd_format::seekable_frame_size(archive_path)?;
+        let password = extract_options.password.as_deref();
+        options.zstd_dict = match zstd_format::dictionary_id(archive_path, password)? {
+            Some(id) => Some(extract_options.zstd_dict.clone().with_context(|| {
+                format!(
+                    "archive '{}' was compressed with zstd dictionary ID {id}; pass it with --dict",
+                    archive_path.display()
+                )
+            })?),
+            None => None,
+        };
+    }
+
+    match format {
+        Format::Zip => rewrite(archive_path, |output| {
+            rewrite_zip(archive_path, output, rename, entries, &options)
+        }),
+        Format::Dedup => rewrite(archive_path, |output| {
+            dedup::rewrite(
+                archive_path,
+                output,
+                rename,
+                entries,
+                &options,
+                extract_options,
+            )
+        }),
+        Format::SevenZ => {
+            // hidden names stay hidden, or the rewrite fails
+            options.sevenz_encrypt_headers =
+                options.password.is_some() && sevenz::open_reader(archive_path, None).is_err();
+            rewrite(archive_path, |output| {
+                rewrite_sevenz(
+                    archive_path,
+                    output,
+                    rename,
+                    entries,
+                    &options,
+                    extract_options,
+                )
+            })
+        }
+        _ => rewrite(archive_path, |output| {
+            rewrite_tarball(
+                format,
+                archive_path,
+                output,
+                rename,
+                entries,
+                &options,
+                extract_options,
+            )
+        }),
+    }
+}
+
+/// whether a file on disk changed after the archived copy, within the archive's resolution
+fn is_newer(metadata: &std::fs::Metadata, archived: Option<SystemTime>, resolution: u64) -> bool {
+    let Some(archived) = archived else {
+        // nothing to compare against
+        return true;
+    };
+    let seconds = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs())
+    };
+    metadata
+        .modified()
+        .is_ok_and(|modified| seconds(modified) >= seconds(archived) + resolution)
+}
+
+/// names already in the archive (without a trailing slash) and their modification times
+fn archived_mtimes(
+    format: Format,
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<HashMap<String, Option<SystemTime>>> {
+    let mut names = HashMap::new();
+    match format {
+        Format::Zip => {
+            let mut archive = open_zip(archive_path)?;
+            for i in 0..archive.len() {
+                let file = archive.by_index_raw(i)?;
+                let mtime = file
+                    .last_modified()
+                    .and_then(|dt| OffsetDateTime::try_from(dt).ok())
+                    .map(SystemTime::from);
+                names.insert(file.name().trim_end_matches('/').to_string(), mtime);
+            }
+        }
+        Format::SevenZ => {
+            let reader = sevenz::open_reader(archive_path, options.password.as_deref())?;
+            for entry in &reader.archive().files {
+                let mtime = entry
+                    .has_last_modified_date
+                    .then(|| SystemTime::from(entry.last_modified_date));
+                names.insert(entry.name.replace('\\', "/"), mtime);
+            }
+        }
+        Format::Dedup => {
+            dedup::read_entries(archive_path, options, |entry, _| {
+                names.insert(entry.path.trim_end_matches('/').to_string(), entry.mtime);
+                Ok(())
+            })?;
+        }
+        _ => {
+            let reader = open_tarball(format, archive_path, options)?;
+            walk_tarball(reader, |member, _| {
+                let mtime = member
+                    .mtime
+                    .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime));
+                names.insert(member.path.clone(), mtime);
+                Ok(())
+            })?;
+        }
+    }
+    Ok(names)
+}
+
+/// files and directories below each input, named relative to the input's parent
+pub(crate) fn collect_inputs(
+    inputs: &[PathBuf],
+    options: &CompressionOptions,
+    filter: &FileFilter,
+) -> Result<Vec<NewEntry>> {
+    let mut entries = Vec::new();
+    for input in inputs {
+        // named from the input itself on, as `compress` names them
+        let base = match input.file_name() {
+            Some(_) => input.parent().unwrap_or(Path::new("")),
+            None => input.as_path(),
+        };
+        collect_input(input, base, options, filter, &mut entries)?;
+    }
+    Ok(entries)
+}
+
+/// the files at and below `input`, named by their path after `base`
+fn collect_input(
+    input: &Path,
+    base: &Path,
+    options: &CompressionOptions,
+    filter: &FileFilter,
+    entries: &mut Vec<NewEntry>,
+) -> Result<()> {
+    let canonical_root = if options.follow_symlinks && !options.allow_symlink_escape {
+        Some(
+            std::fs::canonicalize(input)
+                .with_context(|| format!("Failed to resolve input root '{}'", input.display()))?,
+        )
+    } else {
+        None
+    };
+
+    if input.is_file() {
+        let metadata = std::fs::symlink_metadata(input)
+            .with_context(|| format!("Failed to read metadata for {}", input.display()))?;
+        if metadata.file_type().is_symlink() {
+            if !options.follow_symlinks {
+                bail!(
+                    "symlink '{}' is not supported for archiving (use --follow-symlinks to include targets)",
+                    input.display()
+                );
+            }
+            if let Some(root) = &canonical_root {
+                utils::ensure_symlink_within_root(root, input)?;
+            }
+        }
+        let name = archive_name(input.strip_prefix(base).unwrap_or(input));
+        if filter.should_include_relative(Path::new(&name)) {
+            entries.push(NewEntry {
+                source: input.to_path_buf(),
+                name,
+                metadata: std::fs::metadata(input)?,
+            });
+        }
+        return Ok(());
+    }
+    if !input.is_dir() {
+        bail!("input path '{}' does not exist", input.display());
+    }
+
+    let mut walked = Vec::new();
+    for entry in filter.walk_entries_with_follow(input, options.follow_symlinks) {
+        let entry = entry?;
+        if entry.path_is_symlink() {
+            if !options.follow_symlinks {
+                bail!(
+                    "symlink '{}' is not supported for archiving (use --follow-symlinks to include targets)",
+                    entry.path().display()
+                );
+            }
+            if let Some(root) = &canonical_root {
+                utils::ensure_symlink_within_root(root, entry.path())?;
+            }
+        }
+        walked.push(entry);
+    }
+    if options.deterministic {
+        walked.sort_by(|a, b| a.path().cmp(b.path()));
+    }
+
+    for entry in walked {
+        let path = entry.path();
+        let name = archive_name(path.strip_prefix(base).unwrap_or(path));
+        if name.is_empty() || !(path.is_file() || path.is_dir()) {
+            continue;
+        }
+        entries.push(NewEntry {
+            source: path.to_path_buf(),
+            name,
+            metadata: entry.metadata()?,
+        });
+    }
+    Ok(())
+}
+
+/// the name of a path inside an archive; a leading `/`, `.` or `..` is dropped, as tar does
+fn archive_name(path: &Path) -> String {
+    let normal: PathBuf = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    utils::normalize_archive_path(&normal)
+}
+
+/// describe a file on disk the way the options ask it to be archived
+fn disk_entry(entry: &NewEntry, options: &CompressionOptions) -> Entry {
+    let metadata = &entry.metadata;
+    #[cfg(unix)]
+    let (mode, owner) = (
+        Some(metadata.permissions().mode() & 0o7777),
+        Some((u64::from(metadata.uid()), u64::from(metadata.gid()))),
+    );
+    #[cfg(not(unix))]
+    let (mode, owner) = (None, None);
+
+    Entry {
+        path: entry.name.clone(),
+        kind: if metadata.is_dir() {
+            EntryKind::Directory
+        } else {
+            EntryKind::File
+        },
+        size: metadata.is_file().then_some(metadata.len()),
+        mode: mode.filter(|_| !options.normalize_permissions),
+        mtime: metadata
+            .modified()
+            .ok()
+            .filter(|_| !options.strip_timestamps)
+            .map(|mtime| options.clamp_mtime(mtime)),
+        owner: owner.filter(|_| !options.normalize_ownership),
+    }
+}
+
+/// write the new entries through a sink, advancing `progress` by the bytes of each file
+pub(crate) fn add_entries(
+    sink: &mut dyn Sink,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+    progress: Option<&Progress>,
+) -> Result<()> {
+    let mut written = 0;
+    for entry in entries {
+        let archived = disk_entry(entry, options);
+        if entry.metadata.is_dir() {
+            sink.add(&archived, &mut std::io::empty())?;
+        } else {
+            let mut file = File::open(&entry.source).with_context(|| {
+                format!(
+                    "Failed to open file for archiving {}",
+                    entry.source.display()
+                )
+            })?;
+            sink.add(&archived, &mut file)?;
+            written += entry.metadata.len();
+            if let Some(progress) = progress {
+                progress.set_position(written);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_zip(archive_path: &Path) -> Result<ZipArchive<BufReader<File>>> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive file {}", archive_path.display()))?;
+    ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read ZIP archive from {}", archive_path.display()))
+}
+
+/// options for a zip that already holds entries from elsewhere
+///
+/// normalizing host fields would also rewrite the DOS attributes of the existing entries
+fn keep_host_fields(options: &CompressionOptions) -> CompressionOptions {
+    CompressionOptions {
+        deterministic: false,
+        ..options.clone()
+    }
+}
+
+/// append entries after the last one, rewriting only the central directory
+///
+/// the archive is copied first, so a failure part way leaves the original as it was
+fn append_zip(
+    archive_path: &Path,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+) -> Result<()> {
+    rewrite(archive_path, |output| {
+        let file = copy_for_update(archive_path, output)?;
+        let writer = ZipWriter::new_append(file).with_context(|| {
+            format!("Failed to read ZIP archive from {}", archive_path.display())
+        })?;
+        let options = &keep_host_fields(options);
+        let mut sink = ZipSink { writer, options };
+        add_entries(&mut sink, entries, options, None)?;
+        Box::new(sink).finish()
+    })
+}
+
+/// copy the compressed data of every entry that is kept, then write the new ones
+fn rewrite_zip(
+    archive_path: &Path,
+    output_path: &Path,
+    rename: Rename,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+) -> Result<()> {
+    let mut archive = open_zip(archive_path)?;
+    let file = volumes::create_zip(output_path, options.split_size)?;
+    let options = &keep_host_fields(options);
+    let mut sink = ZipSink {
+        writer: ZipWriter::new(BufWriter::new(file)),
+        options,
+    };
+    let host_fields = zip_format::host_fields(archive_path)?;
+    let mut copied = Vec::new();
+    for (i, fields) in host_fields.into_iter().enumerate() {
+        let file = archive.by_index_raw(i)?;
+        let name = file.name().trim_end_matches('/');
+        match rename(name) {
+            Some(new_name) if new_name == name => sink.writer.raw_copy_file(file)?,
+            Some(new_name) => {
+                let slash = if file.is_dir() { "/" } else { "" };
+                sink.writer
+                    .raw_copy_file_rename(file, format!("{new_name}{slash}"))?;
+            }
+            None => continue,
+        }
+        copied.push(fields);
+    }
+    add_entries(&mut sink, entries, options, None)?;
+    Box::new(sink).finish()?;
+    zip_format::restore_host_fields(output_path, &copied)
+}
+
+/// drop the end-of-archive blocks of a plain tarball and write the new members there
+///
+/// like zip appends, this works on a copy that replaces the original once it is complete
+fn append_tar(
+    archive_path: &Path,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+) -> Result<()> {
+    rewrite(archive_path, |output| {
+        let mut file = copy_for_update(archive_path, output)?;
+        let end = walk_tarball(BufReader::new(&file), |_, _| Ok(()))?;
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+
+        let writer: Box<dyn FinishWrite> = Box::new(BufWriter::new(file));
+        let mut sink = TarSink {
+            builder: tar::Builder::new(writer),
+        };
+        add_entries(&mut sink, entries, options, None)?;
+        Box::new(sink).finish()
+    })
+}
+
+/// copy the archive to `output` and open the copy for appending
+fn copy_for_update(archive_path: &Path, output: &Path) -> Result<File> {
+    std::fs::copy(archive_path, output)
+        .with_context(|| format!("Failed to copy archive {}", archive_path.display()))?;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(output)
+        .with_context(|| format!("Failed to open {}", output.display()))
+}
+
+fn open_tarball(
+    format: Format,
+    archive_path: &Path,
+    options: &ExtractionOptions,
+) -> Result<Box<dyn Read>> {
+    let (payload, reader) = format
+        .stream_reader(archive_path, options)?
+        .expect("single-stream format");
+    if payload == StreamPayload::Raw {
+        bail!(
+            "'{}' holds a single compressed file, not a tarball, so nothing can be added to it",
+            archive_path.display()
+        );
+    }
+    Ok(reader)
+}
+
+/// copy the members that are kept byte for byte, then add the new ones
+fn rewrite_tarball(
+    format: Format,
+    archive_path: &Path,
+    output_path: &Path,
+    rename: Rename,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+) -> Result<()> {
+    let reader = open_tarball(format, archive_path, extract_options)?;
+    let writer = format
+        .tar_writer(output_path, options)?
+        .expect("single-stream format");
+    let mut sink = TarSink {
+        builder: tar::Builder::new(writer),
+    };
+    walk_tarball(reader, |member, data| {
+        let Some(path) = rename(&member.path) else {
+            return Ok(());
+        };
+        let mut header = data.header().clone();
+        // hard links name another member, which may have moved too
+        let link = match (&member.link, header.entry_type()) {
+            (Some(link), tar::EntryType::Link) => rename(link).or_else(|| Some(link.clone())),
+            (link, _) => link.clone(),
+        };
+        if path == member.path && link == member.link {
+            for (header, contents) in &member.prefix {
+                sink.builder.append(header, contents.as_slice())?;
+            }
+            sink.builder.append(&header, data)?;
+            return Ok(());
+        }
+
+        // the names are written afresh; other pax records stay with the member
+        for (prefix_header, contents) in &member.prefix {
+            match prefix_header.entry_type() {
+                tar::EntryType::GNULongName | tar::EntryType::GNULongLink => {}
+                tar::EntryType::XHeader => {
+                    let records = tar::PaxExtensions::new(contents)
+                        .filter_map(|record| record.ok())
+                        .filter(|record| !matches!(record.key(), Ok("path" | "linkpath")))
+                        .filter_map(|record| Some((record.key().ok()?, record.value_bytes())))
+                        .collect::<Vec<_>>();
+                    if !records.is_empty() {
+                        sink.builder.append_pax_extensions(records)?;
+                    }
+                }
+                _ => sink.builder.append(prefix_header, contents.as_slice())?,
+            }
+        }
+        let path = if header.entry_type().is_dir() {
+            format!("{path}/")
+        } else {
+            path
+        };
+        match link {
+            Some(link) => sink.builder.append_link(&mut header, path, link)?,
+            None => sink.builder.append_data(&mut header, path, data)?,
+        }
+        Ok(())
+    })?;
+    add_entries(&mut sink, entries, options, None)?;
+    Box::new(sink).finish()
+}
+
+/// visit each member of a tarball, returning the offset where its end-of-archive blocks start
+///
+/// entries are read raw so the headers in front of a member can be copied along with it;
+/// its path is taken from them when present.
+fn walk_tarball<R: Read>(
+    reader: R,
+    mut visit: impl FnMut(&Member, &mut tar::Entry<R>) -> Result<()>,
+) -> Result<u64> {
+    let mut archive = tar::Archive::new(reader);
+    let mut prefix = Vec::new();
+    let mut end = 0;
+    for entry in archive.entries()?.raw(true) {
+        let mut entry = entry?;
+        end = entry.raw_file_position() + entry.size().next_multiple_of(512);
+        let header = entry.header().clone();
+        if matches!(
+            header.entry_type(),
+            tar::EntryType::GNULongName | tar::EntryType::GNULongLink | tar::EntryType::XHeader
+        ) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            prefix.push((header, contents));
+            continue;
+        }
+
+        let mut path = String::from_utf8_lossy(&header.path_bytes()).into_owned();
+        let mut link = header
+            .link_name_bytes()
+            .map(|link| String::from_utf8_lossy(&link).into_owned());
+        let mut mtime = header.mtime().ok();
+        for (header, contents) in &prefix {
+            let name = || {
+                let name = contents.split(|&byte| byte == 0).next().unwrap_or_default();
+                String::from_utf8_lossy(name).into_owned()
+            };
+            match header.entry_type() {
+                tar::EntryType::GNULongName => path = name(),
+                tar::EntryType::GNULongLink => link = Some(name()),
+                tar::EntryType::XHeader => {
+                    for record in tar::PaxExtensions::new(contents).filter_map(|r| r.ok()) {
+                        let (Ok(key), Ok(value)) = (record.key(), record.value()) else {
+                            continue;
+                        };
+                        match key {
+                            "path" => path = value.to_string(),
+                            "linkpath" => link = Some(value.to_string()),
+                            "mtime" => {
+                                mtime = value
+                                    .split('.')
+                                    .next()
+                                    .and_then(|seconds| seconds.parse().ok())
+                                    .or(mtime);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let member = Member {
+            path: path.trim_end_matches('/').to_string(),
+            link,
+            mtime,
+            prefix: std::mem::take(&mut prefix),
+        };
+        visit(&member, &mut entry)?;
+        // whatever the visitor left unread is skipped by the archive
+    }
+    Ok(end)
+}
+
+/// copy the entries that are kept into a new 7z archive, then add the new ones
+fn rewrite_sevenz(
+    archive_path: &Path,
+    output_path: &Path,
+    rename: Rename,
+    entries: &[NewEntry],
+    options: &CompressionOptions,
+    extract_options: &ExtractionOptions,
+) -> Result<()> {
+    let mut sink = SevenZSink::create(output_path, options)?;
+    let password = extract_options.password.as_deref();
+    sevenz::for_each_entry(archive_path, password, |entry, data| {
+        if let Some(name) = rename(&entry.name.replace('\\', "/")) {
+            let mut entry = entry.clone();
+            entry.name = name;
+            // sevenz-rust reads back every entry it wrote as an anti-item
+            entry.is_anti_item = false;
+            sink.push(entry, data)?;
+        }
+        std::io::copy(data, &mut std::io::sink())?;
+        Ok(())
+    })?;
+    add_entries(&mut sink, entries, options, None)?;
+    Box::new(sink).finish()
+}
+
+/// write a replacement next to the archive, then move it over the original
+fn rewrite(archive_path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let name = archive_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let dir = match archive_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // a unique name keeps two runs on the same archive from sharing a temp file;
+    // it is removed again when dropped on error
+    let temp_file = tempfile::Builder::new()
+        .prefix(&format!(".{name}."))
+        .suffix(".zzz-tmp")
+        .tempfile_in(dir)
+        .with_context(|| format!("Failed to create a temp file in {}", dir.display()))?;
+    write(temp_file.path())?;
+
+    // the data has to be on disk before the rename makes it the archive
+    File::open(temp_file.path())?.sync_all()?;
+    let permissions = std::fs::metadata(archive_path)?.permissions();
+    std::fs::set_permissions(temp_file.path(), permissions)?;
+    temp_file
+        .persist(archive_path)
+        .map_err(|e| e.error)
+        .with_context(|| format!("Failed to replace archive {}", archive_path.display()))?;
+    // and so does the rename itself
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
@@ -195,6 +195,54 @@ pub fn prepare_extract_target(
     Ok(ExtractTarget::Target(target_path))
 }
 
+/// name of the file held by a raw single-stream archive
+///
+/// strips the first matching suffix (case-insensitive); otherwise falls back to the file
+/// stem, or appends `.out` so the output never shadows the archive itself.
+pub fn raw_output_name(archive_path: &Path, suffixes: &[&str]) -> String {
+    let name = archive_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let lower = name.to_lowercase();
+
+    for suffix in suffixes {
+        if lower.ends_with(suffix) && lower.len() > suffix.len() {
+            return name[..name.len() - suffix.len()].to_string();
+        }
+    }
+
+    match archive_path.file_stem() {
+        Some(stem) if stem.len() < name.len() => stem.to_string_lossy().into_owned(),
+        _ => format!("{name}.out"),
+    }
+}
+
+/// whether an archive path was picked by `--entry`; selecting a directory selects everything
+/// below it, and an empty selection keeps every entry
+pub fn entry_selected(path: &str, selections: &[String]) -> bool {
+    let path = path.trim_end_matches('/');
+    selections.is_empty()
+        || selections.iter().any(|selection| {
+            let selection = selection.trim_end_matches('/');
+            path == selection
+                || path
+                    .strip_prefix(selection)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+}
+
+/// number of worker threads for `-j`, where 0 means all available cores
+pub fn resolved_thread_count(requested_threads: u32) -> u32 {
+    if requested_threads == 0 {
+        std::thread::available_parallelism()
+            .map(|parallelism| parallelism.get() as u32)
+            .unwrap_or(1)
+    } else {
+        requested_threads
+    }
+}
+
 /// calculate total size of a directory recursively
 pub fn calculate_dir_size(path: &Path) -> Result<u64> {
     let mut total = 0;
@@ -307,12 +355,106 @@ pub fn prompt_yes_no(message: &str) -> bool {
     }
 }
 
+/// file extensions whose contents are already compressed
+const PRECOMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar",
+    "jpeg", "jpg", "lz4", "lzma", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "ogg", "opus", "png",
+    "pptx", "rar", "tbz2", "tgz", "txz", "webm", "webp", "whl", "woff", "woff2", "xlsx", "xz",
+    "zip", "zst",
+];
+
+/// bytes sampled from the start of a file when estimating entropy
+pub const ENTROPY_SAMPLE_SIZE: usize = 64 * 1024;
+/// samples smaller than this are too short for a meaningful estimate
+const ENTROPY_MIN_SAMPLE: usize = 4 * 1024;
+/// shannon entropy (bits per byte) above which data is treated as incompressible
+const ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// shannon entropy of `data` in bits per byte
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// guess whether a file is already compressed, by extension or by sampling its entropy
+pub fn is_precompressed(path: &Path) -> Result<bool> {
+    use std::io::Read;
+
+    if has_precompressed_extension(path) {
+        return Ok(true);
+    }
+
+    let mut sample = Vec::with_capacity(ENTROPY_SAMPLE_SIZE);
+    std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .take(ENTROPY_SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(looks_precompressed(path, &sample))
+}
+
+/// the same guess for data already in hand: `sample` is the start of the file at `path`
+pub fn looks_precompressed(path: &Path, sample: &[u8]) -> bool {
+    has_precompressed_extension(path)
+        || (sample.len() >= ENTROPY_MIN_SAMPLE && shannon_entropy(sample) > ENTROPY_THRESHOLD)
+}
+
+fn has_precompressed_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PRECOMPRESSED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_raw_output_name() {
+        let suffixes = [".gz", ".tgz"];
+        assert_eq!(
+            raw_output_name(Path::new("dir/notes.txt.GZ"), &suffixes),
+            "notes.txt"
+        );
+        assert_eq!(
+            raw_output_name(Path::new("backup.tgz"), &suffixes),
+            "backup"
+        );
+        assert_eq!(
+            raw_output_name(Path::new("photo.download"), &suffixes),
+            "photo"
+        );
+        assert_eq!(raw_output_name(Path::new("blob"), &suffixes), "blob.out");
+        assert_eq!(raw_output_name(Path::new(".gz"), &suffixes), ".gz.out");
+    }
+
+    #[test]
+    fn test_entry_selected() {
+        let selections = vec!["project/src/".to_string(), "project/README.md".to_string()];
+        assert!(entry_selected("project/src", &selections));
+        assert!(entry_selected("project/src/main.rs", &selections));
+        assert!(entry_selected("project/README.md", &selections));
+        assert!(!entry_selected("project/srcs/lib.rs", &selections));
+        assert!(!entry_selected("project", &selections));
+        assert!(entry_selected("anything", &[]));
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(format_bytes(0), "0 B");
@@ -417,4 +559,30 @@ mod tests {
         assert_eq!(size, 0);
         Ok(())
     }
+
+    #[test]
+    fn test_is_precompressed() -> Result<()> {
+        use rand::RngCore;
+
+        let temp_dir = TempDir::new()?;
+        let text = temp_dir.path().join("notes.txt");
+        fs::write(&text, "plain text compresses well\n".repeat(1024))?;
+        assert!(!is_precompressed(&text)?);
+
+        let photo = temp_dir.path().join("photo.JPG");
+        fs::write(&photo, "not really a jpeg")?;
+        assert!(is_precompressed(&photo)?);
+
+        let mut noise = vec![0u8; 32 * 1024];
+        rand::thread_rng().fill_bytes(&mut noise);
+        let blob = temp_dir.path().join("blob.bin");
+        fs::write(&blob, &noise)?;
+        assert!(is_precompressed(&blob)?);
+
+        // too small to judge by entropy
+        let tiny = temp_dir.path().join("tiny.bin");
+        fs::write(&tiny, &noise[..256])?;
+        assert!(!is_precompressed(&tiny)?);
+        Ok(())
+    }
 }
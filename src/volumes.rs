@@ -0,0 +1,1193 @@
+//! multi-volume archive sets
+//!
+//! a set is recognized from any of its members:
+//! - `name.part1.rar` … `name.partN.rar`, or the older `name.rar`, `name.r00` … naming
+//! - `name.7z.001` … `name.7z.NNN`, an archive cut into plain byte ranges; the name before the
+//!   number has to carry an archive extension, so `dump.100` next to `dump.101` is not a set
+//! - `name.z01` … `name.zNN` plus `name.zip`, a split zip with per-volume offsets
+//!
+//! unrar follows RAR volumes itself, so those only resolve to their first part. the other
+//! layouts are read through [`VolumeReader`], which presents the whole set as one file.
+//! [`create`] and [`create_zip`] write the latter two volume by volume as an archive is written.
+
+use crate::formats::zip::{central_headers, read_eocd, read_eocd64_locator, read_u16, read_u32};
+use crate::formats::Format;
+use crate::Result;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const ZIP_SPAN_MARKER: &[u8; 4] = b"PK\x07\x08";
+const SEVENZ_SIGNATURE: &[u8; 6] = b"7z\xBC\xAF\x27\x1C";
+
+/// what [`VolumeSet::detect`] found for each path, so detecting the format and opening the
+/// archive afterwards scan its directory once; writing volumes clears it
+static DETECTED: Mutex<BTreeMap<PathBuf, Option<VolumeSet>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeKind {
+    /// RAR volumes, opened from the first part by unrar
+    Rar,
+    /// one archive cut into consecutive byte ranges
+    Split,
+    /// a split zip, whose last volume carries the central directory
+    Zip,
+}
+
+/// the members of a multi-volume archive, in order
+#[derive(Debug, Clone)]
+pub struct VolumeSet {
+    pub kind: VolumeKind,
+    pub volumes: Vec<PathBuf>,
+}
+
+impl VolumeSet {
+    /// the volume set `path` belongs to, or `None` for a single-file archive
+    ///
+    /// fails when the set is recognized but some of its members are missing
+    pub fn detect(path: &Path) -> Result<Option<Self>> {
+        let detected = || DETECTED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(set) = detected().get(path) {
+            return Ok(set.clone());
+        }
+        let set = Self::scan(path)?;
+        detected().insert(path.to_path_buf(), set.clone());
+        Ok(set)
+    }
+
+    fn scan(path: &Path) -> Result<Option<Self>> {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(None);
+        };
+        // ascii lowercasing keeps byte offsets valid for slicing `name`
+        let lower = name.to_ascii_lowercase();
+
+        if let Some(stem) = lower.strip_suffix(".rar") {
+            if let Some(dot) = stem.rfind(".part") {
+                let digits = &name[dot + 5..stem.len()];
+                if is_number(digits) {
+                    let members = Members::scan(path, &name[..dot + 5], &name[stem.len()..])?;
+                    let last = members.last().max(1);
+                    let volumes = members.complete(1..=last, digits.len())?;
+                    return Ok(Some(Self::new(VolumeKind::Rar, volumes)));
+                }
+            }
+        }
+
+        // old rar naming: name.rar, name.r00, name.r01, …
+        if let Some((stem, digits)) = numbered_extension(name, &lower, 'r') {
+            let first = rar_first(path, stem, digits)?;
+            let members = Members::scan(path, &format!("{stem}.{}", &digits[..1]), "")?;
+            let width = digits.len() - 1;
+            let last = members.last();
+            let mut volumes = vec![first];
+            volumes.extend(members.complete(0..=last, width)?);
+            return Ok(Some(Self::new(VolumeKind::Rar, volumes)));
+        }
+        if lower.ends_with(".rar") {
+            let stem = &name[..name.len() - 4];
+            let prefix = format!("{stem}.{}", if name.ends_with(".RAR") { "R" } else { "r" });
+            let members = Members::scan(path, &prefix, "")?;
+            if !members.found.is_empty() {
+                let mut volumes = vec![path.to_path_buf()];
+                volumes.extend(members.complete(0..=members.last(), 2)?);
+                return Ok(Some(Self::new(VolumeKind::Rar, volumes)));
+            }
+        }
+
+        // split zip: name.z01, name.z02, …, name.zip
+        let zip_stem = match numbered_extension(name, &lower, 'z') {
+            Some((stem, _)) => Some(stem),
+            None if lower.ends_with(".zip") => Some(&name[..name.len() - 4]),
+            None => None,
+        };
+        if let Some(stem) = zip_stem {
+            let upper = name[stem.len() + 1..].starts_with('Z');
+            let prefix = format!("{stem}.{}", if upper { "Z" } else { "z" });
+            let members = Members::scan(path, &prefix, "")?;
+            let last = path.with_file_name(format!("{stem}.{}", if upper { "ZIP" } else { "zip" }));
+            // the last volume records how many came before it
+            let split = !members.found.is_empty()
+                || (last == path && zip_disk_count(&last).is_ok_and(|disks| disks > 1));
+            if split {
+                if !last.is_file() {
+                    anyhow::bail!(
+                        "incomplete volume set: missing final volume {}",
+                        last.display()
+                    );
+                }
+                let disks = zip_disk_count(&last)?;
+                let mut volumes = members.complete(1..=disks.saturating_sub(1), 2)?;
+                volumes.push(last);
+                return Ok(Some(Self::new(VolumeKind::Zip, volumes)));
+            }
+        }
+
+        // byte split: name.7z.001, name.7z.002, …
+        if let Some(dot) = name.rfind('.') {
+            let digits = &name[dot + 1..];
+            let archive = Format::from_extension(Path::new(&name[..dot])).is_some();
+            if archive && digits.len() >= 3 && is_number(digits) {
+                let members = Members::scan(path, &name[..=dot], "")?;
+                if members.contains(1) || members.found.len() > 1 {
+                    let volumes = members.complete(1..=members.last(), digits.len())?;
+                    return Ok(Some(Self::new(VolumeKind::Split, volumes)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn new(kind: VolumeKind, volumes: Vec<PathBuf>) -> Self {
+        Self { kind, volumes }
+    }
+
+    /// the first member, where every format starts reading
+    pub fn first(&self) -> &Path {
+        &self.volumes[0]
+    }
+
+    /// name of the whole archive with the volume number removed, e.g. `data.7z` for `data.7z.001`
+    pub fn archive_name(&self) -> PathBuf {
+        let first = self.first();
+        match self.kind {
+            VolumeKind::Split => first.with_extension(""),
+            VolumeKind::Rar | VolumeKind::Zip => first.to_path_buf(),
+        }
+    }
+
+    /// join the set into one seekable stream
+    pub fn open(&self) -> Result<VolumeReader> {
+        match self.kind {
+            VolumeKind::Rar => open_file(self.first()),
+            VolumeKind::Split => {
+                let mut reader = VolumeReader::concat(&self.volumes)?;
+                check_sevenz_length(&mut reader, &self.volumes)?;
+                Ok(reader)
+            }
+            VolumeKind::Zip => join_split_zip(&self.volumes),
+        }
+    }
+}
+
+/// open an archive, joining its volumes when it is part of a set
+pub fn open(path: &Path) -> Result<VolumeReader> {
+    match VolumeSet::detect(path)? {
+        Some(set) => set.open(),
+        None => open_file(path),
+    }
+}
+
+/// the path a single-volume reader should open: the first member of a set, or `path` itself
+pub fn first_volume(path: &Path) -> Result<PathBuf> {
+    Ok(match VolumeSet::detect(path)? {
+        Some(set) => set.first().to_path_buf(),
+        None => path.to_path_buf(),
+    })
+}
+
+fn open_file(path: &Path) -> Result<VolumeReader> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open archive file {}", path.display()))?;
+    Ok(VolumeReader::from_file(file)?)
+}
+
+fn is_number(digits: &str) -> bool {
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// split `name.x01` into (`name`, `x01`) for an extension of `letter` and at least two digits
+fn numbered_extension<'a>(name: &'a str, lower: &str, letter: char) -> Option<(&'a str, &'a str)> {
+    let dot = lower.rfind('.')?;
+    let extension = &lower[dot + 1..];
+    let digits = extension.strip_prefix(letter)?;
+    (digits.len() >= 2 && is_number(digits)).then(|| (&name[..dot], &name[dot + 1..]))
+}
+
+/// `name.rar` for an old-style `name.r00` member
+fn rar_first(path: &Path, stem: &str, extension: &str) -> Result<PathBuf> {
+    let upper = extension.starts_with('R');
+    let first = path.with_file_name(format!("{stem}.{}", if upper { "RAR" } else { "rar" }));
+    if !first.is_file() {
+        anyhow::bail!(
+            "incomplete volume set: missing first volume {}",
+            first.display()
+        );
+    }
+    Ok(first)
+}
+
+/// numbered files `{prefix}{number}{suffix}` found next to a member
+struct Members {
+    member: PathBuf,
+    prefix: String,
+    suffix: String,
+    found: BTreeMap<u32, PathBuf>,
+}
+
+impl Members {
+    fn scan(member: &Path, prefix: &str, suffix: &str) -> Result<Self> {
+        let dir = match member.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let lower_prefix = prefix.to_ascii_lowercase();
+        let lower_suffix = suffix.to_ascii_lowercase();
+        let mut found = BTreeMap::new();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_ascii_lowercase) else {
+                continue;
+            };
+            let number = name
+                .strip_prefix(&lower_prefix)
+                .and_then(|rest| rest.strip_suffix(&lower_suffix))
+                .filter(|digits| is_number(digits))
+                .and_then(|digits| digits.parse().ok());
+            if let Some(number) = number {
+                found.insert(number, member.with_file_name(entry.file_name()));
+            }
+        }
+        Ok(Self {
+            member: member.to_path_buf(),
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            found,
+        })
+    }
+
+    fn contains(&self, number: u32) -> bool {
+        self.found.contains_key(&number)
+    }
+
+    fn last(&self) -> u32 {
+        self.found.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// the members numbered `range`, or an error naming every one that is missing
+    fn complete(&self, range: std::ops::RangeInclusive<u32>, width: usize) -> Result<Vec<PathBuf>> {
+        let missing: Vec<String> = range
+            .clone()
+            .filter(|number| !self.contains(*number))
+            .map(|number| {
+                let name = format!("{}{number:0width$}{}", self.prefix, self.suffix);
+                self.member.with_file_name(name).display().to_string()
+            })
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!("incomplete volume set: missing {}", missing.join(", "));
+        }
+        Ok(range.map(|number| self.found[&number].clone()).collect())
+    }
+}
+
+/// a seekable view over a list of byte ranges taken from files or memory
+#[derive(Debug)]
+pub struct VolumeReader {
+    segments: Vec<Segment>,
+    len: u64,
+    position: u64,
+    /// the volume being read and the index of its segment; other volumes are closed
+    volume: Option<(usize, File)>,
+    /// segment index and file offset the last read left off at, so sequential reads skip the seek
+    file_cursor: Option<(usize, u64)>,
+}
+
+#[derive(Debug)]
+struct Segment {
+    /// offset of the segment in the joined stream
+    start: u64,
+    len: u64,
+    source: Source,
+}
+
+#[derive(Debug)]
+enum Source {
+    /// bytes of an open file starting at `offset`
+    File {
+        file: File,
+        offset: u64,
+    },
+    /// bytes of a volume starting at `offset`, opened only while it is read
+    Volume {
+        path: PathBuf,
+        offset: u64,
+    },
+    Memory(Vec<u8>),
+}
+
+impl VolumeReader {
+    fn new(parts: Vec<(Source, u64)>) -> Self {
+        let mut start = 0;
+        let segments = parts
+            .into_iter()
+            .map(|(source, len)| {
+                let segment = Segment { start, len, source };
+                start += len;
+                segment
+            })
+            .collect();
+        Self {
+            segments,
+            len: start,
+            position: 0,
+            volume: None,
+            file_cursor: None,
+        }
+    }
+
+    /// a reader over a single file
+    pub fn from_file(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self::new(vec![(Source::File { file, offset: 0 }, len)]))
+    }
+
+    /// the files one after another
+    fn concat(paths: &[PathBuf]) -> Result<Self> {
+        let parts = paths
+            .iter()
+            .map(|path| {
+                let len = std::fs::metadata(path)
+                    .with_context(|| format!("Failed to open volume {}", path.display()))?
+                    .len();
+                let source = Source::Volume {
+                    path: path.clone(),
+                    offset: 0,
+                };
+                Ok((source, len))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self::new(parts))
+    }
+
+    /// total length of the joined stream
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// keep only the joined bytes in `start..end`
+    fn slice(self, start: u64, end: u64) -> Vec<(Source, u64)> {
+        self.segments
+            .into_iter()
+            .filter_map(|segment| {
+                let from = start.max(segment.start);
+                let to = end.min(segment.start + segment.len);
+                if from >= to {
+                    return None;
+                }
+                let skip = from - segment.start;
+                let source = match segment.source {
+                    Source::File { file, offset } => Source::File {
+                        file,
+                        offset: offset + skip,
+                    },
+                    Source::Volume { path, offset } => Source::Volume {
+                        path,
+                        offset: offset + skip,
+                    },
+                    Source::Memory(data) => {
+                        Source::Memory(data[skip as usize..(to - segment.start) as usize].to_vec())
+                    }
+                };
+                Some((source, to - from))
+            })
+            .collect()
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+        let position = self.position;
+        let index = self
+            .segments
+            .partition_point(|segment| segment.start + segment.len <= position);
+        let segment = &mut self.segments[index];
+        let local = position - segment.start;
+        let want = (buf.len() as u64).min(segment.len - local) as usize;
+        let read = match &mut segment.source {
+            Source::File { file, offset } => read_file(
+                file,
+                index,
+                *offset + local,
+                &mut self.file_cursor,
+                &mut buf[..want],
+            )?,
+            Source::Volume { path, offset } => {
+                if self.volume.as_ref().map(|(open, _)| *open) != Some(index) {
+                    // the previous volume is closed first, so one file is open at a time
+                    self.volume = None;
+                    let file = File::open(&*path).map_err(|e| {
+                        io::Error::new(
+                            e.kind(),
+                            format!("Failed to open volume {}: {e}", path.display()),
+                        )
+                    })?;
+                    self.volume = Some((index, file));
+                }
+                let file = &mut self.volume.as_mut().expect("volume is open").1;
+                read_file(
+                    file,
+                    index,
+                    *offset + local,
+                    &mut self.file_cursor,
+                    &mut buf[..want],
+                )?
+            }
+            Source::Memory(data) => {
+                let local = local as usize;
+                buf[..want].copy_from_slice(&data[local..local + want]);
+                want
+            }
+        };
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "volume is shorter than when it was opened",
+            ));
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// read the file of segment `index` at `position`, seeking unless the last read ended there
+fn read_file(
+    file: &mut File,
+    index: usize,
+    position: u64,
+    cursor: &mut Option<(usize, u64)>,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    // taken up front: after a failed read the file position is unknown
+    if cursor.take() != Some((index, position)) {
+        file.seek(SeekFrom::Start(position))?;
+    }
+    let read = file.read(buf)?;
+    *cursor = Some((index, position + read as u64));
+    Ok(read)
+}
+
+impl Seek for VolumeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// a 7z start header records where the archive ends, so missing trailing volumes show up early
+fn check_sevenz_length(reader: &mut VolumeReader, volumes: &[PathBuf]) -> Result<()> {
+    let mut start_header = [0u8; 32];
+    if reader.len() < 32 {
+        return Ok(());
+    }
+    reader.read_exact(&mut start_header)?;
+    reader.rewind()?;
+    if &start_header[..6] != SEVENZ_SIGNATURE {
+        return Ok(());
+    }
+    let next_header_offset = u64::from_le_bytes(start_header[12..20].try_into().unwrap());
+    let next_header_size = u64::from_le_bytes(start_header[20..28].try_into().unwrap());
+    let expected = 32u64
+        .saturating_add(next_header_offset)
+        .saturating_add(next_header_size);
+    if reader.len() < expected {
+        let last = volumes.last().expect("volume sets are never empty");
+        anyhow::bail!(
+            "incomplete volume set: archive ends {} bytes early after {}; later volumes are missing",
+            expected - reader.len(),
+            last.display()
+        );
+    }
+    Ok(())
+}
+
+/// number of volumes a split zip was written with, from its last volume
+fn zip_disk_count(last: &Path) -> Result<u32> {
+    let mut file =
+        File::open(last).with_context(|| format!("Failed to open volume {}", last.display()))?;
+    let (_, eocd) = read_eocd(&mut file)
+        .with_context(|| format!("Failed to read split ZIP archive {}", last.display()))?;
+    let disk = read_u16(&eocd, 4);
+    if disk == u16::MAX {
+        anyhow::bail!("zip64 split archives are not supported");
+    }
+    Ok(u32::from(disk) + 1)
+}
+
+/// join a split zip into a regular one
+///
+/// the volumes are concatenated without the leading spanning marker, and the central directory is
+/// rewritten in memory so every offset is absolute and every entry lives on disk 0
+fn join_split_zip(volumes: &[PathBuf]) -> Result<VolumeReader> {
+    let last = volumes.last().expect("volume sets are never empty");
+    let mut raw = VolumeReader::concat(volumes)?;
+    let mut volume_starts = Vec::with_capacity(volumes.len());
+    for segment in &raw.segments {
+        volume_starts.push(segment.start);
+    }
+
+    let mut marker = [0u8; 4];
+    let skip = if raw.len() >= 4 {
+        raw.read_exact(&mut marker)?;
+        if &marker == ZIP_SPAN_MARKER {
+            4
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+
+    let (_, mut eocd) = read_eocd(&mut raw)
+        .with_context(|| format!("Failed to read split ZIP archive {}", last.display()))?;
+    let cd_disk = read_u16(&eocd, 6);
+    let entries = read_u16(&eocd, 10);
+    let cd_size = read_u32(&eocd, 12);
+    let cd_offset = read_u32(&eocd, 16);
+    if cd_disk == u16::MAX || entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+        anyhow::bail!("zip64 split archives are not supported");
+    }
+    let invalid = || anyhow::anyhow!("invalid central directory in {}", last.display());
+    let volume_start = |disk: u16| volume_starts.get(usize::from(disk)).copied();
+
+    let cd_start = volume_start(cd_disk).ok_or_else(invalid)? + u64::from(cd_offset);
+    let mut directory = vec![0u8; cd_size as usize];
+    raw.seek(SeekFrom::Start(cd_start))?;
+    raw.read_exact(&mut directory)?;
+
+    let headers = central_headers(&directory);
+    if headers.len() < usize::from(entries) {
+        return Err(invalid());
+    }
+    for &pos in &headers[..usize::from(entries)] {
+        let disk = read_u16(&directory, pos + 34);
+        let local_offset = read_u32(&directory, pos + 42);
+        if disk == u16::MAX || local_offset == u32::MAX {
+            anyhow::bail!("zip64 split archives are not supported");
+        }
+        let offset = (volume_start(disk).ok_or_else(invalid)? + u64::from(local_offset))
+            .checked_sub(skip)
+            .ok_or_else(invalid)?;
+        let offset = u32::try_from(offset)
+            .map_err(|_| anyhow::anyhow!("split ZIP archive is too large to join"))?;
+        directory[pos + 34..pos + 36].copy_from_slice(&0u16.to_le_bytes());
+        directory[pos + 42..pos + 46].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    let directory_offset = u32::try_from(cd_start - skip)
+        .map_err(|_| anyhow::anyhow!("split ZIP archive is too large to join"))?;
+    eocd[4..6].copy_from_slice(&0u16.to_le_bytes());
+    eocd[6..8].copy_from_slice(&0u16.to_le_bytes());
+    eocd[8..10].copy_from_slice(&entries.to_le_bytes());
+    eocd[16..20].copy_from_slice(&directory_offset.to_le_bytes());
+    directory.extend_from_slice(&eocd);
+
+    let mut parts = raw.slice(skip, cd_start);
+    let len = directory.len() as u64;
+    parts.push((Source::Memory(directory), len));
+    Ok(VolumeReader::new(parts))
+}
+
+/// path of volume `number` of a byte-split archive: `name.001`, `name.002`, …
+pub fn split_volume_path(path: &Path, number: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{number:03}"));
+    PathBuf::from(name)
+}
+
+/// path of volume `number` of a split zip, before the final `name.zip`: `name.z01`, …
+pub fn zip_volume_path(path: &Path, number: u32) -> PathBuf {
+    path.with_extension(format!("z{number:02}"))
+}
+
+/// the file an archive is written to, or its volumes when it is split
+#[derive(Debug)]
+pub enum OutputFile {
+    File(File),
+    Volumes(VolumeWriter),
+}
+
+impl OutputFile {
+    /// complete the output once the archive is written
+    ///
+    /// a split zip gets its per-volume offsets here, and becomes a plain zip when it fits in one
+    /// volume
+    pub fn finish(self) -> Result<()> {
+        match self {
+            OutputFile::File(mut file) => Ok(file.flush()?),
+            OutputFile::Volumes(writer) => writer.finish(),
+        }
+    }
+}
+
+impl Read for OutputFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::File(file) => file.read(buf),
+            OutputFile::Volumes(writer) => writer.read(buf),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::File(file) => file.write(buf),
+            OutputFile::Volumes(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::File(file) => file.flush(),
+            OutputFile::Volumes(writer) => writer.flush(),
+        }
+    }
+}
+
+impl Seek for OutputFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            OutputFile::File(file) => file.seek(pos),
+            OutputFile::Volumes(writer) => writer.seek(pos),
+        }
+    }
+}
+
+/// create `path`, or `name.001`, `name.002`, … of at most `split_size` bytes when it is set
+///
+/// this is also the native multi-volume layout of 7z
+pub fn create(path: &Path, split_size: Option<u64>) -> Result<OutputFile> {
+    forget_sets();
+    let Some(size) = split_size else {
+        return create_file(path);
+    };
+    remove_stale(|number| split_volume_path(path, number), 1)?;
+    if path.is_file() {
+        // an unsplit archive of the same name would be taken for the output
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove unsplit archive {}", path.display()))?;
+    }
+    Ok(OutputFile::Volumes(VolumeWriter::create(
+        path,
+        size,
+        Layout::Split,
+    )?))
+}
+
+/// like [`create`] for a zip, split as `name.z01`, `name.z02`, …, `name.zip`
+///
+/// the zip writer sees one stream that starts with the spanning marker, so the offsets it
+/// records are positions in that stream until [`OutputFile::finish`] makes them per volume
+pub fn create_zip(path: &Path, split_size: Option<u64>) -> Result<OutputFile> {
+    forget_sets();
+    let Some(size) = split_size else {
+        return create_file(path);
+    };
+    remove_stale(|number| zip_volume_path(path, number), 1)?;
+    let mut writer = VolumeWriter::create(path, size, Layout::Zip)?;
+    writer.write_all(ZIP_SPAN_MARKER)?;
+    Ok(OutputFile::Volumes(writer))
+}
+
+/// drop what was detected before, now that volumes are being written
+fn forget_sets() {
+    DETECTED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// the archive as one file, opened for reading too so writers can check what they wrote
+fn create_file(path: &Path) -> Result<OutputFile> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .with_context(|| format!("Failed to create output file {}", path.display()))?;
+    Ok(OutputFile::File(file))
+}
+
+/// the files an archive created with `split_size` was written to
+pub fn written(path: &Path, split_size: Option<u64>) -> Vec<PathBuf> {
+    let numbered = |path_of: &dyn Fn(u32) -> PathBuf| {
+        (1..)
+            .map(path_of)
+            .take_while(|volume| volume.is_file())
+            .collect::<Vec<_>>()
+    };
+    if split_size.is_none() {
+        return vec![path.to_path_buf()];
+    }
+    let volumes = numbered(&|number| split_volume_path(path, number));
+    if !volumes.is_empty() {
+        return volumes;
+    }
+    // a split zip, or one that fit in a single volume
+    let mut volumes = numbered(&|number| zip_volume_path(path, number));
+    volumes.push(path.to_path_buf());
+    volumes
+}
+
+/// total size of an archive created with `split_size`, over all of its volumes
+pub fn written_size(path: &Path, split_size: Option<u64>) -> Result<u64> {
+    written(path, split_size)
+        .iter()
+        .map(|volume| {
+            Ok(std::fs::metadata(volume)
+                .with_context(|| format!("Failed to read {}", volume.display()))?
+                .len())
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Split,
+    Zip,
+}
+
+/// a stream cut into volumes of `size` bytes as it is written
+///
+/// volume `k` holds the bytes from `k * size` on. only the volume last accessed is kept open;
+/// archive writers that seek back to patch headers, or read back what they wrote, reopen the
+/// volume they need, so a set is not limited by how many files a process may have open.
+#[derive(Debug)]
+pub struct VolumeWriter {
+    path: PathBuf,
+    layout: Layout,
+    size: u64,
+    /// how many volumes have been created
+    count: usize,
+    /// the open volume and its index
+    current: Option<(usize, File)>,
+    len: u64,
+    position: u64,
+    /// volume index and file offset the last access left off at, so sequential writes skip the seek
+    cursor: Option<(usize, u64)>,
+}
+
+impl VolumeWriter {
+    fn create(path: &Path, size: u64, layout: Layout) -> Result<Self> {
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            layout,
+            size,
+            count: 0,
+            current: None,
+            len: 0,
+            position: 0,
+            cursor: None,
+        };
+        // an empty archive still has a first volume
+        writer.volume(0)?;
+        Ok(writer)
+    }
+
+    fn volume_path(&self, index: usize) -> PathBuf {
+        let number = index as u32 + 1;
+        match self.layout {
+            Layout::Split => split_volume_path(&self.path, number),
+            Layout::Zip => zip_volume_path(&self.path, number),
+        }
+    }
+
+    /// open volume `index` for reading and writing, creating it when `create` is set
+    fn open_volume(&self, index: usize, create: bool) -> io::Result<File> {
+        let path = self.volume_path(index);
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .truncate(create)
+            .open(&path)
+            .map_err(|e| {
+                let action = if create { "create" } else { "open" };
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to {action} volume {}: {e}", path.display()),
+                )
+            })
+    }
+
+    /// volume `index`, created along with any before it, as the open volume
+    fn volume(&mut self, index: usize) -> io::Result<&mut File> {
+        if index >= u32::MAX as usize {
+            return Err(io::Error::other("split size is too small for this archive"));
+        }
+        while self.count <= index {
+            if self.count > 0 {
+                // bytes skipped by a seek past the end read back as zeros, as in a file
+                let size = self.size;
+                self.volume(self.count - 1)?.set_len(size)?;
+            }
+            let file = self.open_volume(self.count, true)?;
+            self.current = Some((self.count, file));
+            self.cursor = None;
+            self.count += 1;
+        }
+        if self.current.as_ref().map(|(open, _)| *open) != Some(index) {
+            // the previous volume is closed first, so one file is open at a time
+            self.current = None;
+            self.current = Some((index, self.open_volume(index, false)?));
+            self.cursor = None;
+        }
+        Ok(&mut self.current.as_mut().expect("volume is open").1)
+    }
+
+    /// the volume holding the current position, positioned there, and the bytes left in it
+    fn seek_volume(&mut self) -> io::Result<(usize, &mut File, u64)> {
+        let index = usize::try_from(self.position / self.size).unwrap_or(usize::MAX);
+        let local = self.position % self.size;
+        let left = self.size - local;
+        self.volume(index)?;
+        // taken after opening: switching volumes forgets where the last access left off
+        let seek = self.cursor.take() != Some((index, local));
+        let file = &mut self.current.as_mut().expect("volume is open").1;
+        if seek {
+            file.seek(SeekFrom::Start(local))?;
+        }
+        Ok((index, file, left))
+    }
+
+    fn advance(&mut self, index: usize, by: usize) {
+        self.position += by as u64;
+        self.cursor = Some((index, self.position - index as u64 * self.size));
+    }
+
+    fn finish(self) -> Result<()> {
+        match self.layout {
+            Layout::Split => Ok(()),
+            Layout::Zip => {
+                // the volumes are renamed or removed
+                let finished = self.finish_zip();
+                forget_sets();
+                finished
+            }
+        }
+    }
+
+    /// make the offsets of a split zip relative to the volume holding each record
+    ///
+    /// the end record is kept in one piece in the last volume, which becomes `name.zip`. a zip
+    /// that fits in one volume is written out as a plain zip without the spanning marker.
+    fn finish_zip(mut self) -> Result<()> {
+        let marker = ZIP_SPAN_MARKER.len() as u64;
+        let (eocd_offset, mut eocd) = read_eocd(&mut self)
+            .with_context(|| format!("Failed to read ZIP archive {}", self.path.display()))?;
+        let entries = usize::from(read_u16(&eocd, 10));
+        let cd_offset = read_u32(&eocd, 16);
+        let zip64 = read_eocd64_locator(&mut self, eocd_offset)?.is_some();
+        if entries == usize::from(u16::MAX) || cd_offset == u32::MAX || zip64 {
+            anyhow::bail!(
+                "zip64 archives cannot be split into zip volumes; use 7z or a tar format"
+            );
+        }
+        let cd_offset = u64::from(cd_offset);
+
+        let single = self.len - marker <= self.size;
+        let starts: Vec<u64> = if single {
+            vec![marker]
+        } else {
+            if eocd.len() as u64 > self.size {
+                anyhow::bail!("split size is smaller than the end record of the ZIP archive");
+            }
+            if self.count >= usize::from(u16::MAX) {
+                anyhow::bail!("split size is too small for this archive");
+            }
+            let mut starts: Vec<u64> = (0..self.count as u64).map(|k| k * self.size).collect();
+            let last = starts.last_mut().expect("at least two volumes");
+            *last = (*last).min(eocd_offset);
+            starts
+        };
+        let last_disk = (starts.len() - 1) as u16;
+        let locate = |offset: u64| {
+            let disk = starts.partition_point(|&start| start <= offset) - 1;
+            (disk as u16, (offset - starts[disk]) as u32)
+        };
+
+        let mut directory = vec![0u8; (eocd_offset - cd_offset) as usize];
+        self.seek(SeekFrom::Start(cd_offset))?;
+        self.read_exact(&mut directory)?;
+        let headers = central_headers(&directory);
+        if headers.len() < entries {
+            anyhow::bail!("invalid central directory in {}", self.path.display());
+        }
+        let mut entries_on_last_disk = 0u16;
+        for &pos in &headers[..entries] {
+            let (disk, offset) = locate(u64::from(read_u32(&directory, pos + 42)));
+            directory[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+            directory[pos + 42..pos + 46].copy_from_slice(&offset.to_le_bytes());
+            if locate(cd_offset + pos as u64).0 == last_disk {
+                entries_on_last_disk += 1;
+            }
+        }
+        let (cd_disk, cd_local) = locate(cd_offset);
+        eocd[4..6].copy_from_slice(&last_disk.to_le_bytes());
+        eocd[6..8].copy_from_slice(&cd_disk.to_le_bytes());
+        eocd[8..10].copy_from_slice(&entries_on_last_disk.to_le_bytes());
+        eocd[16..20].copy_from_slice(&cd_local.to_le_bytes());
+        self.seek(SeekFrom::Start(cd_offset))?;
+        self.write_all(&directory)?;
+
+        if single {
+            self.seek(SeekFrom::Start(eocd_offset))?;
+            self.write_all(&eocd)?;
+            self.seek(SeekFrom::Start(marker))?;
+            let len = self.len - marker;
+            let mut file = File::create(&self.path)
+                .with_context(|| format!("Failed to create output file {}", self.path.display()))?;
+            io::copy(&mut (&mut self).take(len), &mut file)?;
+            self.current = None;
+            for index in 0..self.count {
+                std::fs::remove_file(self.volume_path(index))?;
+            }
+            return Ok(());
+        }
+
+        let last = starts.len() - 1;
+        let boundary = last as u64 * self.size;
+        if eocd_offset < boundary {
+            // the end record straddles two volumes: end the earlier one early instead
+            self.volume(last - 1)?
+                .set_len(eocd_offset - starts[last - 1])?;
+            let file = self.volume(last)?;
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&eocd)?;
+            self.cursor = None;
+        } else {
+            self.seek(SeekFrom::Start(eocd_offset))?;
+            self.write_all(&eocd)?;
+        }
+        let last_path = self.volume_path(last);
+        self.current = None;
+        std::fs::rename(&last_path, &self.path)
+            .with_context(|| format!("Failed to create {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl Read for VolumeWriter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+        let remaining = self.len - self.position;
+        let (index, file, left) = self.seek_volume()?;
+        let want = (buf.len() as u64).min(left).min(remaining) as usize;
+        let read = file.read(&mut buf[..want])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "volume is shorter than what was written to it",
+            ));
+        }
+        self.advance(index, read);
+        Ok(read)
+    }
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let (index, file, left) = self.seek_volume()?;
+        let want = (buf.len() as u64).min(left) as usize;
+        let written = file.write(&buf[..want])?;
+        self.advance(index, written);
+        self.len = self.len.max(self.position);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Seek for VolumeWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// remove volumes from `first` on that a previous, longer archive left behind
+fn remove_stale(path_of: impl Fn(u32) -> PathBuf, first: u32) -> Result<()> {
+    let mut number = first;
+    loop {
+        let path = path_of(number);
+        if !path.is_file() {
+            return Ok(());
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale volume {}", path.display()))?;
+        number += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_parts(dir: &Path, names: &[&str]) {
+        for (i, name) in names.iter().enumerate() {
+            std::fs::write(dir.join(name), format!("part{i}-")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_split_set_from_any_member() {
+        let dir = TempDir::new().unwrap();
+        write_parts(dir.path(), &["data.7z.001", "data.7z.002", "data.7z.003"]);
+
+        let set = VolumeSet::detect(&dir.path().join("data.7z.002"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(set.kind, VolumeKind::Split);
+        assert_eq!(set.volumes.len(), 3);
+        assert_eq!(set.first(), dir.path().join("data.7z.001"));
+        assert_eq!(set.archive_name(), dir.path().join("data.7z"));
+
+        let mut joined = String::new();
+        set.open().unwrap().read_to_string(&mut joined).unwrap();
+        assert_eq!(joined, "part0-part1-part2-");
+    }
+
+    #[test]
+    fn test_missing_volumes_are_named() {
+        let dir = TempDir::new().unwrap();
+        write_parts(
+            dir.path(),
+            &["data.part1.rar", "data.part2.rar", "data.part4.rar"],
+        );
+        let err = VolumeSet::detect(&dir.path().join("data.part4.rar"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing"), "{err}");
+        assert!(err.contains("data.part3.rar"), "{err}");
+
+        write_parts(dir.path(), &["data.7z.002", "data.7z.003"]);
+        let err = VolumeSet::detect(&dir.path().join("data.7z.003"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("data.7z.001"), "{err}");
+    }
+
+    #[test]
+    fn test_single_files_are_not_sets() {
+        let dir = TempDir::new().unwrap();
+        write_parts(
+            dir.path(),
+            &[
+                "report.2024",
+                "plain.zip",
+                "plain.rar",
+                "dump.100",
+                "dump.101",
+            ],
+        );
+        for name in [
+            "report.2024",
+            "plain.zip",
+            "plain.rar",
+            "dump.100",
+            "dump.101",
+        ] {
+            assert!(VolumeSet::detect(&dir.path().join(name)).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_reader_seeks_across_segments() {
+        let mut reader = VolumeReader::new(vec![
+            (Source::Memory(b"abc".to_vec()), 3),
+            (Source::Memory(Vec::new()), 0),
+            (Source::Memory(b"defg".to_vec()), 4),
+        ]);
+        assert_eq!(reader.len(), 7);
+        reader.seek(SeekFrom::End(-5)).unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "cdefg");
+    }
+
+    #[test]
+    fn test_reader_reads_files_after_seeking_back() {
+        let dir = TempDir::new().unwrap();
+        let parts = [dir.path().join("a"), dir.path().join("b")];
+        std::fs::write(&parts[0], "abc").unwrap();
+        std::fs::write(&parts[1], "defg").unwrap();
+        let mut reader = VolumeReader::concat(&parts).unwrap();
+
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "abcdefg");
+        // the files were left at their ends, so going back has to seek them again
+        reader.seek(SeekFrom::Start(1)).unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "bcdefg");
+    }
+
+    #[test]
+    fn test_zip_volumes_written_at_every_size() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.zip");
+        let options = zip::write::FileOptions::<()>::default()
+            .compression_method(zip::CompressionMethod::Stored);
+
+        // small sizes cut the end record and headers at every possible place
+        for size in 22..260 {
+            let mut writer = zip::ZipWriter::new(create_zip(&path, Some(size)).unwrap());
+            writer.start_file("a.txt", options).unwrap();
+            writer.write_all(b"first entry").unwrap();
+            writer.start_file("b.txt", options).unwrap();
+            writer.write_all(b"second").unwrap();
+            writer.finish().unwrap().finish().unwrap();
+
+            let mut archive = zip::ZipArchive::new(open(&path).unwrap()).unwrap();
+            let mut text = String::new();
+            archive
+                .by_name("b.txt")
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!(text, "second", "split size {size}");
+            for volume in written(&path, Some(size)) {
+                assert!(std::fs::metadata(&volume).unwrap().len() <= size);
+            }
+        }
+    }
+}
@@ -286,6 +286,102 @@ fn test_compress_zstd_with_threads() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_compress_gzip_with_threads() -> Result<()> {
+    use std::io::Read;
+
+    let temp_dir = TempDir::new()?;
+    let source_file = temp_dir.path().join("threaded.txt");
+    let output_file = temp_dir.path().join("threaded.txt.gz");
+    let content = "Threaded gzip content. ".repeat(200_000);
+    fs::write(&source_file, &content)?;
+
+    zzz_cmd()
+        .args(["-j", "4", "compress", "-o"])
+        .arg(&output_file)
+        .arg(&source_file)
+        .assert()
+        .success();
+
+    // several blocks, yet a single member: GzDecoder stops after the first one
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(&output_file)?).read_to_string(&mut decoded)?;
+    assert_eq!(decoded, content);
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .arg("extract")
+        .arg(&output_file)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("threaded.txt"))?,
+        content
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_xz_blocks_round_trip_with_threads() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source_dir = temp_dir.path().join("blocks");
+    fs::create_dir(&source_dir)?;
+    for i in 0..4 {
+        fs::write(
+            source_dir.join(format!("part{i}.txt")),
+            format!("xz block {i}\n").repeat(40_000),
+        )?;
+    }
+    let output_file = temp_dir.path().join("blocks.txz");
+
+    zzz_cmd()
+        .args([
+            "-j",
+            "4",
+            "compress",
+            "-l",
+            "3",
+            "--xz-block-size",
+            "256K",
+            "-o",
+        ])
+        .arg(&output_file)
+        .arg(&source_dir)
+        .assert()
+        .success();
+
+    // blocks are decoded in parallel under -j, sequentially under -j1
+    for threads in ["4", "1"] {
+        let extract_dir = temp_dir.path().join(format!("extract-{threads}"));
+        zzz_cmd()
+            .args(["-j", threads, "extract"])
+            .arg(&output_file)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        for i in 0..4 {
+            let name = format!("part{i}.txt");
+            assert_eq!(
+                fs::read(extract_dir.join("blocks").join(&name))?,
+                fs::read(source_dir.join(&name))?
+            );
+        }
+    }
+
+    zzz_cmd()
+        .args(["compress", "--xz-block-size", "0"])
+        .arg(&source_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be greater than zero"));
+
+    Ok(())
+}
+
 #[test]
 fn test_compress_with_custom_excludes() -> Result<()> {
     let temp_dir = TempDir::new()?;
@@ -594,3 +690,76 @@ fn test_verbose_output() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_check_ignore_reports_rule_sources() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let ignore_file = temp_dir.path().join("ignore.txt");
+    fs::write(&ignore_file, "# generated\ndist\n")?;
+
+    zzz_cmd()
+        .args([
+            "check-ignore",
+            "--redact",
+            "-e",
+            "*.log",
+            "-X",
+            ignore_file.to_str().unwrap(),
+            "src/.DS_Store",
+            "config/.env",
+            "logs/app.log",
+            "dist/bundle.js",
+            "src/main.rs",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "src/.DS_Store: excluded by default list pattern '.DS_Store'",
+        ))
+        .stdout(predicate::str::contains(
+            "config/.env: excluded by redact list pattern '.env'",
+        ))
+        .stdout(predicate::str::contains(
+            "logs/app.log: excluded by user pattern '*.log'",
+        ))
+        .stdout(predicate::str::contains(
+            "dist/bundle.js: excluded by ignore file",
+        ))
+        .stdout(predicate::str::contains("src/main.rs").not());
+
+    zzz_cmd()
+        .args(["-v", "check-ignore", "-E", "src/.DS_Store"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("src/.DS_Store: included"));
+
+    Ok(())
+}
+
+#[test]
+fn test_verbose_compress_reports_exclusions() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source_dir = temp_dir.path().join("source");
+    let output_file = temp_dir.path().join("excluded.zst");
+
+    fs::create_dir(&source_dir)?;
+    fs::write(source_dir.join("keep.txt"), "keep")?;
+    fs::write(source_dir.join(".env"), "SECRET=1")?;
+    fs::create_dir(source_dir.join("node_modules"))?;
+    fs::write(source_dir.join("node_modules").join("dep.js"), "dep")?;
+
+    zzz_cmd()
+        .args(["-v", "compress", "--redact", "-o"])
+        .arg(&output_file)
+        .arg(&source_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(redact list pattern '.env')"))
+        .stdout(predicate::str::contains(
+            "(default list pattern 'node_modules')",
+        ))
+        .stdout(predicate::str::contains("dep.js").not())
+        .stdout(predicate::str::contains("keep.txt").not());
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)] // each test crate uses its own subset
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// write `files` below `parent/name`, creating parent directories as needed
+///
+/// a path ending in `/` creates an empty directory instead of a file
+pub fn create_tree(parent: &Path, name: &str, files: &[(&str, &[u8])]) -> anyhow::Result<PathBuf> {
+    let root = parent.join(name);
+    fs::create_dir_all(&root)?;
+    for (path, contents) in files {
+        let path = root.join(path);
+        if path.as_os_str().to_string_lossy().ends_with('/') {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, contents)?;
+    }
+    Ok(root)
+}
+
+/// deterministic pseudo-random bytes, which no compressor can shrink
+pub fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
@@ -0,0 +1,227 @@
+//! Tests for config files and named profiles
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+/// run zzz inside `project` with an isolated user config directory
+fn zzz_cmd(project: &Path, config_home: &Path) -> Command {
+    let mut cmd = cargo_bin_cmd!("zzz");
+    cmd.current_dir(project)
+        .env("XDG_CONFIG_HOME", config_home)
+        .env_remove("HOME");
+    cmd
+}
+
+fn setup() -> Result<(TempDir, std::path::PathBuf, std::path::PathBuf)> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    let config_home = temp_dir.path().join("xdg");
+    fs::create_dir_all(project.join("data"))?;
+    fs::create_dir_all(config_home.join("zzz"))?;
+    fs::write(project.join("data").join("keep.txt"), "keep")?;
+    fs::write(project.join("data").join("debug.log"), "log")?;
+    fs::write(project.join("data").join(".env"), "SECRET=1")?;
+    Ok((temp_dir, project, config_home))
+}
+
+#[test]
+fn test_config_show_merges_user_local_and_profile() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(
+        config_home.join("zzz").join("config.toml"),
+        "[compress]\nlevel = 5\n\n[profiles.backup.compress]\nformat = \"7z\"\nredact = true\n",
+    )?;
+    fs::write(
+        project.join(".zzz.toml"),
+        "[compress]\nlevel = 9\nexclude = [\"*.log\"]\n\n[extract]\nstrip_components = 1\n",
+    )?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# loaded: "))
+        .stdout(predicate::str::contains(".zzz.toml"))
+        .stdout(predicate::str::contains("level = 9"))
+        .stdout(predicate::str::contains("\"*.log\""))
+        .stdout(predicate::str::contains("strip_components = 1"))
+        .stdout(predicate::str::contains("format = \"zst\""));
+
+    zzz_cmd(&project, &config_home)
+        .args(["--profile", "backup", "config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# profile: backup"))
+        .stdout(predicate::str::contains("format = \"7z\""))
+        .stdout(predicate::str::contains("redact = true"));
+
+    Ok(())
+}
+
+#[test]
+fn test_profile_applies_to_compress() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(
+        project.join(".zzz.toml"),
+        "[profiles.backup.compress]\nformat = \"zip\"\nredact = true\nexclude = [\"*.log\"]\n",
+    )?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["--profile", "backup", "compress", "data", "-o", "out.zip"])
+        .assert()
+        .success();
+
+    let archive = zip::ZipArchive::new(fs::File::open(project.join("out.zip"))?)?;
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(names.iter().any(|name| name.ends_with("keep.txt")));
+    assert!(!names.iter().any(|name| name.ends_with("debug.log")));
+    assert!(!names.iter().any(|name| name.ends_with(".env")));
+
+    Ok(())
+}
+
+#[test]
+fn test_cli_flags_take_precedence_over_config() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(project.join(".zzz.toml"), "[compress]\nformat = \"7z\"\n")?;
+
+    // no -o: the configured format picks the default extension
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data"])
+        .assert()
+        .success();
+    assert!(project.join("data.7z").exists());
+
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "-f", "zip", "data"])
+        .assert()
+        .success();
+    assert!(project.join("data.zip").exists());
+
+    zzz_cmd(&project, &config_home)
+        .args(["--no-config", "compress", "data"])
+        .assert()
+        .success();
+    assert!(project.join("data.zst").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_output_extension_beats_configured_format() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(project.join(".zzz.toml"), "[compress]\nformat = \"7z\"\n")?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data/keep.txt", "-o", "out.zip"])
+        .assert()
+        .success();
+    let bytes = fs::read(project.join("out.zip"))?;
+    assert_eq!(&bytes[..4], b"PK\x03\x04");
+
+    // no recognisable extension: the configured format still applies
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data/keep.txt", "-o", "out.bin"])
+        .assert()
+        .success();
+    let bytes = fs::read(project.join("out.bin"))?;
+    assert_eq!(&bytes[..6], b"7z\xBC\xAF\x27\x1C");
+
+    Ok(())
+}
+
+#[test]
+fn test_cli_can_turn_off_configured_flags() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(project.join(".zzz.toml"), "[compress]\nredact = true\n")?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data", "-o", "redacted.zip"])
+        .assert()
+        .success();
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data", "-o", "plain.zip", "--redact=false"])
+        .assert()
+        .success();
+
+    let has_env = |name: &str| -> Result<bool> {
+        let archive = zip::ZipArchive::new(fs::File::open(project.join(name))?)?;
+        let found = archive.file_names().any(|entry| entry.ends_with(".env"));
+        Ok(found)
+    };
+    assert!(!has_env("redacted.zip")?);
+    assert!(has_env("plain.zip")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_profile_and_invalid_config_fail() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(project.join(".zzz.toml"), "[compress]\nlevel = 3\n")?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["--profile", "missing", "config", "show"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("profile 'missing' not found"));
+
+    fs::write(project.join(".zzz.toml"), "[compress]\nlevel = 99\n")?;
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid compression level 99"));
+
+    Ok(())
+}
+
+#[test]
+fn test_local_config_cannot_widen_what_is_read_or_overwritten() -> Result<()> {
+    let (_temp_dir, project, config_home) = setup()?;
+    fs::write(project.join("data").join(".DS_Store"), "finder")?;
+    fs::write(
+        project.join(".zzz.toml"),
+        "[compress]\nno_default_excludes = true\nfollow_symlinks = true\nlevel = 3\n\n\
+         [extract]\noverwrite = true\n\n[profiles.backup.extract]\nkeep_ownership = true\n",
+    )?;
+
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data", "-o", "local.zip"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "ignoring compress.follow_symlinks, compress.no_default_excludes, \
+             extract.overwrite, profiles.backup.extract.keep_ownership",
+        ));
+    let archive = zip::ZipArchive::new(fs::File::open(project.join("local.zip"))?)?;
+    assert!(!archive.file_names().any(|name| name.ends_with(".DS_Store")));
+
+    zzz_cmd(&project, &config_home)
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("level = 3"))
+        .stdout(predicate::str::contains("follow_symlinks = false"))
+        .stdout(predicate::str::contains("overwrite = false"));
+
+    // the user config is still trusted with them
+    fs::write(
+        config_home.join("zzz").join("config.toml"),
+        "[compress]\nno_default_excludes = true\n",
+    )?;
+    zzz_cmd(&project, &config_home)
+        .args(["compress", "data", "-o", "user.zip"])
+        .assert()
+        .success();
+    let archive = zip::ZipArchive::new(fs::File::open(project.join("user.zip"))?)?;
+    assert!(archive.file_names().any(|name| name.ends_with(".DS_Store")));
+
+    Ok(())
+}
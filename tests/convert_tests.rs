@@ -0,0 +1,366 @@
+//! Tests for converting archives between formats
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+/// 2021-06-01 12:00:00 UTC, even so it survives the 2-second zip resolution
+const MTIME: u64 = 1_622_548_800;
+
+/// a zip with a directory, an executable, a plain file and a symlink
+fn create_zip(path: &Path) -> Result<()> {
+    let mut writer = zip::ZipWriter::new(File::create(path)?);
+    let time = zip::DateTime::from_date_and_time(2021, 6, 1, 12, 0, 0)?;
+    let options = FileOptions::<()>::default().last_modified_time(time);
+    writer.add_directory("project/", options.unix_permissions(0o755))?;
+    writer.start_file("project/run.sh", options.unix_permissions(0o755))?;
+    writer.write_all(b"#!/bin/sh\necho hi\n")?;
+    writer.start_file("project/notes.txt", options.unix_permissions(0o640))?;
+    writer.write_all(&b"converted notes\n".repeat(50))?;
+    writer.start_file("project/.env", options.unix_permissions(0o600))?;
+    writer.write_all(b"SECRET=1\n")?;
+    writer.add_symlink("project/latest", "notes.txt", options)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// a gzipped tarball whose entries belong to uid/gid 1000
+fn create_owned_tarball(path: &Path) -> Result<()> {
+    let encoder = flate2::write::GzEncoder::new(File::create(path)?, flate2::Compression::fast());
+    let mut builder = tar::Builder::new(encoder);
+    let contents = b"owned by someone\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_uid(1000);
+    header.set_gid(1000);
+    header.set_mtime(MTIME);
+    builder.append_data(&mut header, "owned/file.txt", &contents[..])?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+struct TarEntry {
+    path: String,
+    mode: u32,
+    mtime: u64,
+    link: Option<String>,
+    contents: Vec<u8>,
+}
+
+fn read_tar_zst(path: &Path) -> Result<Vec<TarEntry>> {
+    let decoder = zstd::stream::read::Decoder::new(File::open(path)?)?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        entries.push(TarEntry {
+            path: entry.path()?.to_string_lossy().into_owned(),
+            mode: entry.header().mode()?,
+            mtime: entry.header().mtime()?,
+            link: entry
+                .link_name()?
+                .map(|link| link.to_string_lossy().into_owned()),
+            contents,
+        });
+    }
+    Ok(entries)
+}
+
+fn find<'a>(entries: &'a [TarEntry], path: &str) -> &'a TarEntry {
+    entries
+        .iter()
+        .find(|entry| entry.path == path)
+        .unwrap_or_else(|| panic!("{path} missing from the converted archive"))
+}
+
+#[test]
+fn test_convert_zip_to_tar_zst_keeps_metadata() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("project.zip");
+    let output = temp_dir.path().join("project.tar.zst");
+    create_zip(&input)?;
+
+    zzz_cmd()
+        .arg("convert")
+        .arg(&input)
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("converted"))
+        .stderr(predicate::str::contains("warning").not());
+
+    let entries = read_tar_zst(&output)?;
+    assert_eq!(find(&entries, "project/").mode, 0o755);
+    let script = find(&entries, "project/run.sh");
+    assert_eq!(script.mode, 0o755);
+    assert_eq!(script.mtime, MTIME);
+    assert_eq!(script.contents, b"#!/bin/sh\necho hi\n");
+    assert_eq!(find(&entries, "project/notes.txt").mode, 0o640);
+    assert_eq!(
+        find(&entries, "project/latest").link.as_deref(),
+        Some("notes.txt")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_tarball_to_zip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = temp_dir.path().join("tree");
+    fs::create_dir_all(tree.join("nested"))?;
+    fs::write(tree.join("a.txt"), "alpha\n".repeat(100))?;
+    fs::write(tree.join("nested/b.txt"), "beta\n")?;
+    let input = temp_dir.path().join("tree.tar.xz");
+    let output = temp_dir.path().join("tree.zip");
+    zzz_cmd()
+        .args(["compress", "-l", "1", "-o"])
+        .arg(&input)
+        .arg(&tree)
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .arg("convert")
+        .arg(&input)
+        .arg(&output)
+        .assert()
+        .success();
+
+    let mut archive = zip::ZipArchive::new(File::open(&output)?)?;
+    let mut contents = String::new();
+    archive
+        .by_name("tree/nested/b.txt")?
+        .read_to_string(&mut contents)?;
+    assert_eq!(contents, "beta\n");
+    assert!(archive.by_name("tree/nested/")?.is_dir());
+
+    zzz_cmd()
+        .arg("extract")
+        .arg(&output)
+        .arg("-C")
+        .arg(temp_dir.path().join("out"))
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(temp_dir.path().join("out/tree/a.txt"))?,
+        fs::read(tree.join("a.txt"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_warns_about_lost_metadata() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_input = temp_dir.path().join("project.zip");
+    create_zip(&zip_input)?;
+
+    // 7z keeps neither symlinks nor unix modes
+    let sevenz = temp_dir.path().join("project.7z");
+    zzz_cmd()
+        .args(["convert", "-l", "1"])
+        .arg(&zip_input)
+        .arg(&sevenz)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skipped 1 symlink(s)"))
+        .stderr(predicate::str::contains("dropped the permissions"));
+    zzz_cmd()
+        .arg("list")
+        .arg(&sevenz)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project/run.sh"))
+        .stdout(predicate::str::contains("project/latest").not());
+
+    // zip has no uid/gid
+    let tarball = temp_dir.path().join("owned.tar.gz");
+    create_owned_tarball(&tarball)?;
+    zzz_cmd()
+        .arg("convert")
+        .arg(&tarball)
+        .arg(temp_dir.path().join("owned.zip"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "dropped the ownership of 1 entries",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_7z_back_to_tarball() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_input = temp_dir.path().join("project.zip");
+    let sevenz = temp_dir.path().join("project.7z");
+    let output = temp_dir.path().join("project.tar.zst");
+    create_zip(&zip_input)?;
+
+    zzz_cmd()
+        .args(["convert", "-l", "1"])
+        .arg(&zip_input)
+        .arg(&sevenz)
+        .assert()
+        .success();
+    zzz_cmd()
+        .arg("convert")
+        .arg(&sevenz)
+        .arg(&output)
+        .assert()
+        .success();
+
+    let entries = read_tar_zst(&output)?;
+    find(&entries, "project/");
+    let notes = find(&entries, "project/notes.txt");
+    assert_eq!(notes.contents, "converted notes\n".repeat(50).as_bytes());
+    assert_eq!(notes.mtime, MTIME);
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_applies_filter_and_redact() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("project.zip");
+    create_zip(&input)?;
+
+    let filtered = temp_dir.path().join("filtered.tar.zst");
+    zzz_cmd()
+        .args(["convert", "-e", "*.sh"])
+        .arg(&input)
+        .arg(&filtered)
+        .assert()
+        .success();
+    let entries = read_tar_zst(&filtered)?;
+    assert!(entries.iter().all(|entry| entry.path != "project/run.sh"));
+    assert!(entries.iter().any(|entry| entry.path == "project/.env"));
+
+    let redacted = temp_dir.path().join("redacted.tar.zst");
+    zzz_cmd()
+        .args(["convert", "--redact"])
+        .arg(&input)
+        .arg(&redacted)
+        .assert()
+        .success();
+    let entries = read_tar_zst(&redacted)?;
+    assert!(entries.iter().all(|entry| entry.path != "project/.env"));
+    let script = find(&entries, "project/run.sh");
+    assert_eq!(script.mode, 0o644);
+    assert_eq!(script.mtime, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_refuses_to_overwrite_input() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("project.zip");
+    create_zip(&input)?;
+
+    zzz_cmd()
+        .args(["convert", "-y"])
+        .arg(&input)
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is the archive being converted"));
+    assert!(zip::ZipArchive::new(File::open(&input)?).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_raw_stream_to_tarball() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = temp_dir.path().join("report.txt");
+    let contents = "raw stream line\n".repeat(10_000);
+    fs::write(&source, &contents)?;
+    let input = temp_dir.path().join("report.txt.gz");
+    zzz_cmd()
+        .args(["compress", "-f", "gz", "-o"])
+        .arg(&input)
+        .arg(&source)
+        .assert()
+        .success();
+
+    // the raw stream has no recorded size, so its contents are spooled before the tar header
+    let output = temp_dir.path().join("report.tar.zst");
+    zzz_cmd()
+        .arg("convert")
+        .arg(&input)
+        .arg(&output)
+        .assert()
+        .success();
+    let entries = read_tar_zst(&output)?;
+    assert_eq!(find(&entries, "report.txt").contents, contents.as_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn test_convert_encrypted_input() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_input = temp_dir.path().join("project.zip");
+    let input = temp_dir.path().join("project.7z");
+    create_zip(&zip_input)?;
+    zzz_cmd()
+        .args(["convert", "--output-password", "pw"])
+        .arg(&zip_input)
+        .arg(&input)
+        .assert()
+        .success();
+
+    // dropping the encryption is allowed, but never silent
+    let plain = temp_dir.path().join("plain.tar.zst");
+    zzz_cmd()
+        .args(["convert", "-p", "pw"])
+        .arg(&input)
+        .arg(&plain)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("is encrypted but"));
+    find(&read_tar_zst(&plain)?, "project/notes.txt");
+
+    let sealed = temp_dir.path().join("sealed.tar.zst");
+    zzz_cmd()
+        .args(["convert", "-p", "pw", "--output-password", "other"])
+        .arg(&input)
+        .arg(&sealed)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("is encrypted but").not());
+    assert!(fs::read(&sealed)?.starts_with(b"ZSTDECRYPT1"));
+    zzz_cmd()
+        .args(["list", "-p", "other"])
+        .arg(&sealed)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project/notes.txt"));
+
+    // formats without encryption refuse an output password
+    zzz_cmd()
+        .args(["convert", "-p", "pw", "--output-password", "pw"])
+        .arg(&input)
+        .arg(temp_dir.path().join("out.zip"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not supported for ZIP"));
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+//! Tests for the deduplicating archive format
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::noise;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+/// a tree holding one large file, an exact copy and a copy with a byte inserted
+fn test_tree(temp_dir: &Path) -> Result<Vec<u8>> {
+    let data = noise(2 * 1024 * 1024, 7);
+    let mut edited = data.clone();
+    edited.insert(1024 * 1024, b'!');
+    common::create_tree(
+        temp_dir,
+        "tree",
+        &[
+            ("data.bin", &data),
+            ("copies/same.bin", &data),
+            ("copies/edited.bin", &edited),
+            ("empty/", b""),
+            ("notes.txt", b"notes\n"),
+        ],
+    )?;
+    Ok(data)
+}
+
+#[test]
+fn test_dedup_roundtrip_stores_shared_chunks_once() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let data = test_tree(temp_dir.path())?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "tree.zzd", "tree"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dedup: 4 files"));
+
+    // three near-identical copies take little more room than one
+    let size = fs::metadata(temp_dir.path().join("tree.zzd"))?.len();
+    assert!(
+        size < data.len() as u64 * 12 / 10,
+        "archive is {size} bytes"
+    );
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "tree.zzd", "-C", "out"])
+        .assert()
+        .success();
+    let out = temp_dir.path().join("out/tree");
+    assert_eq!(fs::read(out.join("data.bin"))?, data);
+    assert_eq!(fs::read(out.join("copies/same.bin"))?, data);
+    assert_eq!(
+        fs::read(out.join("copies/edited.bin"))?,
+        fs::read(temp_dir.path().join("tree/copies/edited.bin"))?
+    );
+    assert_eq!(fs::read_to_string(out.join("notes.txt"))?, "notes\n");
+    assert!(out.join("empty").is_dir());
+    Ok(())
+}
+
+#[test]
+fn test_dedup_list_and_test_report() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    test_tree(temp_dir.path())?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "tree.zzd", "tree"])
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "tree.zzd"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("tree/copies/edited.bin"))
+        .stdout(predicate::str::contains("tree/empty"));
+
+    let output = zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["test", "tree.zzd"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("tree.zzd integrity: OK"));
+    let ratio: f64 = stdout
+        .split("(ratio ")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .expect("dedup ratio in the report")
+        .parse()?;
+    assert!(ratio > 2.5, "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn test_dedup_detects_corruption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    test_tree(temp_dir.path())?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "tree.zzd", "tree"])
+        .assert()
+        .success();
+
+    let path = temp_dir.path().join("tree.zzd");
+    let mut bytes = fs::read(&path)?;
+    bytes[100_000] ^= 0xff;
+    fs::write(&path, bytes)?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["test", "tree.zzd"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("corrupt"));
+    Ok(())
+}
+
+#[test]
+fn test_dedup_encryption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    fs::create_dir(temp_dir.path().join("secret"))?;
+    fs::write(
+        temp_dir.path().join("secret/plans.txt"),
+        "top secret plans\n",
+    )?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "compress",
+            "-l",
+            "1",
+            "-p",
+            "pw",
+            "-o",
+            "secret.zzd",
+            "secret",
+        ])
+        .assert()
+        .success();
+
+    // neither names nor contents are readable without the password
+    let bytes = fs::read(temp_dir.path().join("secret.zzd"))?;
+    assert!(!bytes.windows(5).any(|window| window == b"plans"));
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "secret.zzd"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("requires a password"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "-p", "wrong", "secret.zzd", "-C", "out"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid password"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "-p", "pw", "secret.zzd", "-C", "out"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/secret/plans.txt"))?,
+        "top secret plans\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_dedup_convert_roundtrip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let data = test_tree(temp_dir.path())?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "tree.tar.zst", "tree"])
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["convert", "-l", "1", "tree.tar.zst", "tree.zzd"])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["convert", "-l", "1", "tree.zzd", "back.zip"])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "back.zip", "-C", "out"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(temp_dir.path().join("out/tree/copies/same.bin"))?,
+        data
+    );
+    Ok(())
+}
+
+#[test]
+fn test_dedup_modify_reuses_stored_chunks() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let data = noise(2 * 1024 * 1024, 11);
+    common::create_tree(
+        temp_dir.path(),
+        "tree",
+        &[("data.bin", &data), ("notes.txt", b"notes\n")],
+    )?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "tree.zzd", "tree"])
+        .assert()
+        .success();
+    let archive = temp_dir.path().join("tree.zzd");
+    let full = fs::metadata(&archive)?.len();
+
+    // an edited copy only stores the chunks around the edit
+    let mut edited = data.clone();
+    edited.insert(1024 * 1024, b'!');
+    fs::write(temp_dir.path().join("edited.bin"), &edited)?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "tree.zzd", "edited.bin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 added"));
+    let grown = fs::metadata(&archive)?.len() - full;
+    assert!(grown < data.len() as u64 / 10, "grew by {grown} bytes");
+
+    // chunks still in use survive removing the file that brought them
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["rm", "tree.zzd", "tree/data.bin"])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["test", "tree.zzd"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dedup: 2 files"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "tree.zzd", "-C", "out"])
+        .assert()
+        .success();
+    assert_eq!(fs::read(temp_dir.path().join("out/edited.bin"))?, edited);
+    assert!(!temp_dir.path().join("out/tree/data.bin").exists());
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! Tests for zstd dictionary training and --dict compression
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn record(kind: &str, i: u32) -> String {
+    format!(
+        "{{\"id\":{i},\"kind\":\"{kind}\",\"user\":{{\"name\":\"user-{}\",\"email\":\"user{}@example.com\",\"active\":{}}},\"tags\":[\"alpha\",\"beta\",\"{kind}\"],\"score\":{}.{}}}\n",
+        i * 7 % 101,
+        i * 13 % 97,
+        i.is_multiple_of(2),
+        i % 50,
+        i % 10
+    )
+}
+
+/// write `count` small json records of one `kind` into `parent/name`
+fn create_records(parent: &Path, name: &str, kind: &str, count: u32) -> Result<PathBuf> {
+    let dir = parent.join(name);
+    fs::create_dir_all(&dir)?;
+    for i in 0..count {
+        fs::write(dir.join(format!("{i:04}.json")), record(kind, i))?;
+    }
+    Ok(dir)
+}
+
+fn train(samples: &Path, output: &Path) {
+    zzz_cmd()
+        .args(["dict", "train", "--max-size", "4K", "-o"])
+        .arg(output)
+        .arg(samples)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("trained dictionary ID"));
+}
+
+#[test]
+fn test_dict_round_trip_for_tar_and_raw() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let samples = create_records(temp_dir.path(), "samples", "event", 600)?;
+    let records = create_records(temp_dir.path(), "records", "event", 50)?;
+    let dict = temp_dir.path().join("events.dict");
+    train(&samples, &dict);
+
+    // tarball of many records
+    let archive = temp_dir.path().join("records.tar.zst");
+    zzz_cmd()
+        .args(["compress", "--dict"])
+        .arg(&dict)
+        .arg("-o")
+        .arg(&archive)
+        .arg(&records)
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .args(["list", "--dict"])
+        .arg(&dict)
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("records/0007.json"));
+    zzz_cmd()
+        .args(["test", "--dict"])
+        .arg(&dict)
+        .arg(&archive)
+        .assert()
+        .success();
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .args(["extract", "--dict"])
+        .arg(&dict)
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(extract_dir.join("records/0007.json"))?,
+        fs::read(records.join("0007.json"))?
+    );
+
+    // a single raw record compresses better with the dictionary
+    let input = records.join("0042.json");
+    let with_dict = temp_dir.path().join("0042.json.zst");
+    zzz_cmd()
+        .args(["compress", "--dict"])
+        .arg(&dict)
+        .arg("-o")
+        .arg(&with_dict)
+        .arg(&input)
+        .assert()
+        .success();
+    let without_dict = temp_dir.path().join("plain/0042.json.zst");
+    fs::create_dir_all(without_dict.parent().unwrap())?;
+    zzz_cmd()
+        .arg("compress")
+        .arg("-o")
+        .arg(&without_dict)
+        .arg(&input)
+        .assert()
+        .success();
+    assert!(fs::metadata(&with_dict)?.len() < fs::metadata(&without_dict)?.len());
+
+    let raw_dir = temp_dir.path().join("raw");
+    zzz_cmd()
+        .args(["extract", "--dict"])
+        .arg(&dict)
+        .arg(&with_dict)
+        .arg("-C")
+        .arg(&raw_dir)
+        .assert()
+        .success();
+    assert_eq!(fs::read(raw_dir.join("0042.json"))?, fs::read(&input)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_or_wrong_dict_is_reported() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let samples = create_records(temp_dir.path(), "samples", "event", 600)?;
+    let other_samples = create_records(temp_dir.path(), "other", "metric", 600)?;
+    let records = create_records(temp_dir.path(), "records", "event", 20)?;
+    let dict = temp_dir.path().join("events.dict");
+    let other_dict = temp_dir.path().join("metrics.dict");
+    train(&samples, &dict);
+    train(&other_samples, &other_dict);
+
+    let archive = temp_dir.path().join("records.tar.zst");
+    zzz_cmd()
+        .args(["compress", "--dict"])
+        .arg(&dict)
+        .arg("-o")
+        .arg(&archive)
+        .arg(&records)
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(temp_dir.path().join("none"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "was compressed with zstd dictionary ID",
+        ))
+        .stderr(predicate::str::contains("pass it with --dict"));
+
+    zzz_cmd()
+        .args(["extract", "--dict"])
+        .arg(&other_dict)
+        .arg(&archive)
+        .arg("-C")
+        .arg(temp_dir.path().join("wrong"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("wrong zstd dictionary"));
+
+    Ok(())
+}
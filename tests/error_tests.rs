@@ -46,7 +46,7 @@ fn test_list_nonexistent_archive() {
     let temp_dir = TempDir::new().unwrap();
     let nonexistent_archive = temp_dir.path().join("does_not_exist.zst");
 
-    let result = ZstdFormat::list(&nonexistent_archive);
+    let result = ZstdFormat::list(&nonexistent_archive, &ExtractionOptions::default());
     assert!(result.is_err());
 
     let error_msg = result.unwrap_err().to_string();
@@ -88,7 +88,7 @@ fn test_list_corrupted_archive() -> Result<()> {
     // Create corrupted archive
     fs::write(&corrupted_archive, b"Not a zstd file")?;
 
-    let result = ZstdFormat::list(&corrupted_archive);
+    let result = ZstdFormat::list(&corrupted_archive, &ExtractionOptions::default());
     assert!(result.is_err());
 
     Ok(())
@@ -286,7 +286,10 @@ fn test_zip_integrity_detects_corruption() -> Result<()> {
     }
     fs::write(&archive_path, data)?;
 
-    let result = zzz_arc::formats::zip::ZipFormat::test_integrity(&archive_path);
+    let result = zzz_arc::formats::zip::ZipFormat::test_integrity(
+        &archive_path,
+        &ExtractionOptions::default(),
+    );
     assert!(result.is_err());
 
     Ok(())
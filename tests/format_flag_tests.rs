@@ -154,3 +154,37 @@ fn test_format_flag_with_excludes() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_tzst_extension_selects_zstd_tarball() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("project");
+    fs::create_dir(&input)?;
+    fs::write(input.join("notes.txt"), "tzst content")?;
+
+    // `.tzst` is the short name of `.tar.zst`, so no -f is needed either way
+    let archive = temp_dir.path().join("project.tzst");
+    cargo_bin_cmd!("zzz")
+        .arg("compress")
+        .arg("-o")
+        .arg(&archive)
+        .arg(&input)
+        .assert()
+        .success();
+    assert!(fs::read(&archive)?.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+
+    let output = temp_dir.path().join("out");
+    cargo_bin_cmd!("zzz")
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output.join("project/notes.txt"))?,
+        "tzst content"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+//! Tests for incremental backups and restoring them
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+/// back up `data` below `temp_dir` to `archive`, recording state in `state.json`
+fn backup(temp_dir: &Path, archive: &str) {
+    zzz_cmd()
+        .current_dir(temp_dir)
+        .args([
+            "compress",
+            "-l",
+            "1",
+            "-y",
+            "--snapshot",
+            "state.json",
+            "-o",
+            archive,
+            "data",
+        ])
+        .assert()
+        .success();
+}
+
+fn tar_zst_names(path: &Path) -> Result<Vec<String>> {
+    let mut archive = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(path)?)?);
+    let mut names = Vec::new();
+    for entry in archive.entries()? {
+        names.push(entry?.path()?.to_string_lossy().to_string());
+    }
+    Ok(names)
+}
+
+fn create_data(temp_dir: &Path) -> Result<()> {
+    let data = temp_dir.join("data");
+    fs::create_dir_all(data.join("docs"))?;
+    fs::create_dir_all(data.join("old"))?;
+    fs::write(data.join("keep.txt"), "keep\n")?;
+    fs::write(data.join("docs/notes.txt"), "notes\n")?;
+    fs::write(data.join("old/stale.txt"), "stale\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_incremental_archives_only_changes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_data(temp_dir.path())?;
+    backup(temp_dir.path(), "full.tar.zst");
+
+    let full = tar_zst_names(&temp_dir.path().join("full.tar.zst"))?;
+    assert_eq!(full[0], ".zzz-incremental.json");
+    assert!(full.contains(&"data/keep.txt".to_string()));
+
+    let data = temp_dir.path().join("data");
+    fs::write(data.join("docs/notes.txt"), "more notes\n")?;
+    fs::write(data.join("docs/new.txt"), "new\n")?;
+    // same content with a new mtime is not a change
+    filetime::set_file_mtime(
+        data.join("keep.txt"),
+        filetime::FileTime::from_unix_time(1_000_000_000, 0),
+    )?;
+    fs::remove_dir_all(data.join("old"))?;
+    backup(temp_dir.path(), "inc1.tar.zst");
+
+    let path = temp_dir.path().join("inc1.tar.zst");
+    let mut names = tar_zst_names(&path)?;
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            ".zzz-incremental.json",
+            "data/docs/new.txt",
+            "data/docs/notes.txt"
+        ]
+    );
+
+    let mut archive = tar::Archive::new(zstd::stream::read::Decoder::new(File::open(&path)?)?);
+    let mut manifest = String::new();
+    archive
+        .entries()?
+        .next()
+        .expect("manifest entry")?
+        .read_to_string(&mut manifest)?;
+    let manifest: serde_json::Value = serde_json::from_str(&manifest)?;
+    assert_eq!(manifest["level"], 1);
+    assert_eq!(
+        manifest["deleted"],
+        serde_json::json!(["data/old", "data/old/stale.txt"])
+    );
+
+    let snapshot = fs::read_to_string(temp_dir.path().join("state.json"))?;
+    assert!(snapshot.contains("\"level\": 1"));
+    assert!(snapshot.contains("\"data/docs/new.txt\""));
+    assert!(!snapshot.contains("stale.txt"));
+    Ok(())
+}
+
+#[test]
+fn test_restore_replays_chain() -> Result<()> {
+    for ext in ["tar.zst", "zip", "7z"] {
+        let temp_dir = TempDir::new()?;
+        create_data(temp_dir.path())?;
+        backup(temp_dir.path(), &format!("full.{ext}"));
+
+        let data = temp_dir.path().join("data");
+        fs::remove_dir_all(data.join("old"))?;
+        fs::write(data.join("docs/notes.txt"), "more notes\n")?;
+        backup(temp_dir.path(), &format!("inc1.{ext}"));
+
+        // a file replaced by a directory of the same name
+        fs::remove_file(data.join("keep.txt"))?;
+        fs::create_dir(data.join("keep.txt"))?;
+        fs::write(data.join("keep.txt/inner.txt"), "inner\n")?;
+        backup(temp_dir.path(), &format!("inc2.{ext}"));
+
+        zzz_cmd()
+            .current_dir(temp_dir.path())
+            .args([
+                "restore",
+                &format!("full.{ext}"),
+                &format!("inc1.{ext}"),
+                &format!("inc2.{ext}"),
+                "-C",
+                "restored",
+            ])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("restored 3 archives"));
+
+        let restored = temp_dir.path().join("restored/data");
+        assert_eq!(
+            fs::read_to_string(restored.join("docs/notes.txt"))?,
+            "more notes\n",
+            "{ext}"
+        );
+        assert_eq!(
+            fs::read_to_string(restored.join("keep.txt/inner.txt"))?,
+            "inner\n",
+            "{ext}"
+        );
+        assert!(!restored.join("old").exists(), "{ext}");
+        assert!(!temp_dir
+            .path()
+            .join("restored/.zzz-incremental.json")
+            .exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_restore_checks_order() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_data(temp_dir.path())?;
+    backup(temp_dir.path(), "full.tar.zst");
+    backup(temp_dir.path(), "inc1.tar.zst");
+    backup(temp_dir.path(), "inc2.tar.zst");
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["restore", "inc1.tar.zst", "-C", "restored"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "starts from a full (level 0) backup",
+        ));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["restore", "full.tar.zst", "inc2.tar.zst", "-C", "restored"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is a level 2 backup and cannot follow a level 0 backup",
+        ));
+    // nothing is written before the whole chain is checked
+    assert!(!temp_dir.path().join("restored").exists());
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-o", "plain.tar.zst", "data"])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["restore", "plain.tar.zst", "-C", "restored"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not an incremental backup"));
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_inside_input_is_skipped() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_data(temp_dir.path())?;
+    let args = |archive: &'static str| {
+        [
+            "compress",
+            "-l",
+            "1",
+            "--snapshot",
+            "data/state.json",
+            "-o",
+            archive,
+            "data",
+        ]
+    };
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(args("full.tar.zst"))
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(args("inc1.tar.zst"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        tar_zst_names(&temp_dir.path().join("inc1.tar.zst"))?,
+        [".zzz-incremental.json"]
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_permission_change_is_archived() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    create_data(temp_dir.path())?;
+    backup(temp_dir.path(), "full.tar.zst");
+
+    let script = temp_dir.path().join("data/keep.txt");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    backup(temp_dir.path(), "inc1.tar.zst");
+
+    assert_eq!(
+        tar_zst_names(&temp_dir.path().join("inc1.tar.zst"))?,
+        [".zzz-incremental.json", "data/keep.txt"]
+    );
+    let snapshot = fs::read_to_string(temp_dir.path().join("state.json"))?;
+    assert!(snapshot.contains("\"mode\":493"), "{snapshot}");
+    Ok(())
+}
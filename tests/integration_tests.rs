@@ -140,7 +140,7 @@ fn test_list_archive_contents() -> Result<()> {
     ZstdFormat::compress(&source_dir, &archive_path, &options, &filter, None)?;
 
     // List contents
-    let entries = ZstdFormat::list(&archive_path)?;
+    let entries = ZstdFormat::list(&archive_path, &ExtractionOptions::default())?;
 
     // Verify entries
     assert!(!entries.is_empty());
@@ -0,0 +1,606 @@
+//! Tests for adding files to existing archives
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+/// a project directory with two files, archived as `archive`
+fn create_archive(temp_dir: &Path, archive: &str) -> Result<()> {
+    let project = temp_dir.join("project");
+    fs::create_dir_all(project.join("src"))?;
+    fs::write(project.join("README.md"), "readme\n")?;
+    fs::write(project.join("src/main.rs"), "fn main() {}\n")?;
+    zzz_cmd()
+        .current_dir(temp_dir)
+        .args(["compress", "-l", "1", "-o", archive, "project"])
+        .assert()
+        .success();
+    Ok(())
+}
+
+fn read_tar_entries<R: Read>(reader: R) -> Result<Vec<(String, u32, u64, String)>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        entries.push((
+            entry
+                .path()?
+                .to_string_lossy()
+                .trim_end_matches('/')
+                .to_string(),
+            entry.header().mode()?,
+            entry.header().mtime()?,
+            contents,
+        ));
+    }
+    Ok(entries)
+}
+
+fn read_tar_zst(path: &Path) -> Result<Vec<(String, u32, u64, String)>> {
+    read_tar_entries(zstd::stream::read::Decoder::new(File::open(path)?)?)
+}
+
+fn zip_contents(path: &Path, name: &str) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut contents = String::new();
+    archive.by_name(name)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[test]
+fn test_add_appends_to_zip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.zip")?;
+    let archive = temp_dir.path().join("project.zip");
+    let original = fs::read(&archive)?;
+    fs::write(temp_dir.path().join("notes.txt"), "notes\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.zip", "notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 added, 0 replaced"));
+
+    assert_eq!(zip_contents(&archive, "notes.txt")?, "notes\n");
+    assert_eq!(
+        zip_contents(&archive, "project/src/main.rs")?,
+        "fn main() {}\n"
+    );
+    // the existing entries were left where they were
+    let first_entry = zip::ZipArchive::new(File::open(&archive)?)?
+        .by_index_raw(0)?
+        .data_start()
+        .unwrap_or_default() as usize;
+    assert_eq!(fs::read(&archive)?[..first_entry], original[..first_entry]);
+    // appends go through a copy that replaces the archive
+    for file in fs::read_dir(temp_dir.path())? {
+        let name = file?.file_name();
+        assert!(!name.to_string_lossy().ends_with(".zzz-tmp"), "{name:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_add_appends_to_tar() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.tar")?;
+    let extra = temp_dir.path().join("extra");
+    fs::create_dir(&extra)?;
+    fs::write(extra.join("data.csv"), "a,b\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.tar", "extra"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 added, 0 replaced"));
+
+    let entries = read_tar_entries(File::open(temp_dir.path().join("project.tar"))?)?;
+    let names: Vec<_> = entries.iter().map(|entry| entry.0.as_str()).collect();
+    assert!(names.contains(&"project/README.md"));
+    assert!(names.contains(&"extra"));
+    assert!(names.contains(&"extra/data.csv"));
+
+    Ok(())
+}
+
+#[test]
+fn test_add_replaces_entries_in_compressed_tarball() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.tar.zst")?;
+    fs::write(temp_dir.path().join("project/README.md"), "new readme\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.tar.zst", "project"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 added, 2 replaced"));
+
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let readmes: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.0 == "project/README.md")
+        .collect();
+    assert_eq!(readmes.len(), 1);
+    assert_eq!(readmes[0].3, "new readme\n");
+    assert!(entries.iter().any(|entry| entry.0 == "project/src/main.rs"));
+    // the temporary file was moved over the archive
+    assert_eq!(fs::read_dir(temp_dir.path())?.count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_update_replaces_only_newer_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.zip")?;
+    let archive = temp_dir.path().join("project.zip");
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["update", "project.zip", "project"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 added, 0 replaced"));
+
+    let main_rs = temp_dir.path().join("project/src/main.rs");
+    fs::write(&main_rs, "fn main() { println!(\"hi\"); }\n")?;
+    let later = filetime::FileTime::from_unix_time(
+        filetime::FileTime::from_last_modification_time(&fs::metadata(&main_rs)?).unix_seconds()
+            + 3600,
+        0,
+    );
+    filetime::set_file_mtime(&main_rs, later)?;
+    fs::write(temp_dir.path().join("project/src/lib.rs"), "")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["update", "project.zip", "project"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 added, 1 replaced"));
+
+    assert_eq!(
+        zip_contents(&archive, "project/src/main.rs")?,
+        "fn main() { println!(\"hi\"); }\n"
+    );
+    assert_eq!(zip_contents(&archive, "project/README.md")?, "readme\n");
+    assert_eq!(zip::ZipArchive::new(File::open(&archive)?)?.len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_add_to_encrypted_7z_keeps_encryption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project)?;
+    fs::write(project.join("secret.txt"), "secret\n")?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "compress",
+            "-l",
+            "1",
+            "-p",
+            "pw",
+            "-o",
+            "project.7z",
+            "project",
+        ])
+        .assert()
+        .success();
+    fs::write(temp_dir.path().join("added.txt"), "added\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.7z", "added.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no password was provided"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "-p", "pw", "project.7z", "added.txt"])
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "project.7z", "-C", "out"])
+        .assert()
+        .failure();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["extract", "-p", "pw", "project.7z", "-C", "out"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/project/secret.txt"))?,
+        "secret\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/added.txt"))?,
+        "added\n"
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_add_honors_normalization_options() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.tar.zst")?;
+    let script = temp_dir.path().join("run.sh");
+    fs::write(&script, "#!/bin/sh\n")?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.tar.zst", "run.sh"])
+        .assert()
+        .success();
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let added = entries.iter().find(|entry| entry.0 == "run.sh").unwrap();
+    assert_eq!(added.1, 0o644);
+    assert_ne!(added.2, 0);
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "--keep-permissions", "project.tar.zst", "run.sh"])
+        .assert()
+        .success();
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let added = entries.iter().find(|entry| entry.0 == "run.sh").unwrap();
+    assert_eq!(added.1, 0o755);
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "--redact", "project.tar.zst", "run.sh"])
+        .assert()
+        .success();
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let added = entries.iter().find(|entry| entry.0 == "run.sh").unwrap();
+    assert_eq!((added.1, added.2), (0o644, 0));
+
+    Ok(())
+}
+
+#[test]
+fn test_add_rejects_single_file_stream() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    fs::write(temp_dir.path().join("data.txt"), "data\n")?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "-o", "data.txt.zst", "data.txt"])
+        .assert()
+        .success();
+    fs::write(temp_dir.path().join("more.txt"), "more\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "data.txt.zst", "more.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a tarball"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rm_copies_remaining_zip_entries_raw() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project)?;
+    fs::write(project.join("data.txt"), "data\n".repeat(200))?;
+    fs::write(project.join(".env"), "SECRET=1\n")?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-E", "-o", "project.zip", "project"])
+        .assert()
+        .success();
+    let archive = temp_dir.path().join("project.zip");
+    let compressed = |archive: &Path| -> Result<(u64, u32)> {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?)?;
+        let file = zip.by_name("project/data.txt")?;
+        Ok((file.compressed_size(), file.crc32()))
+    };
+    let before = compressed(&archive)?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["rm", "project.zip", ".env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("removed 1 entries"));
+
+    let mut zip = zip::ZipArchive::new(File::open(&archive)?)?;
+    assert!(zip.by_name("project/.env").is_err());
+    assert_eq!(compressed(&archive)?, before);
+    assert_eq!(
+        zip_contents(&archive, "project/data.txt")?,
+        "data\n".repeat(200)
+    );
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["rm", "project.zip", "missing.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no archive entries match"));
+
+    Ok(())
+}
+
+#[test]
+fn test_mv_renames_directory_in_tarball() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.tar.zst")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "mv",
+            "-l",
+            "1",
+            "project.tar.zst",
+            "project/src",
+            "project/lib",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("renamed 2 entries"));
+
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let names: Vec<_> = entries.iter().map(|entry| entry.0.as_str()).collect();
+    assert!(names.contains(&"project/lib"));
+    assert!(names.contains(&"project/README.md"));
+    assert!(!names.iter().any(|name| name.starts_with("project/src")));
+    let moved = entries
+        .iter()
+        .find(|entry| entry.0 == "project/lib/main.rs")
+        .unwrap();
+    assert_eq!(moved.3, "fn main() {}\n");
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "mv",
+            "project.tar.zst",
+            "project/lib/main.rs",
+            "project/README.md",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists in the archive"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rm_keeps_zstd_encryption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project)?;
+    fs::write(project.join("keep.txt"), "keep\n")?;
+    fs::write(project.join("drop.txt"), "drop\n")?;
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "compress",
+            "-l",
+            "1",
+            "-p",
+            "pw",
+            "-o",
+            "project.tar.zst",
+            "project",
+        ])
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "rm",
+            "-l",
+            "1",
+            "-p",
+            "pw",
+            "project.tar.zst",
+            "project/drop.txt",
+        ])
+        .assert()
+        .success();
+
+    assert!(fs::read(temp_dir.path().join("project.tar.zst"))?.starts_with(b"ZSTDECRYPT1"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "-p", "pw", "project.tar.zst"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project/keep.txt"))
+        .stdout(predicate::str::contains("drop.txt").not());
+
+    Ok(())
+}
+
+#[test]
+fn test_add_names_entries_as_given() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    create_archive(temp_dir.path(), "project.tar.zst")?;
+    let docs = temp_dir.path().join("t/docs");
+    fs::create_dir_all(&docs)?;
+    fs::write(docs.join("a.txt"), "as given\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["update", "project.tar.zst", "t/docs/a.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 added"));
+    // -C reads the paths from a directory without putting it in the names
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "-C", "t", "project.tar.zst", "docs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 added, 0 replaced"));
+
+    let entries = read_tar_zst(&temp_dir.path().join("project.tar.zst"))?;
+    let names: Vec<_> = entries.iter().map(|entry| entry.0.as_str()).collect();
+    assert!(names.contains(&"t/docs/a.txt"), "{names:?}");
+    assert!(names.contains(&"docs/a.txt"), "{names:?}");
+    assert!(!names.contains(&"a.txt"), "{names:?}");
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrite_keeps_seekable_layout() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(&project)?;
+    for i in 0..4u32 {
+        let content: Vec<u8> = (0..64 * 1024u32)
+            .map(|j| (j.wrapping_mul(2_654_435_761).wrapping_add(i) >> 11) as u8)
+            .collect();
+        fs::write(project.join(format!("module{i}.bin")), content)?;
+    }
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-l", "1", "--seekable=16K", "-o"])
+        .args(["project.tzst", "project"])
+        .assert()
+        .success();
+    fs::write(temp_dir.path().join("extra.txt"), "extra\n")?;
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["rm", "project.tzst", "project/module0.bin"])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["add", "project.tzst", "extra.txt"])
+        .assert()
+        .success();
+
+    // still 16 KiB frames, ended by the seek table
+    let archive = fs::read(temp_dir.path().join("project.tzst"))?;
+    let footer = &archive[archive.len() - 9..];
+    assert_eq!(footer[5..], 0x8F92_EAB1u32.to_le_bytes());
+    let frames = u32::from_le_bytes(footer[..4].try_into()?);
+    assert!(frames > 12, "{frames} frames");
+
+    // the tar index lists the new entries without decoding the archive
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "project.tzst"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("extra.txt"))
+        .stdout(predicate::str::contains("module0.bin").not());
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["x", "project.tzst", "-C", "out", "--entry", "extra.txt"])
+        .assert()
+        .success();
+    assert_eq!(fs::read(temp_dir.path().join("out/extra.txt"))?, b"extra\n");
+    assert!(!temp_dir.path().join("out/project").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrite_keeps_zstd_dictionary() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let records = temp_dir.path().join("records");
+    fs::create_dir_all(&records)?;
+    for i in 0..400u32 {
+        let record = format!(
+            "{{\"id\":{i},\"user\":{{\"name\":\"user-{}\",\"active\":{}}},\"tags\":[\"alpha\",\"beta\"]}}\n",
+            i * 7 % 101,
+            i % 2 == 0
+        );
+        fs::write(records.join(format!("{i:04}.json")), record)?;
+    }
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "dict",
+            "train",
+            "--max-size",
+            "4K",
+            "-o",
+            "records.dict",
+            "records",
+        ])
+        .assert()
+        .success();
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "compress",
+            "--dict",
+            "records.dict",
+            "-o",
+            "records.tzst",
+            "records",
+        ])
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["rm", "records.tzst", "records/0001.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass it with --dict"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args([
+            "rm",
+            "--dict",
+            "records.dict",
+            "records.tzst",
+            "records/0001.json",
+        ])
+        .assert()
+        .success();
+
+    // the rewritten frames still need the dictionary
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "records.tzst"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--dict"));
+    zzz_cmd()
+        .current_dir(temp_dir.path())
+        .args(["list", "--dict", "records.dict", "records.tzst"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("records/0002.json"))
+        .stdout(predicate::str::contains("records/0001.json").not());
+
+    Ok(())
+}
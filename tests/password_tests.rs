@@ -882,3 +882,235 @@ fn test_zstd_encryption_format_validation() -> Result<()> {
 
     Ok(())
 }
+
+/// enough files for sevenz-rust's header to shrink when compressed, so it gets encrypted
+fn create_many_files(dir: &std::path::Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for i in 0..20 {
+        fs::write(dir.join(format!("merger-plan-{i:02}.txt")), "top secret")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_7z_list_and_test_with_password() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_dir = tmp_dir.path().join("confidential");
+    create_many_files(&input_dir)?;
+
+    let archive = tmp_dir.path().join("plan.7z");
+    run_compress_command(&archive, &[&input_dir], Some("7z"), Some("pw"))?;
+
+    // the header is encrypted along with the contents
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("merger-plan").not());
+    zzz_cmd()
+        .args(["list", "-p", "pw"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("confidential/merger-plan-07.txt"));
+
+    zzz_cmd().arg("test").arg(&archive).assert().failure();
+    zzz_cmd()
+        .args(["test", "-p", "pw"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("integrity: OK"));
+
+    Ok(())
+}
+
+#[test]
+fn test_7z_encrypted_headers() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_dir = tmp_dir.path().join("confidential");
+    create_many_files(&input_dir)?;
+
+    let archive = tmp_dir.path().join("sealed.7z");
+    zzz_cmd()
+        .args([
+            "compress",
+            "-f",
+            "7z",
+            "-p",
+            "pw",
+            "--encrypt-headers",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&input_dir)
+        .assert()
+        .success();
+
+    let utf16_name: Vec<u8> = "merger-plan"
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    let sealed = fs::read(&archive)?;
+    assert!(!sealed
+        .windows(utf16_name.len())
+        .any(|window| window == utf16_name.as_slice()));
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no password was provided"));
+    zzz_cmd()
+        .args(["list", "-p", "wrong"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid password"));
+
+    let extract_dir = tmp_dir.path().join("out");
+    zzz_cmd()
+        .args(["extract", "-p", "pw", "-C"])
+        .arg(&extract_dir)
+        .arg(&archive)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("confidential/merger-plan-13.txt"))?,
+        "top secret"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_7z_encrypt_headers_refuses_plain_header() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_file = create_test_file(&tmp_dir, "merger-plan.txt", "top secret")?;
+
+    // a single short name does not compress, so sevenz-rust would store the header as it is
+    let archive = tmp_dir.path().join("small.7z");
+    zzz_cmd()
+        .args([
+            "compress",
+            "-f",
+            "7z",
+            "-p",
+            "pw",
+            "--encrypt-headers",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&input_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "too small for sevenz-rust to encrypt",
+        ));
+    assert!(!archive.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypt_headers_requires_password() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_file = create_test_file(&tmp_dir, "test.txt", "test content")?;
+
+    zzz_cmd()
+        .args(["compress", "-f", "7z", "--encrypt-headers", "-o"])
+        .arg(tmp_dir.path().join("test.7z"))
+        .arg(&input_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--password"));
+
+    Ok(())
+}
+
+#[test]
+fn test_7z_remove_keeps_headers_encrypted() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_dir = tmp_dir.path().join("confidential");
+    create_many_files(&input_dir)?;
+
+    let archive = tmp_dir.path().join("sealed.7z");
+    zzz_cmd()
+        .args([
+            "compress",
+            "-f",
+            "7z",
+            "-p",
+            "pw",
+            "--encrypt-headers",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&input_dir)
+        .assert()
+        .success();
+    zzz_cmd()
+        .args(["rm", "-p", "pw"])
+        .arg(&archive)
+        .arg("confidential/merger-plan-00.txt")
+        .assert()
+        .success();
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no password was provided"));
+    zzz_cmd()
+        .args(["list", "-p", "pw"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("merger-plan-00").not())
+        .stdout(predicate::str::contains("confidential/merger-plan-01.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_zstd_list_and_test_with_password() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let input_dir = tmp_dir.path().join("reports");
+    fs::create_dir_all(&input_dir)?;
+    fs::write(input_dir.join("q1.csv"), "quarter,revenue\nq1,100\n")?;
+    fs::write(input_dir.join("q2.csv"), "quarter,revenue\nq2,120\n")?;
+
+    let archive = tmp_dir.path().join("reports.tar.zst");
+    run_compress_command(&archive, &[&input_dir], None, Some("listpass"))?;
+
+    zzz_cmd()
+        .args(["list", "--password", "listpass"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("reports/q1.csv"))
+        .stdout(predicate::str::contains("reports/q2.csv"));
+    zzz_cmd()
+        .args(["list", "-p", "wrongpass"])
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    // with a password, test decrypts and decodes everything instead of checking the header
+    zzz_cmd()
+        .args(["test", "-p", "listpass"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("integrity: OK"));
+    zzz_cmd()
+        .args(["test", "-p", "wrongpass"])
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! Tests for content-based tar detection in single-stream formats and the --tar/--raw override
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use flate2::{write::GzEncoder, Compression};
+use predicates::prelude::*;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn test_tree(parent: &Path) -> Result<std::path::PathBuf> {
+    common::create_tree(
+        parent,
+        "tree",
+        &[
+            (
+                "hello.txt",
+                "hello payload detection\n".repeat(50).as_bytes(),
+            ),
+            ("sub/data.txt", b"nested\n"),
+        ],
+    )
+}
+
+#[test]
+fn test_renamed_tarballs_are_detected_by_content() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    for (ext, renamed) in [
+        ("tgz", "backup.gz"),
+        ("txz", "backup.xz"),
+        ("tar.zst", "backup.zst"),
+        ("tbz2", "backup.bz2"),
+        ("tar.lz4", "backup.lz4"),
+        ("tar.br", "backup.br"),
+    ] {
+        let archive = temp_dir.path().join(format!("out.{ext}"));
+        zzz_cmd()
+            .arg("compress")
+            .arg(&tree)
+            .arg("-o")
+            .arg(&archive)
+            .assert()
+            .success();
+        let renamed = temp_dir.path().join(renamed);
+        fs::rename(&archive, &renamed)?;
+
+        zzz_cmd()
+            .arg("list")
+            .arg(&renamed)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("tree/sub/data.txt"));
+
+        zzz_cmd()
+            .arg("test")
+            .arg(&renamed)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("integrity: OK"));
+
+        let extract_dir = temp_dir.path().join(format!("extract-{ext}"));
+        zzz_cmd()
+            .arg("extract")
+            .arg(&renamed)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read(extract_dir.join("tree/hello.txt"))?,
+            fs::read(tree.join("hello.txt"))?,
+            "{ext}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_stream_with_tarball_name_is_detected() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = temp_dir.path().join("notes.tgz");
+    let mut encoder = GzEncoder::new(fs::File::create(&archive)?, Compression::default());
+    encoder.write_all(b"just some notes, not a tarball\n")?;
+    encoder.finish()?;
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes"));
+
+    zzz_cmd().arg("test").arg(&archive).assert().success();
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("notes"))?,
+        "just some notes, not a tarball\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_tar_and_raw_overrides() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("backup.tar.gz");
+    zzz_cmd()
+        .arg("compress")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&archive)
+        .assert()
+        .success();
+
+    // --raw hands back the decompressed tarball itself
+    let extract_dir = temp_dir.path().join("raw");
+    zzz_cmd()
+        .args(["extract", "--raw"])
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    let tar_bytes = fs::read(extract_dir.join("backup.tar"))?;
+    assert_eq!(&tar_bytes[257..262], b"ustar");
+
+    zzz_cmd()
+        .args(["list", "--raw"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("backup.tar"));
+
+    // --tar on a raw stream fails instead of writing a bogus file
+    let raw = temp_dir.path().join("notes.gz");
+    let mut encoder = GzEncoder::new(fs::File::create(&raw)?, Compression::default());
+    encoder.write_all(&[b'x'; 2048])?;
+    encoder.finish()?;
+    zzz_cmd()
+        .args(["test", "--tar"])
+        .arg(&raw)
+        .assert()
+        .failure();
+
+    zzz_cmd()
+        .args(["list", "--tar", "--raw"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
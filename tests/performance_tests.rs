@@ -5,7 +5,7 @@ use std::time::Instant;
 use tempfile::TempDir;
 use zzz_arc::filter::FileFilter;
 use zzz_arc::formats::zstd::ZstdFormat;
-use zzz_arc::formats::{CompressionFormat, CompressionOptions};
+use zzz_arc::formats::{CompressionFormat, CompressionOptions, ExtractionOptions};
 
 type Result<T> = anyhow::Result<T>;
 
@@ -314,7 +314,7 @@ fn test_listing_performance() -> Result<()> {
 
     // Time listing operation
     let start = Instant::now();
-    let entries = ZstdFormat::list(&archive_path)?;
+    let entries = ZstdFormat::list(&archive_path, &ExtractionOptions::default())?;
     let duration = start.elapsed();
 
     println!("Listing performance (500 files): {duration:?}");
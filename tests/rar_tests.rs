@@ -0,0 +1,187 @@
+//! Tests for reading RAR archives (needs `--features rar`)
+//!
+//! the fixtures are archives written by RAR itself, taken from the test data of the unrar crate
+//! (MIT): `plain.rar` holds `VERSION`, `encrypted.rar` holds `.gitignore` with its data
+//! encrypted with the password `unrar`, and `encrypted-headers.rar` holds the same file with its
+//! names encrypted too, under the password `password`
+#![cfg(feature = "rar")]
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+const VERSION: &str = "unrar-0.4.0";
+const GITIGNORE: &str = "target\nCargo.lock\n";
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+#[test]
+fn test_rar_list_extract_and_test() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = fixture("plain.rar");
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("VERSION"));
+    zzz_cmd()
+        .arg("test")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("integrity: OK"));
+
+    let output_dir = temp_dir.path().join("out");
+    zzz_cmd()
+        .args(["extract", "-C"])
+        .arg(&output_dir)
+        .arg(&archive)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(output_dir.join("VERSION"))?, VERSION);
+
+    Ok(())
+}
+
+#[test]
+fn test_rar_password() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = fixture("encrypted.rar");
+
+    // only the file data is encrypted, so names are listed without the password
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".gitignore"));
+
+    let output_dir = temp_dir.path().join("out");
+    zzz_cmd()
+        .args(["extract", "-p", "unrar", "-C"])
+        .arg(&output_dir)
+        .arg(&archive)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join(".gitignore"))?,
+        GITIGNORE
+    );
+    zzz_cmd()
+        .args(["test", "-p", "unrar"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("integrity: OK"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rar_missing_and_wrong_password() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = fixture("encrypted.rar");
+
+    zzz_cmd()
+        .args(["extract", "-C"])
+        .arg(temp_dir.path().join("none"))
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "password protected but no password was provided",
+        ));
+    zzz_cmd()
+        .arg("test")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no password was provided"));
+    // RAR4 keeps no password check, so a wrong password shows up as a failed checksum
+    zzz_cmd()
+        .args(["test", "-p", "wrong"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("wrong password or corrupt data"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rar_encrypted_headers() -> Result<()> {
+    let archive = fixture("encrypted-headers.rar");
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no password was provided"))
+        .stdout(predicate::str::contains(".gitignore").not());
+    zzz_cmd()
+        .args(["list", "-p", "wrong"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid password"));
+    zzz_cmd()
+        .args(["list", "-p", "password"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".gitignore"));
+    zzz_cmd()
+        .args(["test", "-p", "password"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("integrity: OK"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rar_corrupt_data_is_not_a_password_error() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let mut bytes = fs::read(fixture("plain.rar"))?;
+    // inside the compressed data of VERSION, after its header
+    let offset = bytes
+        .windows(b"VERSION".len())
+        .position(|window| window == b"VERSION")
+        .unwrap()
+        + 10;
+    bytes[offset] ^= 1;
+    let archive = temp_dir.path().join("corrupt.rar");
+    fs::write(&archive, bytes)?;
+
+    zzz_cmd()
+        .arg("test")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("password").not());
+    zzz_cmd()
+        .args(["test", "-p", "unrar"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("wrong password or corrupt data"));
+
+    Ok(())
+}
@@ -1,10 +1,14 @@
-//! Tests for raw gzip/xz streams (non-tar)
+//! Tests for raw gzip/xz/zstd streams (non-tar)
 
 use flate2::{write::GzEncoder, Compression};
 use std::fs::{self, File};
 use tempfile::TempDir;
 use xz2::write::XzEncoder;
-use zzz_arc::formats::{gz::GzipFormat, xz::XzFormat, CompressionFormat, ExtractionOptions};
+use zzz_arc::filter::FileFilter;
+use zzz_arc::formats::{
+    gz::GzipFormat, xz::XzFormat, zstd::ZstdFormat, CompressionFormat, CompressionOptions,
+    ExtractionOptions,
+};
 
 type Result<T> = anyhow::Result<T>;
 
@@ -23,7 +27,7 @@ fn test_raw_gzip_extract_and_list() -> Result<()> {
     std::io::copy(&mut input, &mut encoder)?;
     encoder.finish()?;
 
-    let entries = GzipFormat::list(&archive_path)?;
+    let entries = GzipFormat::list(&archive_path, &ExtractionOptions::default())?;
     assert_eq!(entries.len(), 1);
     assert_eq!(entries[0].path, "file.txt");
     assert!(entries[0].size > 0);
@@ -55,7 +59,7 @@ fn test_raw_xz_extract_and_list() -> Result<()> {
     std::io::copy(&mut input, &mut encoder)?;
     encoder.finish()?;
 
-    let entries = XzFormat::list(&archive_path)?;
+    let entries = XzFormat::list(&archive_path, &ExtractionOptions::default())?;
     assert_eq!(entries.len(), 1);
     assert_eq!(entries[0].path, "file.txt");
     assert!(entries[0].size > 0);
@@ -71,3 +75,66 @@ fn test_raw_xz_extract_and_list() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_raw_zstd_extract_and_list() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive_path = temp_dir.path().join("file.txt.zst");
+    let extract_dir = temp_dir.path().join("extract");
+
+    // written by the zstd library directly, as `zstd file.txt` would
+    fs::write(
+        &archive_path,
+        zstd::encode_all(&b"raw zstd content"[..], 3)?,
+    )?;
+
+    let entries = ZstdFormat::list(&archive_path, &ExtractionOptions::default())?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, "file.txt");
+    assert_eq!(entries[0].size, 16);
+
+    ZstdFormat::test_integrity(&archive_path, &ExtractionOptions::default())?;
+
+    fs::create_dir(&extract_dir)?;
+    let options = ExtractionOptions::default();
+    ZstdFormat::extract(&archive_path, &extract_dir, &options, None)?;
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("file.txt"))?,
+        "raw zstd content"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_zstd_compress_chooses_raw_or_tar_by_name() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source_file = temp_dir.path().join("file.txt");
+    fs::write(&source_file, "zstd naming content")?;
+    let filter = FileFilter::new(true, &[])?;
+    let options = CompressionOptions::default();
+
+    // `<name>.zst` is a plain stream that the zstd cli can decode
+    let raw_archive = temp_dir.path().join("file.txt.zst");
+    ZstdFormat::compress(&source_file, &raw_archive, &options, &filter, None)?;
+    assert_eq!(
+        zstd::decode_all(File::open(&raw_archive)?)?,
+        b"zstd naming content"
+    );
+
+    // tarball names, or names that would lose the filename, keep the tar wrapper
+    for name in ["file.tar.zst", "file.tzst", "renamed.zst"] {
+        let archive = temp_dir.path().join(name);
+        ZstdFormat::compress(&source_file, &archive, &options, &filter, None)?;
+        let decoded = zstd::decode_all(File::open(&archive)?)?;
+        assert!(decoded.len() >= 1024, "{name} should hold a tarball");
+
+        let entries = ZstdFormat::list(&archive, &ExtractionOptions::default())?;
+        assert_eq!(entries.len(), 1, "{name}");
+        assert_eq!(entries[0].path, "file.txt", "{name}");
+        ZstdFormat::test_integrity(&archive, &ExtractionOptions::default())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+//! Reproducible build tests (SOURCE_DATE_EPOCH / --mtime-clamp)
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use filetime::FileTime;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+const EPOCH: i64 = 1_700_000_000;
+
+fn zzz_cmd() -> Command {
+    let mut cmd = cargo_bin_cmd!("zzz");
+    cmd.env_remove("SOURCE_DATE_EPOCH");
+    cmd
+}
+
+/// create the same tree under `parent/tree` with every mtime shifted by `offset`
+fn test_tree(parent: &Path, offset: i64) -> Result<PathBuf> {
+    let root = common::create_tree(
+        parent,
+        "tree",
+        &[
+            ("README", b"reproducible\n"),
+            ("src/main.c", b"int main(void) { return 0; }\n"),
+            ("src/nested/data.bin", &[7u8; 4096]),
+            ("old.txt", b"older than the epoch\n"),
+        ],
+    )?;
+
+    for path in [
+        "README",
+        "src/main.c",
+        "src/nested/data.bin",
+        "src/nested",
+        "src",
+        "",
+    ] {
+        let mtime = FileTime::from_unix_time(EPOCH + 86_400 + offset, 0);
+        filetime::set_file_times(root.join(path), mtime, mtime)?;
+    }
+    // entries older than the epoch keep their own mtime
+    let old = FileTime::from_unix_time(EPOCH - 86_400, 0);
+    filetime::set_file_times(root.join("old.txt"), old, old)?;
+
+    Ok(root)
+}
+
+fn build(tree: &Path, output: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    zzz_cmd()
+        .arg("compress")
+        .arg(tree)
+        .arg("-o")
+        .arg(output)
+        .args(args)
+        .assert()
+        .success();
+    Ok(fs::read(output)?)
+}
+
+#[test]
+fn test_mtime_clamp_builds_are_byte_identical() -> Result<()> {
+    let machine_a = TempDir::new()?;
+    let machine_b = TempDir::new()?;
+    let tree_a = test_tree(machine_a.path(), 0)?;
+    let tree_b = test_tree(machine_b.path(), 3_600)?;
+    let epoch = EPOCH.to_string();
+
+    for ext in ["tar.zst", "tar.gz", "tar.xz", "zip", "7z"] {
+        let a = build(
+            &tree_a,
+            &machine_a.path().join(format!("out.{ext}")),
+            &["--mtime-clamp", &epoch],
+        )?;
+        let b = build(
+            &tree_b,
+            &machine_b.path().join(format!("out.{ext}")),
+            &["--mtime-clamp", &epoch],
+        )?;
+        assert!(a == b, "{ext} builds differ");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_source_date_epoch_env_matches_flag() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path(), 0)?;
+    let epoch = EPOCH.to_string();
+
+    let from_flag = build(
+        &tree,
+        &temp_dir.path().join("flag.tar.gz"),
+        &["--mtime-clamp", &epoch],
+    )?;
+
+    let env_output = temp_dir.path().join("env.tar.gz");
+    zzz_cmd()
+        .env("SOURCE_DATE_EPOCH", &epoch)
+        .arg("compress")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&env_output)
+        .assert()
+        .success();
+    assert_eq!(from_flag, fs::read(&env_output)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_mtime_clamp_only_lowers_timestamps() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path(), 0)?;
+    let archive = temp_dir.path().join("out.tar.zst");
+    build(&tree, &archive, &["--mtime-clamp", &EPOCH.to_string()])?;
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+
+    let mtime = |path: &str| -> Result<i64> {
+        let metadata = fs::metadata(extract_dir.join("tree").join(path))?;
+        Ok(FileTime::from_last_modification_time(&metadata).unix_seconds())
+    };
+    assert_eq!(mtime("README")?, EPOCH);
+    assert_eq!(mtime("src/nested/data.bin")?, EPOCH);
+    assert_eq!(mtime("old.txt")?, EPOCH - 86_400);
+
+    Ok(())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// a fixed tree with pinned mtimes, so archive bytes can be compared against known hashes
+fn create_pinned_tree(parent: &Path) -> Result<PathBuf> {
+    let root = parent.join("pinned");
+    fs::create_dir_all(root.join("docs"))?;
+    fs::create_dir_all(root.join("empty"))?;
+    fs::write(root.join("docs/guide.md"), "# guide\n".repeat(64))?;
+    fs::write(root.join("a.txt"), "alpha\n")?;
+    fs::write(root.join("z.txt"), "")?;
+
+    let mtime = FileTime::from_unix_time(EPOCH, 0);
+    for path in ["docs/guide.md", "a.txt", "z.txt", "docs", "empty", ""] {
+        filetime::set_file_times(root.join(path), mtime, mtime)?;
+    }
+    Ok(root)
+}
+
+#[test]
+fn test_deterministic_zip_and_7z_hashes() -> Result<()> {
+    let run_a = TempDir::new()?;
+    let run_b = TempDir::new()?;
+    let tree_a = create_pinned_tree(run_a.path())?;
+    let tree_b = create_pinned_tree(run_b.path())?;
+    // atimes and ctimes differ between the runs; neither may leak into the archive
+    filetime::set_file_atime(
+        tree_b.join("a.txt"),
+        FileTime::from_unix_time(EPOCH + 42, 0),
+    )?;
+
+    for (ext, expected) in [
+        (
+            "zip",
+            "8c0d9b41ccffb9555c9a9e812d149602d30c00d9de607fc6a6165f802071d9c2",
+        ),
+        (
+            "7z",
+            "c3c55114e538e5350e32bd4dff61caf3942975da1e4d0d3267ebdf92ff2fdad5",
+        ),
+    ] {
+        let a = build(&tree_a, &run_a.path().join(format!("out.{ext}")), &[])?;
+        let b = build(&tree_b, &run_b.path().join(format!("out.{ext}")), &[])?;
+        assert!(a == b, "{ext} builds differ");
+        assert_eq!(sha256_hex(&a), expected, "{ext} output changed");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_zip_matches_sequential() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = create_pinned_tree(temp_dir.path())?;
+    for i in 0..12 {
+        fs::write(
+            tree.join(format!("docs/page{i:02}.md")),
+            format!("page {i}\n").repeat(2_000),
+        )?;
+    }
+
+    for method in ["deflate", "bzip2", "zstd", "store"] {
+        let sequential = build(
+            &tree,
+            &temp_dir.path().join(format!("seq-{method}.zip")),
+            &["-j", "1", "--zip-method", method],
+        )?;
+        let parallel = build(
+            &tree,
+            &temp_dir.path().join(format!("par-{method}.zip")),
+            &["-j", "4", "--zip-method", method],
+        )?;
+        assert!(sequential == parallel, "{method} zip differs under -j 4");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_count_does_not_change_gzip_or_xz_output() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = create_pinned_tree(temp_dir.path())?;
+    // several gzip blocks' worth of poorly compressible data
+    let data: Vec<u8> = (0..3_000_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 27) as u8)
+        .collect();
+    fs::write(tree.join("data.bin"), data)?;
+    let epoch = EPOCH.to_string();
+
+    // small xz blocks so the multithreaded encoder has work to spread
+    for (ext, extra) in [("tgz", &[][..]), ("txz", &["--xz-block-size", "256K"][..])] {
+        let single = build(
+            &tree,
+            &temp_dir.path().join(format!("j1.{ext}")),
+            &[&["-j", "1", "--mtime-clamp", &epoch][..], extra].concat(),
+        )?;
+        let parallel = build(
+            &tree,
+            &temp_dir.path().join(format!("j4.{ext}")),
+            &[&["-j", "4", "--mtime-clamp", &epoch][..], extra].concat(),
+        )?;
+        assert!(single == parallel, "{ext} differs under -j 4");
+    }
+
+    // the default xz block size does not depend on -j either
+    let single = build(
+        &tree,
+        &temp_dir.path().join("default-j1.txz"),
+        &["-j", "1", "-l", "3"],
+    )?;
+    let parallel = build(
+        &tree,
+        &temp_dir.path().join("default-j4.txz"),
+        &["-j", "4", "-l", "3"],
+    )?;
+    assert!(single == parallel, "txz differs under -j 4");
+
+    Ok(())
+}
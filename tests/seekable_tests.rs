@@ -0,0 +1,195 @@
+//! Tests for seekable zstd archives and --entry selection
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+/// a project with several 64 KiB files so a small frame size yields many frames
+fn create_project(parent: &Path) -> Result<PathBuf> {
+    let root = parent.join("project");
+    fs::create_dir_all(root.join("src"))?;
+    fs::create_dir_all(root.join("docs"))?;
+    for i in 0..8u32 {
+        let content: Vec<u8> = (0..64 * 1024u32)
+            .map(|j| (j.wrapping_mul(2_654_435_761).wrapping_add(i) >> 11) as u8)
+            .collect();
+        fs::write(root.join(format!("src/module{i}.bin")), content)?;
+    }
+    fs::write(root.join("docs/guide.md"), "# guide\n".repeat(50))?;
+    fs::write(root.join("README.md"), "seekable\n")?;
+    Ok(root)
+}
+
+fn compress_seekable(input: &Path, archive: &Path) {
+    zzz_cmd()
+        .args(["compress", "-l", "3", "--checksum", "--seekable=16K", "-o"])
+        .arg(archive)
+        .arg(input)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_seekable_round_trip_and_plain_decoder() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = create_project(temp_dir.path())?;
+    let archive = temp_dir.path().join("project.tar.zst");
+    compress_seekable(&project, &archive);
+
+    // the index and seek table are skippable frames, so stock decoders see a tarball
+    let decoded = zstd::stream::decode_all(fs::File::open(&archive)?)?;
+    let mut tarball = tar::Archive::new(decoded.as_slice());
+    let paths: Vec<String> = tarball
+        .entries()?
+        .map(|entry| Ok(entry?.path()?.to_string_lossy().to_string()))
+        .collect::<Result<_>>()?;
+    assert!(paths.contains(&"project/src/module3.bin".to_string()));
+
+    zzz_cmd().arg("test").arg(&archive).assert().success();
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    for i in 0..8 {
+        let name = format!("src/module{i}.bin");
+        assert_eq!(
+            fs::read(extract_dir.join("project").join(&name))?,
+            fs::read(project.join(&name))?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_seekable_entry_skips_unrelated_frames() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = create_project(temp_dir.path())?;
+    let archive = temp_dir.path().join("project.tar.zst");
+    compress_seekable(&project, &archive);
+
+    // damage the first data frame; only entries stored in it become unreadable
+    let mut bytes = fs::read(&archive)?;
+    bytes[200] ^= 0xFF;
+    fs::write(&archive, bytes)?;
+
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(temp_dir.path().join("full"))
+        .assert()
+        .failure();
+
+    let extract_dir = temp_dir.path().join("single");
+    zzz_cmd()
+        .args(["extract", "--entry", "project/src/module6.bin"])
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(extract_dir.join("project/src/module6.bin"))?,
+        fs::read(project.join("src/module6.bin"))?
+    );
+    assert!(!extract_dir.join("project/src/module5.bin").exists());
+
+    // listing reads the index without decoding any frame
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project/src/module0.bin"))
+        .stdout(predicate::str::contains("project/README.md"));
+
+    Ok(())
+}
+
+#[test]
+fn test_entry_selects_directories() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = create_project(temp_dir.path())?;
+    let seekable = temp_dir.path().join("seekable.tar.zst");
+    let plain = temp_dir.path().join("plain.tar.gz");
+    compress_seekable(&project, &seekable);
+    zzz_cmd()
+        .arg("compress")
+        .arg("-o")
+        .arg(&plain)
+        .arg(&project)
+        .assert()
+        .success();
+
+    for archive in [&seekable, &plain] {
+        let extract_dir = temp_dir.path().join("docs-only");
+        zzz_cmd()
+            .args(["extract", "--entry", "project/docs/", "--entry"])
+            .arg("project/README.md")
+            .arg(archive)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        assert!(extract_dir.join("project/docs/guide.md").is_file());
+        assert!(extract_dir.join("project/README.md").is_file());
+        assert!(!extract_dir.join("project/src").exists());
+        fs::remove_dir_all(&extract_dir)?;
+
+        zzz_cmd()
+            .args(["list", "--entry", "project/docs"])
+            .arg(archive)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("project/docs/guide.md"))
+            .stdout(predicate::str::contains("module").not());
+
+        zzz_cmd()
+            .args(["extract", "--entry", "project/missing"])
+            .arg(archive)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "no archive entries match project/missing",
+            ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_seekable_rejects_password() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let project = create_project(temp_dir.path())?;
+    let archive = temp_dir.path().join("project.tar.zst");
+
+    zzz_cmd()
+        .args(["compress", "--seekable", "-p", "secret", "-o"])
+        .arg(&archive)
+        .arg(&project)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--seekable cannot be combined with --password",
+        ));
+    assert!(!archive.exists());
+
+    Ok(())
+}
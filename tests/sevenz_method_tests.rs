@@ -0,0 +1,146 @@
+//! Tests for 7z method selection, levels and solid blocks
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use sevenz_rust::{Password, SevenZReader};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn test_tree(parent: &Path) -> Result<PathBuf> {
+    let contents: Vec<_> = (0..4)
+        .map(|i| format!("solid block content {i}\n").repeat(4_000))
+        .collect();
+    let names: Vec<_> = (0..4).map(|i| format!("file{i}.txt")).collect();
+    let nested = "nested\n".repeat(1_000);
+    let mut files: Vec<(&str, &[u8])> = names
+        .iter()
+        .zip(&contents)
+        .map(|(name, content)| (name.as_str(), content.as_bytes()))
+        .collect();
+    files.push(("nested/data.txt", nested.as_bytes()));
+    files.push(("nested/empty.txt", b""));
+    common::create_tree(parent, "tree", &files)
+}
+
+fn compress(input: &Path, archive: &Path, args: &[&str]) {
+    zzz_cmd()
+        .args(["compress", "-f", "7z", "-o"])
+        .arg(archive)
+        .args(args)
+        .arg(input)
+        .assert()
+        .success();
+}
+
+fn assert_round_trip(tree: &Path, archive: &Path, extract_dir: &Path) -> Result<()> {
+    zzz_cmd()
+        .arg("extract")
+        .arg(archive)
+        .arg("-C")
+        .arg(extract_dir)
+        .assert()
+        .success();
+    for name in [
+        "file0.txt",
+        "file3.txt",
+        "nested/data.txt",
+        "nested/empty.txt",
+    ] {
+        assert_eq!(
+            fs::read(extract_dir.join("tree").join(name))?,
+            fs::read(tree.join(name))?,
+            "{name} differs"
+        );
+    }
+    Ok(())
+}
+
+fn folder_count(archive: &Path) -> Result<usize> {
+    let reader = SevenZReader::open(archive, Password::empty())?;
+    Ok(reader.archive().folders.len())
+}
+
+#[test]
+fn test_solid_blocks() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    // 88 KB per top-level file, so 200K fits two of them (plus nested/data.txt) per block
+    let cases: [(&str, &[&str], usize); 3] = [
+        ("solid", &["--solid"], 1),
+        ("split", &["--solid-block-size", "200K"], 2),
+        ("separate", &[], 6),
+    ];
+    for (name, args, folders) in cases {
+        let archive = temp_dir.path().join(format!("{name}.7z"));
+        compress(&tree, &archive, args);
+        assert_eq!(folder_count(&archive)?, folders, "{name}");
+        assert_round_trip(&tree, &archive, &temp_dir.path().join(name))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_methods_and_levels_in_verbose_list() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    let lzma = temp_dir.path().join("lzma.7z");
+    compress(&tree, &lzma, &["--7z-method", "lzma", "-l", "22"]);
+    assert_round_trip(&tree, &lzma, &temp_dir.path().join("lzma"))?;
+    // the dictionary shrinks to the next power of two above the input size
+    zzz_cmd()
+        .args(["list", "-v"])
+        .arg(&lzma)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("LZMA:19"))
+        .stdout(predicate::str::contains("tree/file2.txt"));
+
+    let fast = temp_dir.path().join("fast.7z");
+    compress(&tree, &fast, &["-l", "1"]);
+    // level 1 maps to preset 0 and its 256 KiB dictionary
+    zzz_cmd()
+        .args(["list", "-v"])
+        .arg(&fast)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("LZMA2:18"));
+
+    Ok(())
+}
+
+#[test]
+fn test_unavailable_7z_methods() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("input.txt");
+    fs::write(&input, "content")?;
+
+    zzz_cmd()
+        .args(["compress", "--7z-method", "bcj", "-f", "7z"])
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "7z method 'bcj' cannot be written by the 7z backend",
+        ));
+    zzz_cmd()
+        .args(["compress", "--7z-method", "ppmd", "-f", "7z"])
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unsupported 7z method 'ppmd'"));
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+//! Tests for plain tar and the bzip2, lz4, lzma and brotli stream formats
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn test_tree(parent: &Path) -> Result<std::path::PathBuf> {
+    common::create_tree(
+        parent,
+        "tree",
+        &[
+            ("hello.txt", "hello stream formats\n".repeat(100).as_bytes()),
+            (
+                "sub/data.bin",
+                &(0..=255u8).cycle().take(10_000).collect::<Vec<_>>(),
+            ),
+        ],
+    )
+}
+
+#[test]
+fn test_tarball_round_trip_for_stream_formats() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    for ext in [
+        "tar", "tbz2", "tar.bz2", "tar.lz4", "tar.lzma", "tlz", "tar.br",
+    ] {
+        let archive = temp_dir.path().join(format!("out.{ext}"));
+        zzz_cmd()
+            .arg("compress")
+            .arg(&tree)
+            .arg("-o")
+            .arg(&archive)
+            .assert()
+            .success();
+
+        zzz_cmd()
+            .arg("list")
+            .arg(&archive)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("tree/hello.txt"))
+            .stdout(predicate::str::contains("tree/sub/data.bin"));
+
+        zzz_cmd()
+            .arg("test")
+            .arg(&archive)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("integrity: OK"));
+
+        let extract_dir = temp_dir.path().join(format!("extract-{ext}"));
+        zzz_cmd()
+            .arg("extract")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read(extract_dir.join("tree/hello.txt"))?,
+            fs::read(tree.join("hello.txt"))?,
+            "{ext}"
+        );
+        assert_eq!(
+            fs::read(extract_dir.join("tree/sub/data.bin"))?,
+            fs::read(tree.join("sub/data.bin"))?,
+            "{ext}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_stream_round_trip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("notes.txt");
+    fs::write(&input, "raw single-file stream\n".repeat(200))?;
+
+    for ext in ["bz2", "lz4", "lzma", "br"] {
+        let archive = temp_dir.path().join(format!("notes.txt.{ext}"));
+        zzz_cmd()
+            .arg("compress")
+            .arg(&input)
+            .arg("-o")
+            .arg(&archive)
+            .assert()
+            .success();
+
+        zzz_cmd()
+            .arg("list")
+            .arg(&archive)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("notes.txt"));
+
+        let extract_dir = temp_dir.path().join(format!("extract-{ext}"));
+        zzz_cmd()
+            .arg("extract")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read(extract_dir.join("notes.txt"))?,
+            fs::read(&input)?,
+            "{ext}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_stream_rejects_directory_input() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    zzz_cmd()
+        .arg("compress")
+        .arg(&tree)
+        .arg("-o")
+        .arg(temp_dir.path().join("tree.bz2"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Directory input requires a .tar.bz2",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_formats_detected_by_magic() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    for (ext, name) in [
+        ("tar", "tar"),
+        ("tbz2", "bzip2"),
+        ("tar.lz4", "LZ4"),
+        ("tar.lzma", "LZMA"),
+    ] {
+        let archive = temp_dir.path().join(format!("out.{ext}"));
+        zzz_cmd()
+            .arg("compress")
+            .arg(&tree)
+            .arg("-o")
+            .arg(&archive)
+            .assert()
+            .success();
+
+        let renamed = temp_dir.path().join(format!("{name}.download"));
+        fs::rename(&archive, &renamed)?;
+        zzz_cmd()
+            .args(["-v", "list"])
+            .arg(&renamed)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!("detected {name} format")))
+            .stdout(predicate::str::contains("tree/hello.txt"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_format_flag_for_stream_formats() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    test_tree(temp_dir.path())?;
+
+    for (flag, ext) in [
+        ("tar", "tar"),
+        ("bz2", "tbz2"),
+        ("lz4", "tar.lz4"),
+        ("lzma", "tar.lzma"),
+        ("br", "tar.br"),
+    ] {
+        zzz_cmd()
+            .current_dir(temp_dir.path())
+            .args(["compress", "-f", flag, "tree"])
+            .assert()
+            .success();
+        assert!(
+            temp_dir.path().join(format!("tree.{ext}")).exists(),
+            "{flag}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_formats_reject_passwords_and_warn_about_levels() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    for ext in ["tar", "tbz2", "tar.lz4", "lz4", "tar.lzma", "tar.br"] {
+        let archive = temp_dir.path().join(format!("secret.{ext}"));
+        zzz_cmd()
+            .arg("compress")
+            .arg(tree.join("hello.txt"))
+            .arg("-o")
+            .arg(&archive)
+            .args(["-p", "hunter2"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Password protection is not supported",
+            ));
+        assert!(!archive.exists(), "{ext} output left behind");
+    }
+
+    zzz_cmd()
+        .arg("compress")
+        .arg(&tree)
+        .arg("-o")
+        .arg(temp_dir.path().join("out.tar.lz4"))
+        .args(["-l", "3"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("--level is ignored"));
+
+    Ok(())
+}
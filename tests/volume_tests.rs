@@ -0,0 +1,425 @@
+//! Tests for reading multi-volume archive sets
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn test_tree(parent: &Path) -> Result<PathBuf> {
+    // noise is incompressible, so the archive spans several volumes
+    common::create_tree(
+        parent,
+        "tree",
+        &[
+            ("file0.bin", &common::noise(40_000, 0x9E37_79B9_7F4A_7C15)),
+            ("file1.bin", &common::noise(40_000, 0x2545_F491_4F6C_DD1D)),
+            ("file2.bin", &common::noise(40_000, 0x6A09_E667_F3BC_C908)),
+            ("nested/notes.txt", "volume notes\n".repeat(100).as_bytes()),
+        ],
+    )
+}
+
+fn compress(input: &Path, archive: &Path, format: &str) {
+    zzz_cmd()
+        .args(["compress", "-l", "1", "-f", format, "-o"])
+        .arg(archive)
+        .arg(input)
+        .assert()
+        .success();
+}
+
+/// cut `archive` into `name.001`, `name.002`, … of `size` bytes
+fn split_bytes(archive: &Path, size: usize) -> Result<Vec<PathBuf>> {
+    let data = fs::read(archive)?;
+    fs::remove_file(archive)?;
+    let mut volumes = Vec::new();
+    for (i, chunk) in data.chunks(size).enumerate() {
+        let volume = PathBuf::from(format!("{}.{:03}", archive.display(), i + 1));
+        fs::write(&volume, chunk)?;
+        volumes.push(volume);
+    }
+    Ok(volumes)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// turn a single-file zip into a two-volume split zip (`name.z01` + `name.zip`), cutting at `at`
+fn split_zip(archive: &Path, at: usize) -> Result<PathBuf> {
+    let data = fs::read(archive)?;
+    let eocd = data.len() - 22;
+    assert_eq!(read_u32(&data, eocd), 0x0605_4b50, "zip has a comment");
+    let cd_offset = read_u32(&data, eocd + 16) as usize;
+    assert!(at < cd_offset);
+
+    let mut first = b"PK\x07\x08".to_vec();
+    first.extend_from_slice(&data[..at]);
+    fs::write(archive.with_extension("z01"), first)?;
+
+    // offsets in the central directory become relative to the volume holding each entry
+    let mut last = data[at..].to_vec();
+    let mut pos = cd_offset - at;
+    for _ in 0..read_u16(&data, eocd + 10) {
+        let local = read_u32(&last, pos + 42) as usize;
+        let (disk, offset) = if local < at {
+            (0u16, local + 4)
+        } else {
+            (1, local - at)
+        };
+        last[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+        last[pos + 42..pos + 46].copy_from_slice(&(offset as u32).to_le_bytes());
+        pos += 46
+            + read_u16(&last, pos + 28) as usize
+            + read_u16(&last, pos + 30) as usize
+            + read_u16(&last, pos + 32) as usize;
+    }
+    let eocd = last.len() - 22;
+    let entries = read_u16(&last, eocd + 10);
+    last[eocd + 4..eocd + 6].copy_from_slice(&1u16.to_le_bytes());
+    last[eocd + 6..eocd + 8].copy_from_slice(&1u16.to_le_bytes());
+    last[eocd + 8..eocd + 10].copy_from_slice(&entries.to_le_bytes());
+    last[eocd + 16..eocd + 20].copy_from_slice(&((cd_offset - at) as u32).to_le_bytes());
+    fs::File::create(archive)?.write_all(&last)?;
+    Ok(archive.with_extension("z01"))
+}
+
+fn assert_set_works(tree: &Path, member: &Path, extract_dir: &Path) -> Result<()> {
+    zzz_cmd()
+        .arg("list")
+        .arg(member)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("tree/file2.bin"))
+        .stdout(predicate::str::contains("tree/nested/notes.txt"));
+    zzz_cmd().arg("test").arg(member).assert().success();
+    zzz_cmd()
+        .arg("extract")
+        .arg(member)
+        .arg("-C")
+        .arg(extract_dir)
+        .assert()
+        .success();
+    for name in ["file0.bin", "file2.bin", "nested/notes.txt"] {
+        assert_eq!(
+            fs::read(extract_dir.join("tree").join(name))?,
+            fs::read(tree.join(name))?,
+            "{name} differs"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_split_7z_from_any_volume() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.7z");
+    compress(&tree, &archive, "7z");
+    let volumes = split_bytes(&archive, 50_000)?;
+    assert!(volumes.len() >= 3);
+
+    assert_set_works(&tree, &volumes[1], &temp_dir.path().join("out"))?;
+
+    // a missing trailing volume is caught from the 7z start header
+    fs::remove_file(volumes.last().unwrap())?;
+    zzz_cmd()
+        .arg("list")
+        .arg(&volumes[0])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("incomplete volume set"));
+
+    Ok(())
+}
+
+#[test]
+fn test_split_tarball() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.tar.zst");
+    compress(&tree, &archive, "zst");
+    let volumes = split_bytes(&archive, 30_000)?;
+
+    assert_set_works(&tree, &volumes[0], &temp_dir.path().join("out"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_split_zip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.zip");
+    compress(&tree, &archive, "zip");
+    let first = split_zip(&archive, 60_000)?;
+
+    assert_set_works(&tree, &first, &temp_dir.path().join("from_first"))?;
+    assert_set_works(&tree, &archive, &temp_dir.path().join("from_last"))?;
+
+    fs::remove_file(&first)?;
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("incomplete volume set: missing"))
+        .stderr(predicate::str::contains("data.z01"));
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_middle_volume() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.7z");
+    compress(&tree, &archive, "7z");
+    let volumes = split_bytes(&archive, 30_000)?;
+    fs::remove_file(&volumes[1])?;
+
+    for command in ["list", "test", "extract"] {
+        zzz_cmd()
+            .arg(command)
+            .arg(&volumes[0])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("incomplete volume set: missing"))
+            .stderr(predicate::str::contains("data.7z.002"));
+    }
+
+    Ok(())
+}
+
+fn compress_split(input: &Path, archive: &Path, format: &str, size: &str) {
+    zzz_cmd()
+        .args([
+            "compress",
+            "-y",
+            "-l",
+            "1",
+            "-f",
+            format,
+            "--split-size",
+            size,
+            "-o",
+        ])
+        .arg(archive)
+        .arg(input)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("volumes"));
+}
+
+fn assert_volume_sizes(volumes: &[PathBuf], limit: u64) -> Result<()> {
+    for volume in volumes {
+        let len = fs::metadata(volume)?.len();
+        assert!(len <= limit, "{} is {len} bytes", volume.display());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_split_size_writes_volumes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    for (format, name) in [("zst", "data.tar.zst"), ("7z", "data.7z")] {
+        let archive = temp_dir.path().join(name);
+        compress_split(&tree, &archive, format, "64K");
+        assert!(!archive.exists());
+        let volumes: Vec<PathBuf> = (1..=2)
+            .map(|n| PathBuf::from(format!("{}.{n:03}", archive.display())))
+            .collect();
+        assert_volume_sizes(&volumes, 64 * 1024)?;
+        assert_set_works(&tree, &volumes[0], &temp_dir.path().join(format))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_split_size_writes_zip_volumes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.zip");
+
+    compress_split(&tree, &archive, "zip", "64K");
+    let first = temp_dir.path().join("data.z01");
+    let volumes = [first.clone(), archive.clone()];
+    assert_volume_sizes(&volumes, 64 * 1024)?;
+    assert_eq!(&fs::read(&first)?[..4], b"PK\x07\x08");
+    assert_set_works(&tree, &first, &temp_dir.path().join("out"))?;
+
+    // an archive that fits in one volume stays a plain zip, without stale volumes
+    zzz_cmd()
+        .args([
+            "compress",
+            "-y",
+            "-l",
+            "1",
+            "-f",
+            "zip",
+            "--split-size",
+            "1M",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .success();
+    assert!(!first.exists());
+    assert_set_works(&tree, &archive, &temp_dir.path().join("again"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_split_size_minimum() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+
+    zzz_cmd()
+        .args(["compress", "--split-size", "1K"])
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("split size '1K' is too small"));
+
+    Ok(())
+}
+
+/// zzz with at most 64 open files, like running it after `ulimit -n 64`
+#[cfg(unix)]
+fn zzz_with_few_files() -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "ulimit -n 64 && exec \"$0\" \"$@\"",
+        env!("CARGO_BIN_EXE_zzz"),
+    ]);
+    cmd
+}
+
+#[cfg(unix)]
+#[test]
+fn test_split_into_more_volumes_than_open_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = common::create_tree(
+        temp_dir.path(),
+        "tree",
+        &[(
+            "big.bin",
+            &common::noise(100 * 64 * 1024, 0x9E37_79B9_7F4A_7C15),
+        )],
+    )?;
+
+    for (format, name, first) in [
+        ("7z", "data.7z", "data.7z.001"),
+        ("zip", "data.zip", "data.z01"),
+    ] {
+        let archive = temp_dir.path().join(name);
+        zzz_with_few_files()
+            .args([
+                "compress",
+                "-l",
+                "1",
+                "-f",
+                format,
+                "--split-size",
+                "64K",
+                "-o",
+            ])
+            .arg(&archive)
+            .arg(&tree)
+            .assert()
+            .success();
+
+        let extract_dir = temp_dir.path().join(format);
+        zzz_with_few_files()
+            .arg("extract")
+            .arg(temp_dir.path().join(first))
+            .arg("-C")
+            .arg(&extract_dir)
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read(extract_dir.join("tree/big.bin"))?,
+            fs::read(tree.join("big.bin"))?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_split_removes_its_volumes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("data.7z");
+    // the second volume cannot be created over a directory
+    fs::create_dir(temp_dir.path().join("data.7z.002"))?;
+
+    zzz_cmd()
+        .args([
+            "compress",
+            "-l",
+            "1",
+            "-f",
+            "7z",
+            "--split-size",
+            "64K",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("data.7z.002"));
+    assert!(!temp_dir.path().join("data.7z.001").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_numbered_names_without_archive_extension_are_single_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("dump.tar.zst");
+    compress(&tree, &archive, "zst");
+    // two unrelated dumps that happen to have consecutive numbers
+    fs::rename(&archive, temp_dir.path().join("dump.100"))?;
+    fs::copy(
+        temp_dir.path().join("dump.100"),
+        temp_dir.path().join("dump.101"),
+    )?;
+
+    zzz_cmd()
+        .arg("extract")
+        .arg(temp_dir.path().join("dump.101"))
+        .arg("-C")
+        .arg(temp_dir.path().join("out"))
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(temp_dir.path().join("out/tree/file1.bin"))?,
+        fs::read(tree.join("file1.bin"))?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Tests for zip compression method selection and auto-store
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use rand::RngCore;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use zip::CompressionMethod;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn entry_methods(archive_path: &Path) -> Result<Vec<(String, CompressionMethod)>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(archive_path)?)?;
+    let mut methods = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.is_file() {
+            methods.push((entry.name().to_string(), entry.compression()));
+        }
+    }
+    Ok(methods)
+}
+
+fn method_of(methods: &[(String, CompressionMethod)], suffix: &str) -> CompressionMethod {
+    methods
+        .iter()
+        .find(|(name, _)| name.ends_with(suffix))
+        .map(|(_, method)| *method)
+        .unwrap_or_else(|| panic!("entry {suffix} not found"))
+}
+
+fn create_mixed_tree(parent: &Path) -> Result<std::path::PathBuf> {
+    let root = parent.join("mixed");
+    fs::create_dir_all(&root)?;
+    fs::write(
+        root.join("notes.txt"),
+        "zip method test content\n".repeat(512),
+    )?;
+    fs::write(root.join("image.png"), "pretend png data\n".repeat(512))?;
+    let mut noise = vec![0u8; 64 * 1024];
+    rand::thread_rng().fill_bytes(&mut noise);
+    fs::write(root.join("noise.bin"), noise)?;
+    Ok(root)
+}
+
+#[test]
+fn test_zip_methods_round_trip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = create_mixed_tree(temp_dir.path())?;
+
+    let cases = [
+        ("store", CompressionMethod::Stored),
+        ("deflate", CompressionMethod::Deflated),
+        ("bzip2", CompressionMethod::Bzip2),
+        ("zstd", CompressionMethod::Zstd),
+    ];
+
+    for (name, expected) in cases {
+        for level in ["1", "22"] {
+            let archive = temp_dir.path().join(format!("{name}-{level}.zip"));
+            zzz_cmd()
+                .args(["compress", "--zip-method", name, "-l", level, "-o"])
+                .arg(&archive)
+                .arg(&tree)
+                .assert()
+                .success();
+
+            let methods = entry_methods(&archive)?;
+            assert_eq!(method_of(&methods, "notes.txt"), expected, "{name}");
+
+            let extract_dir = temp_dir.path().join(format!("out-{name}-{level}"));
+            zzz_cmd()
+                .arg("extract")
+                .arg(&archive)
+                .arg("-C")
+                .arg(&extract_dir)
+                .assert()
+                .success();
+            assert_eq!(
+                fs::read(extract_dir.join("mixed/notes.txt"))?,
+                fs::read(tree.join("notes.txt"))?
+            );
+            assert_eq!(
+                fs::read(extract_dir.join("mixed/noise.bin"))?,
+                fs::read(tree.join("noise.bin"))?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zip_auto_stores_compressed_inputs() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = create_mixed_tree(temp_dir.path())?;
+
+    let archive = temp_dir.path().join("auto.zip");
+    zzz_cmd()
+        .args(["compress", "--zip-method", "zstd", "-o"])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .success();
+    let methods = entry_methods(&archive)?;
+    assert_eq!(method_of(&methods, "notes.txt"), CompressionMethod::Zstd);
+    // by extension
+    assert_eq!(method_of(&methods, "image.png"), CompressionMethod::Stored);
+    // by entropy
+    assert_eq!(method_of(&methods, "noise.bin"), CompressionMethod::Stored);
+
+    let archive = temp_dir.path().join("forced.zip");
+    zzz_cmd()
+        .args(["compress", "--no-auto-store", "-o"])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .success();
+    let methods = entry_methods(&archive)?;
+    assert_eq!(
+        method_of(&methods, "image.png"),
+        CompressionMethod::Deflated
+    );
+    assert_eq!(
+        method_of(&methods, "noise.bin"),
+        CompressionMethod::Deflated
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_zip_method() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("input.txt");
+    fs::write(&input, "content")?;
+
+    zzz_cmd()
+        .args(["compress", "--zip-method", "lzw", "-f", "zip"])
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unsupported zip method 'lzw'"));
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Tests for zstd tuning flags (--long, --window-log, --strategy, --target-block-size, --checksum, --ultra)
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+
+type Result<T> = anyhow::Result<T>;
+
+fn zzz_cmd() -> Command {
+    cargo_bin_cmd!("zzz")
+}
+
+fn test_tree(parent: &Path) -> Result<std::path::PathBuf> {
+    let block: Vec<u8> = (0..64 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+    common::create_tree(
+        parent,
+        "image",
+        &[
+            ("disk.img", &block.repeat(8)),
+            ("notes.txt", "tuned zstd\n".repeat(100).as_bytes()),
+        ],
+    )
+}
+
+#[test]
+fn test_long_window_round_trip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("image.tar.zst");
+
+    zzz_cmd()
+        .args([
+            "compress",
+            "-l",
+            "3",
+            "--long=28",
+            "--ultra",
+            "--checksum",
+            "--strategy",
+            "btopt",
+            "--target-block-size",
+            "16K",
+            "-o",
+        ])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .success();
+
+    // frame header descriptor: bit 2 is the content checksum flag
+    let bytes = fs::read(&archive)?;
+    assert_eq!(&bytes[..4], &[0x28, 0xB5, 0x2F, 0xFD]);
+    assert_ne!(bytes[4] & 0b100, 0, "checksum flag not set");
+
+    // a decoder with the stock 128 MiB limit refuses the 256 MiB window
+    let mut stock = zstd::stream::read::Decoder::new(fs::File::open(&archive)?)?;
+    assert!(stock.read_to_end(&mut Vec::new()).is_err());
+
+    zzz_cmd()
+        .arg("list")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("image/disk.img"));
+    zzz_cmd().arg("test").arg(&archive).assert().success();
+
+    let extract_dir = temp_dir.path().join("extract");
+    zzz_cmd()
+        .arg("extract")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(extract_dir.join("image/disk.img"))?,
+        fs::read(tree.join("disk.img"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_large_window_requires_ultra() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tree = test_tree(temp_dir.path())?;
+    let archive = temp_dir.path().join("image.tar.zst");
+
+    zzz_cmd()
+        .args(["compress", "--window-log", "28", "-o"])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --ultra"));
+    assert!(!archive.exists());
+
+    // the default --long window stays within the stock decoder limit
+    zzz_cmd()
+        .args(["compress", "-l", "3", "--long", "-o"])
+        .arg(&archive)
+        .arg(&tree)
+        .assert()
+        .success();
+    let mut stock = zstd::stream::read::Decoder::new(fs::File::open(&archive)?)?;
+    stock.read_to_end(&mut Vec::new())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_zstd_tuning_values() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("input.txt");
+    fs::write(&input, "content")?;
+
+    zzz_cmd()
+        .args(["compress", "--strategy", "turbo"])
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unsupported zstd strategy 'turbo'",
+        ));
+
+    zzz_cmd()
+        .args(["compress", "--target-block-size", "1M"])
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("out of range"));
+
+    zzz_cmd()
+        .args(["compress", "--long=40"])
+        .arg(&input)
+        .assert()
+        .failure();
+
+    Ok(())
+}